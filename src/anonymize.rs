@@ -0,0 +1,204 @@
+//! De-identification of the datasets for sharing the files outside: a list of the identifying elements is removed or
+//! emptied, the person names are emptied at every level, the private elements are removed, and the UIDs are replaced
+//! consistently across the files, so that the studies and the references between them are kept.
+//! This is not the Basic Application Level Confidentiality Profile of PS3.15, and isn't marked as such: the dates, the
+//! descriptions and the comments are kept, and the pixel data is not checked for burned-in annotations.
+
+use crate::dataset::{new_uid, sequence, text_element, write_file};
+use dicom::core::value::Value;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{InMemDicomObject, open_file};
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The Other Patient IDs.
+const OTHER_PATIENT_IDS: Tag = Tag(0x0010, 0x1000);
+
+/// The Other Patient IDs Sequence.
+const OTHER_PATIENT_IDS_SEQUENCE: Tag = Tag(0x0010, 0x1002);
+
+/// The method written in DeidentificationMethod.
+const METHOD: &str = "rsdicombrowser: identifiers removed, UIDs replaced, dates kept";
+
+/// The elements removed.
+const REMOVED_TAGS: [Tag; 31] = [
+    tags::INSTITUTION_NAME,
+    tags::INSTITUTION_ADDRESS,
+    tags::INSTITUTIONAL_DEPARTMENT_NAME,
+    tags::STATION_NAME,
+    tags::PHYSICIANS_OF_RECORD,
+    tags::PERFORMING_PHYSICIAN_NAME,
+    tags::NAME_OF_PHYSICIANS_READING_STUDY,
+    tags::OPERATORS_NAME,
+    tags::ADMITTING_DIAGNOSES_DESCRIPTION,
+    tags::REFERENCED_PATIENT_SEQUENCE,
+    tags::ISSUER_OF_PATIENT_ID,
+    tags::PATIENT_BIRTH_TIME,
+    OTHER_PATIENT_IDS,
+    OTHER_PATIENT_IDS_SEQUENCE,
+    tags::OTHER_PATIENT_NAMES,
+    tags::PATIENT_BIRTH_NAME,
+    tags::PATIENT_AGE,
+    tags::PATIENT_SIZE,
+    tags::PATIENT_WEIGHT,
+    tags::PATIENT_ADDRESS,
+    tags::PATIENT_MOTHER_BIRTH_NAME,
+    tags::MILITARY_RANK,
+    tags::MEDICAL_RECORD_LOCATOR,
+    tags::PATIENT_TELEPHONE_NUMBERS,
+    tags::ETHNIC_GROUP,
+    tags::OCCUPATION,
+    tags::ADDITIONAL_PATIENT_HISTORY,
+    tags::PATIENT_COMMENTS,
+    tags::DEVICE_SERIAL_NUMBER,
+    tags::REQUESTING_PHYSICIAN,
+    tags::REQUEST_ATTRIBUTES_SEQUENCE,
+];
+
+/// The elements kept but emptied, since they are required in the modules.
+const EMPTIED_TAGS: [(Tag, VR); 5] = [
+    (tags::PATIENT_BIRTH_DATE, VR::DA),
+    (tags::PATIENT_SEX, VR::CS),
+    (tags::ACCESSION_NUMBER, VR::SH),
+    (tags::REFERRING_PHYSICIAN_NAME, VR::PN),
+    (tags::STUDY_ID, VR::SH),
+];
+
+/// The transfer syntaxes with the native pixel data, which can be written in each other.
+const NATIVE_TRANSFER_SYNTAXES: [&str; 3] = [
+    uids::IMPLICIT_VR_LITTLE_ENDIAN,
+    uids::EXPLICIT_VR_LITTLE_ENDIAN,
+    uids::EXPLICIT_VR_BIG_ENDIAN,
+];
+
+/// The root of the UIDs defined by the standard, e.g. the SOP classes and the transfer syntaxes, which are kept.
+const STANDARD_UID_ROOT: &str = "1.2.840.10008.";
+
+/// The replacement patient, and the UIDs replaced so far.
+pub struct Anonymizer {
+    pub patient_name: String,
+    pub patient_id: String,
+    uids: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    pub fn new(patient_name: &str, patient_id: &str) -> Self {
+        Self {
+            patient_name: patient_name.to_string(),
+            patient_id: patient_id.to_string(),
+            uids: HashMap::new(),
+        }
+    }
+
+    /// Get the replacement of the UID, the same one for every file.
+    fn replace_uid(&mut self, uid: &str) -> String {
+        let uid = uid.trim_end_matches(['\0', ' ']);
+        if uid.is_empty() || uid.starts_with(STANDARD_UID_ROOT) {
+            return uid.to_string();
+        }
+
+        self.uids
+            .entry(uid.to_string())
+            .or_insert_with(new_uid)
+            .clone()
+    }
+
+    /// De-identify the elements of the dataset and of the items of its sequences.
+    fn anonymize_elements(&mut self, obj: &mut InMemDicomObject) {
+        let tags: Vec<Tag> = obj.iter().map(|x| x.header().tag).collect();
+
+        for tag in tags {
+            if tag.group() % 2 == 1 || REMOVED_TAGS.contains(&tag) {
+                obj.remove_element(tag);
+                continue;
+            }
+            if let Some((_, vr)) = EMPTIED_TAGS.iter().find(|(x, _)| *x == tag) {
+                obj.put(text_element(tag, *vr, ""));
+                continue;
+            }
+
+            let Ok(elem) = obj.element(tag) else {
+                continue;
+            };
+            match (elem.vr(), elem.value()) {
+                // The names of the patient, the staff and the observers, e.g. in the SR content items.
+                (VR::PN, Value::Primitive(_)) => {
+                    obj.put(text_element(tag, VR::PN, ""));
+                }
+                (VR::UI, Value::Primitive(_)) => {
+                    let Ok(value) = elem.to_str().map(|x| x.to_string()) else {
+                        continue;
+                    };
+                    // The multi-valued UIDs are replaced one by one.
+                    let uids: Vec<String> =
+                        value.split('\\').map(|x| self.replace_uid(x)).collect();
+                    obj.put(text_element(tag, VR::UI, &uids.join("\\")));
+                }
+                (_, Value::Sequence(seq)) => {
+                    let mut items = seq.items().to_vec();
+                    for item in &mut items {
+                        self.anonymize_elements(item);
+                    }
+                    obj.put(sequence(tag, items));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// De-identify the dataset, marking it with the method used. The patient name is set after the other names are
+    /// emptied.
+    pub fn anonymize(&mut self, obj: &mut InMemDicomObject) {
+        self.anonymize_elements(obj);
+
+        obj.put(text_element(tags::PATIENT_NAME, VR::PN, &self.patient_name));
+        obj.put(text_element(tags::PATIENT_ID, VR::LO, &self.patient_id));
+        obj.put(text_element(tags::PATIENT_IDENTITY_REMOVED, VR::CS, "YES"));
+        obj.put(text_element(tags::DEIDENTIFICATION_METHOD, VR::LO, METHOD));
+    }
+}
+
+/// Write the anonymized copies of the files into the folder, named by their order so that the original names don't
+/// leak any identifiers.
+/// The files are converted to the transfer syntax if given, except the ones with compressed pixel data, which are
+/// kept in their transfer syntax and returned as warnings.
+pub fn anonymize_files(
+    files: &[PathBuf],
+    anonymizer: &mut Anonymizer,
+    transfer_syntax: Option<&str>,
+    dir: &Path,
+) -> Result<(Vec<PathBuf>, Vec<String>)> {
+    std::fs::create_dir_all(dir)?;
+    let mut targets = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (index, file) in files.iter().enumerate() {
+        let obj = open_file(file).map_err(Error::other)?;
+        let original = obj
+            .meta()
+            .transfer_syntax()
+            .trim_end_matches('\0')
+            .to_string();
+        let target_syntax = match transfer_syntax {
+            Some(x) if NATIVE_TRANSFER_SYNTAXES.contains(&original.as_str()) => x.to_string(),
+            Some(x) if x != original => {
+                warnings.push(format!(
+                    "{}: compressed in {original}, not converted",
+                    file.display()
+                ));
+                original
+            }
+            _ => original,
+        };
+        let mut dataset = obj.into_inner();
+        anonymizer.anonymize(&mut dataset);
+
+        let target = dir.join(format!("I{:07}.dcm", index + 1));
+        write_file(dataset, &target_syntax, &target)?;
+        targets.push(target);
+    }
+
+    Ok((targets, warnings))
+}
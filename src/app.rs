@@ -1,97 +1,2574 @@
+use crate::archive;
+use crate::audio::AudioPlayer;
+use crate::audit::AuditLog;
+use crate::bulk_edit::{BulkEditTool, EditAction};
+use crate::checksum;
+use crate::cli_commands;
+use crate::corruption::CorruptionTool;
+use crate::crash::{self, CrashRecovery, RecoveryAction};
+use crate::dataset::{
+    DicomInfo, dump_line_tag, dump_line_vr, element_display, element_string, is_binary_vr,
+    is_non_image_modality, open_header, probe_dicom, read_full_value, save_element_value,
+    tag_keyword,
+};
+use crate::date_shift::DateShiftTool;
+use crate::diagnostics;
+use crate::dicomdir::DicomdirTool;
+use crate::dump::{self, Dump, DumpOutput, DumpSource, DumpStyle};
+use crate::export;
+use crate::extract::{self, Embedded};
+use crate::fhir;
+use crate::filter::{TagFilter, filter_lines};
+use crate::folder_summary::FolderSummary;
+use crate::fonts;
+use crate::frequency::{self, FrequencyReport};
+use crate::functional_groups::FunctionalGroupBrowser;
+use crate::i18n::{self, tr, trf};
+use crate::kos::KosTool;
+use crate::multiframe;
+use crate::onboarding::{self, SampleDownload};
+use crate::organize::{OrganizeAction, OrganizeTool};
+use crate::orientation;
+use crate::orthanc::OrthancBrowser;
+use crate::perf::PerfStats;
+use crate::pinned;
+use crate::privacy::{self, PrivacyMask};
+use crate::quick_view::QuickView;
+use crate::references::{self, Reference};
+use crate::remote::RemoteFolder;
+use crate::s3::BucketBrowser;
+use crate::same_value::SameValueQuery;
+use crate::scan::{self, FileState, ProbeIndex, Problem, Rescan, ScanResult, ScannedFile};
+use crate::scripting::{self, ScriptContext};
+use crate::search::{self, SearchScope};
+use crate::series_check::{self, SeriesReport};
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::share_bundle::ShareBundleTool;
+use crate::size_breakdown::{self, SizeBreakdown};
+use crate::stats::{self, ValueStats};
+use crate::study_graph::StudyGraph;
+use crate::study_zip::{self, StudyZipTool};
+use crate::summary::SummaryRegistry;
+use crate::surface::SurfaceTool;
+use crate::synthetic::SyntheticStudyTool;
+use crate::tag_presence::TagPresenceReport;
+use crate::tasks::{self, Task, TaskPool};
+use crate::teaching::TeachingFiles;
+use crate::template_check::TemplateCheckTool;
+use crate::timeline::Timeline;
+use crate::uid_regen::UidRegenTool;
+use crate::undo::{self, UndoEntry};
+use crate::update::UpdateCheck;
 use core::f32;
-use dicom::object::open_file;
-use dicom_dump::DumpOptions;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::{DefaultDicomObject, open_file};
 use egui::Widget;
 use egui_file_dialog::FileDialog;
-use egui_ltreeview::{Action, TreeView};
-use regex::RegexBuilder;
-use rsdirtreebuilder::dir_tree_builder::{DirTreeBuilder, PathSizeInfo};
+use egui_ltreeview::{Action, NodeBuilder, TreeView, TreeViewState};
 use std::{
+    cell::Cell,
     collections::HashMap,
     path::{Path, PathBuf},
-    time::Duration,
+    sync::{Arc, mpsc::TryRecvError},
+    time::{Duration, Instant},
 };
 
+const OPEN_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O);
+const REFRESH_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F5);
+const FIND_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::F);
+const FIND_NEXT_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F3);
+const FIND_PREV_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::SHIFT, egui::Key::F3);
+const PERF_OVERLAY_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F12);
+
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 pub struct TemplateApp {
-    base_dir: PathBuf,
-    dicom_files: Vec<PathSizeInfo>,
+    /// The root folders of the workspace, each scanned on its own.
+    roots: Vec<PathBuf>,
+    root_scans: HashMap<PathBuf, ScanResult>,
+    /// The rescan of the root folders on a worker thread, with the roots rescanned.
+    pending_scan: Option<(Vec<PathBuf>, Task<Rescan<Result<DicomInfo, String>>>)>,
+    /// The file to select once the scan in progress is done, e.g. a file just copied into a root folder.
+    selection_after_scan: Option<PathBuf>,
+    /// The extraction of the tar archive on a worker thread, with the archive.
+    pending_archive: Option<Task<(PathBuf, std::io::Result<usize>)>>,
+    dicom_files: Vec<ScannedFile>,
     selected_file: Option<PathBuf>,
     search_input: String,
-    dicom_dump: HashMap<PathBuf, String>,
+    search_scope: SearchScope,
+    dicom_dump: HashMap<PathBuf, Dump>,
+    /// The file last opened for the dump, to dump it again when the format is switched.
+    dump_source: Option<DumpSource>,
+    /// The dump being generated on a worker thread, with its file.
+    pending_dump: Option<(PathBuf, Task<DumpOutput>)>,
+    /// The dump is shown in a separate OS window.
+    dump_detached: bool,
     matched_pos: Option<usize>,
     scroll_pos: Option<usize>,
     search_results: Option<Vec<usize>>,
     file_dialog: FileDialog,
+    file_dialog_purpose: FileDialogPurpose,
+    tag_filters: Vec<TagFilter>,
+    show_value_stats: bool,
+    show_sparklines: bool,
+    value_stats: Option<(PathBuf, Vec<ValueStats>)>,
+    show_functional_groups: bool,
+    functional_groups: FunctionalGroupBrowser,
+    selected_obj: Option<DefaultDicomObject>,
+    /// The masked copy of the selected header for the privacy mode, made when first shown.
+    privacy_mask: Option<PrivacyMask>,
+    /// The selected file was removed or changed since the scan.
+    selected_file_state: FileState,
+    /// The folder selected in the tree, whose summary is shown instead of the dump.
+    selected_dir: Option<PathBuf>,
+    folder_summary: Option<(PathBuf, FolderSummary)>,
+    pending_folder_summary: Option<(PathBuf, Task<FolderSummary>)>,
+    /// The header of the previously selected file, to compare the values with on hover.
+    previous_selection: Option<(PathBuf, DefaultDicomObject)>,
+    summary_registry: SummaryRegistry,
+    settings: Settings,
+    show_settings: bool,
+    focus_search: bool,
+    script_output: Option<String>,
+    selected_nodes: Vec<PathBuf>,
+    show_organize: bool,
+    organize: OrganizeTool,
+    show_dicomdir: bool,
+    dicomdir: DicomdirTool,
+    show_study_zip: bool,
+    study_zip: StudyZipTool,
+    /// The file whose study is picked for the ZIP export in the tree context menu.
+    study_zip_request: Cell<Option<PathBuf>>,
+    show_share_bundle: bool,
+    share_bundle: ShareBundleTool,
+    show_surface: bool,
+    show_quick_view: bool,
+    quick_view: QuickView,
+    /// The playback of the audio of the selected file.
+    audio_player: Option<AudioPlayer>,
+    surface: SurfaceTool,
+    show_kos: bool,
+    kos: KosTool,
+    show_history: bool,
+    audit_log: AuditLog,
+    /// The last edit in the undo journal, read again after each edit or undo rather than on every frame.
+    last_undo_entry: Option<UndoEntry>,
+    read_only_flag: bool,
+    scan_problems: Vec<Problem>,
+    /// The result of the last checksum verification, shown with the problems.
+    checksum_summary: Option<String>,
+    /// The checksum verification on a worker thread, with the manifest.
+    pending_checksum: Option<Task<(PathBuf, std::io::Result<(usize, Vec<Problem>)>)>>,
+    show_problems: bool,
+    probe_index: ProbeIndex<Result<DicomInfo, String>>,
+    scanned_files: Vec<ScannedFile>,
+    skip_reasons: HashMap<PathBuf, String>,
+    dicom_info: HashMap<PathBuf, DicomInfo>,
+    /// The files in the folder by their SOP instance UIDs.
+    instance_files: HashMap<String, PathBuf>,
+    show_series_check: bool,
+    series_reports: Vec<SeriesReport>,
+    pending_series_check: Option<Task<Vec<SeriesReport>>>,
+    /// The index of the series report of each checked file with warnings.
+    series_warnings: HashMap<PathBuf, usize>,
+    tree_state: TreeViewState<PathBuf>,
+    show_timeline: bool,
+    timeline: Option<Timeline>,
+    pending_timeline: Option<Task<Timeline>>,
+    show_study_graph: bool,
+    /// The graphs of the studies in the folder by their study instance UIDs.
+    study_graphs: Option<HashMap<String, StudyGraph>>,
+    pending_study_graphs: Option<Task<HashMap<String, StudyGraph>>>,
+    show_teaching_files: bool,
+    teaching_files: TeachingFiles,
+    /// De-identify the dump of the selected file in the diagnostic bundle.
+    diagnostic_deidentify: bool,
+    /// Include a screenshot in the diagnostic bundle, taken in the privacy mode.
+    diagnostic_screenshot_included: bool,
+    /// The diagnostic bundle is waiting for the screenshot.
+    diagnostic_requested: bool,
+    /// The privacy mode to restore once the screenshot is taken.
+    diagnostic_privacy_mode: Option<bool>,
+    diagnostic_screenshot: Option<Arc<egui::ColorImage>>,
+    /// The session saved by the last crash, to be restored or discarded.
+    crash_recovery: Option<CrashRecovery>,
+    tasks: TaskPool,
+    show_tasks: bool,
+    perf: PerfStats,
+    show_perf_overlay: bool,
+    update_check: UpdateCheck,
+    samples: SampleDownload,
+    show_orthanc: bool,
+    orthanc: OrthancBrowser,
+    show_remote: bool,
+    remote: RemoteFolder,
+    show_s3: bool,
+    s3: BucketBrowser,
+    new_modality: String,
+    show_template_check: bool,
+    template_check: TemplateCheckTool,
+    show_frequency: bool,
+    frequency: FrequencyReport,
+    show_tag_presence: bool,
+    tag_presence: TagPresenceReport,
+    show_bulk_edit: bool,
+    bulk_edit: BulkEditTool,
+    show_uid_regen: bool,
+    uid_regen: UidRegenTool,
+    show_date_shift: bool,
+    date_shift: DateShiftTool,
+    show_synthetic: bool,
+    synthetic: SyntheticStudyTool,
+    show_corruption: bool,
+    corruption: CorruptionTool,
+    show_size_breakdown: bool,
+    size_breakdown: Option<(PathBuf, Result<SizeBreakdown, String>)>,
+    pending_size_breakdown: Option<Task<(PathBuf, Result<SizeBreakdown, String>)>>,
+    /// The complete values of the truncated elements expanded in the dump of the selected file, each with whether it
+    /// identifies the patient, so that it is masked while the privacy mode is on.
+    expanded_values: HashMap<Tag, (Result<String, String>, bool)>,
+    /// The element of the selected file whose value is to be saved to the file picked.
+    saved_element: Option<Tag>,
+    /// The indexes of the dump lines passing the tag filters, with the file, filters and line count they are for.
+    visible_lines: Option<(PathBuf, Vec<TagFilter>, usize, Vec<usize>)>,
+    /// How long the last search took.
+    search_time: Option<Duration>,
+    show_same_value: bool,
+    same_value_query: Option<SameValueQuery>,
+    pending_same_value: Option<Task<SameValueQuery>>,
+    /// The split or merge of the multi-frame instances on a worker thread, with the operation and the source files.
+    pending_write: Option<Task<(&'static str, Vec<PathBuf>, std::io::Result<Vec<PathBuf>>)>>,
+    show_references: bool,
+    references: Option<(PathBuf, Vec<Reference>)>,
+}
+
+/// What the path picked in the file dialog will be used for.
+#[derive(Clone, Copy, PartialEq)]
+enum FileDialogPurpose {
+    OpenDir,
+    AddRoot,
+    ExportCsv,
+    ExportJson,
+    SaveSession,
+    LoadSession,
+    PickFont,
+    OrganizeTarget,
+    DicomdirTarget,
+    PickTemplate,
+    OpenArchive,
+    PickManifest,
+    ExportFrequencies,
+    SaveElementValue,
+    ExportFhir,
+    StudyZipOutput,
+    ShareBundleOutput,
+    DiagnosticBundle,
+    SurfaceOutput,
+    BulkEditTarget,
+    UidRegenTarget,
+    DateShiftTarget,
+    SyntheticTarget,
+    CorruptionTarget,
 }
 
 impl TemplateApp {
     /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// The read-only mode is forced on if `read_only` is set by the `--read-only` flag.
+    pub fn new(cc: &eframe::CreationContext<'_>, read_only: bool) -> Self {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         cc.egui_ctx.set_theme(egui::Theme::Light);
-        cc.egui_ctx.set_pixels_per_point(1.2);
+
+        let settings: Settings = cc
+            .storage
+            .and_then(|x| eframe::get_value(x, eframe::APP_KEY))
+            .unwrap_or_default();
+        cc.egui_ctx.set_pixels_per_point(settings.ui_scale);
+        fonts::apply_fonts(&cc.egui_ctx, settings.font_path.as_deref());
+        if let Err(e) = i18n::set_language(settings.language.as_deref()) {
+            log::error!("Failed to load language {:?}: {e}", settings.language);
+        }
+        crash::install_panic_hook();
+        let mut tasks = TaskPool::new(settings.worker_threads);
+        let mut update_check = UpdateCheck::default();
+        if settings.check_for_updates {
+            update_check.start(&mut tasks);
+        }
 
         Self {
-            base_dir: PathBuf::new(),
+            roots: Vec::new(),
+            root_scans: HashMap::new(),
+            pending_scan: None,
+            selection_after_scan: None,
+            pending_archive: None,
             dicom_files: Vec::new(),
             selected_file: None,
             search_input: "".to_string(),
+            search_scope: SearchScope::default(),
             dicom_dump: HashMap::new(),
+            dump_source: None,
+            pending_dump: None,
+            dump_detached: false,
             matched_pos: None,
             scroll_pos: None,
             search_results: None,
             file_dialog: FileDialog::new(),
+            file_dialog_purpose: FileDialogPurpose::OpenDir,
+            tag_filters: Vec::new(),
+            show_value_stats: false,
+            show_sparklines: true,
+            value_stats: None,
+            show_functional_groups: false,
+            functional_groups: FunctionalGroupBrowser::default(),
+            selected_obj: None,
+            privacy_mask: None,
+            selected_file_state: FileState::Unchanged,
+            selected_dir: None,
+            folder_summary: None,
+            pending_folder_summary: None,
+            previous_selection: None,
+            summary_registry: SummaryRegistry::default(),
+            settings,
+            show_settings: false,
+            focus_search: false,
+            script_output: None,
+            selected_nodes: Vec::new(),
+            show_organize: false,
+            organize: OrganizeTool::default(),
+            show_dicomdir: false,
+            dicomdir: DicomdirTool::default(),
+            show_study_zip: false,
+            study_zip: StudyZipTool::default(),
+            study_zip_request: Cell::new(None),
+            show_share_bundle: false,
+            share_bundle: ShareBundleTool::default(),
+            show_surface: false,
+            show_quick_view: false,
+            quick_view: QuickView::default(),
+            audio_player: None,
+            surface: SurfaceTool::default(),
+            show_kos: false,
+            kos: KosTool::default(),
+            show_history: false,
+            audit_log: AuditLog::load(),
+            last_undo_entry: undo::last_entry(),
+            read_only_flag: read_only,
+            scan_problems: Vec::new(),
+            checksum_summary: None,
+            pending_checksum: None,
+            show_problems: false,
+            probe_index: ProbeIndex::default(),
+            scanned_files: Vec::new(),
+            skip_reasons: HashMap::new(),
+            dicom_info: HashMap::new(),
+            instance_files: HashMap::new(),
+            show_series_check: false,
+            series_reports: Vec::new(),
+            pending_series_check: None,
+            series_warnings: HashMap::new(),
+            tree_state: TreeViewState::default(),
+            show_timeline: false,
+            timeline: None,
+            pending_timeline: None,
+            show_study_graph: false,
+            study_graphs: None,
+            pending_study_graphs: None,
+            show_teaching_files: false,
+            teaching_files: TeachingFiles::load(),
+            diagnostic_deidentify: true,
+            diagnostic_screenshot_included: false,
+            diagnostic_requested: false,
+            diagnostic_privacy_mode: None,
+            diagnostic_screenshot: None,
+            crash_recovery: CrashRecovery::load(),
+            tasks,
+            show_tasks: false,
+            perf: PerfStats::default(),
+            show_perf_overlay: false,
+            update_check,
+            samples: SampleDownload::default(),
+            show_orthanc: false,
+            orthanc: OrthancBrowser::default(),
+            show_remote: false,
+            remote: RemoteFolder::default(),
+            show_s3: false,
+            s3: BucketBrowser::default(),
+            new_modality: String::new(),
+            show_template_check: false,
+            template_check: TemplateCheckTool::default(),
+            show_frequency: false,
+            frequency: FrequencyReport::default(),
+            show_tag_presence: false,
+            tag_presence: TagPresenceReport::default(),
+            show_bulk_edit: false,
+            bulk_edit: BulkEditTool::default(),
+            show_uid_regen: false,
+            uid_regen: UidRegenTool::default(),
+            show_date_shift: false,
+            date_shift: DateShiftTool::default(),
+            show_synthetic: false,
+            synthetic: SyntheticStudyTool::default(),
+            show_corruption: false,
+            corruption: CorruptionTool::default(),
+            show_size_breakdown: false,
+            size_breakdown: None,
+            pending_size_breakdown: None,
+            expanded_values: HashMap::new(),
+            saved_element: None,
+            visible_lines: None,
+            search_time: None,
+            show_same_value: false,
+            same_value_query: None,
+            pending_same_value: None,
+            pending_write: None,
+            show_references: false,
+            references: None,
         }
     }
 }
 
 impl TemplateApp {
     /// Handle dir open by enumerating the directory recursively and storing the dicom files.
+    /// The folder replaces all the root folders of the workspace, and is scanned on a worker thread.
     fn handle_file_open(&mut self, path: &Path) {
         self.dicom_files.clear();
-        self.base_dir = path.to_path_buf();
+        self.roots = vec![path.to_path_buf()];
+        self.root_scans.clear();
+        self.probe_index.clear();
+        self.dicom_dump.clear();
+        self.dump_source = None;
+        self.cancel_dump();
+        self.selected_dir = None;
+
+        self.update_file_lists();
+        self.start_scan(&[path.to_path_buf()]);
+    }
+
+    /// Extract the tar archive into a cache folder on a worker thread, and open the folder when done.
+    /// Any earlier extraction of the same archive is replaced.
+    fn handle_archive_open(&mut self, path: &Path) {
+        tasks::cancel(&mut self.pending_archive);
+        let archive = path.to_path_buf();
+        let name = trf(
+            "Extract {}",
+            path.file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string()),
+        );
+        let task = self.tasks.spawn(name, move |context| {
+            let dir = archive::cache_dir(&archive);
+            let result = if dir.exists() {
+                std::fs::remove_dir_all(&dir)
+            } else {
+                Ok(())
+            }
+            .and_then(|_| archive::extract_tar(&archive, &dir, context.token()));
+            (archive, result)
+        });
+        self.pending_archive = Some(task);
+    }
+
+    /// Open the folder of the archive extracted.
+    fn handle_archive_extracted(&mut self, archive: &Path, result: std::io::Result<usize>) {
+        match result {
+            Ok(count) => {
+                log::info!("Extracted {count} files of {}", archive.display());
+                self.handle_file_open(&archive::cache_dir(archive));
+            }
+            Err(e) => log::error!("Failed to extract {}: {e}", archive.display()),
+        }
+    }
+
+    /// Add the folder to the workspace as another root folder, unless it overlaps one of the roots.
+    fn handle_root_added(&mut self, path: &Path) {
+        if let Some(root) = self
+            .roots
+            .iter()
+            .find(|x| path.starts_with(x) || x.starts_with(path))
+        {
+            log::error!(
+                "{} overlaps the folder {} in the workspace",
+                path.display(),
+                root.display()
+            );
+            return;
+        }
+
+        self.roots.push(path.to_path_buf());
+        self.start_scan(&[path.to_path_buf()]);
+    }
+
+    /// Remove the root folder from the workspace, with the selection if it's in the folder.
+    fn handle_root_removed(&mut self, root: &Path) {
+        self.roots.retain(|x| x != root);
+        self.root_scans.remove(root);
+        self.update_file_lists();
+
+        if self
+            .selected_file
+            .as_ref()
+            .is_some_and(|x| x.starts_with(root))
+        {
+            self.selected_file = None;
+            self.selected_obj = None;
+            self.privacy_mask = None;
+        }
+        if let Some(dir) = self.selected_dir.clone() {
+            if dir.starts_with(root) {
+                self.selected_dir = None;
+            } else {
+                self.handle_dir_selected(&dir);
+            }
+        }
+    }
+
+    /// Rescan all the root folders, only probing the files changed since the last scan.
+    fn handle_refresh(&mut self) {
+        let roots = self.roots.clone();
+        self.start_scan(&roots);
+    }
+
+    /// Rescan one root folder, only probing the files changed since the last scan.
+    fn handle_root_refresh(&mut self, root: &Path) {
+        self.start_scan(&[root.to_path_buf()]);
+    }
+
+    /// Keep the selection and the tree state after a rescan, unless the selected file or folder is gone.
+    fn refresh_selection(&mut self) {
+        if let Some(dir) = self.selected_dir.clone() {
+            if dir.is_dir() {
+                self.handle_dir_selected(&dir);
+            } else {
+                self.selected_dir = None;
+            }
+        }
+        if let Some(selected_file) = self.selected_file.clone() {
+            if self.dicom_files.iter().any(|x| x.path() == selected_file) {
+                if !self.dicom_dump.contains_key(&selected_file)
+                    && !self.is_dump_pending(&selected_file)
+                {
+                    self.handle_file_selected(&selected_file);
+                }
+            } else {
+                self.selected_file = None;
+                self.selected_obj = None;
+                self.privacy_mask = None;
+                self.selected_file_state = FileState::Unchanged;
+            }
+        }
+    }
+
+    /// Rescan the root folders and probe their files on a worker thread, only probing the files changed since the last
+    /// scan. The scan in progress is cancelled, and its roots are rescanned too.
+    fn start_scan(&mut self, roots: &[PathBuf]) {
+        let mut scanned_roots = roots.to_vec();
+        if let Some((pending_roots, task)) = self.pending_scan.take() {
+            task.cancel();
+            scanned_roots.extend(pending_roots.into_iter().filter(|x| !roots.contains(x)));
+        }
+        scanned_roots.retain(|x| self.roots.contains(x));
+
+        let other_files: Vec<ScannedFile> = self
+            .roots
+            .iter()
+            .filter(|x| !scanned_roots.contains(x))
+            .filter_map(|x| self.root_scans.get(x))
+            .flat_map(|x| x.files.iter().cloned())
+            .collect();
+        let options = self.settings.scan.clone();
+        let probe_index = self.probe_index.clone();
+        let task_roots = scanned_roots.clone();
+        let task = self.tasks.spawn(tr("Scan the folders"), move |context| {
+            scan::rescan(
+                &task_roots,
+                &other_files,
+                &options,
+                probe_index,
+                probe_dicom,
+                context,
+            )
+        });
+        self.pending_scan = Some((scanned_roots, task));
+    }
+
+    /// Take the rescan when it is done, and drop the cached dumps of the changed files.
+    fn poll_scan(&mut self) {
+        let Some((_, task)) = self.pending_scan.as_ref() else {
+            return;
+        };
+
+        let rescan = match task.try_recv() {
+            Ok(rescan) => rescan,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.pending_scan = None;
+                return;
+            }
+        };
+        self.pending_scan = None;
+
+        for path in &rescan.changed {
+            self.dicom_dump.remove(path);
+            if self.dump_source.as_ref().is_some_and(|x| x.path == *path) {
+                self.dump_source = None;
+            }
+            if self.is_dump_pending(path) {
+                self.cancel_dump();
+            }
+        }
+        self.probe_index = rescan.probe_index;
+        for (root, result) in rescan.results {
+            // The root may have been removed during the scan.
+            if self.roots.contains(&root) {
+                self.root_scans.insert(root, result);
+            }
+        }
+        self.update_file_lists();
+        self.refresh_selection();
+        if let Some(file) = self.selection_after_scan.take()
+            && self.dicom_files.iter().any(|x| x.path() == file)
+        {
+            self.select_file(&file);
+        }
+    }
+
+    /// Collect the dicom files of all the root folders from the probe results, and drop the cached dumps of the removed
+    /// files. The results of the series check are dropped too, as they are of the files before.
+    fn update_file_lists(&mut self) {
+        self.series_reports.clear();
+        tasks::cancel(&mut self.pending_series_check);
+        self.series_warnings.clear();
+        self.skip_reasons.clear();
+        self.dicom_info.clear();
+        self.scanned_files.clear();
+        self.scan_problems.clear();
+        for root in &self.roots {
+            let Some(result) = self.root_scans.get(root) else {
+                continue;
+            };
+            for file in &result.files {
+                let path = file.path().to_path_buf();
+                match self.probe_index.get(file) {
+                    Some(Ok(info)) => {
+                        self.dicom_info.insert(path, info.clone());
+                    }
+                    Some(Err(reason)) => {
+                        self.skip_reasons.insert(path, reason.clone());
+                    }
+                    None => {}
+                }
+            }
+            self.scanned_files.extend(result.files.iter().cloned());
+            self.scan_problems.extend(result.problems.iter().cloned());
+        }
+        self.dicom_files = self
+            .scanned_files
+            .iter()
+            .filter(|x| self.dicom_info.contains_key(x.path()))
+            .cloned()
+            .collect();
+        self.dicom_dump
+            .retain(|path, _| self.dicom_files.iter().any(|x| x.path() == path));
+        self.instance_files = self
+            .dicom_info
+            .iter()
+            .filter(|(_, info)| !info.sop_instance_uid.is_empty())
+            .map(|(path, info)| (info.sop_instance_uid.clone(), path.clone()))
+            .collect();
+
+        self.show_problems = !self.scan_problems.is_empty();
+        self.checksum_summary = None;
+        self.folder_summary = None;
+        if let Some((_, task)) = self.pending_folder_summary.take() {
+            task.cancel();
+        }
+        self.timeline = None;
+        tasks::cancel(&mut self.pending_timeline);
+        self.study_graphs = None;
+        tasks::cancel(&mut self.pending_study_graphs);
+        crash::remember_session(&self.session());
+    }
+
+    /// Select the file in the tree as if it was clicked, expanding its parent directories.
+    fn select_file(&mut self, path: &Path) {
+        self.selected_dir = None;
+        self.tree_state.set_one_selected(path.to_path_buf());
+        self.tree_state.expand_parents_of(&path.to_path_buf());
+        self.selected_nodes = vec![path.to_path_buf()];
+        self.handle_file_selected(path);
+    }
+
+    /// Handle the keyboard shortcuts, so that the main actions don't need the mouse.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|x| x.consume_shortcut(&OPEN_SHORTCUT)) {
+            self.open_file_dialog(FileDialogPurpose::OpenDir);
+        }
+        if ctx.input_mut(|x| x.consume_shortcut(&REFRESH_SHORTCUT)) && !self.dicom_files.is_empty()
+        {
+            self.handle_refresh();
+        }
+        if ctx.input_mut(|x| x.consume_shortcut(&FIND_SHORTCUT)) {
+            self.focus_search = true;
+        }
+        // Check Shift+F3 first, since F3 without modifiers matches it as well.
+        if ctx.input_mut(|x| x.consume_shortcut(&FIND_PREV_SHORTCUT))
+            && !self.search_input.is_empty()
+        {
+            self.handle_search(false);
+        }
+        if ctx.input_mut(|x| x.consume_shortcut(&FIND_NEXT_SHORTCUT))
+            && !self.search_input.is_empty()
+        {
+            self.handle_search(true);
+        }
+        if ctx.input_mut(|x| x.consume_shortcut(&PERF_OVERLAY_SHORTCUT)) {
+            self.show_perf_overlay = !self.show_perf_overlay;
+        }
+    }
+
+    /// Get the selected files, including the dicom files under the selected directories.
+    fn selected_files(&self) -> Vec<PathBuf> {
+        self.dicom_files
+            .iter()
+            .map(|x| x.path())
+            .filter(|x| self.selected_nodes.iter().any(|node| x.starts_with(node)))
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Check if the features modifying or removing files are disabled.
+    fn is_read_only(&self) -> bool {
+        self.read_only_flag || self.settings.read_only
+    }
+
+    /// Show the window to organize the selected files into folders by a tag template.
+    fn show_organize_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let read_only = self.is_read_only();
+        let mut is_open = self.show_organize;
+        let mut action = OrganizeAction::None;
+
+        egui::Window::new(tr("Organize"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                action = self.organize.ui(ui, &files, &mut self.tasks, read_only);
+            });
+
+        self.show_organize = is_open;
+        // The files moved by the run are taken by the polling in update.
+        if action == OrganizeAction::PickTarget {
+            self.open_file_dialog(FileDialogPurpose::OrganizeTarget);
+        }
+    }
+
+    /// Show the window to set the value of a tag in the selected files.
+    fn show_bulk_edit_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let read_only = self.is_read_only();
+        let mut is_open = self.show_bulk_edit;
+        let mut action = EditAction::None;
+
+        egui::Window::new(tr("Bulk edit"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                action = self.bulk_edit.ui(ui, &files, &mut self.tasks, read_only);
+            });
+
+        self.show_bulk_edit = is_open;
+        // The files written by the edit are taken by the polling in update.
+        if let EditAction::PickTarget = action {
+            self.open_file_dialog(FileDialogPurpose::BulkEditTarget);
+        }
+    }
+
+    /// Show the window to regenerate the UIDs of the selected files.
+    fn show_uid_regen_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let read_only = self.is_read_only();
+        let mut is_open = self.show_uid_regen;
+        let mut action = EditAction::None;
+
+        egui::Window::new(tr("Regenerate UIDs"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                action = self.uid_regen.ui(
+                    ui,
+                    &files,
+                    &mut self.settings.uid_root,
+                    &mut self.tasks,
+                    read_only,
+                );
+            });
+
+        self.show_uid_regen = is_open;
+        // The files written by the edit are taken by the polling in update.
+        if let EditAction::PickTarget = action {
+            self.open_file_dialog(FileDialogPurpose::UidRegenTarget);
+        }
+    }
+
+    /// Show the window to shift the dates of the selected files.
+    fn show_date_shift_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let read_only = self.is_read_only();
+        let mut is_open = self.show_date_shift;
+        let mut action = EditAction::None;
+
+        egui::Window::new(tr("Shift dates"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                action = self.date_shift.ui(ui, &files, &mut self.tasks, read_only);
+            });
+
+        self.show_date_shift = is_open;
+        // The files written by the edit are taken by the polling in update.
+        if let EditAction::PickTarget = action {
+            self.open_file_dialog(FileDialogPurpose::DateShiftTarget);
+        }
+    }
+
+    /// Show the window to generate a synthetic series.
+    fn show_synthetic_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_synthetic;
+        let mut action = EditAction::None;
+
+        egui::Window::new(tr("Generate synthetic study"))
+            .open(&mut is_open)
+            .show(ctx, |ui| action = self.synthetic.ui(ui, &mut self.tasks));
+
+        self.show_synthetic = is_open;
+        // The files written by the generation are taken by the polling in update.
+        if let EditAction::PickTarget = action {
+            self.open_file_dialog(FileDialogPurpose::SyntheticTarget);
+        }
+    }
+
+    /// Show the window to browse the Orthanc servers, pull from them and push the selected files to them.
+    fn show_orthanc_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+
+        egui::Window::new(tr("Orthanc"))
+            .open(&mut self.show_orthanc)
+            .show(ctx, |ui| {
+                self.orthanc.ui(
+                    ui,
+                    &self.settings.orthanc_servers,
+                    &files,
+                    &mut self.tasks,
+                    self.settings.privacy_mode,
+                );
+            });
+    }
+
+    /// Show the window to open a folder over SFTP, or a file over SMB.
+    fn show_remote_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new(tr("Open remote folder"))
+            .open(&mut self.show_remote)
+            .show(ctx, |ui| {
+                self.remote.ui(ui, &mut self.tasks);
+            });
+    }
+
+    /// Show the window to browse an S3 bucket and open the objects selected.
+    fn show_s3_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new(tr("S3 bucket"))
+            .open(&mut self.show_s3)
+            .show(ctx, |ui| {
+                self.s3.ui(ui, &mut self.tasks);
+            });
+    }
+
+    /// Open the folder pulled from a server, e.g. Orthanc or an S3 bucket, in the workspace if a folder is open.
+    fn handle_folder_pulled(&mut self, dir: &Path) {
+        if self.roots.is_empty() {
+            self.handle_file_open(dir);
+        } else if self.roots.iter().any(|x| x == dir) {
+            self.handle_root_refresh(dir);
+        } else {
+            self.handle_root_added(dir);
+        }
+    }
+
+    /// Show the window to write corrupted copies of the selected file.
+    fn show_corruption_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_corruption;
+        let mut action = EditAction::None;
+
+        egui::Window::new(tr("Corrupted copies"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                action = self.corruption.ui(ui, self.selected_file.as_deref());
+            });
+
+        self.show_corruption = is_open;
+        match action {
+            EditAction::None => {}
+            EditAction::PickTarget => self.open_file_dialog(FileDialogPurpose::CorruptionTarget),
+            EditAction::FilesWritten(targets) => {
+                let sources = self.selected_file.clone().into_iter().collect();
+                self.handle_files_written("Write corrupted copies", sources, Ok(targets));
+            }
+        }
+    }
+
+    /// Drop the cached dumps of the files edited in place, and rescan to show the edits.
+    fn handle_files_edited(&mut self, files: &[PathBuf]) {
+        self.last_undo_entry = undo::last_entry();
+        for file in files {
+            self.dicom_dump.remove(file);
+            if self.dump_source.as_ref().is_some_and(|x| x.path == *file) {
+                self.dump_source = None;
+            }
+        }
+        self.handle_refresh();
+    }
+
+    /// Restore the originals of the last edit in place from the undo journal.
+    fn handle_undo(&mut self) {
+        match undo::undo_last() {
+            Ok(files) => {
+                self.audit_log
+                    .record("Undo edit", Vec::new(), files.clone());
+                self.handle_files_edited(&files);
+            }
+            Err(e) => {
+                log::error!("Failed to undo the last edit: {e}");
+                self.last_undo_entry = undo::last_entry();
+            }
+        }
+    }
+
+    /// Show the window to create a DICOMDIR for the selected files.
+    fn show_dicomdir_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let mut is_open = self.show_dicomdir;
+        let mut pick_output = false;
+
+        egui::Window::new(tr("Create DICOMDIR"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                pick_output = self.dicomdir.ui(ui, &files, &mut self.audit_log);
+            });
+
+        self.show_dicomdir = is_open;
+        if pick_output {
+            self.open_file_dialog(FileDialogPurpose::DicomdirTarget);
+        }
+    }
+
+    /// Pick the study of the file for the ZIP export, and show the export window.
+    fn handle_study_zip_request(&mut self, file: &Path) {
+        let study_uid = match open_header(file) {
+            Ok(obj) => element_string(&obj, tags::STUDY_INSTANCE_UID).unwrap_or_default(),
+            Err(e) => {
+                log::error!("Failed to read {}: {e}", file.display());
+                return;
+            }
+        };
+
+        let files = study_zip::study_files(&self.folder_files(), &study_uid);
+        self.study_zip.set_study(study_uid, files);
+        self.show_study_zip = true;
+    }
+
+    /// Show the window to export a study as a ZIP archive.
+    fn show_study_zip_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_study_zip;
+        let mut pick_output = false;
+
+        egui::Window::new(tr("Export study as ZIP"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                pick_output = self.study_zip.ui(ui, &mut self.audit_log);
+            });
+
+        self.show_study_zip = is_open;
+        if pick_output {
+            self.open_file_dialog(FileDialogPurpose::StudyZipOutput);
+        }
+    }
+
+    /// Show the wizard to create an anonymized bundle of the selected files for sharing.
+    fn show_share_bundle_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let mut is_open = self.show_share_bundle;
+        let mut pick_output = false;
+
+        egui::Window::new(tr("Create share bundle"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                pick_output = self.share_bundle.ui(ui, &files, &mut self.audit_log);
+            });
+
+        self.show_share_bundle = is_open;
+        if pick_output {
+            self.open_file_dialog(FileDialogPurpose::ShareBundleOutput);
+        }
+    }
+
+    /// Show the window to export the surface of an ROI of the selected structure set.
+    fn show_surface_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_surface;
+        let mut pick_output = false;
+
+        egui::Window::new(tr("Export ROI surface"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                pick_output =
+                    self.surface
+                        .ui(ui, self.selected_file.as_deref(), &mut self.audit_log);
+            });
+
+        self.show_surface = is_open;
+        if pick_output {
+            self.open_file_dialog(FileDialogPurpose::SurfaceOutput);
+        }
+    }
+
+    /// Show the window of the chosen tags of the selected file, masked in the privacy mode.
+    fn show_quick_view_window(&mut self, ctx: &egui::Context) {
+        self.update_privacy_mask();
+        let mut is_open = self.show_quick_view;
+        let obj = match self
+            .privacy_mask
+            .as_ref()
+            .filter(|_| self.settings.privacy_mode)
+        {
+            Some(mask) => Some(mask.object()),
+            None => self.selected_obj.as_deref(),
+        };
+
+        egui::Window::new(tr("Quick view"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                self.quick_view
+                    .ui(ui, &mut self.settings.quick_view_layouts, obj);
+            });
+
+        self.show_quick_view = is_open;
+    }
+
+    /// Show the window to create a Key Object Selection document referencing the selected files.
+    fn show_kos_window(&mut self, ctx: &egui::Context) {
+        let files = self.selected_files();
+        let mut is_open = self.show_kos;
+        let mut created = None;
+
+        egui::Window::new(tr("Key object selection"))
+            .open(&mut is_open)
+            .show(ctx, |ui| created = self.kos.ui(ui, &files));
+
+        self.show_kos = is_open;
+        if let Some(path) = created {
+            self.handle_files_written("Key object selection", files, Ok(vec![path]));
+        }
+    }
+
+    /// Record the files written by a tool in the audit log and rescan the folder to show them, or log the error.
+    fn handle_files_written(
+        &mut self,
+        operation: &str,
+        sources: Vec<PathBuf>,
+        result: std::io::Result<Vec<PathBuf>>,
+    ) {
+        match result {
+            Ok(targets) => {
+                self.audit_log.record(operation, sources, targets);
+                self.handle_refresh();
+            }
+            Err(e) => log::error!("Failed to write the files: {e}"),
+        }
+    }
+
+    /// Extract the embedded document, video or audio of the selected file next to it.
+    fn handle_extract_embedded(&mut self) {
+        let Some(selected_file) = self.selected_file.clone() else {
+            return;
+        };
+        let dir = selected_file
+            .parent()
+            .expect("a file should have a parent")
+            .to_path_buf();
+
+        self.handle_files_written(
+            "Extract embedded content",
+            vec![selected_file.clone()],
+            extract::extract(&selected_file, &dir).map(|x| vec![x]),
+        );
+    }
+
+    /// Extract the embedded document, video or audio of the selected file to the temporary folder, and open it with the
+    /// default app of the system.
+    fn handle_open_embedded(&self) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            return;
+        };
+
+        let result =
+            extract::extract_temp(selected_file).and_then(|x| extract::open_with_default_app(&x));
+        if let Err(e) = result {
+            log::error!(
+                "Failed to open the content of {}: {e}",
+                selected_file.display()
+            );
+        }
+    }
+
+    /// Run the user script against the selected file and keep its output.
+    fn handle_run_script(&mut self, script: &Path) {
+        let dump = self.get_dicom_dump().map_or(String::new(), Dump::to_text);
+        let context = ScriptContext {
+            path: self.selected_file.as_deref(),
+            obj: self.selected_obj.as_deref(),
+            dump: &dump,
+            files: self.dicom_files.iter().map(|x| x.path()).collect(),
+        };
+        self.script_output = Some(scripting::run(script, &context));
+    }
+
+    /// Switch the UI language, or go back to English if `None`.
+    fn set_language(&mut self, language: Option<String>) {
+        match i18n::set_language(language.as_deref()) {
+            Ok(()) => self.settings.language = language,
+            Err(e) => log::error!("Failed to load language {language:?}: {e}"),
+        }
+    }
+
+    /// Open the file dialog for the given purpose.
+    fn open_file_dialog(&mut self, purpose: FileDialogPurpose) {
+        self.file_dialog_purpose = purpose;
+        match purpose {
+            FileDialogPurpose::OpenDir
+            | FileDialogPurpose::AddRoot
+            | FileDialogPurpose::OrganizeTarget
+            | FileDialogPurpose::DicomdirTarget
+            | FileDialogPurpose::BulkEditTarget
+            | FileDialogPurpose::UidRegenTarget
+            | FileDialogPurpose::DateShiftTarget
+            | FileDialogPurpose::SyntheticTarget
+            | FileDialogPurpose::CorruptionTarget => {
+                self.file_dialog.pick_directory();
+            }
+            FileDialogPurpose::ExportCsv
+            | FileDialogPurpose::ExportJson
+            | FileDialogPurpose::SaveSession
+            | FileDialogPurpose::ExportFrequencies
+            | FileDialogPurpose::SaveElementValue
+            | FileDialogPurpose::ExportFhir
+            | FileDialogPurpose::StudyZipOutput
+            | FileDialogPurpose::ShareBundleOutput
+            | FileDialogPurpose::DiagnosticBundle
+            | FileDialogPurpose::SurfaceOutput => {
+                self.file_dialog.save_file();
+            }
+            FileDialogPurpose::LoadSession
+            | FileDialogPurpose::PickFont
+            | FileDialogPurpose::PickTemplate
+            | FileDialogPurpose::OpenArchive
+            | FileDialogPurpose::PickManifest => {
+                self.file_dialog.pick_file();
+            }
+        }
+    }
+
+    /// Handle the path picked in the file dialog.
+    fn handle_file_dialog_picked(&mut self, ctx: &egui::Context, path: &Path) {
+        match self.file_dialog_purpose {
+            FileDialogPurpose::OpenDir => self.handle_file_open(path),
+            FileDialogPurpose::AddRoot => self.handle_root_added(path),
+            FileDialogPurpose::ExportCsv => self.handle_export(path, false),
+            FileDialogPurpose::ExportJson => self.handle_export(path, true),
+            FileDialogPurpose::ExportFhir => self.handle_fhir_export(path),
+            FileDialogPurpose::SaveSession => {
+                if let Err(e) = self.session().save(path) {
+                    log::error!("Failed to save session {}: {e}", path.display());
+                }
+            }
+            FileDialogPurpose::LoadSession => match Session::load(path) {
+                Ok(session) => self.restore_session(session),
+                Err(e) => log::error!("Failed to load session {}: {e}", path.display()),
+            },
+            FileDialogPurpose::OrganizeTarget => {
+                self.organize.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::DicomdirTarget => {
+                self.dicomdir.output_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::BulkEditTarget => {
+                self.bulk_edit.output.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::UidRegenTarget => {
+                self.uid_regen.output.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::DateShiftTarget => {
+                self.date_shift.output.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::SyntheticTarget => {
+                self.synthetic.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::CorruptionTarget => {
+                self.corruption.target_dir = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::StudyZipOutput => {
+                self.study_zip.output = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::ShareBundleOutput => {
+                self.share_bundle.output = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::SurfaceOutput => {
+                self.surface.output = Some(path.to_path_buf());
+            }
+            FileDialogPurpose::DiagnosticBundle => self.handle_diagnostic_bundle(path),
+            FileDialogPurpose::PickTemplate => self.template_check.load(path),
+            FileDialogPurpose::OpenArchive => self.handle_archive_open(path),
+            FileDialogPurpose::PickManifest => self.handle_checksum_verify(path),
+            FileDialogPurpose::SaveElementValue => {
+                if let Some(selected_file) = self.selected_file.as_ref()
+                    && let Some(tag) = self.saved_element
+                    && let Err(e) = save_element_value(selected_file, tag, path)
+                {
+                    log::error!(
+                        "Failed to save the value of {tag} to {}: {e}",
+                        path.display()
+                    );
+                }
+            }
+            FileDialogPurpose::ExportFrequencies => {
+                let result = std::fs::File::create(path)
+                    .and_then(|file| self.frequency.write_csv(&mut std::io::BufWriter::new(file)));
+                if let Err(e) = result {
+                    log::error!("Failed to export {}: {e}", path.display());
+                }
+            }
+            FileDialogPurpose::PickFont => {
+                self.settings.font_path = Some(path.to_path_buf());
+                fonts::apply_fonts(ctx, Some(path));
+            }
+        }
+    }
+
+    /// Show the settings of the font and the UI scale.
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_settings;
+        egui::Window::new(tr("Settings"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings grid").show(ui, |ui| {
+                    ui.label(tr("UI scale"));
+                    let response =
+                        ui.add(egui::Slider::new(&mut self.settings.ui_scale, 0.5..=3.0));
+                    // Only apply the scale when the drag is done, so that the slider doesn't move under the pointer.
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        ctx.set_pixels_per_point(self.settings.ui_scale);
+                    }
+                    ui.end_row();
+
+                    ui.label(tr("Font"));
+                    ui.horizontal(|ui| {
+                        let font_name = self
+                            .settings
+                            .font_path
+                            .as_ref()
+                            .map_or(tr("Default"), |x| x.display().to_string());
+                        ui.monospace(font_name);
+                        if ui.button(tr("Browse")).clicked() {
+                            self.open_file_dialog(FileDialogPurpose::PickFont);
+                        }
+                        if ui.button(tr("Reset")).clicked() {
+                            self.settings.font_path = None;
+                            fonts::apply_fonts(ctx, None);
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label(tr("Worker threads"));
+                    if ui
+                        .add(egui::DragValue::new(&mut self.settings.worker_threads).range(1..=16))
+                        .changed()
+                    {
+                        self.tasks.set_thread_count(self.settings.worker_threads);
+                    }
+                    ui.end_row();
+
+                    ui.label(tr("Updates"));
+                    ui.checkbox(
+                        &mut self.settings.check_for_updates,
+                        tr("Check for a new version at startup"),
+                    );
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.strong(tr("External tools"));
+                ui.label(tr(
+                    "Placeholders: {path}, {folder}, {filename} or a tag keyword, e.g. {SOPInstanceUID}",
+                ));
+                egui::Grid::new("external tools grid").show(ui, |ui| {
+                    ui.label(tr("Name"));
+                    ui.label(tr("Program"));
+                    ui.label(tr("Arguments"));
+                    ui.end_row();
+
+                    let mut removed = None;
+                    for (index, tool) in self.settings.external_tools.iter_mut().enumerate() {
+                        ui.text_edit_singleline(&mut tool.name);
+                        ui.text_edit_singleline(&mut tool.program);
+                        ui.text_edit_singleline(&mut tool.args);
+                        if ui.button("🗑").on_hover_text(tr("Remove")).clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                    if let Some(index) = removed {
+                        self.settings.external_tools.remove(index);
+                    }
+                });
+                if ui.button(tr("Add tool")).clicked() {
+                    self.settings.external_tools.push(Default::default());
+                }
+
+                ui.separator();
+                ui.strong(tr("DICOM endpoints"));
+                ui.horizontal(|ui| {
+                    ui.label(tr("Calling AE title"));
+                    ui.text_edit_singleline(&mut self.settings.calling_ae_title);
+                });
+                egui::Grid::new("endpoints grid").show(ui, |ui| {
+                    ui.label(tr("Name"));
+                    ui.label(tr("AE title"));
+                    ui.label(tr("Host"));
+                    ui.label(tr("Port"));
+                    ui.end_row();
+
+                    let mut removed = None;
+                    for (index, endpoint) in self.settings.endpoints.iter_mut().enumerate() {
+                        ui.text_edit_singleline(&mut endpoint.name);
+                        ui.text_edit_singleline(&mut endpoint.ae_title);
+                        ui.text_edit_singleline(&mut endpoint.host);
+                        ui.add(egui::DragValue::new(&mut endpoint.port));
+                        if ui.button("🗑").on_hover_text(tr("Remove")).clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                    if let Some(index) = removed {
+                        self.settings.endpoints.remove(index);
+                    }
+                });
+                if ui.button(tr("Add endpoint")).clicked() {
+                    self.settings.endpoints.push(Default::default());
+                }
+
+                ui.separator();
+                ui.strong(tr("Orthanc servers"));
+                egui::Grid::new("orthanc servers grid").show(ui, |ui| {
+                    ui.label(tr("Name"));
+                    ui.label(tr("URL"));
+                    ui.label(tr("Token"));
+                    ui.end_row();
+
+                    let mut removed = None;
+                    for (index, server) in self.settings.orthanc_servers.iter_mut().enumerate() {
+                        ui.text_edit_singleline(&mut server.name);
+                        ui.text_edit_singleline(&mut server.url);
+                        ui.checkbox(&mut server.token_auth, "")
+                            .on_hover_text(tr("Send the ORTHANC_TOKEN environment variable as a bearer token"));
+                        if ui.button("🗑").on_hover_text(tr("Remove")).clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                    if let Some(index) = removed {
+                        self.settings.orthanc_servers.remove(index);
+                    }
+                });
+                if ui.button(tr("Add Orthanc server")).clicked() {
+                    self.settings.orthanc_servers.push(Default::default());
+                }
+
+                ui.separator();
+                ui.strong(tr("Tree colors"));
+                ui.checkbox(&mut self.settings.color_code_tree, tr("Color code by modality"));
+                egui::Grid::new("modality colors grid").show(ui, |ui| {
+                    let mut removed = None;
+                    for (modality, color) in self.settings.modality_colors.iter_mut() {
+                        ui.label(modality.as_str());
+                        ui.color_edit_button_srgb(color);
+                        if ui.button("🗑").on_hover_text(tr("Remove")).clicked() {
+                            removed = Some(modality.clone());
+                        }
+                        ui.end_row();
+                    }
+                    if let Some(modality) = removed {
+                        self.settings.modality_colors.remove(&modality);
+                    }
+
+                    ui.add(egui::TextEdit::singleline(&mut self.new_modality).desired_width(80.0));
+                    if ui.button(tr("Add")).clicked() && !self.new_modality.trim().is_empty() {
+                        self.settings
+                            .modality_colors
+                            .insert(self.new_modality.trim().to_uppercase(), [128, 128, 128]);
+                        self.new_modality.clear();
+                    }
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.strong(tr("Folder scan"));
+                let scan = &mut self.settings.scan;
+                ui.checkbox(&mut scan.follow_symlinks, tr("Follow symbolic links"));
+                ui.checkbox(&mut scan.skip_hidden, tr("Skip hidden files and folders"));
+                egui::Grid::new("scan grid").show(ui, |ui| {
+                    ui.label(tr("Include"));
+                    ui.text_edit_singleline(&mut scan.include_patterns)
+                        .on_hover_text(tr("File name patterns separated by ;, e.g. *.dcm; IM*"));
+                    ui.end_row();
+
+                    ui.label(tr("Exclude"));
+                    ui.text_edit_singleline(&mut scan.exclude_patterns)
+                        .on_hover_text(tr("File and folder name patterns separated by ;"));
+                    ui.end_row();
+
+                    ui.label(tr("Maximum depth"));
+                    ui.add(egui::DragValue::new(&mut scan.max_depth))
+                        .on_hover_text(tr("0 for unlimited"));
+                    ui.end_row();
+
+                    ui.label(tr("File size (KiB)"));
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut scan.min_file_size_kib));
+                        ui.label("-");
+                        ui.add(egui::DragValue::new(&mut scan.max_file_size_kib))
+                            .on_hover_text(tr("0 for unlimited"));
+                    });
+                    ui.end_row();
+                });
+            });
+        self.show_settings = is_open;
+    }
+
+    /// Show the paths which couldn't be scanned.
+    fn show_problems_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new(tr("Problems"))
+            .open(&mut self.show_problems)
+            .show(ctx, |ui| {
+                if let Some(summary) = self.checksum_summary.as_ref() {
+                    ui.label(summary);
+                }
+                if self.scan_problems.is_empty() {
+                    ui.label(tr("No problems found in the last scan."));
+                }
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("problems grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for problem in &self.scan_problems {
+                                ui.monospace(problem.path.display().to_string());
+                                ui.label(&problem.message);
+                                ui.end_row();
+                            }
+                        });
+
+                    egui::CollapsingHeader::new(trf("Skipped files ({})", self.skip_reasons.len()))
+                        .show(ui, |ui| {
+                            egui::Grid::new("skipped files grid")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for file in &self.scanned_files {
+                                        if let Some(reason) = self.skip_reasons.get(file.path()) {
+                                            ui.monospace(file.path().display().to_string());
+                                            ui.label(reason);
+                                            ui.end_row();
+                                        }
+                                    }
+                                });
+                        });
+                });
+            });
+    }
+
+    /// Get the paths of all DICOM files in the folder.
+    fn folder_files(&self) -> Vec<PathBuf> {
+        self.dicom_files
+            .iter()
+            .map(|x| x.path().to_path_buf())
+            .collect()
+    }
+
+    /// Check the completeness of every series in the folder on a worker thread.
+    fn handle_series_check(&mut self) {
+        tasks::cancel(&mut self.pending_series_check);
+        let files = self.folder_files();
+        let task = self.tasks.spawn(tr("Check series"), move |context| {
+            series_check::check(&files, context)
+        });
+        self.pending_series_check = Some(task);
+    }
+
+    /// Show the results of the series check, and flag the files of the series with warnings.
+    fn handle_series_checked(&mut self, reports: Vec<SeriesReport>) {
+        self.series_reports = reports;
+        self.series_warnings = self
+            .series_reports
+            .iter()
+            .enumerate()
+            .filter(|(_, report)| !report.warnings.is_empty())
+            .flat_map(|(index, report)| report.files.iter().map(move |x| (x.clone(), index)))
+            .collect();
+        self.show_series_check = true;
+    }
+
+    /// Show the results of the series completeness check.
+    fn show_series_check_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new(tr("Series check"))
+            .open(&mut self.show_series_check)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for report in &self.series_reports {
+                        let icon = if report.warnings.is_empty() {
+                            "✔"
+                        } else {
+                            "⚠"
+                        };
+                        egui::CollapsingHeader::new(format!(
+                            "{icon} {} ({})",
+                            report.description,
+                            trf("{} files", report.files.len())
+                        ))
+                        .id_salt(&report.series_uid)
+                        .show(ui, |ui| {
+                            ui.monospace(&report.series_uid);
+                            if report.warnings.is_empty() {
+                                ui.label(tr("No problems found."));
+                            }
+                            for warning in &report.warnings {
+                                ui.label(warning);
+                            }
+                        });
+                    }
+                });
+            });
+    }
+
+    /// Verify the files against the checksum manifest on a worker thread.
+    fn handle_checksum_verify(&mut self, manifest: &Path) {
+        tasks::cancel(&mut self.pending_checksum);
+        let manifest = manifest.to_path_buf();
+        let task = self.tasks.spawn(tr("Verify checksums"), move |context| {
+            let result = checksum::verify(&manifest, context);
+            (manifest, result)
+        });
+        self.pending_checksum = Some(task);
+    }
+
+    /// Add the missing and mismatched files of the verification to the problems.
+    fn handle_checksum_verified(
+        &mut self,
+        manifest: &Path,
+        result: std::io::Result<(usize, Vec<Problem>)>,
+    ) {
+        match result {
+            Ok((count, problems)) => {
+                self.checksum_summary = Some(format!(
+                    "{}: {}, {}",
+                    manifest.display(),
+                    trf("{} files verified", count),
+                    trf("{} problems", problems.len())
+                ));
+                self.scan_problems.extend(problems);
+                self.show_problems = true;
+            }
+            Err(e) => log::error!("Failed to read {}: {e}", manifest.display()),
+        }
+    }
+
+    /// Show the window to validate the files in the folder against a golden template.
+    fn show_template_check_window(&mut self, ctx: &egui::Context) {
+        let files = self.folder_files();
+        let mut is_open = self.show_template_check;
+        let mut pick_template = false;
+
+        egui::Window::new(tr("Template check"))
+            .open(&mut is_open)
+            .show(ctx, |ui| pick_template = self.template_check.ui(ui, &files));
+
+        self.show_template_check = is_open;
+        if pick_template {
+            self.open_file_dialog(FileDialogPurpose::PickTemplate);
+        }
+    }
+
+    /// Show the window to count the distinct values of a tag across the files in the folder.
+    fn show_frequency_window(&mut self, ctx: &egui::Context) {
+        let files = self.folder_files();
+        let mut is_open = self.show_frequency;
+        let mut export = false;
+
+        egui::Window::new(tr("Value frequency"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                export = self.frequency.ui(ui, &files, self.settings.privacy_mode);
+            });
+
+        self.show_frequency = is_open;
+        if export {
+            self.open_file_dialog(FileDialogPurpose::ExportFrequencies);
+        }
+    }
+
+    /// Show the window to find the files in the folder containing a tag or missing it, and select the file clicked.
+    fn show_tag_presence_window(&mut self, ctx: &egui::Context) {
+        let files = self.folder_files();
+        let mut clicked = None;
+
+        egui::Window::new(tr("Tag presence"))
+            .open(&mut self.show_tag_presence)
+            .show(ctx, |ui| {
+                clicked = self
+                    .tag_presence
+                    .ui(ui, &files, &self.roots, &mut self.tasks);
+            });
+
+        if let Some(path) = clicked {
+            self.select_file(&path);
+        }
+    }
+
+    /// Find the files in the folder with the same value of the element as the selected file on a worker thread.
+    fn handle_same_value_query(&mut self, tag: Tag) {
+        let Some(value) = self
+            .selected_obj
+            .as_ref()
+            .and_then(|x| frequency::value_of(x, tag))
+        else {
+            return;
+        };
+
+        tasks::cancel(&mut self.pending_same_value);
+        let files = self.folder_files();
+        let task = self
+            .tasks
+            .spawn(tr("Find files with same value"), move |context| {
+                SameValueQuery::run(&files, tag, value, context)
+            });
+        self.pending_same_value = Some(task);
+    }
+
+    /// Show the files with the same value, and select the file clicked.
+    fn show_same_value_window(&mut self, ctx: &egui::Context) {
+        let Some(query) = self.same_value_query.as_ref() else {
+            return;
+        };
+        let mut clicked = None;
+
+        egui::Window::new(tr("Files with same value"))
+            .open(&mut self.show_same_value)
+            .show(ctx, |ui| {
+                clicked = query.ui(ui, &self.roots, self.settings.privacy_mode);
+            });
+
+        if let Some(path) = clicked {
+            self.select_file(&path);
+        }
+    }
+
+    /// Show the timeline of the studies, and select the first file of the study clicked.
+    /// The studies are collected on a worker thread when the window is first shown.
+    fn show_timeline_window(&mut self, ctx: &egui::Context) {
+        if self.timeline.is_none() && self.pending_timeline.is_none() {
+            let files = self.folder_files();
+            let task = self.tasks.spawn(tr("Timeline"), move |context| {
+                Timeline::collect(&files, context)
+            });
+            self.pending_timeline = Some(task);
+        }
+        let mut clicked = None;
+
+        egui::Window::new(tr("Timeline"))
+            .open(&mut self.show_timeline)
+            .default_width(600.0)
+            .show(ctx, |ui| match self.timeline.as_ref() {
+                Some(timeline) => clicked = timeline.ui(ui, self.settings.privacy_mode),
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(tr("Reading the files of the folder…"));
+                    });
+                }
+            });
+
+        if let Some(path) = clicked {
+            self.select_file(&path);
+        }
+    }
+
+    /// Show the graph of the study of the selected file, and select the instance clicked.
+    /// The graphs of all the studies are collected on a worker thread when the window is first shown.
+    fn show_study_graph_window(&mut self, ctx: &egui::Context) {
+        let Some(study_uid) = self
+            .selected_obj
+            .as_ref()
+            .and_then(|x| element_string(x, tags::STUDY_INSTANCE_UID))
+        else {
+            return;
+        };
+
+        if self.study_graphs.is_none() && self.pending_study_graphs.is_none() {
+            let files = self.folder_files();
+            let task = self.tasks.spawn(tr("Study graph"), move |context| {
+                StudyGraph::collect(&files, context)
+            });
+            self.pending_study_graphs = Some(task);
+        }
+
+        let mut clicked = None;
+        egui::Window::new(tr("Study graph"))
+            .open(&mut self.show_study_graph)
+            .default_width(600.0)
+            .show(ctx, |ui| match self.study_graphs.as_ref() {
+                Some(graphs) => {
+                    if let Some(graph) = graphs.get(&study_uid) {
+                        clicked = graph.ui(ui, self.selected_file.as_ref());
+                    }
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(tr("Reading the files of the folder…"));
+                    });
+                }
+            });
+
+        if let Some(path) = clicked {
+            self.select_file(&path);
+        }
+    }
+
+    /// Show the notes and labels of the teaching files, selecting the file clicked in the list.
+    fn show_teaching_files_window(&mut self, ctx: &egui::Context) {
+        let mut clicked = None;
+        egui::Window::new(tr("Teaching files"))
+            .open(&mut self.show_teaching_files)
+            .show(ctx, |ui| {
+                clicked = self.teaching_files.ui(ui, self.selected_file.as_ref());
+            });
+
+        if let Some(path) = clicked {
+            if path.is_file() {
+                self.select_file(&path);
+            } else {
+                log::error!("{} no longer exists", path.display());
+            }
+        }
+    }
+
+    /// Take the screenshot requested for the diagnostic bundle when it arrives, and pick where to save the bundle.
+    fn handle_diagnostic_screenshot(&mut self, ctx: &egui::Context) {
+        let screenshot = ctx.input(|x| {
+            x.raw.events.iter().find_map(|event| match event {
+                egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                _ => None,
+            })
+        });
+
+        if let Some(screenshot) = screenshot {
+            self.diagnostic_requested = false;
+            if let Some(privacy_mode) = self.diagnostic_privacy_mode.take() {
+                self.settings.privacy_mode = privacy_mode;
+            }
+            self.diagnostic_screenshot = Some(screenshot);
+            self.open_file_dialog(FileDialogPurpose::DiagnosticBundle);
+        }
+    }
+
+    /// Write the diagnostic bundle, with the screenshot if one was taken.
+    fn handle_diagnostic_bundle(&mut self, path: &Path) {
+        let screenshot = self.diagnostic_screenshot.take();
+
+        if let Err(e) = diagnostics::write_bundle(
+            path,
+            screenshot.as_deref(),
+            &self.settings,
+            self.selected_file.as_deref(),
+            self.diagnostic_deidentify,
+        ) {
+            log::error!(
+                "Failed to write the diagnostic bundle {}: {e}",
+                path.display()
+            );
+        }
+    }
+
+    /// Ask whether to restore the session saved by the last crash.
+    fn show_crash_recovery_window(&mut self, ctx: &egui::Context) {
+        let Some(recovery) = self.crash_recovery.as_ref() else {
+            return;
+        };
+
+        let mut action = RecoveryAction::None;
+        egui::Window::new(tr("Crash recovery"))
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| action = recovery.ui(ui));
+
+        match action {
+            RecoveryAction::None => {}
+            RecoveryAction::Restore => {
+                if let Some(recovery) = self.crash_recovery.take() {
+                    self.restore_session(recovery.session);
+                }
+            }
+            RecoveryAction::RestoreWithoutSelection => {
+                if let Some(mut recovery) = self.crash_recovery.take() {
+                    recovery.session.selected_file = None;
+                    self.restore_session(recovery.session);
+                }
+            }
+            RecoveryAction::Discard => self.crash_recovery = None,
+        }
+    }
+
+    /// Show the summary, the search and the dump of the selected file.
+    fn dump_panel_ui(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if let Some(dir) = self.selected_dir.as_ref() {
+            self.folder_summary_ui(ui, dir);
+            return;
+        }
+
+        let mut rescan = false;
+        if self.selected_file.is_some() && self.selected_file_state != FileState::Unchanged {
+            ui.horizontal(|ui| {
+                let text = if self.selected_file_state == FileState::Missing {
+                    tr("The file is no longer available.")
+                } else {
+                    tr("The file changed since the folder was scanned.")
+                };
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {text}"));
+                rescan = ui.button(tr("Rescan")).clicked();
+            });
+            ui.separator();
+        }
+        if rescan {
+            self.handle_refresh();
+        }
+
+        // The masked header stands in for the selected one in the summaries, and its values are masked in the dump.
+        self.update_privacy_mask();
+        let privacy_mode = self.settings.privacy_mode;
+
+        let (mut open_embedded, mut extract_embedded, mut play_audio) = (false, false, false);
+        // Stop the audio of the file selected before.
+        if self
+            .audio_player
+            .as_ref()
+            .is_some_and(|x| self.selected_file.as_ref() != Some(&x.path))
+        {
+            self.audio_player = None;
+        }
+        if let Some(obj) = self.selected_obj.as_ref() {
+            let shown_obj = match self.privacy_mask.as_ref().filter(|_| privacy_mode) {
+                Some(mask) => mask.object(),
+                None => &**obj,
+            };
+            self.summary_registry.ui(ui, shown_obj);
+            orientation::ui(ui, obj);
+            // Documents and videos can't be shown here, so offer to open them in other apps. The audio is played here.
+            if let Some(embedded) = Embedded::of(obj) {
+                ui.horizontal(|ui| {
+                    ui.label(tr(embedded.label()));
+                    if let Embedded::Audio = embedded {
+                        play_audio = ui
+                            .add_enabled(self.audio_player.is_none(), egui::Button::new(tr("Play")))
+                            .clicked();
+                    }
+                    open_embedded = ui
+                        .button(tr("Open"))
+                        .on_hover_text(tr("Extract to a temporary folder and open it"))
+                        .clicked();
+                    extract_embedded = ui
+                        .add_enabled(!self.is_read_only(), egui::Button::new(tr("Extract")))
+                        .on_hover_text(tr("Extract next to the file"))
+                        .clicked();
+                });
+            }
+            if let Some(player) = self.audio_player.as_mut() {
+                player.ui(ui);
+            }
+            ui.separator();
+        }
+        if play_audio && let Some(selected_file) = self.selected_file.as_ref() {
+            match AudioPlayer::new(selected_file) {
+                Ok(player) => self.audio_player = Some(player),
+                Err(e) => log::error!("Failed to play {}: {e}", selected_file.display()),
+            }
+        }
+        if open_embedded {
+            self.handle_open_embedded();
+        }
+        if extract_embedded {
+            self.handle_extract_embedded();
+        }
+
+        if self.settings.show_hints {
+            onboarding::hint(
+                ui,
+                "Search the header below, and press Enter or F3 for the next match.",
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.label(tr("Format:"));
+            let mut changed = false;
+            for style in DumpStyle::ALL {
+                changed |= ui
+                    .selectable_value(&mut self.settings.dump_style, style, tr(style.label()))
+                    .changed();
+            }
+            if changed {
+                self.handle_dump_style_changed();
+            }
+            if !self.dump_detached
+                && ui
+                    .small_button("⧉")
+                    .on_hover_text(tr("Show the dump in a separate window"))
+                    .clicked()
+            {
+                self.dump_detached = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            let label = ui.label(tr("Search:"));
+            let response = ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.search_input).desired_width(f32::INFINITY),
+                )
+                .labelled_by(label.id);
+            if self.focus_search {
+                response.request_focus();
+                self.focus_search = false;
+            }
+            if response.changed() {
+                self.matched_pos = None;
+                self.search_results = None;
+            }
+            if response.lost_focus() && ui.input(|x| x.key_pressed(egui::Key::Enter)) {
+                self.handle_search(true);
+                response.request_focus();
+            }
+
+            // A pasted UID or tag is searched right away, in the values or the tags.
+            let is_pasted = response.changed()
+                && ui.input(|x| x.events.iter().any(|e| matches!(e, egui::Event::Paste(_))));
+            if is_pasted && let Some(scope) = search::detect_scope(&self.search_input) {
+                self.search_input = self.search_input.trim().to_string();
+                self.search_scope = scope;
+                self.handle_search(true);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Search in:"));
+            for scope in SearchScope::ALL {
+                if ui
+                    .selectable_value(&mut self.search_scope, scope, tr(scope.label()))
+                    .changed()
+                {
+                    self.matched_pos = None;
+                    self.search_results = None;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button(tr("Prev"))
+                .on_hover_text(ctx.format_shortcut(&FIND_PREV_SHORTCUT))
+                .clicked()
+            {
+                self.handle_search(false);
+            }
+            if ui
+                .button(tr("Next"))
+                .on_hover_text(ctx.format_shortcut(&FIND_NEXT_SHORTCUT))
+                .clicked()
+                && !self.search_input.is_empty()
+            {
+                self.handle_search(true);
+            }
+            if ui.button(tr("Clear")).clicked() {
+                self.search_input.clear();
+                self.matched_pos = None;
+                self.search_results = None;
+            }
+
+            let search_status = self
+                .search_results
+                .as_ref()
+                .map_or("".into(), |x| trf("{} matches", x.len()));
+            let search_status = match self.search_time.filter(|_| !search_status.is_empty()) {
+                Some(time) => {
+                    format!("{search_status} ({:.1} ms)", time.as_secs_f64() * 1000.0)
+                }
+                None => search_status,
+            };
+
+            let response = ui.label(egui::RichText::new(search_status).color(egui::Color32::BLUE));
+            // Let screen readers announce the match count when it changes.
+            ctx.accesskit_node_builder(response.id, |node| {
+                node.set_live(egui::accesskit::Live::Polite);
+            });
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            for filter in TagFilter::ALL {
+                let is_active = self.tag_filters.contains(&filter);
+                if ui.selectable_label(is_active, tr(filter.label())).clicked() {
+                    if is_active {
+                        self.tag_filters.retain(|x| *x != filter);
+                    } else {
+                        self.tag_filters.push(filter);
+                    }
+                    self.matched_pos = None;
+                    self.search_results = None;
+                }
+            }
+        });
+
+        if !self.settings.pinned_tags.is_empty() {
+            ui.separator();
+            let shown_obj = match self.privacy_mask.as_ref().filter(|_| privacy_mode) {
+                Some(mask) => Some(mask.object()),
+                None => self.selected_obj.as_deref(),
+            };
+            pinned::ui(ui, &mut self.settings.pinned_tags, shown_obj);
+        }
+
+        ui.separator();
+
+        if let Some(selected_file) = self.selected_file.as_ref()
+            && self.is_dump_pending(selected_file)
+        {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr("Dumping the file…"));
+            });
+        }
+        if self.get_dicom_dump().is_some_and(Dump::has_replacements) {
+            let charset = self
+                .selected_obj
+                .as_ref()
+                .and_then(|x| element_string(x, tags::SPECIFIC_CHARACTER_SET))
+                .unwrap_or_default();
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                tr("⚠ Some text could not be decoded and is shown as �."),
+            )
+            .on_hover_text(trf("Specific Character Set: {}", charset));
+        }
+
+        // Only the rows in view are laid out, so that dumps with millions of lines stay responsive.
+        self.update_visible_lines();
+        let dump = self
+            .selected_file
+            .as_ref()
+            .and_then(|x| self.dicom_dump.get(x));
+        let visible_lines = self.visible_lines.as_ref().map_or(&[][..], |x| &x.3);
+        let privacy_mask = self.privacy_mask.as_ref().filter(|_| privacy_mode);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        // Scroll the dump by keyboard when no text field takes the keys.
+        let (page_up, page_down, home, end) = if ctx.wants_keyboard_input() {
+            (false, false, false, false)
+        } else {
+            ui.input(|x| {
+                (
+                    x.key_pressed(egui::Key::PageUp),
+                    x.key_pressed(egui::Key::PageDown),
+                    x.key_pressed(egui::Key::Home),
+                    x.key_pressed(egui::Key::End),
+                )
+            })
+        };
+        if home {
+            self.scroll_pos = Some(0);
+        }
+        if end {
+            self.scroll_pos = visible_lines.last().copied();
+        }
+
+        let mut scroll_area = egui::ScrollArea::both();
+        if let Some(pos) = self.scroll_pos.take() {
+            let row = visible_lines.partition_point(|x| *x < pos);
+            scroll_area = scroll_area
+                .vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+
+        let mut toggled = None;
+        let mut saved = None;
+        let mut queried = None;
+        let mut pin_toggled = None;
+        scroll_area.show_rows(ui, row_height, visible_lines.len(), |ui, rows| {
+            let page = ui.clip_rect().height();
+            if page_up {
+                ui.scroll_with_delta(egui::vec2(0.0, page));
+            }
+            if page_down {
+                ui.scroll_with_delta(egui::vec2(0.0, -page));
+            }
+
+            for index in rows.map(|x| visible_lines[x]) {
+                let Some(line) = dump.and_then(|x| x.line(index)) else {
+                    continue;
+                };
+                let shown_line = privacy_mask.map_or(line.into(), |x| x.mask_line(line));
+                let is_phi = dump_line_tag(line)
+                    .is_some_and(|tag| privacy::is_phi(tag, dump_line_vr(line).unwrap_or(VR::UN)));
+                let is_masked = privacy_mask.is_some() && is_phi;
+                let rich_text = egui::RichText::new(shown_line).monospace();
+                // The top-level elements are dragged onto the quick view to add them.
+                let drag_tag = dump_line_tag(line).filter(|_| self.show_quick_view);
+                let show_line = |ui: &mut egui::Ui| {
+                    let label = if self.matched_pos.is_some_and(|x| x == index) {
+                        egui::Label::new(
+                            rich_text
+                                .strong()
+                                .background_color(egui::Color32::LIGHT_GRAY),
+                        )
+                        .wrap_mode(egui::TextWrapMode::Extend)
+                    } else {
+                        egui::Label::new(rich_text)
+                    };
+                    match drag_tag {
+                        Some(tag) => {
+                            let id = egui::Id::new(("dump line", index));
+                            ui.dnd_drag_source(id, tag, |ui| label.ui(ui)).response
+                        }
+                        None => label.ui(ui),
+                    }
+                };
+
+                // Long values are cut in the dump. The complete value of a top-level element is read on demand, and
+                // shown in a window so that the rows keep their height.
+                let truncated_tag = dump::truncated_line_tag(line);
+                let response = match truncated_tag {
+                    Some(tag) => {
+                        ui.horizontal(|ui| {
+                            let response = show_line(ui);
+                            let text = if self.expanded_values.contains_key(&tag) {
+                                tr("hide full value")
+                            } else {
+                                tr("… show full value")
+                            };
+                            if ui.small_button(text).clicked() {
+                                toggled = Some((tag, is_phi));
+                            }
+                            response
+                        })
+                        .inner
+                    }
+                    None => show_line(ui),
+                };
+
+                // The bulk data can be saved as raw bytes, e.g. a LUT or a private payload.
+                // The other values can be looked up in the other files.
+                if let Some(tag) = dump_line_tag(line) {
+                    let is_binary = dump_line_vr(line).is_some_and(is_binary_vr);
+                    let is_pinned = pinned::is_pinned(&self.settings.pinned_tags, tag);
+                    let response = match self.previous_selection.as_ref() {
+                        Some((previous_file, previous_obj)) => {
+                            let previous = element_display(previous_obj, tag);
+                            let current = self
+                                .selected_obj
+                                .as_ref()
+                                .and_then(|x| element_display(x, tag));
+                            let name = previous_file
+                                .file_name()
+                                .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                            let change = if previous == current {
+                                tr("unchanged")
+                            } else {
+                                tr("changed")
+                            };
+                            let previous = match previous {
+                                Some(_) if is_masked => privacy::MASK.to_string(),
+                                Some(x) => x,
+                                None => tr("absent"),
+                            };
+                            response.on_hover_text(format!("{name}: {previous}\n({change})"))
+                        }
+                        None => response,
+                    };
+                    response.context_menu(|ui| {
+                        let text = if is_pinned {
+                            tr("Unpin from the header")
+                        } else {
+                            tr("Pin to the header")
+                        };
+                        if ui.button(text).clicked() {
+                            pin_toggled = Some(tag);
+                            ui.close();
+                        }
+                        if is_binary {
+                            if ui.button(tr("Save element value to file…")).clicked() {
+                                saved = Some(tag);
+                                ui.close();
+                            }
+                        } else if ui.button(tr("Find files with same value")).clicked() {
+                            queried = Some(tag);
+                            ui.close();
+                        }
+                    });
+                }
+            }
+        });
+
+        if let Some(tag) = saved {
+            self.saved_element = Some(tag);
+            self.open_file_dialog(FileDialogPurpose::SaveElementValue);
+        }
+
+        if let Some(tag) = queried {
+            self.handle_same_value_query(tag);
+        }
+
+        if let Some(tag) = pin_toggled {
+            pinned::toggle(&mut self.settings.pinned_tags, tag);
+        }
+
+        if let Some((tag, is_phi)) = toggled
+            && let Some(selected_file) = self.selected_file.as_ref()
+            && self.expanded_values.remove(&tag).is_none()
+        {
+            let value = read_full_value(selected_file, tag);
+            self.expanded_values.insert(tag, (value, is_phi));
+        }
+
+        self.show_full_values(ctx);
+    }
+
+    /// Show the complete values read from the file, each in a window which is closed to hide it. The identifying values
+    /// are masked while the privacy mode is on, even if they were expanded before.
+    fn show_full_values(&mut self, ctx: &egui::Context) {
+        let mut closed = Vec::new();
+        for (tag, (value, is_phi)) in &self.expanded_values {
+            let value = match value {
+                Ok(_) if *is_phi && self.settings.privacy_mode => Ok(privacy::MASK),
+                Ok(value) => Ok(value.as_str()),
+                Err(e) => Err(e),
+            };
+            let mut is_open = true;
+            egui::Window::new(format!("{tag} {}", tag_keyword(*tag)))
+                .id(egui::Id::new(("full value", *tag)))
+                .open(&mut is_open)
+                .default_size([500.0, 300.0])
+                .show(ctx, |ui| match value {
+                    Ok(value) => {
+                        egui::ScrollArea::both().show(ui, |ui| {
+                            ui.add(
+                                egui::Label::new(egui::RichText::new(value).monospace())
+                                    .wrap_mode(egui::TextWrapMode::Wrap),
+                            );
+                        });
+                    }
+                    Err(e) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                });
+            if !is_open {
+                closed.push(*tag);
+            }
+        }
+        for tag in closed {
+            self.expanded_values.remove(&tag);
+        }
+    }
+
+    /// Show the dump in a separate OS window, e.g. on another monitor, following the selection in the tree.
+    fn show_detached_dump(&mut self, ctx: &egui::Context) {
+        let title = self.selected_file.as_ref().map_or(tr("Dump"), |x| {
+            x.file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string())
+        });
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("detached dump"),
+            egui::ViewportBuilder::default()
+                .with_title(title)
+                .with_inner_size([800.0, 600.0]),
+            |ctx, _class| {
+                egui::CentralPanel::default().show(ctx, |ui| self.dump_panel_ui(ctx, ui));
+                if ctx.input(|x| x.viewport().close_requested()) {
+                    self.dump_detached = false;
+                }
+            },
+        );
+    }
+
+    /// Get the current workspace as a session.
+    fn session(&self) -> Session {
+        Session {
+            base_dir: self.roots.first().cloned(),
+            other_roots: self.roots.iter().skip(1).cloned().collect(),
+            selected_file: self.selected_file.clone(),
+            search_input: self.search_input.clone(),
+            tag_filters: self.tag_filters.clone(),
+            show_value_stats: self.show_value_stats,
+            show_functional_groups: self.show_functional_groups,
+        }
+    }
+
+    /// Restore the workspace from the session.
+    fn restore_session(&mut self, session: Session) {
+        if let Some(base_dir) = session.base_dir.as_ref() {
+            self.handle_file_open(base_dir);
+            for root in &session.other_roots {
+                self.handle_root_added(root);
+            }
+        }
+        if let Some(selected_file) = session.selected_file.as_ref()
+            && selected_file.is_file()
+        {
+            // The file is selected in the tree once the scan of its folder is done.
+            if self.pending_scan.is_some() {
+                self.selection_after_scan = Some(selected_file.clone());
+            } else {
+                self.select_file(selected_file);
+            }
+        }
+
+        self.search_input = session.search_input;
+        self.tag_filters = session.tag_filters;
+        self.show_value_stats = session.show_value_stats;
+        self.show_functional_groups = session.show_functional_groups;
+    }
+
+    /// Handle the flat export of the selected dicom file to CSV or JSON.
+    fn handle_export(&self, path: &Path, is_json: bool) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            return;
+        };
+
+        let result = open_file(selected_file)
+            .map_err(std::io::Error::other)
+            .and_then(|obj| {
+                let rows = export::flatten(&obj);
+                let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+                if is_json {
+                    export::write_json(&rows, &mut writer)
+                } else {
+                    export::write_csv(&rows, &mut writer)
+                }
+            });
+
+        if let Err(e) = result {
+            log::error!("Failed to export {}: {e}", selected_file.display());
+        }
+    }
+
+    /// Export the study of the selected file as a FHIR ImagingStudy resource, from all of its files in the folder.
+    fn handle_fhir_export(&self, path: &Path) {
+        let Some(study_uid) = self
+            .selected_obj
+            .as_ref()
+            .and_then(|x| element_string(x, tags::STUDY_INSTANCE_UID))
+        else {
+            return;
+        };
+        let Some(study) = fhir::imaging_study(&self.folder_files(), &study_uid) else {
+            log::error!("No files of the study {study_uid} found");
+            return;
+        };
+
+        let result = std::fs::File::create(path).and_then(|file| {
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &study)
+                .map_err(std::io::Error::other)
+        });
+        if let Err(e) = result {
+            log::error!("Failed to export {}: {e}", path.display());
+        }
+    }
+
+    /// Show the statistics of the multi-valued numeric elements of the selected file.
+    fn show_value_stats_window(&mut self, ctx: &egui::Context) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            return;
+        };
+
+        // Compute the statistics once per selected file.
+        if self
+            .value_stats
+            .as_ref()
+            .is_none_or(|(path, _)| path != selected_file)
+        {
+            let results = open_header(selected_file).map_or(Vec::new(), |obj| stats::collect(&obj));
+            self.value_stats = Some((selected_file.clone(), results));
+        }
+
+        let mut is_open = self.show_value_stats;
+        egui::Window::new(tr("Value statistics"))
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.show_sparklines, tr("Show sparklines"));
+                ui.separator();
+
+                let Some((_, results)) = self.value_stats.as_ref() else {
+                    return;
+                };
+                if results.is_empty() {
+                    ui.label(tr("No multi-valued numeric elements."));
+                    return;
+                }
+
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("value stats grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in ["Element", "VR", "Count", "Min", "Max", "Mean"] {
+                                ui.strong(tr(header));
+                            }
+                            if self.show_sparklines {
+                                ui.strong(tr("Values"));
+                            }
+                            ui.end_row();
+
+                            for entry in results {
+                                ui.monospace(&entry.path);
+                                ui.monospace(entry.vr.to_string());
+                                ui.label(entry.values.len().to_string());
+                                ui.label(format!("{:.4}", entry.min));
+                                ui.label(format!("{:.4}", entry.max));
+                                ui.label(format!("{:.4}", entry.mean));
+                                if self.show_sparklines {
+                                    stats::sparkline(ui, entry);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        self.show_value_stats = is_open;
+    }
+
+    /// Show what makes the selected file big.
+    fn show_size_breakdown_window(&mut self, ctx: &egui::Context) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            return;
+        };
+
+        // Read the whole file on a worker thread once per selected file, as it may be several GiB.
+        let breakdown = self
+            .size_breakdown
+            .as_ref()
+            .filter(|(path, _)| path == selected_file);
+        if breakdown.is_none() && self.pending_size_breakdown.is_none() {
+            let file = selected_file.clone();
+            let task = self.tasks.spawn(tr("Size breakdown"), move |_| {
+                let result = size_breakdown::breakdown(&file);
+                (file, result)
+            });
+            self.pending_size_breakdown = Some(task);
+        }
+
+        egui::Window::new(tr("Size breakdown"))
+            .open(&mut self.show_size_breakdown)
+            .show(ctx, |ui| match breakdown {
+                Some((_, Ok(breakdown))) => breakdown.ui(ui),
+                Some((_, Err(e))) => {
+                    ui.label(e);
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(tr("Reading the file…"));
+                    });
+                }
+            });
+    }
 
-        let builder = DirTreeBuilder::build(rsdirtreebuilder::dir_tree_builder::Params::new(path))
-            .expect("Builder expected to be created");
+    /// Show the instances referenced by the selected file, and select the referenced file clicked.
+    fn show_references_window(&mut self, ctx: &egui::Context) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            return;
+        };
 
-        while !builder.is_finished() {
-            std::thread::sleep(Duration::from_millis(100));
+        // Collect the references once per selected file.
+        if self
+            .references
+            .as_ref()
+            .is_none_or(|(path, _)| path != selected_file)
+        {
+            let results = self
+                .selected_obj
+                .as_ref()
+                .map_or(Vec::new(), |obj| references::collect(obj));
+            self.references = Some((selected_file.clone(), results));
         }
 
-        self.dicom_files = builder
-            .iter()
-            .mode(rsdirtreebuilder::dir_tree_builder::TraveseMode::DFS)
-            .filter(|x| !x.is_dir() && open_file(x.path()).is_ok())
-            .collect();
+        let mut clicked = None;
+        egui::Window::new(tr("References"))
+            .open(&mut self.show_references)
+            .show(ctx, |ui| {
+                if let Some((_, results)) = self.references.as_ref() {
+                    clicked = references::ui(ui, results, &self.instance_files);
+                }
+            });
 
-        self.dicom_dump.clear();
+        if let Some(path) = clicked {
+            self.select_file(&path);
+        }
     }
 
     /// Get the current selected dicom dump.
-    fn get_dicom_dump(&self) -> &str {
-        let entry = if let Some(selected_file) = self.selected_file.as_ref() {
-            self.dicom_dump.get(selected_file)
-        } else {
-            None
-        };
+    fn get_dicom_dump(&self) -> Option<&Dump> {
+        self.dicom_dump.get(self.selected_file.as_ref()?)
+    }
 
-        if let Some(entry) = entry {
-            entry.as_str()
-        } else {
-            ""
+    /// Update the indexes of the dump lines passing the tag filters if the dump or the filters changed.
+    fn update_visible_lines(&mut self) {
+        let Some(selected_file) = self.selected_file.as_ref() else {
+            self.visible_lines = None;
+            return;
+        };
+        let dump = self.dicom_dump.get(selected_file);
+        let line_count = dump.map_or(0, Dump::line_count);
+        if self
+            .visible_lines
+            .as_ref()
+            .is_some_and(|(path, filters, count, _)| {
+                path == selected_file && *filters == self.tag_filters && *count == line_count
+            })
+        {
+            return;
         }
+
+        let lines = dump.map_or(Vec::new(), |dump| {
+            filter_lines(dump.lines(), &self.tag_filters)
+                .map(|(index, _)| index)
+                .collect()
+        });
+        self.visible_lines = Some((
+            selected_file.clone(),
+            self.tag_filters.clone(),
+            line_count,
+            lines,
+        ));
     }
 
     /// Handle the search in the dicom dump.
     fn handle_search(&mut self, is_forward_search: bool) {
         // Search the dicom dump if there are no results yet.
         if self.search_results.is_none() {
+            let start = Instant::now();
             self.search_results = Some(self.search());
+            self.search_time = Some(start.elapsed());
         }
 
         // Go and fetch the next match.
@@ -107,49 +2584,236 @@ impl TemplateApp {
     /// Handle the dicom file selected by caching the entry.
     fn handle_file_selected(&mut self, node_id: &Path) {
         // Reset the currnent search results.
-        self.selected_file = Some(node_id.to_path_buf());
+        let previous_file = self.selected_file.replace(node_id.to_path_buf());
+        crash::remember_session(&self.session());
         self.search_results = None;
         self.matched_pos = None;
         self.scroll_pos = Some(0);
+        self.expanded_values.clear();
+        tasks::cancel(&mut self.pending_size_breakdown);
 
-        // Get the dicom dump from the cache or get it from the file.
-        self.dicom_dump
-            .entry(node_id.to_path_buf())
-            .or_insert_with(|| {
-                if let Ok(obj) = open_file(node_id) {
-                    let mut out = Vec::new();
-
-                    DumpOptions::new()
-                        .width(256)
-                        .color_mode(dicom_dump::ColorMode::Never)
-                        .no_limit(false)
-                        .no_text_limit(false)
-                        .format(dicom_dump::DumpFormat::Text)
-                        .dump_file_to_with_limits(&mut out, &obj)
-                        .unwrap();
-                    String::from_utf8(out).unwrap()
-                } else {
-                    "".to_string()
+        // Keep the header of the selected file for the summary, and the one of the previous file for the comparison.
+        let selected_obj = open_header(node_id).ok();
+        match previous_file {
+            Some(previous_file) if previous_file != node_id => {
+                self.previous_selection = std::mem::replace(&mut self.selected_obj, selected_obj)
+                    .map(|obj| (previous_file, obj));
+            }
+            _ => self.selected_obj = selected_obj,
+        }
+        self.privacy_mask = None;
+
+        // The file may have been removed or changed since the scan, and its cached dump is stale then.
+        self.selected_file_state = self
+            .dicom_files
+            .iter()
+            .find(|x| x.path() == node_id)
+            .map_or(FileState::Unchanged, ScannedFile::check);
+        if self.selected_file_state != FileState::Unchanged {
+            self.dicom_dump.remove(node_id);
+            if self.dump_source.as_ref().is_some_and(|x| x.path == node_id) {
+                self.dump_source = None;
+            }
+            self.cancel_dump();
+        }
+        if self.selected_file_state == FileState::Missing {
+            return;
+        }
+
+        // Get the dicom dump from the cache or get it from the file, cancelling the dump of the previous selection.
+        if !self.is_dump_pending(node_id) {
+            self.cancel_dump();
+            let is_cached = self.dicom_dump.contains_key(node_id);
+            self.perf.record_dump_cache(is_cached);
+            if !is_cached {
+                self.start_dump(node_id);
+            }
+        }
+    }
+
+    /// Summarize the files of the folder on a worker thread, unless it is summarized.
+    fn handle_dir_selected(&mut self, dir: &Path) {
+        if self.folder_summary.as_ref().is_some_and(|(x, _)| x == dir)
+            || self
+                .pending_folder_summary
+                .as_ref()
+                .is_some_and(|(x, _)| x == dir)
+        {
+            return;
+        }
+
+        if let Some((_, task)) = self.pending_folder_summary.take() {
+            task.cancel();
+        }
+        let files: Vec<PathBuf> = self
+            .dicom_files
+            .iter()
+            .map(|x| x.path())
+            .filter(|x| x.starts_with(dir))
+            .map(Path::to_path_buf)
+            .collect();
+        let name = trf(
+            "Summarize {}",
+            dir.file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string()),
+        );
+        let task = self
+            .tasks
+            .spawn(name, move |context| FolderSummary::collect(&files, context));
+        self.pending_folder_summary = Some((dir.to_path_buf(), task));
+    }
+
+    fn poll_folder_summary(&mut self) {
+        let Some((dir, task)) = self.pending_folder_summary.as_ref() else {
+            return;
+        };
+
+        match task.try_recv() {
+            Ok(summary) => self.folder_summary = Some((dir.clone(), summary)),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.pending_folder_summary = None;
+    }
+
+    /// Take the results of the work on the folders when done on the worker threads.
+    fn poll_background_tasks(&mut self) {
+        self.poll_scan();
+        if let Some((archive, result)) = tasks::poll(&mut self.pending_archive) {
+            self.handle_archive_extracted(&archive, result);
+        }
+        if let Some(reports) = tasks::poll(&mut self.pending_series_check) {
+            self.handle_series_checked(reports);
+        }
+        if let Some((manifest, result)) = tasks::poll(&mut self.pending_checksum) {
+            self.handle_checksum_verified(&manifest, result);
+        }
+        if let Some(query) = tasks::poll(&mut self.pending_same_value) {
+            self.same_value_query = Some(query);
+            self.show_same_value = true;
+        }
+        if let Some(timeline) = tasks::poll(&mut self.pending_timeline) {
+            self.timeline = Some(timeline);
+        }
+        if let Some(breakdown) = tasks::poll(&mut self.pending_size_breakdown) {
+            self.size_breakdown = Some(breakdown);
+        }
+        if let Some(graphs) = tasks::poll(&mut self.pending_study_graphs) {
+            self.study_graphs = Some(graphs);
+        }
+        if let Some((operation, sources, result)) = tasks::poll(&mut self.pending_write) {
+            self.handle_files_written(operation, sources, result);
+        }
+    }
+
+    /// Show the summary of the selected folder.
+    fn folder_summary_ui(&self, ui: &mut egui::Ui, dir: &Path) {
+        ui.heading(dir.display().to_string());
+        match self.folder_summary.as_ref() {
+            Some((x, summary)) if x == dir => summary.ui(ui, self.settings.privacy_mode),
+            _ => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(tr("Reading the files of the folder…"));
+                });
+            }
+        }
+    }
+
+    /// Mask the header of the selected file for the privacy mode, if not done yet.
+    fn update_privacy_mask(&mut self) {
+        if self.settings.privacy_mode
+            && self.privacy_mask.is_none()
+            && let Some(obj) = self.selected_obj.as_ref()
+        {
+            self.privacy_mask = Some(PrivacyMask::new(obj));
+        }
+    }
+
+    fn is_dump_pending(&self, path: &Path) -> bool {
+        self.pending_dump.as_ref().is_some_and(|(x, _)| x == path)
+    }
+
+    /// Cancel the dump in progress, e.g. of a file no longer selected.
+    fn cancel_dump(&mut self) {
+        if let Some((_, task)) = self.pending_dump.take() {
+            task.cancel();
+        }
+    }
+
+    /// Dump the file on a worker thread, reusing the opened file if it is the one. The dump in progress is cancelled.
+    fn start_dump(&mut self, path: &Path) {
+        self.cancel_dump();
+        let source = self.dump_source.take().filter(|x| x.path == path);
+        let style = self.settings.dump_style;
+        let file = path.to_path_buf();
+        let name = trf(
+            "Dump {}",
+            path.file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string()),
+        );
+        let task = self.tasks.spawn(name, move |context| {
+            dump::dump_file(&file, source, style, context.token())
+        });
+        self.pending_dump = Some((path.to_path_buf(), task));
+    }
+
+    /// Take the dump when it is done, keeping the opened file to dump it again in another format.
+    fn poll_dump(&mut self) {
+        let Some((path, task)) = self.pending_dump.as_ref() else {
+            return;
+        };
+
+        match task.try_recv() {
+            Ok(output) => {
+                self.perf.record_dump(output.open_time, output.dump_time);
+                self.dump_source = output.source;
+                self.dicom_dump.insert(path.clone(), output.dump);
+                // The search ran before the dump was there.
+                if self.selected_file.as_ref() == Some(path) {
+                    self.search_results = None;
+                    self.matched_pos = None;
                 }
-            });
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.pending_dump = None;
+    }
+
+    /// Dump the selected file again in the new format, reusing the opened file if it is the selected one.
+    /// The cached dumps of the other files are dropped, as they are in the old format.
+    fn handle_dump_style_changed(&mut self) {
+        self.dicom_dump.clear();
+        self.search_results = None;
+        self.matched_pos = None;
+        self.expanded_values.clear();
+
+        if let Some(selected_file) = self.selected_file.clone() {
+            self.start_dump(&selected_file);
+        }
     }
 
     /// Search the dicom dump for the text.
     fn search(&self) -> Vec<usize> {
-        let text = self.get_dicom_dump();
         let mut results = Vec::new();
-
-        if text.is_empty() {
+        let Some(dump) = self.get_dicom_dump().filter(|x| !x.is_empty()) else {
             return results;
-        }
-
-        let regex = RegexBuilder::new(&regex::escape(&self.search_input))
-            .case_insensitive(true)
-            .build()
-            .unwrap();
+        };
 
-        for (index, line) in text.split("\n").enumerate() {
-            if regex.is_match(line) {
+        let text = self.search_input.to_lowercase();
+        let tag = search::parse_tag(&self.search_input);
+        for (index, line) in filter_lines(dump.lines(), &self.tag_filters) {
+            let is_match = match self.search_scope {
+                SearchScope::Anywhere => dump.line_contains(index, &text),
+                SearchScope::Tag => tag.is_some_and(|x| search::line_has_tag(line, x)),
+                // Most lines don't match at all, so only the matching ones are lowercased to check their values.
+                SearchScope::Value => {
+                    dump.line_contains(index, &text)
+                        && search::line_value(line).to_lowercase().contains(&text)
+                }
+            };
+            if is_match {
                 results.push(index);
             }
         }
@@ -185,13 +2849,34 @@ impl TemplateApp {
         }
     }
 
-    /// Build the UI treeview.
-    /// The dicom files are assumed to be sorted by the paths, so that we will just need to follow the directory up and down.
+    /// Build the UI treeview, with a top-level directory for each root folder.
     fn build_ui_treeview(&self, builder: &mut egui_ltreeview::TreeViewBuilder<'_, PathBuf>) {
-        builder.dir(self.base_dir.clone(), self.base_dir.display().to_string());
-        let mut current_dir = self.base_dir.to_path_buf();
+        for root in &self.roots {
+            self.build_root_treeview(builder, root);
+        }
+    }
+
+    /// Build the UI treeview of a root folder.
+    /// The dicom files are assumed to be sorted depth first, so that we will just need to follow the directory up and down.
+    /// The skipped files are shown greyed out with the reason if enabled.
+    /// The DICOM files have a context menu to copy the command lines of the dcmtk and gdcm tools.
+    fn build_root_treeview(
+        &self,
+        builder: &mut egui_ltreeview::TreeViewBuilder<'_, PathBuf>,
+        root: &Path,
+    ) {
+        builder.dir(root.to_path_buf(), root.display().to_string());
+        let mut current_dir = root.to_path_buf();
 
-        for entry in &self.dicom_files {
+        let files = self
+            .root_scans
+            .get(root)
+            .map_or(&[][..], |x| x.files.as_slice())
+            .iter()
+            .filter(|x| {
+                self.settings.show_skipped_files || !self.skip_reasons.contains_key(x.path())
+            });
+        for entry in files {
             let parent_dir = entry.path().parent().expect("a file should have a parent");
 
             // Go up until the directory of the file is under the current_dir.
@@ -212,32 +2897,138 @@ impl TemplateApp {
             }
 
             // Now add the file.
-            builder.leaf(
-                entry.path().to_path_buf(),
-                entry
-                    .path()
-                    .file_name()
-                    .expect("file entry should have a filename")
-                    .display()
-                    .to_string(),
-            );
+            let name = entry
+                .path()
+                .file_name()
+                .expect("file entry should have a filename")
+                .display()
+                .to_string();
+            let series_warnings = self
+                .series_warnings
+                .get(entry.path())
+                .map(|x| &self.series_reports[*x].warnings);
+            let id = entry.path().to_path_buf();
+            let node = match (self.skip_reasons.get(entry.path()), series_warnings) {
+                (Some(reason), _) => {
+                    builder.node(NodeBuilder::leaf(id).label_ui(move |ui| {
+                        ui.weak(&name).on_hover_text(reason);
+                    }));
+                    continue;
+                }
+                (None, Some(warnings)) => NodeBuilder::leaf(id).label_ui(move |ui| {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {name}"))
+                        .on_hover_text(warnings.join("\n"));
+                }),
+                (None, None) => match self
+                    .dicom_info
+                    .get(entry.path())
+                    .filter(|_| self.settings.color_code_tree)
+                {
+                    Some(info) => {
+                        let icon = if is_non_image_modality(&info.modality) {
+                            "📄 "
+                        } else {
+                            ""
+                        };
+                        let color = self
+                            .settings
+                            .modality_colors
+                            .get(&info.modality)
+                            .map(|[r, g, b]| egui::Color32::from_rgb(*r, *g, *b));
+                        NodeBuilder::leaf(id).label_ui(move |ui| {
+                            let text = egui::RichText::new(format!("{icon}{name}"));
+                            let text = match color {
+                                Some(color) => text.color(color),
+                                None => text,
+                            };
+                            ui.label(text).on_hover_text(&info.modality);
+                        })
+                    }
+                    None => NodeBuilder::leaf(id).label(name),
+                },
+            };
+
+            let path = entry.path();
+            builder.node(node.context_menu(move |ui| {
+                if ui.button(tr("Export study as ZIP")).clicked() {
+                    self.study_zip_request.set(Some(path.to_path_buf()));
+                    ui.close();
+                }
+                ui.menu_button(tr("Copy command"), |ui| {
+                    cli_commands::menu_ui(
+                        ui,
+                        path,
+                        &self.settings.calling_ae_title,
+                        &self.settings.endpoints,
+                        &self.settings.orthanc_servers,
+                    );
+                });
+            }));
         }
 
-        // Go up until the current_dir hits the base_dir.
-        if let Ok(path_diff) = current_dir.strip_prefix(&self.base_dir) {
+        // Go up until the current_dir hits the root.
+        if let Ok(path_diff) = current_dir.strip_prefix(root) {
             for _ in path_diff.components() {
                 builder.close_dir();
             }
         }
 
-        // Close the base dir.
+        // Close the root.
         builder.close_dir();
     }
 }
 
 impl eframe::App for TemplateApp {
+    /// Called by the framework to save the settings before shutdown.
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.perf.begin_frame();
+        self.handle_shortcuts(ctx);
+        self.show_crash_recovery_window(ctx);
+        self.update_check.poll();
+        if let Some(dir) = self.samples.poll() {
+            self.handle_file_open(&dir);
+        }
+        if let Some(dir) = self.orthanc.poll(&mut self.audit_log) {
+            self.handle_folder_pulled(&dir);
+        }
+        if let Some((dir, file)) = self.remote.poll() {
+            if self.roots.contains(&dir) {
+                self.handle_root_refresh(&dir);
+            } else {
+                self.handle_file_open(&dir);
+            }
+            self.selection_after_scan = Some(file);
+        }
+        if let Some(dir) = self.s3.poll() {
+            self.handle_folder_pulled(&dir);
+        }
+        if self.synthetic.poll(&mut self.audit_log).is_some() {
+            self.handle_refresh();
+        }
+        if self.organize.poll(&mut self.audit_log) {
+            self.handle_refresh();
+        }
+        if let Some(files) = self.bulk_edit.poll(&mut self.audit_log) {
+            self.handle_files_edited(&files);
+        }
+        if let Some(files) = self.uid_regen.poll(&mut self.audit_log) {
+            self.handle_files_edited(&files);
+        }
+        if let Some(files) = self.date_shift.poll(&mut self.audit_log) {
+            self.handle_files_edited(&files);
+        }
+        self.poll_dump();
+        self.poll_folder_summary();
+        self.poll_background_tasks();
+        if self.tasks.is_busy() {
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
@@ -247,20 +3038,335 @@ impl eframe::App for TemplateApp {
                 // NOTE: no File->Quit on web pages!
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
-                    ui.menu_button("File", |ui| {
-                        if ui.button("Open").clicked() {
-                            self.file_dialog.pick_directory();
+                    ui.menu_button(tr("File"), |ui| {
+                        if ui
+                            .add(
+                                egui::Button::new(tr("Open"))
+                                    .shortcut_text(ctx.format_shortcut(&OPEN_SHORTCUT)),
+                            )
+                            .clicked()
+                        {
+                            self.open_file_dialog(FileDialogPurpose::OpenDir);
+                        }
+                        if ui.button(tr("Open tar archive")).clicked() {
+                            self.open_file_dialog(FileDialogPurpose::OpenArchive);
+                        }
+                        if ui.button(tr("Open remote folder")).clicked() {
+                            self.show_remote = true;
+                        }
+                        if ui.button(tr("Browse Orthanc")).clicked() {
+                            self.show_orthanc = true;
+                        }
+                        if ui.button(tr("Browse S3 bucket")).clicked() {
+                            self.show_s3 = true;
+                        }
+                        if ui
+                            .add_enabled(
+                                !self.roots.is_empty(),
+                                egui::Button::new(tr("Add folder to workspace")),
+                            )
+                            .clicked()
+                        {
+                            self.open_file_dialog(FileDialogPurpose::AddRoot);
+                        }
+                        let mut refreshed_root = None;
+                        let mut removed_root = None;
+                        ui.add_enabled_ui(self.roots.len() > 1, |ui| {
+                            ui.menu_button(tr("Workspace folders"), |ui| {
+                                for root in &self.roots {
+                                    ui.menu_button(root.display().to_string(), |ui| {
+                                        if ui.button(tr("Refresh")).clicked() {
+                                            refreshed_root = Some(root.clone());
+                                        }
+                                        if ui.button(tr("Remove from workspace")).clicked() {
+                                            removed_root = Some(root.clone());
+                                        }
+                                    });
+                                }
+                            });
+                        });
+                        if let Some(root) = refreshed_root {
+                            self.handle_root_refresh(&root);
+                        }
+                        if let Some(root) = removed_root {
+                            self.handle_root_removed(&root);
                         }
-                        if ui.button("Quit").clicked() {
+                        if ui
+                            .add_enabled(
+                                !self.roots.is_empty(),
+                                egui::Button::new(tr("Refresh"))
+                                    .shortcut_text(ctx.format_shortcut(&REFRESH_SHORTCUT)),
+                            )
+                            .clicked()
+                        {
+                            self.handle_refresh();
+                        }
+                        ui.add_enabled_ui(self.selected_file.is_some(), |ui| {
+                            if ui.button(tr("Export as CSV")).clicked() {
+                                self.open_file_dialog(FileDialogPurpose::ExportCsv);
+                            }
+                            if ui.button(tr("Export as JSON")).clicked() {
+                                self.open_file_dialog(FileDialogPurpose::ExportJson);
+                            }
+                            if ui.button(tr("Export study as FHIR ImagingStudy")).clicked() {
+                                self.open_file_dialog(FileDialogPurpose::ExportFhir);
+                            }
+                            if ui.button(tr("Export study as ZIP")).clicked()
+                                && let Some(selected_file) = self.selected_file.clone()
+                            {
+                                self.handle_study_zip_request(&selected_file);
+                            }
+                        });
+                        ui.separator();
+                        if ui.button(tr("Save session")).clicked() {
+                            self.open_file_dialog(FileDialogPurpose::SaveSession);
+                        }
+                        if ui.button(tr("Load session")).clicked() {
+                            self.open_file_dialog(FileDialogPurpose::LoadSession);
+                        }
+                        ui.separator();
+                        if ui.button(tr("Quit")).clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
                 }
+                ui.menu_button(tr("View"), |ui| {
+                    ui.checkbox(&mut self.show_value_stats, tr("Value statistics"));
+                    ui.checkbox(&mut self.show_size_breakdown, tr("Size breakdown"));
+                    ui.checkbox(&mut self.show_references, tr("References"));
+                    ui.checkbox(&mut self.show_functional_groups, tr("Functional groups"));
+                    ui.separator();
+                    ui.menu_button(tr("Language"), |ui| {
+                        let mut language = self.settings.language.clone();
+                        ui.radio_value(&mut language, None, "English");
+                        for x in i18n::available_languages() {
+                            ui.radio_value(&mut language, Some(x.clone()), x);
+                        }
+                        if language != self.settings.language {
+                            self.set_language(language);
+                        }
+                    });
+                    ui.checkbox(&mut self.show_timeline, tr("Timeline"));
+                    ui.checkbox(&mut self.show_study_graph, tr("Study graph"));
+                    ui.checkbox(&mut self.show_teaching_files, tr("Teaching files"));
+                    ui.checkbox(&mut self.settings.show_hints, tr("Hints"));
+                    ui.checkbox(&mut self.dump_detached, tr("Dump in a separate window"));
+                    ui.checkbox(&mut self.show_history, tr("History"));
+                    ui.checkbox(&mut self.show_tasks, tr("Tasks"));
+                    ui.checkbox(&mut self.show_quick_view, tr("Quick view"));
+                    ui.checkbox(&mut self.settings.privacy_mode, tr("Privacy mode"))
+                        .on_hover_text(tr("Mask the names, the IDs and the birth dates on screen"));
+                    ui.checkbox(
+                        &mut self.show_perf_overlay,
+                        trf(
+                            "Performance overlay ({})",
+                            ctx.format_shortcut(&PERF_OVERLAY_SHORTCUT),
+                        ),
+                    );
+                    ui.checkbox(
+                        &mut self.settings.show_skipped_files,
+                        tr("Show non-DICOM files"),
+                    );
+                    ui.checkbox(
+                        &mut self.show_problems,
+                        trf("Problems ({})", self.scan_problems.len()),
+                    );
+                    ui.checkbox(&mut self.show_settings, tr("Settings"));
+                });
+                ui.menu_button(tr("Tools"), |ui| {
+                    let read_only = self.is_read_only();
+                    ui.add_enabled(
+                        !self.read_only_flag,
+                        egui::Checkbox::new(&mut self.settings.read_only, tr("Read-only")),
+                    )
+                    .on_disabled_hover_text(tr("Set by the --read-only flag"));
+                    ui.separator();
+                    ui.checkbox(&mut self.show_organize, tr("Organize"));
+                    ui.checkbox(&mut self.show_dicomdir, tr("Create DICOMDIR"));
+                    ui.checkbox(&mut self.show_share_bundle, tr("Create share bundle"));
+                    if ui
+                        .add_enabled(
+                            !self.dicom_files.is_empty(),
+                            egui::Button::new(tr("Check series")),
+                        )
+                        .clicked()
+                    {
+                        self.handle_series_check();
+                    }
+                    ui.checkbox(&mut self.show_template_check, tr("Template check"));
+                    if ui.button(tr("Verify checksums")).clicked() {
+                        self.open_file_dialog(FileDialogPurpose::PickManifest);
+                    }
+                    ui.checkbox(&mut self.show_frequency, tr("Value frequency"));
+                    ui.checkbox(&mut self.show_tag_presence, tr("Tag presence"));
+                    ui.checkbox(&mut self.show_bulk_edit, tr("Bulk edit"));
+                    ui.checkbox(&mut self.show_uid_regen, tr("Regenerate UIDs"));
+                    ui.checkbox(&mut self.show_date_shift, tr("Shift dates"));
+                    ui.add_enabled(
+                        !read_only,
+                        egui::Checkbox::new(
+                            &mut self.show_synthetic,
+                            tr("Generate synthetic study"),
+                        ),
+                    );
+                    ui.add_enabled(
+                        !read_only,
+                        egui::Checkbox::new(&mut self.show_corruption, tr("Corrupted copies")),
+                    )
+                    .on_hover_text(tr("Write broken copies of the file to test other systems"));
+                    if let Some(entry) = self.last_undo_entry.as_ref() {
+                        let text = format!(
+                            "{} ({})",
+                            trf("Undo {}", &entry.operation),
+                            trf("{} files", entry.file_count())
+                        );
+                        if ui
+                            .add_enabled(!read_only, egui::Button::new(text))
+                            .on_hover_text(&entry.timestamp)
+                            .clicked()
+                        {
+                            self.handle_undo();
+                            ui.close();
+                        }
+                    }
+                    ui.checkbox(&mut self.show_surface, tr("Export ROI surface"));
+                    ui.add_enabled(
+                        !read_only,
+                        egui::Checkbox::new(&mut self.show_kos, tr("Key object selection")),
+                    );
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            !read_only && self.selected_file.is_some(),
+                            egui::Button::new(tr("Split multi-frame")),
+                        )
+                        .clicked()
+                        && let Some(selected_file) = self.selected_file.clone()
+                    {
+                        tasks::cancel(&mut self.pending_write);
+                        let task = self.tasks.spawn(tr("Split multi-frame"), move |context| {
+                            let result = multiframe::split(&selected_file, context);
+                            ("Split multi-frame", vec![selected_file], result)
+                        });
+                        self.pending_write = Some(task);
+                    }
+                    let files = self.selected_files();
+                    if ui
+                        .add_enabled(
+                            !read_only && files.len() > 1,
+                            egui::Button::new(tr("Merge into multi-frame")),
+                        )
+                        .clicked()
+                    {
+                        tasks::cancel(&mut self.pending_write);
+                        let task = self
+                            .tasks
+                            .spawn(tr("Merge into multi-frame"), move |context| {
+                                let result = multiframe::merge(&files, context).map(|x| vec![x]);
+                                ("Merge into multi-frame", files, result)
+                            });
+                        self.pending_write = Some(task);
+                    }
+                    ui.separator();
+                    ui.add_enabled_ui(self.selected_file.is_some(), |ui| {
+                        ui.menu_button(tr("Copy command"), |ui| {
+                            if let Some(selected_file) = self.selected_file.as_ref() {
+                                cli_commands::menu_ui(
+                                    ui,
+                                    selected_file,
+                                    &self.settings.calling_ae_title,
+                                    &self.settings.endpoints,
+                                    &self.settings.orthanc_servers,
+                                );
+                            }
+                        });
+                        ui.menu_button(tr("Open with"), |ui| {
+                            if self.settings.external_tools.is_empty() {
+                                ui.label(tr("Add external tools in the settings."));
+                            }
+                            for tool in &self.settings.external_tools {
+                                if ui.button(&tool.name).clicked()
+                                    && let Some(selected_file) = self.selected_file.as_ref()
+                                    && let Err(e) =
+                                        tool.launch(selected_file, self.selected_obj.as_deref())
+                                {
+                                    log::error!("Failed to launch {}: {e}", tool.name);
+                                }
+                            }
+                        });
+                    });
+                });
+                ui.menu_button(tr("Scripts"), |ui| {
+                    let scripts = scripting::available_scripts();
+                    if scripts.is_empty() {
+                        ui.label(trf("No scripts in {}", scripting::scripts_dir().display()));
+                    }
+                    for script in scripts {
+                        let name = script
+                            .file_stem()
+                            .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                        if ui.button(name).clicked() {
+                            self.handle_run_script(&script);
+                        }
+                    }
+                });
+                ui.menu_button(tr("Help"), |ui| {
+                    if let Some(release) = self.update_check.available.as_ref() {
+                        ui.hyperlink_to(
+                            trf("New version {} available", &release.version),
+                            &release.url,
+                        );
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.update_check.is_checking(),
+                            egui::Button::new(tr("Check for updates")),
+                        )
+                        .clicked()
+                    {
+                        self.update_check.start(&mut self.tasks);
+                    }
+                    ui.separator();
+                    ui.checkbox(
+                        &mut self.diagnostic_deidentify,
+                        tr("De-identify the dump in the diagnostic bundle"),
+                    );
+                    ui.checkbox(
+                        &mut self.diagnostic_screenshot_included,
+                        tr("Include a screenshot in the privacy mode"),
+                    );
+                    if ui.button(tr("Capture diagnostic bundle")).clicked() {
+                        if self.diagnostic_screenshot_included {
+                            // The screenshot is of the next frame, which is then drawn in the privacy mode.
+                            self.diagnostic_privacy_mode =
+                                Some(std::mem::replace(&mut self.settings.privacy_mode, true));
+                            ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(
+                                egui::UserData::default(),
+                            ));
+                            self.diagnostic_requested = true;
+                        } else {
+                            self.diagnostic_screenshot = None;
+                            self.open_file_dialog(FileDialogPurpose::DiagnosticBundle);
+                        }
+                        ui.close();
+                    }
+                });
             });
 
             ui.horizontal(|ui| {
-                if ui.button("📂").clicked() {
-                    self.file_dialog.pick_directory();
+                let response = ui.button("📂").on_hover_text(format!(
+                    "{} ({})",
+                    tr("Open folder"),
+                    ctx.format_shortcut(&OPEN_SHORTCUT)
+                ));
+                response.widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Button, true, tr("Open folder"))
+                });
+                if response.clicked() {
+                    self.open_file_dialog(FileDialogPurpose::OpenDir);
+                }
+                if self.is_read_only() {
+                    ui.colored_label(ui.visuals().warn_fg_color, tr("Read-only"));
                 }
             });
 
@@ -268,11 +3374,140 @@ impl eframe::App for TemplateApp {
             self.file_dialog.update(ctx);
             // Check if the user picked a file.
             if let Some(path) = self.file_dialog.take_picked() {
-                self.handle_file_open(&path);
+                self.handle_file_dialog_picked(ctx, &path);
             }
         });
 
-        if !self.dicom_files.is_empty() {
+        if self.show_value_stats {
+            self.show_value_stats_window(ctx);
+        }
+        if self.show_size_breakdown {
+            self.show_size_breakdown_window(ctx);
+        }
+        if self.show_references {
+            self.show_references_window(ctx);
+        }
+        if self.show_settings {
+            self.show_settings_window(ctx);
+        }
+        if self.show_tasks {
+            egui::Window::new(tr("Tasks"))
+                .open(&mut self.show_tasks)
+                .show(ctx, |ui| self.tasks.ui(ui));
+        }
+        if self.show_problems {
+            self.show_problems_window(ctx);
+        }
+        if self.show_series_check {
+            self.show_series_check_window(ctx);
+        }
+        if self.show_template_check {
+            self.show_template_check_window(ctx);
+        }
+        if self.show_frequency {
+            self.show_frequency_window(ctx);
+        }
+        if self.show_tag_presence {
+            self.show_tag_presence_window(ctx);
+        }
+        if self.show_bulk_edit {
+            self.show_bulk_edit_window(ctx);
+        }
+        if self.show_uid_regen {
+            self.show_uid_regen_window(ctx);
+        }
+        if self.show_date_shift {
+            self.show_date_shift_window(ctx);
+        }
+        if self.show_synthetic && !self.is_read_only() {
+            self.show_synthetic_window(ctx);
+        }
+        if self.show_corruption && !self.is_read_only() {
+            self.show_corruption_window(ctx);
+        }
+        if self.show_orthanc {
+            self.show_orthanc_window(ctx);
+        }
+        if self.show_remote {
+            self.show_remote_window(ctx);
+        }
+        if self.show_s3 {
+            self.show_s3_window(ctx);
+        }
+        if self.show_same_value {
+            self.show_same_value_window(ctx);
+        }
+        if self.show_timeline && !self.dicom_files.is_empty() {
+            self.show_timeline_window(ctx);
+        }
+        if self.show_study_graph {
+            self.show_study_graph_window(ctx);
+        }
+        if self.show_teaching_files {
+            self.show_teaching_files_window(ctx);
+        }
+        if self.diagnostic_requested {
+            self.handle_diagnostic_screenshot(ctx);
+        }
+        if self.show_history {
+            egui::Window::new(tr("History"))
+                .open(&mut self.show_history)
+                .show(ctx, |ui| self.audit_log.ui(ui));
+        }
+        if self.show_organize {
+            self.show_organize_window(ctx);
+        }
+        if self.show_dicomdir {
+            self.show_dicomdir_window(ctx);
+        }
+        if let Some(file) = self.study_zip_request.take() {
+            self.handle_study_zip_request(&file);
+        }
+        if self.show_study_zip {
+            self.show_study_zip_window(ctx);
+        }
+        if self.show_share_bundle {
+            self.show_share_bundle_window(ctx);
+        }
+        if self.show_quick_view {
+            self.show_quick_view_window(ctx);
+        }
+        if self.show_surface {
+            self.show_surface_window(ctx);
+        }
+        if self.show_kos && !self.is_read_only() {
+            self.show_kos_window(ctx);
+        }
+
+        if self.show_functional_groups
+            && let Some(selected_file) = self.selected_file.as_ref()
+        {
+            egui::Window::new(tr("Functional groups"))
+                .open(&mut self.show_functional_groups)
+                .show(ctx, |ui| self.functional_groups.ui(ui, selected_file));
+        }
+
+        if let Some(output) = self.script_output.as_ref() {
+            let mut is_open = true;
+            egui::TopBottomPanel::bottom("script output")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(tr("Script output"));
+                        if ui.button(tr("Close")).clicked() {
+                            is_open = false;
+                        }
+                    });
+                    egui::ScrollArea::both().show(ui, |ui| {
+                        ui.monospace(output);
+                    });
+                });
+            if !is_open {
+                self.script_output = None;
+            }
+        }
+
+        if !self.dicom_files.is_empty() || self.pending_scan.is_some() {
             egui::SidePanel::left(egui::Id::new("tree view"))
                 .resizable(true)
                 .show(ctx, |ui| {
@@ -281,18 +3516,43 @@ impl eframe::App for TemplateApp {
                             egui::scroll_area::ScrollBarVisibility::VisibleWhenNeeded,
                         )
                         .show(ui, |ui| {
+                            if self.pending_scan.is_some() {
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label(tr("Scanning the folders…"));
+                                });
+                            }
+                            if self.settings.show_hints {
+                                onboarding::hint(
+                                    ui,
+                                    "Select files to show their headers, right-click for more actions.",
+                                );
+                            }
                             let id = ui.make_persistent_id("Names tree view");
+                            // The state is kept by the app, so that the selection can be changed from other views.
+                            let mut tree_state = std::mem::take(&mut self.tree_state);
                             let (_response, actions) = TreeView::new(id)
                                 // .override_indent(Some(2.0))
-                                .show(ui, |builder| {
+                                .show_state(ui, &mut tree_state, |builder| {
                                     self.build_ui_treeview(builder);
                                 });
+                            self.tree_state = tree_state;
 
                             for action in actions.iter() {
                                 match action {
                                     Action::SetSelected(nodes) => {
+                                        self.selected_nodes = nodes.clone();
+                                        self.selected_dir = match nodes.as_slice() {
+                                            [node] if node.is_dir() => Some(node.clone()),
+                                            _ => None,
+                                        };
+                                        if let Some(dir) = self.selected_dir.clone() {
+                                            self.handle_dir_selected(&dir);
+                                        }
                                         nodes.iter().for_each(|node_id| {
-                                            if node_id.is_file() {
+                                            if node_id.is_file()
+                                                && !self.skip_reasons.contains_key(node_id)
+                                            {
                                                 self.handle_file_selected(node_id);
                                             }
                                         });
@@ -305,71 +3565,31 @@ impl eframe::App for TemplateApp {
                         });
                 });
 
-            egui::CentralPanel::default().show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Search:");
-                    let response = ui.add(
-                        egui::TextEdit::singleline(&mut self.search_input)
-                            .desired_width(f32::INFINITY),
-                    );
-                    if response.changed() {
-                        self.matched_pos = None;
-                        self.search_results = None;
-                    }
-                    if response.lost_focus() && ui.input(|x| x.key_pressed(egui::Key::Enter)) {
-                        self.handle_search(true);
-                        response.request_focus();
-                    }
-                });
-
-                ui.horizontal(|ui| {
-                    if ui.button("Prev").clicked() {
-                        self.handle_search(false);
-                    }
-                    if ui.button("Next").clicked() && !self.search_input.is_empty() {
-                        self.handle_search(true);
-                    }
-                    if ui.button("Clear").clicked() {
-                        self.search_input.clear();
-                        self.matched_pos = None;
-                        self.search_results = None;
-                    }
-
-                    let search_status = self
-                        .search_results
-                        .as_ref()
-                        .map_or("".into(), |x| format!("{} matches", x.len()));
-
-                    ui.label(egui::RichText::new(search_status).color(egui::Color32::BLUE));
-                });
-
-                ui.separator();
-
-                egui::ScrollArea::both().show(ui, |ui| {
-                    for (index, line) in self.get_dicom_dump().split("\n").enumerate() {
-                        let rich_text = egui::RichText::new(line).monospace();
-
-                        let response = if self.matched_pos.is_some_and(|x| x == index) {
-                            egui::Label::new(
-                                rich_text
-                                    .strong()
-                                    .background_color(egui::Color32::LIGHT_GRAY),
-                            )
-                            .wrap_mode(egui::TextWrapMode::Extend)
-                            .ui(ui)
-                        } else {
-                            egui::Label::new(rich_text).ui(ui)
-                        };
-
-                        if self.scroll_pos.is_some_and(|x| x == index) {
-                            response.scroll_to_me(None);
-                        }
+            if self.dump_detached {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.label(tr("The dump is shown in a separate window."));
+                    if ui.button(tr("Dock the dump")).clicked() {
+                        self.dump_detached = false;
                     }
-                    self.scroll_pos = None;
                 });
-            });
+                self.show_detached_dump(ctx);
+            } else {
+                egui::CentralPanel::default().show(ctx, |ui| self.dump_panel_ui(ctx, ui));
+            }
         } else {
-            egui::CentralPanel::default().show(ctx, |_ui| {});
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let open_shortcut = ctx.format_shortcut(&OPEN_SHORTCUT);
+                if onboarding::welcome_ui(ui, &mut self.samples, &mut self.tasks, &open_shortcut) {
+                    self.open_file_dialog(FileDialogPurpose::OpenDir);
+                }
+            });
+        }
+
+        self.perf.end_frame();
+        if self.show_perf_overlay {
+            self.perf.ui(ctx, self.search_time);
+            // Keep the frame times current while the overlay is shown.
+            ctx.request_repaint_after(Duration::from_millis(500));
         }
     }
 }
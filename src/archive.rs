@@ -0,0 +1,170 @@
+//! Reading of tar archives, e.g. the TCIA downloads, by extracting the members into a cache folder to be browsed.
+//! The gzip-compressed archives, `.tar.gz` or `.tgz`, are decompressed while they are read.
+
+use crate::tasks::CancelToken;
+use flate2::read::MultiGzDecoder;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Error, Read, Result};
+use std::path::{Component, Path, PathBuf};
+
+const BLOCK_SIZE: u64 = 512;
+
+/// Get the folder in the temp dir to extract the archive into, named after the archive and keyed by its real path,
+/// size and modification time, so that the archives of the same name, or an archive replaced since, don't share it.
+pub fn cache_dir(archive: &Path) -> PathBuf {
+    let name = archive
+        .file_name()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string());
+
+    let mut hasher = DefaultHasher::new();
+    std::fs::canonicalize(archive)
+        .unwrap_or_else(|_| archive.to_path_buf())
+        .hash(&mut hasher);
+    if let Ok(metadata) = std::fs::metadata(archive) {
+        metadata.len().hash(&mut hasher);
+        metadata.modified().ok().hash(&mut hasher);
+    }
+
+    std::env::temp_dir()
+        .join("rsdicombrowser")
+        .join("archives")
+        .join(format!("{name}-{:016x}", hasher.finish()))
+}
+
+/// Get the text of a header field, up to the first NUL.
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// Parse the size field, in octal or in the base-256 encoding of the large sizes.
+fn parse_size(bytes: &[u8]) -> Result<u64> {
+    if bytes[0] & 0x80 != 0 {
+        return Ok(bytes[1..]
+            .iter()
+            .fold(u64::from(bytes[0] & 0x7F), |acc, x| {
+                (acc << 8) | u64::from(*x)
+            }));
+    }
+
+    let text = field(bytes);
+    u64::from_str_radix(text.trim(), 8)
+        .map_err(|_| Error::other(format!("invalid member size {text:?}")))
+}
+
+/// Get the path of the member under the folder, keeping only the normal components so that nothing is written outside.
+fn member_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let components: Vec<_> = Path::new(name)
+        .components()
+        .filter_map(|x| match x {
+            Component::Normal(x) => Some(x),
+            _ => None,
+        })
+        .collect();
+
+    (!components.is_empty()).then(|| {
+        components
+            .iter()
+            .fold(dir.to_path_buf(), |acc, x| acc.join(x))
+    })
+}
+
+/// Get the path given by the records of a pax extended header, e.g. `30 path=some/long/name.dcm\n`.
+fn pax_path(records: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(records)
+        .lines()
+        .filter_map(|x| x.split_once(' ').map(|(_, record)| record))
+        .find_map(|x| x.strip_prefix("path=").map(str::to_string))
+}
+
+/// Read the data of the member into memory, e.g. the long name of the next member.
+fn read_data(reader: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(size).read_to_end(&mut data)?;
+    skip_padding(reader, size)?;
+    Ok(data)
+}
+
+/// Skip to the end of the block after the member data.
+fn skip_padding(reader: &mut impl Read, size: u64) -> Result<()> {
+    let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+    std::io::copy(&mut reader.by_ref().take(padding), &mut std::io::sink())?;
+    Ok(())
+}
+
+/// Skip the data of the member.
+fn skip_data(reader: &mut impl Read, size: u64) -> Result<()> {
+    std::io::copy(&mut reader.by_ref().take(size), &mut std::io::sink())?;
+    skip_padding(reader, size)
+}
+
+/// Extract the regular files of the tar archive into the folder, decompressing it if it is gzip-compressed.
+/// Returns the number of files extracted. Stops between the members if cancelled.
+pub fn extract_tar(archive: &Path, dir: &Path, token: &CancelToken) -> Result<usize> {
+    let mut file = BufReader::new(std::fs::File::open(archive)?);
+    if file.fill_buf()?.starts_with(&[0x1F, 0x8B]) {
+        extract_members(BufReader::new(MultiGzDecoder::new(file)), dir, token)
+    } else {
+        extract_members(file, dir, token)
+    }
+}
+
+/// Extract the regular files of the uncompressed tar stream into the folder.
+fn extract_members(mut reader: impl Read, dir: &Path, token: &CancelToken) -> Result<usize> {
+    let mut header = [0u8; BLOCK_SIZE as usize];
+    let mut long_name = None;
+    let mut count = 0;
+
+    loop {
+        if token.is_cancelled() {
+            return Err(Error::other("the extraction was cancelled"));
+        }
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // Some archives are cut without the end blocks.
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        // The archive ends with zero blocks.
+        if header.iter().all(|x| *x == 0) {
+            break;
+        }
+        let size = parse_size(&header[124..136])?;
+        match header[156] {
+            // The GNU long name of the next member.
+            b'L' => long_name = Some(field(&read_data(&mut reader, size)?)),
+            // The pax extended header of the next member.
+            b'x' => long_name = pax_path(&read_data(&mut reader, size)?).or(long_name),
+            b'0' | 0 | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let prefix = field(&header[345..500]);
+                    let name = field(&header[..100]);
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{prefix}/{name}")
+                    }
+                });
+                let Some(output) = member_path(dir, &name) else {
+                    skip_data(&mut reader, size)?;
+                    continue;
+                };
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut file = std::fs::File::create(&output)?;
+                std::io::copy(&mut reader.by_ref().take(size), &mut file)?;
+                skip_padding(&mut reader, size)?;
+                count += 1;
+            }
+            // The directories are created with their files, and the links and devices are skipped.
+            _ => {
+                long_name = None;
+                skip_data(&mut reader, size)?;
+            }
+        }
+    }
+
+    Ok(count)
+}
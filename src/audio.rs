@@ -0,0 +1,244 @@
+//! Decoding of the audio waveforms, e.g. of the Basic Voice Audio instances, into 16-bit PCM WAV files, and their
+//! playback in the app.
+
+use crate::i18n::tr;
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::{InMemDicomObject, open_file};
+use rodio::buffer::SamplesBuffer;
+use rodio::{OutputStream, Sink};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Decode a G.711 mu-law sample.
+fn mu_law(x: u8) -> i16 {
+    let x = !x;
+    let exponent = (x >> 4) & 0x07;
+    let mantissa = i16::from(x & 0x0F);
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+
+    if x & 0x80 != 0 { -magnitude } else { magnitude }
+}
+
+/// Decode a G.711 A-law sample.
+fn a_law(x: u8) -> i16 {
+    let x = x ^ 0x55;
+    let exponent = (x >> 4) & 0x07;
+    let mantissa = i16::from(x & 0x0F);
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 8,
+        _ => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+
+    if x & 0x80 != 0 { magnitude } else { -magnitude }
+}
+
+fn value<T: std::str::FromStr>(item: &InMemDicomObject, tag: Tag) -> Result<T> {
+    item.element(tag)
+        .map_err(Error::other)?
+        .to_str()
+        .map_err(Error::other)?
+        .trim()
+        .parse()
+        .map_err(|_| Error::other(format!("invalid value of {tag}")))
+}
+
+/// Convert the waveform samples to 16-bit PCM by the sample interpretation.
+fn decode_samples(data: &[u8], interpretation: &str, bits_allocated: u16) -> Result<Vec<i16>> {
+    let samples = match (interpretation, bits_allocated) {
+        ("SB", 8) => data.iter().map(|x| i16::from(*x as i8) << 8).collect(),
+        ("UB", 8) => data.iter().map(|x| (i16::from(*x) - 128) << 8).collect(),
+        ("MB", 8) => data.iter().map(|x| mu_law(*x)).collect(),
+        ("AB", 8) => data.iter().map(|x| a_law(*x)).collect(),
+        ("SS", 16) => data
+            .chunks_exact(2)
+            .map(|x| i16::from_le_bytes([x[0], x[1]]))
+            .collect(),
+        ("US", 16) => data
+            .chunks_exact(2)
+            .map(|x| (i32::from(u16::from_le_bytes([x[0], x[1]])) - 32768) as i16)
+            .collect(),
+        _ => {
+            return Err(Error::other(format!(
+                "unsupported waveform samples {interpretation} of {bits_allocated} bits"
+            )));
+        }
+    };
+
+    Ok(samples)
+}
+
+/// Write the interleaved samples as a WAV file.
+fn wav(samples: &[i16], channels: u16, sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    // PCM
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * u32::from(channels) * 2).to_le_bytes());
+    out.extend_from_slice(&(channels * 2).to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    out
+}
+
+/// The decoded samples of a waveform, interleaved by channel.
+struct Samples {
+    samples: Vec<i16>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+/// Decode the first multiplex group of the waveform sequence.
+fn decode(obj: &InMemDicomObject) -> Result<Samples> {
+    let group = obj
+        .element(tags::WAVEFORM_SEQUENCE)
+        .map_err(Error::other)?
+        .items()
+        .and_then(|x| x.first())
+        .ok_or_else(|| Error::other("no waveform multiplex group"))?;
+
+    let channels: u16 = value(group, tags::NUMBER_OF_WAVEFORM_CHANNELS)?;
+    let sampling_frequency: f64 = value(group, tags::SAMPLING_FREQUENCY)?;
+    let bits_allocated: u16 = value(group, tags::WAVEFORM_BITS_ALLOCATED)?;
+    let interpretation: String = value(group, tags::WAVEFORM_SAMPLE_INTERPRETATION)?;
+    let data = group
+        .element(tags::WAVEFORM_DATA)
+        .map_err(Error::other)?
+        .to_bytes()
+        .map_err(Error::other)?;
+    if channels == 0 {
+        return Err(Error::other("no waveform channels"));
+    }
+
+    let sample_rate = sampling_frequency.round() as u32;
+    if sample_rate == 0 {
+        return Err(Error::other("invalid sampling frequency"));
+    }
+
+    Ok(Samples {
+        samples: decode_samples(&data, &interpretation, bits_allocated)?,
+        channels,
+        sample_rate,
+    })
+}
+
+/// Decode the first multiplex group of the waveform sequence into a WAV file.
+pub fn to_wav(obj: &InMemDicomObject) -> Result<Vec<u8>> {
+    let x = decode(obj)?;
+    Ok(wav(&x.samples, x.channels, x.sample_rate))
+}
+
+/// Format the time as minutes and seconds, e.g. `1:05`.
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The playback of the audio waveform of a file on the default output device.
+pub struct AudioPlayer {
+    pub path: PathBuf,
+    samples: Samples,
+    duration: Duration,
+    // The stream has to be kept for the sink to play.
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+impl AudioPlayer {
+    /// Decode the audio of the file, and start playing it.
+    pub fn new(path: &Path) -> Result<Self> {
+        let obj = open_file(path).map_err(Error::other)?;
+        let samples = decode(&obj)?;
+        let frames = samples.samples.len() / usize::from(samples.channels);
+        let duration = Duration::from_secs_f64(frames as f64 / f64::from(samples.sample_rate));
+
+        let (stream, handle) = OutputStream::try_default().map_err(Error::other)?;
+        let sink = Sink::try_new(&handle).map_err(Error::other)?;
+        let player = Self {
+            path: path.to_path_buf(),
+            samples,
+            duration,
+            _stream: stream,
+            sink,
+        };
+        player.queue();
+        Ok(player)
+    }
+
+    /// Queue the whole waveform on the sink.
+    fn queue(&self) {
+        self.sink.append(SamplesBuffer::new(
+            self.samples.channels,
+            self.samples.sample_rate,
+            self.samples.samples.clone(),
+        ));
+    }
+
+    /// Move to the time, queueing the waveform again if it was played to the end.
+    fn seek(&self, time: Duration) {
+        if self.sink.empty() {
+            self.queue();
+        }
+        if let Err(e) = self.sink.try_seek(time) {
+            log::error!("Failed to seek the audio: {e}");
+        }
+    }
+
+    /// Show the play and pause button, the position slider and the time.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let is_playing = !self.sink.is_paused() && !self.sink.empty();
+        if is_playing {
+            // Keep moving the slider while playing.
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        ui.horizontal(|ui| {
+            let label = if is_playing { "⏸" } else { "▶" };
+            let tooltip = if is_playing { "Pause" } else { "Play" };
+            if ui.button(label).on_hover_text(tr(tooltip)).clicked() {
+                if is_playing {
+                    self.sink.pause();
+                } else {
+                    if self.sink.empty() {
+                        self.queue();
+                    }
+                    self.sink.play();
+                }
+            }
+            if ui.button("⏹").on_hover_text(tr("Stop")).clicked() {
+                self.sink.pause();
+                self.seek(Duration::ZERO);
+            }
+
+            let position = if self.sink.empty() {
+                self.duration
+            } else {
+                self.sink.get_pos().min(self.duration)
+            };
+            let mut seconds = position.as_secs_f64();
+            let slider = egui::Slider::new(&mut seconds, 0.0..=self.duration.as_secs_f64())
+                .show_value(false);
+            if ui.add(slider).changed() {
+                self.seek(Duration::from_secs_f64(seconds));
+            }
+            ui.monospace(format!(
+                "{} / {}",
+                format_time(position),
+                format_time(self.duration)
+            ));
+        });
+    }
+}
@@ -0,0 +1,112 @@
+//! Append-only audit trail of the operations writing files, kept as JSON lines in the data folder of the user.
+
+use crate::i18n::tr;
+use crate::settings::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// An operation which wrote files.
+#[derive(Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub user: String,
+    pub operation: String,
+    pub sources: Vec<PathBuf>,
+    pub targets: Vec<PathBuf>,
+}
+
+/// Get the path of the audit log file.
+pub fn audit_log_path() -> PathBuf {
+    app_data_dir("audit.jsonl")
+}
+
+/// Get the name of the user running the app.
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// The audit log, with the entries recorded so far.
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Load the entries of the audit log file. Lines which can't be parsed are skipped.
+    pub fn load() -> Self {
+        let entries = std::fs::File::open(audit_log_path())
+            .map(|file| {
+                std::io::BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str(&line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { entries }
+    }
+
+    /// Record the operation and append it to the audit log file.
+    pub fn record(&mut self, operation: &str, sources: Vec<PathBuf>, targets: Vec<PathBuf>) {
+        let entry = AuditEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            user: current_user(),
+            operation: operation.to_string(),
+            sources,
+            targets,
+        };
+
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(audit_log_path())
+            .and_then(|mut file| {
+                let line = serde_json::to_string(&entry)?;
+                writeln!(file, "{line}")
+            });
+        if let Err(e) = result {
+            log::error!("Failed to write the audit log: {e}");
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// Show the entries, the latest first.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        ui.label(audit_log_path().display().to_string());
+        ui.separator();
+
+        let paths = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|x| x.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("audit grid").striped(true).show(ui, |ui| {
+                ui.strong(tr("Time"));
+                ui.strong(tr("User"));
+                ui.strong(tr("Operation"));
+                ui.strong(tr("Sources"));
+                ui.strong(tr("Targets"));
+                ui.end_row();
+
+                for entry in self.entries.iter().rev() {
+                    ui.monospace(&entry.timestamp);
+                    ui.label(&entry.user);
+                    ui.label(tr(&entry.operation));
+                    ui.label(entry.sources.len().to_string())
+                        .on_hover_text(paths(&entry.sources));
+                    ui.label(entry.targets.len().to_string())
+                        .on_hover_text(paths(&entry.targets));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,455 @@
+//! Editing the value of one tag across the selected files, e.g. a wrong StudyDescription in the 400 instances of a
+//! study. The edited files are written as copies into a folder, or in place with the originals kept in the undo journal.
+//! All the files are written to temporary files first and then renamed over the targets, so that a failed write never
+//! leaves a half-written file behind, and a file which can't be edited stops the edit before any file is overwritten.
+//! The preview and the edit run on the worker threads, as a study can have thousands of files.
+
+use crate::audit::AuditLog;
+use crate::dataset::{check_text_value, is_binary_vr, open_header, tag_keyword, text_element};
+use crate::frequency::value_of;
+use crate::i18n::{tr, trf};
+use crate::organize::avoid_collision;
+use crate::tasks::{self, Task, TaskContext, TaskPool};
+use crate::undo::UndoRecorder;
+use dicom::core::{DataDictionary, Tag, VR};
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::{DefaultDicomObject, open_file};
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+use std::sync::{Arc, Mutex};
+
+/// What the app should do after a window editing the files is shown.
+pub enum EditAction {
+    None,
+    PickTarget,
+    /// The files were written, in place or as copies.
+    FilesWritten(Vec<PathBuf>),
+}
+
+/// The files written by an edit so far, shared with the task writing them, so that the files written before a failure
+/// or a cancellation are audited too.
+#[derive(Default)]
+pub struct Written {
+    /// The operation recorded in the audit log.
+    pub operation: String,
+    /// The source and the target of each file written.
+    pub files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Get the temporary file the target is written to, before it is renamed over the target.
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string());
+    target.with_file_name(format!(".{name}.tmp"))
+}
+
+/// Edit the files into temporary files next to their targets, as copies into the folder, or in place if none is given.
+/// Returns the source and the target of each file. No target is touched yet, and the temporary files are removed if
+/// any file fails to open or to edit, or if the task is cancelled.
+pub fn edit_to_temp_files(
+    files: &[PathBuf],
+    output_dir: Option<&Path>,
+    context: &TaskContext,
+    mut edit: impl FnMut(&mut DefaultDicomObject) -> Result<()>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if let Some(dir) = output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut taken = HashSet::new();
+    let mut edits = Vec::new();
+
+    let mut result = Ok(());
+    for (index, file) in files.iter().enumerate() {
+        if context.is_cancelled() {
+            result = Err(Error::other(tr("The edit was cancelled.")));
+            break;
+        }
+        context.set_progress(index as f32 / files.len() as f32);
+
+        let target = match output_dir {
+            Some(dir) => {
+                let name = file.file_name().unwrap_or_default();
+                let target = avoid_collision(dir.join(name), &taken);
+                taken.insert(target.clone());
+                target
+            }
+            None => file.clone(),
+        };
+        let temp = temp_path(&target);
+        edits.push((file.clone(), target));
+
+        let edited = open_file(file).map_err(Error::other).and_then(|mut obj| {
+            edit(&mut obj)?;
+            obj.write_to_file(&temp).map_err(Error::other)
+        });
+        if let Err(e) = edited {
+            result = Err(Error::other(format!("{}: {e}", file.display())));
+            break;
+        }
+    }
+
+    match result {
+        Ok(()) => Ok(edits),
+        Err(e) => {
+            for (_, target) in &edits {
+                let _ = std::fs::remove_file(temp_path(target));
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Rename the temporary files over the targets, keeping the originals of the files edited in place in the undo journal
+/// under the name of the operation. Each file renamed is added to `written`. The renames aren't cancelled, so that the
+/// files are either all written or stopped by a failure.
+pub fn rename_edited(
+    edits: &[(PathBuf, PathBuf)],
+    in_place_operation: Option<&str>,
+    written: &Mutex<Written>,
+) -> Result<()> {
+    let rename = || -> Result<()> {
+        let mut recorder = in_place_operation.map(UndoRecorder::begin).transpose()?;
+        for (file, target) in edits {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.backup(file)?;
+            }
+            std::fs::rename(temp_path(target), target)
+                .map_err(|e| Error::other(format!("{}: {e}", target.display())))?;
+            written
+                .lock()
+                .unwrap_or_else(|x| x.into_inner())
+                .files
+                .push((file.clone(), target.clone()));
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.written(target)?;
+            }
+        }
+        Ok(())
+    };
+    let result = rename();
+
+    // The temporary files left by a failure are removed, the others were renamed.
+    for (_, target) in edits {
+        let _ = std::fs::remove_file(temp_path(target));
+    }
+    result
+}
+
+/// Edit the files and write them, as copies into the folder, or in place if none is given. All the files are edited
+/// into temporary files first, and only then renamed over the targets.
+pub fn write_edited(
+    files: &[PathBuf],
+    output_dir: Option<&Path>,
+    operation: &str,
+    written: &Mutex<Written>,
+    context: &TaskContext,
+    edit: impl FnMut(&mut DefaultDicomObject) -> Result<()>,
+) -> Result<()> {
+    let edits = edit_to_temp_files(files, output_dir, context, edit)?;
+    rename_edited(&edits, output_dir.is_none().then_some(operation), written)
+}
+
+/// An edit of the files running in the background, with the files written so far.
+pub struct PendingEdit {
+    /// The task returns a note to add to the status once the files are written.
+    task: Task<Result<String>>,
+    written: Arc<Mutex<Written>>,
+}
+
+impl PendingEdit {
+    /// Start the edit on a worker thread, named after the operation.
+    pub fn spawn(
+        tasks: &mut TaskPool,
+        operation: String,
+        edit: impl FnOnce(&TaskContext, &Mutex<Written>) -> Result<String> + Send + 'static,
+    ) -> Self {
+        let written = Arc::new(Mutex::new(Written {
+            operation: operation.clone(),
+            files: Vec::new(),
+        }));
+        let shared = written.clone();
+        let task = tasks.spawn(operation, move |context| edit(context, &shared));
+        Self { task, written }
+    }
+
+    /// Take the result of the edit if it is done, recording the files written in the audit log, including those written
+    /// before a failure or a cancellation. Returns the status to show, and the files written.
+    pub fn poll(
+        pending: &mut Option<Self>,
+        audit_log: &mut AuditLog,
+    ) -> Option<(String, Vec<PathBuf>)> {
+        let result = match pending.as_ref()?.task.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other(tr("The edit was cancelled."))),
+        };
+        let pending = pending.take()?;
+        let written =
+            std::mem::take(&mut *pending.written.lock().unwrap_or_else(|x| x.into_inner()));
+
+        if written.files.is_empty() {
+            return Some((
+                result.err().map_or(String::new(), |e| e.to_string()),
+                Vec::new(),
+            ));
+        }
+        let (sources, targets): (Vec<PathBuf>, Vec<PathBuf>) = written.files.into_iter().unzip();
+        audit_log.record(&written.operation, sources, targets.clone());
+        let status = match result {
+            Ok(note) => format!("{} {note}", trf("{} files written.", targets.len())),
+            Err(e) => format!("{} {e}", trf("{} files written.", targets.len())),
+        };
+        Some((status.trim_end().to_string(), targets))
+    }
+}
+
+/// Where the edited files are written: as copies into a folder, or in place.
+#[derive(Default)]
+pub struct EditOutput {
+    pub in_place: bool,
+    pub target_dir: Option<PathBuf>,
+}
+
+impl EditOutput {
+    /// Show the choice as rows of a grid. Returns true if the target folder should be picked.
+    pub fn grid_ui(&mut self, ui: &mut egui::Ui, read_only: bool) -> bool {
+        let mut pick_target = false;
+        if read_only {
+            self.in_place = false;
+        }
+
+        ui.label(tr("Output"));
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.in_place, false, tr("Copies in a folder"));
+            ui.add_enabled_ui(!read_only, |ui| {
+                ui.radio_value(&mut self.in_place, true, tr("In place"))
+                    .on_hover_text(tr("The originals are kept in the undo journal"))
+                    .on_disabled_hover_text(tr("Disabled in read-only mode"));
+            });
+        });
+        ui.end_row();
+
+        if !self.in_place {
+            ui.label(tr("Target folder"));
+            ui.horizontal(|ui| {
+                let target = self
+                    .target_dir
+                    .as_ref()
+                    .map_or(tr("None"), |x| x.display().to_string());
+                ui.monospace(target);
+                pick_target = ui.button(tr("Browse")).clicked();
+            });
+            ui.end_row();
+        }
+
+        pick_target
+    }
+
+    /// Get the folder to write the copies into, or none to write in place. None if the folder isn't picked yet.
+    pub fn output_dir(&self) -> Option<Option<PathBuf>> {
+        if self.in_place {
+            Some(None)
+        } else {
+            self.target_dir.clone().map(Some)
+        }
+    }
+}
+
+/// Get the value representation to write the tag with: the one of the element in the file, or else the one in the
+/// dictionary. Only the text elements can be edited.
+fn text_vr(obj: &DefaultDicomObject, tag: Tag) -> Result<VR> {
+    let vr = match obj.get(tag) {
+        Some(elem) => elem.vr(),
+        None => StandardDataDictionary
+            .by_tag(tag)
+            .map(|x| x.vr.relaxed())
+            .ok_or_else(|| Error::other(trf("The value representation of {} is unknown", tag)))?,
+    };
+    if is_binary_vr(vr) || vr == VR::SQ {
+        return Err(Error::other(tr("Only the text elements can be edited.")));
+    }
+    Ok(vr)
+}
+
+/// Check the value against the value representation of the tag in each file, before any file is written.
+fn check_value(files: &[PathBuf], tag: Tag, value: &str) -> Result<()> {
+    for file in files {
+        let obj = open_header(file).map_err(Error::other)?;
+        let vr = text_vr(&obj, tag)?;
+        check_text_value(vr, value)
+            .map_err(|e| Error::other(format!("{}: {e}", file.display())))?;
+    }
+    Ok(())
+}
+
+/// The tag and the value to set, with the preview of the current values.
+#[derive(Default)]
+pub struct BulkEditTool {
+    keyword: String,
+    value: String,
+    pub output: EditOutput,
+    /// The tag previewed, with the current value in each file.
+    preview: Option<(Tag, Vec<(PathBuf, Option<String>)>)>,
+    pending_preview: Option<Task<(Tag, Vec<(PathBuf, Option<String>)>)>>,
+    pending: Option<PendingEdit>,
+    status: String,
+}
+
+impl BulkEditTool {
+    fn run_preview(&mut self, files: &[PathBuf], tasks: &mut TaskPool) {
+        self.status.clear();
+        let Some(tag) = StandardDataDictionary.parse_tag(self.keyword.trim()) else {
+            self.status = trf("Unknown tag {}", self.keyword.trim());
+            return;
+        };
+        if tag.group() == 0x0002 {
+            self.status = tr("The file meta group can't be edited.");
+            return;
+        }
+
+        let files = files.to_vec();
+        self.preview = None;
+        tasks::cancel(&mut self.pending_preview);
+        self.pending_preview = Some(tasks.spawn(tr("Preview the bulk edit"), move |context| {
+            let mut values = Vec::new();
+            for (index, file) in files.iter().enumerate() {
+                if context.is_cancelled() {
+                    break;
+                }
+                context.set_progress(index as f32 / files.len() as f32);
+                let value = open_header(file).ok().and_then(|x| value_of(&x, tag));
+                values.push((file.clone(), value));
+            }
+            (tag, values)
+        }));
+    }
+
+    /// Take the preview and the result of the edit when they are done. Returns the files written.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> Option<Vec<PathBuf>> {
+        if let Some(preview) = tasks::poll(&mut self.pending_preview) {
+            self.preview = Some(preview);
+        }
+        let (status, targets) = PendingEdit::poll(&mut self.pending, audit_log)?;
+        self.status = status;
+        (!targets.is_empty()).then_some(targets)
+    }
+
+    /// Show the tag and the value to set, and the preview. The files are only written after a preview.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        tasks: &mut TaskPool,
+        read_only: bool,
+    ) -> EditAction {
+        let mut action = EditAction::None;
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("bulk edit grid").show(ui, |ui| {
+            ui.label(tr("Tag"));
+            if ui
+                .text_edit_singleline(&mut self.keyword)
+                .on_hover_text(tr("A keyword or gggg,eeee"))
+                .changed()
+            {
+                self.preview = None;
+                tasks::cancel(&mut self.pending_preview);
+            }
+            ui.end_row();
+
+            ui.label(tr("New value"));
+            ui.text_edit_singleline(&mut self.value);
+            ui.end_row();
+
+            if self.output.grid_ui(ui, read_only) {
+                action = EditAction::PickTarget;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let is_busy = self.pending_preview.is_some() || self.pending.is_some();
+            if ui
+                .add_enabled(
+                    !files.is_empty() && !is_busy,
+                    egui::Button::new(tr("Preview")),
+                )
+                .clicked()
+            {
+                self.run_preview(files, tasks);
+            }
+
+            let output_dir = self.output.output_dir();
+            let can_apply = self.preview.is_some() && output_dir.is_some() && !is_busy;
+            if ui
+                .add_enabled(can_apply, egui::Button::new(tr("Apply")))
+                .clicked()
+                && let Some(output_dir) = output_dir
+                && let Some((tag, preview)) = self.preview.take()
+            {
+                let sources: Vec<PathBuf> = preview.into_iter().map(|(file, _)| file).collect();
+                let operation = trf("Set {}", tag_keyword(tag));
+                let value = self.value.clone();
+                self.pending = Some(PendingEdit::spawn(
+                    tasks,
+                    operation.clone(),
+                    move |context, written| {
+                        check_value(&sources, tag, &value)?;
+                        write_edited(
+                            &sources,
+                            output_dir.as_deref(),
+                            &operation,
+                            written,
+                            context,
+                            |obj| {
+                                let vr = text_vr(obj, tag)?;
+                                obj.put(text_element(tag, vr, &value));
+                                Ok(())
+                            },
+                        )?;
+                        Ok(String::new())
+                    },
+                ));
+                self.status.clear();
+            }
+            if is_busy {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        let Some((tag, preview)) = self.preview.as_ref() else {
+            return action;
+        };
+        ui.separator();
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("bulk edit preview grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong(tr("File"));
+                    ui.strong(tag_keyword(*tag));
+                    ui.strong(tr("New value"));
+                    ui.end_row();
+                    for (file, value) in preview {
+                        let name = file
+                            .file_name()
+                            .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                        ui.label(name).on_hover_text(file.display().to_string());
+                        match value {
+                            Some(value) => ui.monospace(value),
+                            None => ui.weak(tr("(missing)")),
+                        };
+                        ui.monospace(&self.value);
+                        ui.end_row();
+                    }
+                });
+        });
+
+        action
+    }
+}
@@ -0,0 +1,127 @@
+//! Verification of the files against a checksum manifest, e.g. the `md5sum` or `sha256sum` output shipped with a
+//! download, or the `manifest-md5.txt` of a BagIt bag.
+
+use crate::i18n::{tr, trf};
+use crate::scan::Problem;
+use crate::tasks::TaskContext;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::io::{BufReader, Read, Result};
+use std::path::Path;
+
+/// The digest algorithms of the manifests, told apart by the length of the hex digests.
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn of(hex: &str) -> Option<Self> {
+        match hex.len() {
+            32 => Some(Self::Md5),
+            64 => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// Compute the hex digest of the file, reading it in blocks.
+fn hex_digest<D: Digest>(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut hasher = D::new();
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect())
+}
+
+/// Compute the hex digest of the file with the algorithm.
+fn file_digest(path: &Path, algorithm: Algorithm) -> Result<String> {
+    match algorithm {
+        Algorithm::Md5 => hex_digest::<Md5>(path),
+        Algorithm::Sha256 => hex_digest::<Sha256>(path),
+    }
+}
+
+/// Parse a line of the manifest into the hex digest and the relative path.
+/// Both the `<digest>  <path>` lines of `md5sum`, with the optional `*` of the binary mode, and the CSV lines with
+/// the digest in either column are read.
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let is_digest =
+        |x: &str| Algorithm::of(x).is_some() && x.chars().all(|x| x.is_ascii_hexdigit());
+
+    if let Some((digest, path)) = line.split_once(char::is_whitespace)
+        && is_digest(digest)
+    {
+        let path = path.trim_start();
+        return Some((
+            digest.to_lowercase(),
+            path.strip_prefix('*').unwrap_or(path).to_string(),
+        ));
+    }
+
+    let (first, second) = line.split_once(',')?;
+    let (first, second) = (
+        first.trim().trim_matches('"'),
+        second.trim().trim_matches('"'),
+    );
+    match (is_digest(first), is_digest(second)) {
+        (true, _) => Some((first.to_lowercase(), second.to_string())),
+        (false, true) => Some((second.to_lowercase(), first.to_string())),
+        _ => None,
+    }
+}
+
+/// Verify the files listed in the manifest, relative to the folder of the manifest.
+/// Returns the number of files verified, and the missing, unreadable and mismatched files as problems.
+pub fn verify(manifest: &Path, context: &TaskContext) -> Result<(usize, Vec<Problem>)> {
+    let text = std::fs::read_to_string(manifest)?;
+    let dir = manifest.parent().unwrap_or(Path::new(""));
+    let mut count = 0;
+    let mut problems = Vec::new();
+
+    let entries: Vec<_> = text.lines().filter_map(parse_line).collect();
+    let total = entries.len();
+    for (index, (digest, relative_path)) in entries.into_iter().enumerate() {
+        if context.is_cancelled() {
+            return Err(std::io::Error::other("the verification was cancelled"));
+        }
+        context.set_progress(index as f32 / total as f32);
+        let path = dir.join(&relative_path);
+        let Some(algorithm) = Algorithm::of(&digest) else {
+            continue;
+        };
+        count += 1;
+
+        let message = if !path.is_file() {
+            tr("Missing file")
+        } else {
+            match file_digest(&path, algorithm) {
+                Ok(actual) if actual == digest => continue,
+                Ok(actual) => format!(
+                    "{}, {}",
+                    trf("Checksum mismatch: expected {}", digest),
+                    trf("got {}", actual)
+                ),
+                Err(e) => trf("Failed to read: {}", e),
+            }
+        };
+        problems.push(Problem { path, message });
+    }
+
+    Ok((count, problems))
+}
@@ -0,0 +1,179 @@
+//! Ready-to-run command lines of the dcmtk and gdcm tools for a file, to be pasted into a terminal.
+//! The uploads to the Orthanc servers are curl commands to their REST API.
+
+use crate::i18n::tr;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A DICOM node to send the files to.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub name: String,
+    pub ae_title: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ae_title: String::new(),
+            host: "localhost".to_string(),
+            port: 104,
+        }
+    }
+}
+
+/// An Orthanc server, by the base URL of its REST API, e.g. `http://localhost:8042`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OrthancServer {
+    pub name: String,
+    pub url: String,
+    /// Send the token in the `ORTHANC_TOKEN` environment variable as a bearer token, so that it isn't kept in the
+    /// settings or the copied command.
+    pub token_auth: bool,
+}
+
+impl Default for OrthancServer {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: "http://localhost:8042".to_string(),
+            token_auth: false,
+        }
+    }
+}
+
+/// The keywords printed by the `dcmdump +P` command.
+const PRINTED_KEYWORDS: [&str; 4] = [
+    "PatientName",
+    "PatientID",
+    "StudyInstanceUID",
+    "SeriesInstanceUID",
+];
+
+/// Quote the argument for the shell if it has any character other than the safe ones. The backslash is only safe on
+/// Windows, where it separates the folders, and a `%` is escaped outside of the quotes there, as cmd expands the
+/// variables even inside them.
+fn quote(arg: &str) -> String {
+    let is_safe = |x: char| {
+        x.is_ascii_alphanumeric() || "-_./:+=@".contains(x) || (cfg!(windows) && x == '\\')
+    };
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\"\"").replace('%', "\"^%\""))
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Build the command lines for the file, with the label of each.
+pub fn commands(
+    path: &Path,
+    calling_ae_title: &str,
+    endpoints: &[Endpoint],
+    orthanc_servers: &[OrthancServer],
+) -> Vec<(String, String)> {
+    let path = quote(&path.display().to_string());
+    let printed = PRINTED_KEYWORDS.map(|x| format!("+P {x}")).join(" ");
+
+    let mut commands = vec![
+        ("dcmdump".to_string(), format!("dcmdump {path}")),
+        (
+            format!("dcmdump {}", tr("(patient and UIDs)")),
+            format!("dcmdump {printed} {path}"),
+        ),
+        ("gdcmdump".to_string(), format!("gdcmdump {path}")),
+        ("gdcminfo".to_string(), format!("gdcminfo {path}")),
+    ];
+
+    for endpoint in endpoints {
+        let calling = quote(calling_ae_title);
+        let called = quote(&endpoint.ae_title);
+        let host = quote(&endpoint.host);
+        let port = endpoint.port;
+
+        commands.extend([
+            (
+                format!("echoscu → {}", endpoint.name),
+                format!("echoscu -aet {calling} -aec {called} {host} {port}"),
+            ),
+            (
+                format!("dcmsend → {}", endpoint.name),
+                format!("dcmsend -aet {calling} -aec {called} {host} {port} {path}"),
+            ),
+            (
+                format!("storescu → {}", endpoint.name),
+                format!("storescu -aet {calling} -aec {called} {host} {port} {path}"),
+            ),
+            (
+                format!("gdcmscu → {}", endpoint.name),
+                format!("gdcmscu --store --aetitle {calling} --call {called} {host} {port} {path}"),
+            ),
+        ]);
+    }
+
+    for server in orthanc_servers {
+        let url = quote(&format!("{}/instances", server.url.trim_end_matches('/')));
+        let auth = match (server.token_auth, cfg!(windows)) {
+            (false, _) => "",
+            (true, false) => " -H \"Authorization: Bearer $ORTHANC_TOKEN\"",
+            (true, true) => " -H \"Authorization: Bearer %ORTHANC_TOKEN%\"",
+        };
+
+        commands.push((
+            format!("Orthanc → {}", server.name),
+            format!("curl -X POST{auth} {url} --data-binary @{path}"),
+        ));
+    }
+
+    commands
+}
+
+/// Show the command lines of the file as buttons which copy them to the clipboard.
+pub fn menu_ui(
+    ui: &mut egui::Ui,
+    path: &Path,
+    calling_ae_title: &str,
+    endpoints: &[Endpoint],
+    orthanc_servers: &[OrthancServer],
+) {
+    for (label, command) in commands(path, calling_ae_title, endpoints, orthanc_servers) {
+        if ui.button(label).on_hover_text(&command).clicked() {
+            ui.ctx().copy_text(command);
+            ui.close();
+        }
+    }
+    if endpoints.is_empty() && orthanc_servers.is_empty() {
+        ui.separator();
+        ui.weak(tr(
+            "Add DICOM endpoints or Orthanc servers in the settings for the send commands.",
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::quote;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn backslashes_are_quoted() {
+        assert_eq!(quote("/data/IM1.dcm"), "/data/IM1.dcm");
+        assert_eq!(quote(r"dir\IM1.dcm"), r"'dir\IM1.dcm'");
+        assert_eq!(quote(r"AE\1"), r"'AE\1'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn percent_signs_are_escaped() {
+        assert_eq!(quote(r"C:\data\IM1.dcm"), r"C:\data\IM1.dcm");
+        assert_eq!(quote("100%"), r#""100"^%"""#);
+        assert_eq!(quote("%TEMP% dir"), r#"""^%"TEMP"^%" dir""#);
+    }
+}
@@ -0,0 +1,407 @@
+//! Writing copies of a file with controlled corruptions, to test how the downstream systems handle broken files.
+//! The truncation, the VR and the length are corrupted in the encoded bytes, so only the little endian, not deflated,
+//! transfer syntaxes are supported. The UIDs are corrupted in the dataset, as they are still encoded correctly.
+
+use crate::bulk_edit::EditAction;
+use crate::dataset::{new_uid, open_header, text_element};
+use crate::i18n::{tr, trf};
+use crate::organize::avoid_collision;
+use dicom::core::{DataDictionary, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags, uids};
+use dicom::object::open_file;
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The length of an element of undefined length, i.e. ended by a delimitation item.
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Corruption {
+    Truncation,
+    BadVr,
+    WrongLength,
+    InvalidUids,
+}
+
+impl Corruption {
+    const ALL: [Self; 4] = [
+        Self::Truncation,
+        Self::BadVr,
+        Self::WrongLength,
+        Self::InvalidUids,
+    ];
+
+    fn label(self) -> String {
+        match self {
+            Self::Truncation => tr("Truncation"),
+            Self::BadVr => tr("Bad VR"),
+            Self::WrongLength => tr("Wrong length"),
+            Self::InvalidUids => tr("Invalid UIDs"),
+        }
+    }
+
+    fn description(self) -> String {
+        match self {
+            Self::Truncation => tr("The end of the file is cut off"),
+            Self::BadVr => tr("The VR of the element is replaced by an unknown one"),
+            Self::WrongLength => tr("The length of the element runs past the end of the file"),
+            Self::InvalidUids => tr(
+                "The study, series and SOP instance UIDs have an empty component, letters and too many characters",
+            ),
+        }
+    }
+
+    /// The suffix of the name of the copy.
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::Truncation => "truncated",
+            Self::BadVr => "bad_vr",
+            Self::WrongLength => "wrong_length",
+            Self::InvalidUids => "invalid_uids",
+        }
+    }
+}
+
+/// The header of an element in the encoded bytes.
+struct Header {
+    tag: Tag,
+    /// The offset of the VR, none in implicit VR.
+    vr_offset: Option<usize>,
+    length_offset: usize,
+    /// The size of the length, 2 or 4 bytes.
+    length_size: usize,
+    length: u32,
+    /// The offset of the value.
+    value_offset: usize,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Read the header of the element at the offset. The items and the delimiters have no VR in either encoding.
+fn read_header(bytes: &[u8], offset: usize, explicit: bool) -> Option<Header> {
+    let tag = Tag(read_u16(bytes, offset)?, read_u16(bytes, offset + 2)?);
+    if !explicit || tag.group() == 0xFFFE {
+        return Some(Header {
+            tag,
+            vr_offset: None,
+            length_offset: offset + 4,
+            length_size: 4,
+            length: read_u32(bytes, offset + 4)?,
+            value_offset: offset + 8,
+        });
+    }
+
+    let vr = bytes.get(offset + 4..offset + 6)?;
+    let is_long = matches!(
+        vr,
+        b"OB"
+            | b"OD"
+            | b"OF"
+            | b"OL"
+            | b"OV"
+            | b"OW"
+            | b"SQ"
+            | b"SV"
+            | b"UC"
+            | b"UN"
+            | b"UR"
+            | b"UT"
+            | b"UV"
+    );
+    Some(if is_long {
+        Header {
+            tag,
+            vr_offset: Some(offset + 4),
+            length_offset: offset + 8,
+            length_size: 4,
+            length: read_u32(bytes, offset + 8)?,
+            value_offset: offset + 12,
+        }
+    } else {
+        Header {
+            tag,
+            vr_offset: Some(offset + 4),
+            length_offset: offset + 6,
+            length_size: 2,
+            length: u32::from(read_u16(bytes, offset + 6)?),
+            value_offset: offset + 8,
+        }
+    })
+}
+
+/// Get the offset after the element of undefined length whose value starts at the offset, by following the items and
+/// the delimiters nested in it.
+fn skip_undefined(bytes: &[u8], mut offset: usize, explicit: bool) -> Option<usize> {
+    let mut depth = 1;
+    while depth > 0 {
+        let header = read_header(bytes, offset, explicit)?;
+        offset = header.value_offset;
+        match (header.tag, header.length) {
+            // The end of an item or of a sequence.
+            (Tag(0xFFFE, 0xE00D | 0xE0DD), _) => depth -= 1,
+            // An item or a sequence of undefined length.
+            (_, UNDEFINED_LENGTH) => depth += 1,
+            // The items of defined length are skipped as a whole, like the other elements.
+            (_, length) => offset += length as usize,
+        }
+    }
+    Some(offset)
+}
+
+/// Find the header of the top-level element in the dataset.
+fn find_element(bytes: &[u8], tag: Tag, explicit: bool) -> Result<Header> {
+    let not_found = || Error::other(trf("{} was not found at the top level", tag));
+    if bytes.get(128..132) != Some(b"DICM".as_slice()) {
+        return Err(Error::other(tr("The file has no DICOM preamble.")));
+    }
+
+    // The file meta group is always in explicit VR little endian.
+    let mut offset = 132;
+    while let Some(header) = read_header(bytes, offset, true)
+        && header.tag.group() == 0x0002
+    {
+        offset = header.value_offset + header.length as usize;
+    }
+
+    while let Some(header) = read_header(bytes, offset, explicit) {
+        if header.tag == tag {
+            return Ok(header);
+        }
+        if header.tag > tag {
+            break;
+        }
+        offset = if header.length == UNDEFINED_LENGTH {
+            skip_undefined(bytes, header.value_offset, explicit).ok_or_else(not_found)?
+        } else {
+            header.value_offset + header.length as usize
+        };
+    }
+    Err(not_found())
+}
+
+/// Write the copy of the file with the corruption. The VR and the length are corrupted in the element with the tag,
+/// which is only needed for them.
+fn write_corrupted(
+    file: &Path,
+    corruption: Corruption,
+    tag: Option<Tag>,
+    percent: u8,
+    target: &Path,
+) -> Result<()> {
+    if corruption == Corruption::InvalidUids {
+        let mut obj = open_file(file).map_err(Error::other)?;
+        obj.put(text_element(tags::STUDY_INSTANCE_UID, VR::UI, "1.2..3"));
+        obj.put(text_element(tags::SERIES_INSTANCE_UID, VR::UI, "1.2.ABC.4"));
+        obj.put(text_element(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            &format!("{}.{}", new_uid(), "1234567890".repeat(3)),
+        ));
+        return obj.write_to_file(target).map_err(Error::other);
+    }
+
+    let mut bytes = std::fs::read(file)?;
+    let transfer_syntax = open_header(file)
+        .map_err(Error::other)?
+        .meta()
+        .transfer_syntax()
+        .trim_end_matches('\0')
+        .to_string();
+    let explicit = match transfer_syntax.as_str() {
+        uids::IMPLICIT_VR_LITTLE_ENDIAN => false,
+        uids::EXPLICIT_VR_BIG_ENDIAN | uids::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN => {
+            return Err(Error::other(trf(
+                "The transfer syntax {} is not supported",
+                transfer_syntax,
+            )));
+        }
+        _ => true,
+    };
+
+    let element_header = |bytes: &[u8]| {
+        let tag = tag.ok_or_else(|| Error::other(tr("No element is given to corrupt.")))?;
+        find_element(bytes, tag, explicit)
+    };
+    match corruption {
+        Corruption::Truncation => {
+            bytes.truncate(bytes.len() * usize::from(percent) / 100);
+        }
+        Corruption::BadVr => {
+            let header = element_header(&bytes)?;
+            let Some(vr_offset) = header.vr_offset else {
+                return Err(Error::other(tr(
+                    "The VR can only be corrupted in the explicit VR transfer syntaxes.",
+                )));
+            };
+            bytes[vr_offset..vr_offset + 2].copy_from_slice(b"ZZ");
+        }
+        Corruption::WrongLength => {
+            let header = element_header(&bytes)?;
+            // Past the end of the file if the length can hold it, else as long as the length can be.
+            let length = (bytes.len() - header.value_offset + 2) & !1;
+            let offset = header.length_offset;
+            if header.length_size == 2 {
+                let length = length.min(0xFFFE) as u16;
+                bytes[offset..offset + 2].copy_from_slice(&length.to_le_bytes());
+            } else {
+                let length = length.min(0xFFFF_FFFE) as u32;
+                bytes[offset..offset + 4].copy_from_slice(&length.to_le_bytes());
+            }
+        }
+        Corruption::InvalidUids => {}
+    }
+
+    std::fs::write(target, bytes)
+}
+
+/// The corruptions to apply, each written to its own copy, and the folder to write the copies into.
+pub struct CorruptionTool {
+    chosen: HashSet<Corruption>,
+    /// The element whose VR or length is corrupted.
+    keyword: String,
+    /// The percentage of the file kept by the truncation.
+    percent: u8,
+    pub target_dir: Option<PathBuf>,
+    status: String,
+}
+
+impl Default for CorruptionTool {
+    fn default() -> Self {
+        Self {
+            chosen: Corruption::ALL.into_iter().collect(),
+            keyword: "PatientName".to_string(),
+            percent: 50,
+            target_dir: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl CorruptionTool {
+    /// Write a copy of the file for each chosen corruption. Returns the copies written, with the error which stopped
+    /// the writing, if any.
+    fn run(&self, file: &Path, target_dir: &Path) -> (Vec<PathBuf>, Result<()>) {
+        let mut targets = Vec::new();
+        let result = self.write_copies(file, target_dir, &mut targets);
+        (targets, result)
+    }
+
+    fn write_copies(
+        &self,
+        file: &Path,
+        target_dir: &Path,
+        targets: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        // The element is only needed to corrupt its VR or its length.
+        let needs_tag = self.chosen.contains(&Corruption::BadVr)
+            || self.chosen.contains(&Corruption::WrongLength);
+        let tag = needs_tag
+            .then(|| {
+                StandardDataDictionary
+                    .parse_tag(self.keyword.trim())
+                    .ok_or_else(|| Error::other(trf("Unknown tag {}", self.keyword.trim())))
+            })
+            .transpose()?;
+        std::fs::create_dir_all(target_dir)?;
+
+        let stem = file
+            .file_stem()
+            .map_or(String::new(), |x| x.to_string_lossy().to_string());
+        let mut taken = HashSet::new();
+        for corruption in Corruption::ALL {
+            if !self.chosen.contains(&corruption) {
+                continue;
+            }
+            let target = avoid_collision(
+                target_dir.join(format!("{stem}_{}.dcm", corruption.suffix())),
+                &taken,
+            );
+            taken.insert(target.clone());
+            write_corrupted(file, corruption, tag, self.percent, &target)?;
+            targets.push(target);
+        }
+        Ok(())
+    }
+
+    /// Show the corruptions to apply to the selected file. Returns the copies written.
+    pub fn ui(&mut self, ui: &mut egui::Ui, file: Option<&Path>) -> EditAction {
+        let mut action = EditAction::None;
+
+        match file {
+            Some(file) => ui.monospace(file.display().to_string()),
+            None => ui.label(tr("No file selected")),
+        };
+        for corruption in Corruption::ALL {
+            let mut is_chosen = self.chosen.contains(&corruption);
+            if ui
+                .checkbox(&mut is_chosen, corruption.label())
+                .on_hover_text(corruption.description())
+                .changed()
+            {
+                if is_chosen {
+                    self.chosen.insert(corruption);
+                } else {
+                    self.chosen.remove(&corruption);
+                }
+            }
+        }
+
+        egui::Grid::new("corruption grid").show(ui, |ui| {
+            ui.label(tr("Kept by the truncation"));
+            ui.add(egui::Slider::new(&mut self.percent, 1..=99).suffix("%"));
+            ui.end_row();
+
+            ui.label(tr("Element with the bad VR or length"));
+            ui.text_edit_singleline(&mut self.keyword)
+                .on_hover_text(tr("A keyword or gggg,eeee"));
+            ui.end_row();
+
+            ui.label(tr("Target folder"));
+            ui.horizontal(|ui| {
+                let target = self
+                    .target_dir
+                    .as_ref()
+                    .map_or(tr("None"), |x| x.display().to_string());
+                ui.monospace(target);
+                if ui.button(tr("Browse")).clicked() {
+                    action = EditAction::PickTarget;
+                }
+            });
+            ui.end_row();
+        });
+
+        let can_run = file.is_some() && !self.chosen.is_empty() && self.target_dir.is_some();
+        if ui
+            .add_enabled(can_run, egui::Button::new(tr("Write corrupted copies")))
+            .clicked()
+            && let Some(file) = file
+            && let Some(target_dir) = self.target_dir.as_ref()
+        {
+            let (targets, result) = self.run(file, target_dir);
+            self.status = trf("{} files written.", targets.len());
+            if let Err(e) = result {
+                self.status = format!("{} {e}", self.status);
+            }
+            if !targets.is_empty() {
+                action = EditAction::FilesWritten(targets);
+            }
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        action
+    }
+}
@@ -0,0 +1,148 @@
+//! Recovery from crashes: a panic hook writes a crash report and the last known session in the data folder of the
+//! user, and the next start offers to restore the session.
+//! The session is kept serialized whenever the workspace or the selection changes, so that the hook only writes a
+//! string and doesn't touch the state of the app, which may be inconsistent while panicking.
+
+use crate::i18n::tr;
+use crate::session::Session;
+use crate::settings::app_data_dir;
+use crate::tasks;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The last known session, as JSON.
+static LAST_SESSION: Mutex<Option<String>> = Mutex::new(None);
+
+/// Get the path of the crash report of the last crash.
+pub fn crash_report_path() -> PathBuf {
+    app_data_dir("crash_report.txt")
+}
+
+/// Get the path of the session saved by the last crash, which is removed once restored or discarded.
+fn crash_session_path() -> PathBuf {
+    app_data_dir("crash_session.json")
+}
+
+/// Keep the session to be saved if the app crashes.
+pub fn remember_session(session: &Session) {
+    match serde_json::to_string_pretty(session) {
+        Ok(json) => {
+            if let Ok(mut last_session) = LAST_SESSION.lock() {
+                *last_session = Some(json);
+            }
+        }
+        Err(e) => log::error!("Failed to serialize the session: {e}"),
+    }
+}
+
+/// Install the panic hook saving the crash report and the session, before the default hook prints the panic.
+/// A panic in a worker thread only ends its task, so it is logged without offering the recovery on the next start.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if tasks::is_worker_thread() {
+            log::error!("A background task panicked: {info}");
+            default_hook(info);
+            return;
+        }
+
+        let report = format!(
+            "{} {}\n{}\n\n{}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            info,
+            std::backtrace::Backtrace::force_capture()
+        );
+        let _ = std::fs::write(crash_report_path(), report);
+
+        // The lock may be poisoned by a panic while holding it, and the session is still usable then.
+        let session = LAST_SESSION
+            .lock()
+            .unwrap_or_else(|x| x.into_inner())
+            .clone();
+        if let Some(session) = session {
+            let _ = std::fs::write(crash_session_path(), session);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// The choice of the user on the session of the last crash.
+pub enum RecoveryAction {
+    None,
+    Restore,
+    /// Restore the folders without the selected file, in case the file caused the crash.
+    RestoreWithoutSelection,
+    Discard,
+}
+
+/// The session of the last crash, with the panic message of its report.
+pub struct CrashRecovery {
+    pub session: Session,
+    message: String,
+}
+
+impl CrashRecovery {
+    /// Load the session saved by the last crash, if any.
+    pub fn load() -> Option<Self> {
+        let path = crash_session_path();
+        if !path.exists() {
+            return None;
+        }
+
+        let session = Session::load(&path);
+        let message = std::fs::read_to_string(crash_report_path())
+            .ok()
+            .map(|x| {
+                x.lines()
+                    .skip(1)
+                    .take_while(|x| !x.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default();
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::error!("Failed to remove {}: {e}", path.display());
+        }
+
+        match session {
+            Ok(session) => Some(Self { session, message }),
+            Err(e) => {
+                log::error!("Failed to load the crash session {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Show the crash and ask whether to restore the session.
+    pub fn ui(&self, ui: &mut egui::Ui) -> RecoveryAction {
+        let mut action = RecoveryAction::None;
+
+        ui.label(tr(
+            "The app closed unexpectedly. Restore the previous session?",
+        ));
+        ui.monospace(&self.message);
+        if let Some(selected_file) = self.session.selected_file.as_ref() {
+            ui.label(tr("The selected file was:"));
+            ui.monospace(selected_file.display().to_string());
+        }
+        ui.label(crash_report_path().display().to_string());
+
+        ui.horizontal(|ui| {
+            if ui.button(tr("Restore")).clicked() {
+                action = RecoveryAction::Restore;
+            }
+            if self.session.selected_file.is_some()
+                && ui.button(tr("Restore without the selection")).clicked()
+            {
+                action = RecoveryAction::RestoreWithoutSelection;
+            }
+            if ui.button(tr("Discard")).clicked() {
+                action = RecoveryAction::Discard;
+            }
+        });
+
+        action
+    }
+}
@@ -0,0 +1,533 @@
+use crate::i18n::{tr, trf};
+use dicom::core::value::{DataSetSequence, Value};
+use dicom::core::{DataDictionary, DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom::object::mem::InMemElement;
+use dicom::object::meta::FileMetaTableBuilder;
+use dicom::object::{DefaultDicomObject, InMemDicomObject, OpenFileOptions, ReadError, open_file};
+use regex::{Captures, Regex};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Open the dicom file up to the pixel data, so that large files don't need to be read entirely.
+pub fn open_header(path: &Path) -> Result<DefaultDicomObject, ReadError> {
+    OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .open_file(path)
+}
+
+/// What is kept of a dicom file found by the folder scan.
+#[derive(Clone)]
+pub struct DicomInfo {
+    pub modality: String,
+    pub sop_instance_uid: String,
+}
+
+/// Check if the objects of the modality are documents rather than images, e.g. structured reports.
+pub fn is_non_image_modality(modality: &str) -> bool {
+    matches!(
+        modality,
+        "SR" | "PR" | "KO" | "DOC" | "RTSTRUCT" | "RTPLAN" | "RTRECORD"
+    )
+}
+
+/// Check if the file is a dicom file which can be read, or get the reason why it is skipped.
+pub fn probe_dicom(path: &Path) -> Result<DicomInfo, String> {
+    let e = match open_header(path) {
+        Ok(obj) => {
+            return Ok(DicomInfo {
+                modality: element_string(&obj, tags::MODALITY).unwrap_or_default(),
+                sop_instance_uid: element_string(&obj, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+            });
+        }
+        Err(e) => e,
+    };
+
+    let mut preamble = [0; 132];
+    let has_marker = std::fs::File::open(path)
+        .and_then(|mut x| x.read_exact(&mut preamble))
+        .is_ok()
+        && (preamble[128..] == *b"DICM" || preamble[..4] == *b"DICM");
+
+    Err(if has_marker {
+        trf("parse error: {}", e)
+    } else {
+        tr("no DICM marker")
+    })
+}
+
+/// Get the keyword of the tag, or the (gggg,eeee) form if it is not in the standard dictionary.
+pub fn tag_keyword(tag: Tag) -> String {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map_or_else(|| tag.to_string(), |entry| entry.alias.to_string())
+}
+
+/// Visit every element of the dataset, including the ones nested in sequence items.
+/// Each element is given with its path, e.g. `ReferencedSeriesSequence[0].SeriesInstanceUID`.
+pub fn visit_elements(obj: &InMemDicomObject, visitor: &mut impl FnMut(&str, &InMemElement)) {
+    visit_elements_with_prefix(obj, "", visitor);
+}
+
+fn visit_elements_with_prefix(
+    obj: &InMemDicomObject,
+    prefix: &str,
+    visitor: &mut impl FnMut(&str, &InMemElement),
+) {
+    for elem in obj {
+        let path = format!("{prefix}{}", tag_keyword(elem.header().tag));
+        visitor(&path, elem);
+
+        if let Value::Sequence(seq) = elem.value() {
+            for (index, item) in seq.items().iter().enumerate() {
+                visit_elements_with_prefix(item, &format!("{path}[{index}]."), visitor);
+            }
+        }
+    }
+}
+
+/// Check if the value representation holds bulk binary data rather than text.
+pub fn is_binary_vr(vr: VR) -> bool {
+    matches!(
+        vr,
+        VR::OB | VR::OD | VR::OF | VR::OL | VR::OV | VR::OW | VR::UN
+    )
+}
+
+/// Convert a primitive value to a display string, summarising binary values by their size.
+pub fn primitive_to_string(vr: VR, value: &PrimitiveValue) -> String {
+    if is_binary_vr(vr) {
+        format!("<{} bytes>", value.calculate_byte_len())
+    } else {
+        value.to_str().trim_end_matches(['\0', ' ']).to_string()
+    }
+}
+
+/// Get the tag of a top-level element line in the text dump, e.g. `(0010,0010) PN [...]`.
+/// Nested lines are indented, so they don't have a top-level tag.
+pub fn dump_line_tag(line: &str) -> Option<Tag> {
+    let (group, element) = line
+        .strip_prefix('(')?
+        .get(..10)?
+        .strip_suffix(')')?
+        .split_once(',')?;
+    if ![group, element]
+        .iter()
+        .all(|x| x.len() == 4 && x.bytes().all(|x| x.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+
+    Some(Tag(
+        u16::from_str_radix(group, 16).ok()?,
+        u16::from_str_radix(element, 16).ok()?,
+    ))
+}
+
+/// The number of the bytes of a binary value shown in its preview.
+const MAX_PREVIEW_BYTES: usize = 4096;
+
+/// Show the start of a binary value as a hex dump, 16 bytes a line with their offsets.
+fn hex_preview(bytes: &[u8]) -> String {
+    let mut lines: Vec<String> = bytes[..bytes.len().min(MAX_PREVIEW_BYTES)]
+        .chunks(16)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|x| format!("{x:02x}")).collect();
+            format!("{:08x}  {}", index * 16, hex.join(" "))
+        })
+        .collect();
+    if bytes.len() > MAX_PREVIEW_BYTES {
+        lines.push(trf("… {} bytes in total", bytes.len()));
+    }
+    lines.join("\n")
+}
+
+/// Read the complete value of the top-level element from the file, as the dump only shows the start of long values.
+/// The binary values are shown as a hex dump of their start.
+/// The file is only read up to the pixel data unless the element comes after it.
+pub fn read_full_value(path: &Path, tag: Tag) -> Result<String, String> {
+    let obj = if tag >= tags::PIXEL_DATA {
+        open_file(path)
+    } else {
+        open_header(path)
+    }
+    .map_err(|e| e.to_string())?;
+    let elem = obj.element(tag).map_err(|e| e.to_string())?;
+
+    match elem.value() {
+        Value::Primitive(value) if is_binary_vr(elem.vr()) => Ok(hex_preview(&value.to_bytes())),
+        Value::Primitive(value) => Ok(primitive_to_string(elem.vr(), value)),
+        _ => Err(tr(
+            "Only the values of the primitive elements can be shown.",
+        )),
+    }
+}
+
+/// Get the value representation of a top-level element line in the text dump, e.g. `(7FE0,0010) OW ...`.
+pub fn dump_line_vr(line: &str) -> Option<VR> {
+    dump_line_tag(line)?;
+    line.get(12..14)?.parse().ok()
+}
+
+/// Write the raw bytes of the top-level element value to the target file, e.g. a LUT or a private payload.
+/// The fragments of encapsulated pixel data are written one after another, without the item headers.
+pub fn save_element_value(path: &Path, tag: Tag, target: &Path) -> std::io::Result<()> {
+    let obj = open_file(path).map_err(std::io::Error::other)?;
+    let elem = obj.element(tag).map_err(std::io::Error::other)?;
+
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(target)?);
+    match elem.value() {
+        Value::Primitive(value) => writer.write_all(&value.to_bytes())?,
+        Value::PixelSequence(seq) => {
+            for fragment in seq.fragments() {
+                writer.write_all(fragment)?;
+            }
+        }
+        Value::Sequence(_) => {
+            return Err(std::io::Error::other("a sequence has no value to save"));
+        }
+    }
+    writer.flush()
+}
+
+/// Get the value of the element as a trimmed string, if present.
+pub fn element_string(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    let elem = obj.get(tag)?;
+    let value = elem.to_str().ok()?;
+    Some(value.trim_end_matches(['\0', ' ']).to_string())
+}
+
+/// Get the value of the element for display, with the binary values summarised and the sequences by their items.
+pub fn element_display(obj: &InMemDicomObject, tag: Tag) -> Option<String> {
+    let elem = obj.get(tag)?;
+    Some(match elem.value() {
+        Value::Primitive(value) => primitive_to_string(elem.vr(), value),
+        Value::Sequence(seq) => trf("{} items", seq.items().len()),
+        Value::PixelSequence(seq) => trf("{} fragments", seq.fragments().len()),
+    })
+}
+
+/// Fill in the `{name}` placeholders of the template with the values given by the closure.
+pub fn expand_template(template: &str, mut value_of: impl FnMut(&str) -> String) -> String {
+    let regex = Regex::new(r"\{([A-Za-z0-9,]+)\}").expect("placeholder regex should be valid");
+
+    regex
+        .replace_all(template, |captures: &Captures<'_>| value_of(&captures[1]))
+        .to_string()
+}
+
+/// Get the value of the tag (keyword or gggg,eeee) as a string, or an empty string if missing.
+pub fn keyword_value(obj: &InMemDicomObject, keyword: &str) -> String {
+    StandardDataDictionary
+        .parse_tag(keyword)
+        .and_then(|tag| element_string(obj, tag))
+        .unwrap_or_default()
+}
+
+/// Generate a new UID under the `2.25` root from 128 random bits.
+pub fn new_uid() -> String {
+    new_uid_with_root("2.25")
+}
+
+/// The maximum length of a UID.
+pub const MAX_UID_LENGTH: usize = 64;
+
+/// Generate a new UID under the root from 128 random bits, keeping as many of the leading random digits as fit in the
+/// maximum length of a UID, so that the last component is never empty nor starts with a zero.
+pub fn new_uid_with_root(root: &str) -> String {
+    let random = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(salt);
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_nanos()),
+        );
+        hasher.finish()
+    };
+
+    let mut digits = ((u128::from(random(0)) << 64) | u128::from(random(1))).to_string();
+    digits.truncate(MAX_UID_LENGTH.saturating_sub(root.len() + 1).max(1));
+    format!("{root}.{digits}")
+}
+
+/// Create an element with a text value.
+pub fn text_element(tag: Tag, vr: VR, value: &str) -> InMemElement {
+    DataElement::new(tag, vr, PrimitiveValue::from(value))
+}
+
+/// Check if the value is a time `HHMMSS.FFFFFF`, of which the minutes, the seconds and the fraction are optional.
+fn is_time(value: &str) -> bool {
+    let (hhmmss, fraction) = value.split_once('.').unwrap_or((value, ""));
+    matches!(hhmmss.len(), 2 | 4 | 6)
+        && hhmmss.bytes().all(|x| x.is_ascii_digit())
+        && fraction.len() <= 6
+        && fraction.bytes().all(|x| x.is_ascii_digit())
+        && chrono::NaiveTime::parse_from_str(&format!("{hhmmss:0<6}"), "%H%M%S").is_ok()
+}
+
+/// Check if the single value has the format of the value representation.
+fn is_valid_value(vr: VR, value: &str) -> bool {
+    let is_digits = |x: &str| x.bytes().all(|x| x.is_ascii_digit());
+    match vr {
+        VR::AS => {
+            value.len() == 4
+                && value.get(..3).is_some_and(is_digits)
+                && matches!(value.get(3..), Some("D" | "W" | "M" | "Y"))
+        }
+        VR::CS => value
+            .bytes()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit() || x == b' ' || x == b'_'),
+        VR::DA => value.len() == 8 && chrono::NaiveDate::parse_from_str(value, "%Y%m%d").is_ok(),
+        VR::DS => value.trim().parse::<f64>().is_ok_and(f64::is_finite),
+        VR::DT => {
+            let (datetime, offset) = match value.find(['+', '-']) {
+                Some(i) => (&value[..i], &value[i + 1..]),
+                None => (value, "0000"),
+            };
+            let (date, time) = datetime.split_at(datetime.len().min(8));
+            offset.len() == 4
+                && is_digits(offset)
+                && matches!(date.len(), 4 | 6 | 8)
+                && is_digits(date)
+                && (time.is_empty() || date.len() == 8 && is_time(time))
+        }
+        VR::IS => value.trim().parse::<i32>().is_ok(),
+        VR::PN => value.split('=').count() <= 3 && value.split('=').all(|x| x.len() <= 64),
+        VR::TM => is_time(value),
+        VR::UI => value
+            .split('.')
+            .all(|x| !x.is_empty() && is_digits(x) && (x == "0" || !x.starts_with('0'))),
+        _ => true,
+    }
+}
+
+/// Check the text value against the format and the maximum length of the value representation, e.g. a DA is
+/// `YYYYMMDD` and a LO is up to 64 characters. The values of the multi-valued VRs are separated by backslashes.
+/// Returns the reason if it isn't valid.
+pub fn check_text_value(vr: VR, value: &str) -> Result<(), String> {
+    let max_length = match vr {
+        VR::AE | VR::CS | VR::DS | VR::SH => 16,
+        VR::AS => 4,
+        VR::DA => 8,
+        VR::DT => 26,
+        VR::IS => 12,
+        VR::LO => 64,
+        VR::LT => 10240,
+        VR::PN => 64 * 3 + 2,
+        VR::ST => 1024,
+        VR::TM => 14,
+        VR::UI => MAX_UID_LENGTH,
+        _ => usize::MAX,
+    };
+    let values: Vec<&str> = match vr {
+        VR::LT | VR::ST | VR::UT | VR::UR => vec![value],
+        _ => value.split('\\').collect(),
+    };
+
+    for x in values {
+        if x.len() > max_length {
+            return Err(trf(
+                "A value is longer than the maximum of {} characters.",
+                max_length,
+            ));
+        }
+        if !x.is_empty() && !is_valid_value(vr, x) {
+            return Err(trf("\"{}\" isn't a valid value.", x));
+        }
+    }
+    Ok(())
+}
+
+/// Create a sequence element with the items.
+pub fn sequence(tag: Tag, items: Vec<InMemDicomObject>) -> InMemElement {
+    DataElement::new(tag, VR::SQ, DataSetSequence::from(items))
+}
+
+/// Write the dataset as a file in the given transfer syntax.
+/// The media storage SOP class and instance UIDs of the file meta group are taken from the dataset.
+pub fn write_file(
+    obj: InMemDicomObject,
+    transfer_syntax: &str,
+    path: &Path,
+) -> std::io::Result<()> {
+    obj.with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+        .map_err(std::io::Error::other)?
+        .write_to_file(path)
+        .map_err(std::io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MAX_UID_LENGTH, dump_line_tag, dump_line_vr, element_display, new_uid_with_root,
+        save_element_value, sequence, text_element, write_file,
+    };
+    use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rsdicombrowser-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn tag_of_text_dump_lines() {
+        assert_eq!(
+            dump_line_tag("(0010,0010) PN Patient's Name                [Doe^John]"),
+            Some(Tag(0x0010, 0x0010))
+        );
+        assert_eq!(
+            dump_line_tag("(7FE0,0010) OW Pixel Data                    (65536 bytes)"),
+            Some(Tag(0x7FE0, 0x0010))
+        );
+        assert_eq!(
+            dump_line_tag("(0029,10ff) LO Private                      [x]"),
+            Some(Tag(0x0029, 0x10FF))
+        );
+    }
+
+    #[test]
+    fn tag_of_brief_dump_lines() {
+        assert_eq!(
+            dump_line_tag("(0008,0060) CS Modality = CT"),
+            Some(Tag(0x0008, 0x0060))
+        );
+        assert_eq!(dump_line_tag("  (0008,0100) SH CodeValue = 123"), None);
+    }
+
+    #[test]
+    fn lines_without_a_top_level_tag() {
+        assert_eq!(dump_line_tag(""), None);
+        assert_eq!(dump_line_tag("# Dicom-Data-Set"), None);
+        assert_eq!(dump_line_tag("  (FFFE,E000) na Item"), None);
+        assert_eq!(dump_line_tag("(0010,0010"), None);
+        assert_eq!(dump_line_tag("(10,0010) PN"), None);
+        assert_eq!(dump_line_tag("(0010,+010) PN"), None);
+        assert_eq!(dump_line_tag("(001g,0010) PN"), None);
+        assert_eq!(dump_line_tag("(00é0,0010) PN"), None);
+    }
+
+    #[test]
+    fn vr_of_dump_lines() {
+        assert_eq!(
+            dump_line_vr("(0010,0010) PN Patient's Name [Doe^John]"),
+            Some(VR::PN)
+        );
+        assert_eq!(dump_line_vr("(7FE0,0010) OW Pixel Data"), Some(VR::OW));
+        assert_eq!(dump_line_vr("  (0010,0010) PN Patient's Name"), None);
+    }
+
+    #[test]
+    fn binary_values_are_saved_by_their_tag() {
+        let payload = vec![0x01, 0x02, 0x03, 0x00, 0xFE, 0xFF];
+        let private_payload = vec![0xCA, 0xFE];
+        let obj = InMemDicomObject::from_element_iter([
+            text_element(tags::SOP_CLASS_UID, VR::UI, "1.2.840.10008.5.1.4.1.1.7"),
+            text_element(tags::SOP_INSTANCE_UID, VR::UI, "1.2.3.4"),
+            DataElement::new(
+                tags::ICC_PROFILE,
+                VR::OB,
+                PrimitiveValue::from(payload.clone()),
+            ),
+            DataElement::new(
+                Tag(0x0029, 0x1010),
+                VR::OB,
+                PrimitiveValue::from(private_payload.clone()),
+            ),
+        ]);
+        let file = temp_path("save-element.dcm");
+        let target = temp_path("save-element.bin");
+        let private_target = temp_path("save-private-element.bin");
+        let missing_target = temp_path("save-missing-element.bin");
+        write_file(obj, "1.2.840.10008.1.2.1", &file).unwrap();
+
+        save_element_value(&file, tags::ICC_PROFILE, &target).unwrap();
+        save_element_value(&file, Tag(0x0029, 0x1010), &private_target).unwrap();
+        let missing = save_element_value(&file, tags::PIXEL_DATA, &missing_target);
+        let saved = std::fs::read(&target);
+        let private_saved = std::fs::read(&private_target);
+        let _ = std::fs::remove_file(&file);
+        let _ = std::fs::remove_file(&target);
+        let _ = std::fs::remove_file(&private_target);
+
+        assert_eq!(saved.unwrap(), payload);
+        assert_eq!(private_saved.unwrap(), private_payload);
+        assert!(missing.is_err());
+        assert!(!missing_target.exists());
+    }
+
+    #[test]
+    fn values_of_a_line_are_compared_for_display() {
+        let previous = InMemDicomObject::from_element_iter([
+            text_element(tags::SERIES_NUMBER, VR::IS, "3 "),
+            sequence(
+                tags::REFERENCED_IMAGE_SEQUENCE,
+                vec![InMemDicomObject::new_empty()],
+            ),
+            text_element(Tag(0x0029, 0x1010), VR::LO, "VENDOR "),
+        ]);
+        let current = InMemDicomObject::from_element_iter([
+            text_element(tags::SERIES_NUMBER, VR::IS, "3"),
+            DataElement::new(
+                tags::ICC_PROFILE,
+                VR::OB,
+                PrimitiveValue::from(vec![0u8; 4]),
+            ),
+            DataElement::new(
+                Tag(0x0029, 0x1010),
+                VR::UN,
+                PrimitiveValue::from(b"VENDOR".to_vec()),
+            ),
+        ]);
+
+        assert_eq!(
+            element_display(&previous, tags::SERIES_NUMBER),
+            element_display(&current, tags::SERIES_NUMBER)
+        );
+        assert_eq!(
+            element_display(&current, tags::SERIES_NUMBER).as_deref(),
+            Some("3")
+        );
+
+        assert_eq!(
+            element_display(&previous, tags::REFERENCED_IMAGE_SEQUENCE).as_deref(),
+            Some("1 items")
+        );
+        assert_eq!(
+            element_display(&current, tags::REFERENCED_IMAGE_SEQUENCE),
+            None
+        );
+        assert_eq!(element_display(&previous, tags::ICC_PROFILE), None);
+        assert_eq!(
+            element_display(&current, tags::ICC_PROFILE).as_deref(),
+            Some("<4 bytes>")
+        );
+
+        assert_eq!(
+            element_display(&previous, Tag(0x0029, 0x1010)).as_deref(),
+            Some("VENDOR")
+        );
+        assert_eq!(
+            element_display(&current, Tag(0x0029, 0x1010)).as_deref(),
+            Some("<6 bytes>")
+        );
+    }
+
+    #[test]
+    fn uids_fit_under_long_roots() {
+        let root = "1.2.826.0.1.3680043.10.1234.5678.9012.34";
+        for root in [root, "2.25"] {
+            let uid = new_uid_with_root(root);
+            assert!(uid.len() <= MAX_UID_LENGTH, "{uid}");
+            let random = uid.strip_prefix(&format!("{root}.")).unwrap();
+            assert!(!random.is_empty() && random.bytes().all(|x| x.is_ascii_digit()));
+            assert!(random == "0" || !random.starts_with('0'), "{uid}");
+        }
+    }
+}
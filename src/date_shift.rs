@@ -0,0 +1,283 @@
+//! Shifting of the dates of the selected files by a fixed number of days, or by a random one per patient, e.g. to
+//! de-identify a study while keeping the intervals between the dates, or to make test data look recent.
+//! The DA values and the date part of the DT values are shifted by whole days, so that the TM values, and the times
+//! in the DT values, stay consistent with the dates they go with and are kept as they are.
+//! The random offsets of the patients are kept in a file in the data folder of the user, so that a patient is shifted
+//! the same way after a restart, and the offsets used are recorded in the audit log.
+
+use crate::audit::AuditLog;
+use crate::bulk_edit::{EditAction, EditOutput, PendingEdit, edit_to_temp_files, rename_edited};
+use crate::dataset::{element_string, sequence, text_element};
+use crate::i18n::{tr, trf};
+use crate::settings::app_data_dir;
+use crate::tasks::TaskPool;
+use chrono::{NaiveDate, TimeDelta};
+use dicom::core::value::Value;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+fn patient_days_path() -> PathBuf {
+    app_data_dir("date_shift_offsets.json")
+}
+
+/// Read the random offsets given to the patients before, if any.
+fn load_patient_days() -> HashMap<String, i64> {
+    std::fs::read_to_string(patient_days_path())
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+fn save_patient_days(patient_days: &HashMap<String, i64>) -> Result<()> {
+    let text = serde_json::to_string_pretty(patient_days).map_err(Error::other)?;
+    std::fs::write(patient_days_path(), text)
+}
+
+/// Get the key of the patient of the dataset for the random offsets: the PatientID, or else the study, so that the
+/// files without a PatientID don't all share one offset.
+fn patient_key(obj: &InMemDicomObject) -> (String, bool) {
+    match element_string(obj, tags::PATIENT_ID).filter(|x| !x.is_empty()) {
+        Some(patient_id) => (patient_id, true),
+        None => (
+            format!(
+                "<no PatientID, study {}>",
+                element_string(obj, tags::STUDY_INSTANCE_UID).unwrap_or_default()
+            ),
+            false,
+        ),
+    }
+}
+
+/// Shift the date at the start of the DA or DT value by the days. Values without a complete date, e.g. a year only,
+/// are kept.
+fn shift_date(value: &str, days: i64) -> String {
+    let Some(date) = value
+        .get(..8)
+        .and_then(|x| NaiveDate::parse_from_str(x, "%Y%m%d").ok())
+        .and_then(|x| x.checked_add_signed(TimeDelta::days(days)))
+    else {
+        return value.to_string();
+    };
+    format!("{}{}", date.format("%Y%m%d"), &value[8..])
+}
+
+/// Shift the DA and DT values of the dataset and of the items of its sequences.
+fn shift_dates(obj: &mut InMemDicomObject, days: i64) {
+    let tags: Vec<Tag> = obj.iter().map(|x| x.header().tag).collect();
+
+    for tag in tags {
+        let Ok(elem) = obj.element(tag) else {
+            continue;
+        };
+        match (elem.vr(), elem.value()) {
+            (vr @ (VR::DA | VR::DT), Value::Primitive(_)) => {
+                let Ok(value) = elem.to_str().map(|x| x.to_string()) else {
+                    continue;
+                };
+                // The multiple values are shifted one by one.
+                let shifted: Vec<String> = value
+                    .trim_end_matches(['\0', ' '])
+                    .split('\\')
+                    .map(|x| shift_date(x.trim(), days))
+                    .collect();
+                obj.put(text_element(tag, vr, &shifted.join("\\")));
+            }
+            (_, Value::Sequence(seq)) => {
+                let mut items = seq.items().to_vec();
+                for item in &mut items {
+                    shift_dates(item, days);
+                }
+                obj.put(sequence(tag, items));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Get a random number of days within the maximum either way, never zero.
+fn random_days(max_days: i64) -> i64 {
+    let max_days = max_days.max(1);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(max_days as u64);
+    let days = (hasher.finish() % (2 * max_days as u64)) as i64 - max_days;
+    if days >= 0 { days + 1 } else { days }
+}
+
+/// The offset, the output and the offsets given to the patients so far.
+pub struct DateShiftTool {
+    is_random: bool,
+    /// The days to shift by, or the maximum of the random days.
+    days: i64,
+    /// The random offsets of the patients, by PatientID, kept so that the patients are shifted the same way each time.
+    /// The files without a PatientID are keyed by their study.
+    patient_days: HashMap<String, i64>,
+    pub output: EditOutput,
+    pending: Option<PendingEdit>,
+    status: String,
+}
+
+impl Default for DateShiftTool {
+    fn default() -> Self {
+        Self {
+            is_random: false,
+            days: 365,
+            patient_days: load_patient_days(),
+            output: EditOutput::default(),
+            pending: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl DateShiftTool {
+    /// Take the result of the shift when it is done, with the offsets given to the new patients. Returns the files
+    /// written.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> Option<Vec<PathBuf>> {
+        let (status, targets) = PendingEdit::poll(&mut self.pending, audit_log)?;
+        self.status = status;
+        self.patient_days = load_patient_days();
+        (!targets.is_empty()).then_some(targets)
+    }
+
+    /// Show the offset and the output, and the offsets given to the patients.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        tasks: &mut TaskPool,
+        read_only: bool,
+    ) -> EditAction {
+        let mut action = EditAction::None;
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("date shift grid").show(ui, |ui| {
+            ui.label(tr("Offset"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.is_random, false, tr("Fixed"));
+                ui.radio_value(&mut self.is_random, true, tr("Random per patient"));
+            });
+            ui.end_row();
+
+            ui.label(if self.is_random {
+                tr("Maximum days")
+            } else {
+                tr("Days")
+            });
+            let range = if self.is_random {
+                1..=36500
+            } else {
+                -36500..=36500
+            };
+            ui.add(egui::DragValue::new(&mut self.days).range(range))
+                .on_hover_text(tr("Negative days shift the dates back"));
+            ui.end_row();
+
+            if self.output.grid_ui(ui, read_only) {
+                action = EditAction::PickTarget;
+            }
+        });
+
+        let output_dir = self.output.output_dir();
+        let can_run =
+            !files.is_empty() && self.days != 0 && output_dir.is_some() && self.pending.is_none();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(can_run, egui::Button::new(tr("Shift dates")))
+                .clicked()
+                && let Some(output_dir) = output_dir
+            {
+                let (is_random, days) = (self.is_random, self.days);
+                let files = files.to_vec();
+                let mut patient_days = self.patient_days.clone();
+                self.pending = Some(PendingEdit::spawn(
+                    tasks,
+                    tr("Shift dates"),
+                    move |context, written| {
+                        let mut used_days = BTreeMap::new();
+                        let mut without_patient_id = 0;
+                        let edits =
+                            edit_to_temp_files(&files, output_dir.as_deref(), context, |obj| {
+                                let days = if is_random {
+                                    let (key, has_patient_id) = patient_key(obj);
+                                    if !has_patient_id {
+                                        without_patient_id += 1;
+                                    }
+                                    let days = *patient_days
+                                        .entry(key.clone())
+                                        .or_insert_with(|| random_days(days));
+                                    used_days.insert(key, days);
+                                    days
+                                } else {
+                                    days
+                                };
+                                shift_dates(obj, days);
+                                Ok(())
+                            })?;
+                        if is_random && let Err(e) = save_patient_days(&patient_days) {
+                            log::error!("Failed to save the patient offsets: {e}");
+                        }
+
+                        // The offsets are recorded, as they are needed to trace the shifted dates back.
+                        let operation = if is_random {
+                            let offsets: Vec<String> = used_days
+                                .iter()
+                                .map(|(key, days)| format!("{key} {days:+}"))
+                                .collect();
+                            format!("Shift dates: {}", offsets.join(", "))
+                        } else {
+                            format!("Shift dates: {days:+}")
+                        };
+                        written.lock().unwrap_or_else(|x| x.into_inner()).operation = operation;
+                        let in_place_operation = output_dir.is_none().then_some("Shift dates");
+                        rename_edited(&edits, in_place_operation, written)?;
+
+                        Ok(if without_patient_id > 0 {
+                            trf(
+                                "{} files have no PatientID, and are shifted by their study.",
+                                without_patient_id,
+                            )
+                        } else {
+                            String::new()
+                        })
+                    },
+                ));
+                self.status.clear();
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        if self.is_random && !self.patient_days.is_empty() {
+            ui.separator();
+            egui::CollapsingHeader::new(trf("Patient offsets ({})", self.patient_days.len())).show(
+                ui,
+                |ui| {
+                    egui::Grid::new("patient days grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            let mut patients: Vec<_> = self.patient_days.iter().collect();
+                            patients.sort();
+                            for (patient_id, days) in patients {
+                                ui.monospace(patient_id);
+                                ui.label(trf("{} days", days));
+                                ui.end_row();
+                            }
+                        });
+                },
+            );
+        }
+
+        action
+    }
+}
@@ -0,0 +1,171 @@
+//! The diagnostic bundle attached to the bug reports: a ZIP archive with the logs of this run and the previous one, the
+//! settings, the report of the last crash, the dump of the selected file, which can be de-identified first, and a
+//! screenshot of the app in the privacy mode if asked.
+//! The log is written to a file in the data folder of the user as well as to stderr, so that it is there for the
+//! bundle. The log of the previous run is kept, as the problem reported often made the user restart the app.
+
+use crate::anonymize::Anonymizer;
+use crate::crash::crash_report_path;
+use crate::settings::{Settings, app_data_dir};
+use crate::zip::ZipWriter;
+use dicom::object::{FileMetaTableBuilder, open_file};
+use dicom_dump::DumpOptions;
+use std::io::{Error, Result, Write};
+use std::path::{Path, PathBuf};
+
+/// Get the path of the log file, which is moved to `previous_log_path` on every start.
+pub fn log_path() -> PathBuf {
+    app_data_dir("rsdicombrowser.log")
+}
+
+/// Get the path of the log file of the previous run.
+fn previous_log_path() -> PathBuf {
+    app_data_dir("rsdicombrowser.log.prev")
+}
+
+/// Write the log to both stderr and the log file.
+struct LogWriter {
+    file: Option<std::fs::File>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if let Some(file) = self.file.as_mut() {
+            // Keep logging to stderr if the file can't be written.
+            let _ = file.write_all(buf);
+        }
+        std::io::stderr().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+        }
+        std::io::stderr().flush()
+    }
+}
+
+/// Initialize the logger from `RUST_LOG`, writing to stderr and the log file, after keeping the log of the previous
+/// run.
+pub fn init_logging() {
+    // The log isn't there on the first run.
+    let _ = std::fs::rename(log_path(), previous_log_path());
+    let writer = LogWriter {
+        file: std::fs::File::create(log_path()).ok(),
+    };
+    env_logger::Builder::from_default_env()
+        .target(env_logger::Target::Pipe(Box::new(writer)))
+        .init();
+}
+
+/// Encode the image as a 24-bit BMP, which needs no compression and opens everywhere.
+fn encode_bmp(image: &egui::ColorImage) -> Vec<u8> {
+    let [width, height] = image.size;
+    let row_size = (width * 3).div_ceil(4) * 4;
+    let data_size = row_size * height;
+
+    let mut bmp = Vec::with_capacity(54 + data_size);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&((54 + data_size) as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&54u32.to_le_bytes());
+    // The BITMAPINFOHEADER, uncompressed, with no palette.
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(height as i32).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&24u16.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&(data_size as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 16]);
+
+    // The rows are stored bottom up, in BGR.
+    for row in image.pixels.chunks_exact(width.max(1)).rev() {
+        let start = bmp.len();
+        for pixel in row {
+            bmp.extend_from_slice(&[pixel.b(), pixel.g(), pixel.r()]);
+        }
+        bmp.resize(start + row_size, 0);
+    }
+
+    bmp
+}
+
+/// Dump the file as text, after de-identifying it if asked.
+fn dump_file(path: &Path, deidentify: bool) -> Result<String> {
+    let mut obj = open_file(path).map_err(Error::other)?;
+    if deidentify {
+        let transfer_syntax = obj
+            .meta()
+            .transfer_syntax()
+            .trim_end_matches('\0')
+            .to_string();
+        let mut dataset = obj.into_inner();
+        Anonymizer::new("ANONYMOUS", "ANON0001").anonymize(&mut dataset);
+        obj = dataset
+            .with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+            .map_err(Error::other)?;
+    }
+
+    let mut out = Vec::new();
+    DumpOptions::new()
+        .width(256)
+        .color_mode(dicom_dump::ColorMode::Never)
+        .no_limit(false)
+        .no_text_limit(false)
+        .format(dicom_dump::DumpFormat::Text)
+        .dump_file_to_with_limits(&mut out, &obj)?;
+    Ok(String::from_utf8_lossy(&out).to_string())
+}
+
+/// Write the diagnostic bundle with the screenshot if any, the logs, the settings and the dump of the selected file.
+/// The parts which can't be read are noted in `errors.txt` rather than failing the bundle.
+pub fn write_bundle(
+    path: &Path,
+    screenshot: Option<&egui::ColorImage>,
+    settings: &Settings,
+    selected_file: Option<&Path>,
+    deidentify: bool,
+) -> Result<()> {
+    let mut zip = ZipWriter::new(std::io::BufWriter::new(std::fs::File::create(path)?));
+    let mut errors = Vec::new();
+
+    if let Some(screenshot) = screenshot {
+        zip.add_file("screenshot.bmp", &encode_bmp(screenshot))?;
+    }
+    let about = format!(
+        "{} {}\n{} {}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    zip.add_file("about.txt", about.as_bytes())?;
+
+    match std::fs::read(log_path()) {
+        Ok(log) => zip.add_file("rsdicombrowser.log", &log)?,
+        Err(e) => errors.push(format!("{}: {e}", log_path().display())),
+    }
+    if let Ok(log) = std::fs::read(previous_log_path()) {
+        zip.add_file("rsdicombrowser.log.prev", &log)?;
+    }
+    zip.add_file("settings.json", &serde_json::to_vec_pretty(settings)?)?;
+    if let Ok(report) = std::fs::read(crash_report_path()) {
+        zip.add_file("crash_report.txt", &report)?;
+    }
+
+    if let Some(selected_file) = selected_file {
+        match dump_file(selected_file, deidentify) {
+            Ok(dump) => zip.add_file("dump.txt", dump.as_bytes())?,
+            Err(e) => errors.push(format!("{}: {e}", selected_file.display())),
+        }
+    }
+
+    if !errors.is_empty() {
+        zip.add_file("errors.txt", errors.join("\n").as_bytes())?;
+    }
+    zip.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,549 @@
+//! Creation of a DICOMDIR for media export.
+//! The selected files are copied into a `DICOM/Pnnnnnnn/Snnnnnnn/Snnnnnnn/Innnnnnn` tree with file IDs valid for the
+//! media profiles, and the DICOMDIR is written next to it.
+//! The records declare UTF-8 as their character set, which the values copied from the files are decoded to. An
+//! output folder which already has a DICOMDIR or a DICOM folder is refused rather than overwritten.
+//! The DICOMDIR is encoded here directly in explicit VR little endian with defined lengths, because the directory
+//! records link to each other by byte offsets which need to be known when writing.
+
+use crate::audit::AuditLog;
+use crate::dataset::{element_string, new_uid, open_header};
+use crate::i18n::{tr, trf};
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME, InMemDicomObject};
+use std::path::{Path, PathBuf};
+
+const MEDIA_STORAGE_DIRECTORY_STORAGE: &str = "1.2.840.10008.1.3.10";
+
+const FILE_SET_ID: Tag = Tag(0x0004, 0x1130);
+const OFFSET_OF_FIRST_ROOT_RECORD: Tag = Tag(0x0004, 0x1200);
+const OFFSET_OF_LAST_ROOT_RECORD: Tag = Tag(0x0004, 0x1202);
+const FILE_SET_CONSISTENCY_FLAG: Tag = Tag(0x0004, 0x1212);
+const DIRECTORY_RECORD_SEQUENCE: Tag = Tag(0x0004, 0x1220);
+const OFFSET_OF_NEXT_RECORD: Tag = Tag(0x0004, 0x1400);
+const RECORD_IN_USE_FLAG: Tag = Tag(0x0004, 0x1410);
+const OFFSET_OF_LOWER_LEVEL_RECORD: Tag = Tag(0x0004, 0x1420);
+const DIRECTORY_RECORD_TYPE: Tag = Tag(0x0004, 0x1430);
+const REFERENCED_FILE_ID: Tag = Tag(0x0004, 0x1500);
+const REFERENCED_SOP_CLASS_UID_IN_FILE: Tag = Tag(0x0004, 0x1510);
+const REFERENCED_SOP_INSTANCE_UID_IN_FILE: Tag = Tag(0x0004, 0x1511);
+const REFERENCED_TRANSFER_SYNTAX_UID_IN_FILE: Tag = Tag(0x0004, 0x1512);
+const UTF8_CHARACTER_SET: &str = "ISO_IR 192";
+
+/// The general purpose media profiles and the transfer syntaxes they allow.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MediaProfile {
+    GeneralCd,
+    GeneralDvdJpeg,
+    GeneralDvdJpeg2000,
+}
+
+impl MediaProfile {
+    pub const ALL: [MediaProfile; 3] = [
+        MediaProfile::GeneralCd,
+        MediaProfile::GeneralDvdJpeg,
+        MediaProfile::GeneralDvdJpeg2000,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MediaProfile::GeneralCd => "STD-GEN-CD",
+            MediaProfile::GeneralDvdJpeg => "STD-GEN-DVD-JPEG",
+            MediaProfile::GeneralDvdJpeg2000 => "STD-GEN-DVD-J2K",
+        }
+    }
+
+    /// Check if the transfer syntax is allowed on the media.
+    pub fn allows(&self, transfer_syntax: &str) -> bool {
+        let allowed: &[&str] = match self {
+            MediaProfile::GeneralCd => &[uids::EXPLICIT_VR_LITTLE_ENDIAN],
+            MediaProfile::GeneralDvdJpeg => &[
+                uids::EXPLICIT_VR_LITTLE_ENDIAN,
+                "1.2.840.10008.1.2.4.50", // JPEG Baseline
+                "1.2.840.10008.1.2.4.51", // JPEG Extended
+                "1.2.840.10008.1.2.4.57", // JPEG Lossless
+                "1.2.840.10008.1.2.4.70", // JPEG Lossless SV1
+            ],
+            MediaProfile::GeneralDvdJpeg2000 => &[
+                uids::EXPLICIT_VR_LITTLE_ENDIAN,
+                "1.2.840.10008.1.2.4.90", // JPEG 2000 Lossless
+                "1.2.840.10008.1.2.4.91", // JPEG 2000
+            ],
+        };
+        allowed.contains(&transfer_syntax)
+    }
+}
+
+/// A directory record with its key elements and its lower level records.
+struct Record {
+    key: String,
+    elements: Vec<(Tag, VR, Vec<u8>)>,
+    children: Vec<Record>,
+}
+
+impl Record {
+    fn new(key: String, record_type: &str) -> Self {
+        Self {
+            key,
+            elements: vec![
+                (
+                    DIRECTORY_RECORD_TYPE,
+                    VR::CS,
+                    text_value(VR::CS, record_type),
+                ),
+                (
+                    tags::SPECIFIC_CHARACTER_SET,
+                    VR::CS,
+                    text_value(VR::CS, UTF8_CHARACTER_SET),
+                ),
+            ],
+            children: Vec::new(),
+        }
+    }
+
+    /// Add the elements copied from the dataset, leaving them empty if missing.
+    fn with_elements(mut self, obj: &InMemDicomObject, elements: &[(Tag, VR)]) -> Self {
+        for (tag, vr) in elements {
+            let value = element_string(obj, *tag).unwrap_or_default();
+            self.elements.push((*tag, *vr, text_value(*vr, &value)));
+        }
+        self
+    }
+
+    /// Get the child record with the key, adding it if it is not there yet. The 1-based index is returned as well.
+    fn child(&mut self, key: &str, make: impl FnOnce() -> Record) -> (usize, &mut Record) {
+        let index = match self.children.iter().position(|x| x.key == key) {
+            Some(index) => index,
+            None => {
+                self.children.push(make());
+                self.children.len() - 1
+            }
+        };
+        (index + 1, &mut self.children[index])
+    }
+}
+
+/// Pad the text to an even length, with a null for UIDs and a space otherwise.
+fn text_value(vr: VR, value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    if bytes.len() % 2 == 1 {
+        bytes.push(if vr == VR::UI { 0 } else { b' ' });
+    }
+    bytes
+}
+
+/// Encode the element in explicit VR little endian.
+fn encode_element(out: &mut Vec<u8>, tag: Tag, vr: VR, value: &[u8]) {
+    out.extend(tag.group().to_le_bytes());
+    out.extend(tag.element().to_le_bytes());
+    out.extend(vr.to_string().as_bytes());
+    if matches!(
+        vr,
+        VR::OB
+            | VR::OD
+            | VR::OF
+            | VR::OL
+            | VR::OV
+            | VR::OW
+            | VR::SQ
+            | VR::UC
+            | VR::UN
+            | VR::UR
+            | VR::UT
+    ) {
+        out.extend([0, 0]);
+        out.extend((value.len() as u32).to_le_bytes());
+    } else {
+        out.extend((value.len() as u16).to_le_bytes());
+    }
+    out.extend(value);
+}
+
+/// Encode the directory record as a sequence item with the offsets to the next and the lower level records.
+fn encode_record(record: &Record, next_offset: u32, lower_offset: u32) -> Vec<u8> {
+    let mut elements = vec![
+        (
+            OFFSET_OF_NEXT_RECORD,
+            VR::UL,
+            next_offset.to_le_bytes().to_vec(),
+        ),
+        (RECORD_IN_USE_FLAG, VR::US, 0xFFFFu16.to_le_bytes().to_vec()),
+        (
+            OFFSET_OF_LOWER_LEVEL_RECORD,
+            VR::UL,
+            lower_offset.to_le_bytes().to_vec(),
+        ),
+    ];
+    elements.extend(record.elements.iter().cloned());
+    elements.sort_by_key(|(tag, _, _)| *tag);
+
+    let mut body = Vec::new();
+    for (tag, vr, value) in &elements {
+        encode_element(&mut body, *tag, *vr, value);
+    }
+
+    let mut out = Vec::new();
+    out.extend(0xFFFEu16.to_le_bytes());
+    out.extend(0xE000u16.to_le_bytes());
+    out.extend((body.len() as u32).to_le_bytes());
+    out.extend(body);
+    out
+}
+
+/// A record in the flattened order of the sequence, with the indexes of the next and the first lower level records.
+struct FlatRecord<'a> {
+    record: &'a Record,
+    next: Option<usize>,
+    lower: Option<usize>,
+}
+
+/// Flatten the records depth first, and get the indexes of the given records.
+fn flatten<'a>(records: &'a [Record], flat: &mut Vec<FlatRecord<'a>>) -> Vec<usize> {
+    let mut indexes = Vec::new();
+
+    for record in records {
+        let index = flat.len();
+        flat.push(FlatRecord {
+            record,
+            next: None,
+            lower: None,
+        });
+        indexes.push(index);
+
+        let children = flatten(&record.children, flat);
+        flat[index].lower = children.first().copied();
+    }
+    for x in indexes.windows(2) {
+        flat[x[0]].next = Some(x[1]);
+    }
+
+    indexes
+}
+
+/// Encode the file meta information of the DICOMDIR, including the preamble.
+fn encode_meta() -> Vec<u8> {
+    let mut elements = Vec::new();
+    encode_element(&mut elements, Tag(0x0002, 0x0001), VR::OB, &[0, 1]);
+    for (tag, value) in [
+        (
+            Tag(0x0002, 0x0002),
+            MEDIA_STORAGE_DIRECTORY_STORAGE.to_string(),
+        ),
+        (Tag(0x0002, 0x0003), new_uid()),
+        (
+            Tag(0x0002, 0x0010),
+            uids::EXPLICIT_VR_LITTLE_ENDIAN.to_string(),
+        ),
+        (Tag(0x0002, 0x0012), IMPLEMENTATION_CLASS_UID.to_string()),
+    ] {
+        encode_element(&mut elements, tag, VR::UI, &text_value(VR::UI, &value));
+    }
+    encode_element(
+        &mut elements,
+        Tag(0x0002, 0x0013),
+        VR::SH,
+        &text_value(VR::SH, IMPLEMENTATION_VERSION_NAME),
+    );
+
+    let mut out = vec![0; 128];
+    out.extend(b"DICM");
+    encode_element(
+        &mut out,
+        Tag(0x0002, 0x0000),
+        VR::UL,
+        &(elements.len() as u32).to_le_bytes(),
+    );
+    out.extend(elements);
+    out
+}
+
+/// Encode the whole DICOMDIR file.
+fn encode_dicomdir(file_set_id: &str, patients: &[Record]) -> Vec<u8> {
+    let meta = encode_meta();
+
+    let mut flat = Vec::new();
+    let roots = flatten(patients, &mut flat);
+
+    // The sizes of the records don't depend on the offset values, so the positions can be worked out first.
+    let sizes: Vec<usize> = flat
+        .iter()
+        .map(|x| encode_record(x.record, 0, 0).len())
+        .collect();
+    let encode_header = |first: u32, last: u32, items_length: u32| {
+        let mut out = Vec::new();
+        encode_element(
+            &mut out,
+            FILE_SET_ID,
+            VR::CS,
+            &text_value(VR::CS, file_set_id),
+        );
+        encode_element(
+            &mut out,
+            OFFSET_OF_FIRST_ROOT_RECORD,
+            VR::UL,
+            &first.to_le_bytes(),
+        );
+        encode_element(
+            &mut out,
+            OFFSET_OF_LAST_ROOT_RECORD,
+            VR::UL,
+            &last.to_le_bytes(),
+        );
+        encode_element(
+            &mut out,
+            FILE_SET_CONSISTENCY_FLAG,
+            VR::US,
+            &0u16.to_le_bytes(),
+        );
+        // The sequence header, with the items following it.
+        out.extend(DIRECTORY_RECORD_SEQUENCE.group().to_le_bytes());
+        out.extend(DIRECTORY_RECORD_SEQUENCE.element().to_le_bytes());
+        out.extend(b"SQ");
+        out.extend([0, 0]);
+        out.extend(items_length.to_le_bytes());
+        out
+    };
+
+    let first_position = meta.len() + encode_header(0, 0, 0).len();
+    let positions: Vec<u32> = sizes
+        .iter()
+        .scan(first_position, |position, size| {
+            let current = *position;
+            *position += size;
+            Some(current as u32)
+        })
+        .collect();
+    let offset_of = |index: Option<usize>| index.map_or(0, |x| positions[x]);
+
+    let mut out = meta;
+    out.extend(encode_header(
+        offset_of(roots.first().copied()),
+        offset_of(roots.last().copied()),
+        sizes.iter().sum::<usize>() as u32,
+    ));
+    for x in &flat {
+        out.extend(encode_record(
+            x.record,
+            offset_of(x.next),
+            offset_of(x.lower),
+        ));
+    }
+    out
+}
+
+/// Get the directory record type of the instance from its modality.
+fn instance_record_type(obj: &InMemDicomObject) -> &'static str {
+    match element_string(obj, tags::MODALITY).as_deref() {
+        Some("SR") => "SR",
+        Some("PR") => "PRESENTATION",
+        Some("KO") => "KEY OBJECT DOC",
+        Some("RTSTRUCT") => "RT STRUCTURE SET",
+        Some("RTPLAN") => "RT PLAN",
+        Some("RTDOSE") => "RT DOSE",
+        Some("DOC") => "ENCAP DOC",
+        _ => "IMAGE",
+    }
+}
+
+/// Copy the files into the media folder structure and write the DICOMDIR. The warnings are returned.
+/// Fails without writing anything if the folder already has a DICOMDIR or a DICOM folder.
+pub fn create(
+    files: &[PathBuf],
+    output_dir: &Path,
+    file_set_id: &str,
+    profile: MediaProfile,
+) -> std::io::Result<Vec<String>> {
+    for name in ["DICOMDIR", "DICOM"] {
+        let path = output_dir.join(name);
+        if path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                trf("{} already exists, choose an empty folder", path.display()),
+            ));
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let mut root = Record::new(String::new(), "");
+
+    for file in files {
+        let obj = match open_header(file) {
+            Ok(x) => x,
+            Err(e) => {
+                warnings.push(format!("{}: {e}", file.display()));
+                continue;
+            }
+        };
+        let transfer_syntax = obj.meta().transfer_syntax().to_string();
+        if !profile.allows(&transfer_syntax) {
+            warnings.push(format!(
+                "{}: {}",
+                file.display(),
+                trf(
+                    "transfer syntax {} is not allowed by the profile",
+                    &transfer_syntax
+                )
+            ));
+        }
+
+        let key = |tag| element_string(&obj, tag).unwrap_or_default();
+        let (patient_index, patient) = root.child(&key(tags::PATIENT_ID), || {
+            Record::new(key(tags::PATIENT_ID), "PATIENT").with_elements(
+                &obj,
+                &[(tags::PATIENT_NAME, VR::PN), (tags::PATIENT_ID, VR::LO)],
+            )
+        });
+        let (study_index, study) = patient.child(&key(tags::STUDY_INSTANCE_UID), || {
+            Record::new(key(tags::STUDY_INSTANCE_UID), "STUDY").with_elements(
+                &obj,
+                &[
+                    (tags::STUDY_DATE, VR::DA),
+                    (tags::STUDY_TIME, VR::TM),
+                    (tags::ACCESSION_NUMBER, VR::SH),
+                    (tags::STUDY_DESCRIPTION, VR::LO),
+                    (tags::STUDY_INSTANCE_UID, VR::UI),
+                    (tags::STUDY_ID, VR::SH),
+                ],
+            )
+        });
+        let (series_index, series) = study.child(&key(tags::SERIES_INSTANCE_UID), || {
+            Record::new(key(tags::SERIES_INSTANCE_UID), "SERIES").with_elements(
+                &obj,
+                &[
+                    (tags::MODALITY, VR::CS),
+                    (tags::SERIES_INSTANCE_UID, VR::UI),
+                    (tags::SERIES_NUMBER, VR::IS),
+                ],
+            )
+        });
+
+        let components = [
+            "DICOM".to_string(),
+            format!("P{patient_index:07}"),
+            format!("S{study_index:07}"),
+            format!("S{series_index:07}"),
+            format!("I{:07}", series.children.len() + 1),
+        ];
+        let target = components
+            .iter()
+            .fold(output_dir.to_path_buf(), |path, x| path.join(x));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(file, &target)?;
+
+        let mut instance = Record::new(key(tags::SOP_INSTANCE_UID), instance_record_type(&obj))
+            .with_elements(&obj, &[(tags::INSTANCE_NUMBER, VR::IS)]);
+        instance.elements.extend([
+            (
+                REFERENCED_FILE_ID,
+                VR::CS,
+                text_value(VR::CS, &components.join("\\")),
+            ),
+            (
+                REFERENCED_SOP_CLASS_UID_IN_FILE,
+                VR::UI,
+                text_value(VR::UI, obj.meta().media_storage_sop_class_uid()),
+            ),
+            (
+                REFERENCED_SOP_INSTANCE_UID_IN_FILE,
+                VR::UI,
+                text_value(VR::UI, obj.meta().media_storage_sop_instance_uid()),
+            ),
+            (
+                REFERENCED_TRANSFER_SYNTAX_UID_IN_FILE,
+                VR::UI,
+                text_value(VR::UI, &transfer_syntax),
+            ),
+        ]);
+        series.children.push(instance);
+    }
+
+    std::fs::write(
+        output_dir.join("DICOMDIR"),
+        encode_dicomdir(file_set_id, &root.children),
+    )?;
+    Ok(warnings)
+}
+
+/// The state of the DICOMDIR window.
+pub struct DicomdirTool {
+    pub file_set_id: String,
+    pub profile: MediaProfile,
+    pub output_dir: Option<PathBuf>,
+    status: String,
+}
+
+impl Default for DicomdirTool {
+    fn default() -> Self {
+        Self {
+            file_set_id: "DICOMBROWSER".to_string(),
+            profile: MediaProfile::GeneralCd,
+            output_dir: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl DicomdirTool {
+    /// Show the DICOMDIR options. Returns true if the output folder should be picked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf], audit_log: &mut AuditLog) -> bool {
+        let mut pick_output = false;
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("dicomdir grid").show(ui, |ui| {
+            ui.label(tr("File-set ID"));
+            ui.add(egui::TextEdit::singleline(&mut self.file_set_id).char_limit(16));
+            ui.end_row();
+
+            ui.label(tr("Media profile"));
+            egui::ComboBox::from_id_salt("media profile")
+                .selected_text(self.profile.label())
+                .show_ui(ui, |ui| {
+                    for profile in MediaProfile::ALL {
+                        ui.selectable_value(&mut self.profile, profile, profile.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label(tr("Output folder"));
+            ui.horizontal(|ui| {
+                let output = self
+                    .output_dir
+                    .as_ref()
+                    .map_or(tr("None"), |x| x.display().to_string());
+                ui.monospace(output);
+                pick_output = ui.button(tr("Browse")).clicked();
+            });
+            ui.end_row();
+        });
+
+        if let Some(output_dir) = self.output_dir.as_ref()
+            && ui
+                .add_enabled(!files.is_empty(), egui::Button::new(tr("Create")))
+                .clicked()
+        {
+            let file_set_id = self.file_set_id.to_uppercase();
+            let result = create(files, output_dir, &file_set_id, self.profile);
+            if result.is_ok() {
+                audit_log.record(
+                    "Create DICOMDIR",
+                    files.to_vec(),
+                    vec![output_dir.join("DICOMDIR")],
+                );
+            }
+            self.status = match result {
+                Ok(warnings) if warnings.is_empty() => tr("DICOMDIR created."),
+                Ok(warnings) => warnings.join("\n"),
+                Err(e) => e.to_string(),
+            };
+        }
+
+        if !self.status.is_empty() {
+            egui::ScrollArea::vertical().show(ui, |ui| ui.label(&self.status));
+        }
+
+        pick_output
+    }
+}
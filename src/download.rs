@@ -0,0 +1,172 @@
+//! Downloads over HTTP(S), and over SFTP or SMB for the remote folders. The app has no HTTP client, so the requests
+//! are made by `curl`, which ships with Windows 10 and later, macOS and most Linux distributions.
+//! The callers make the requests on the worker threads, so that a slow server doesn't block the UI.
+
+use std::io::{Error, Result, Write};
+use std::path::Path;
+use std::process::Stdio;
+
+/// Encode the text for a URL, keeping only the unreserved characters, e.g. the name of a file or a query value.
+pub fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|x| match x {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(x).to_string()
+            }
+            _ => format!("%{x:02X}"),
+        })
+        .collect()
+}
+
+/// Create the `curl` command failing on the HTTP errors, with the common options.
+/// A transfer is aborted when it stalls rather than after a fixed time, so that the large files can be downloaded.
+fn curl_command() -> std::process::Command {
+    let mut command = std::process::Command::new("curl");
+    command.args([
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--location",
+        "--connect-timeout",
+        "30",
+        "--speed-limit",
+        "1",
+        "--speed-time",
+        "60",
+    ]);
+    // Don't flash a console window from the GUI app.
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// The user and the password of a server, and the headers carrying secrets, e.g. a bearer token, passed to `curl` in
+/// a config on its standard input rather than as arguments, which the other users of the machine could see.
+#[derive(Clone, Default)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+    pub headers: Vec<String>,
+}
+
+impl Credentials {
+    fn config(&self) -> String {
+        let quote = |x: &str| {
+            x.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        };
+        let mut config = String::new();
+        if !self.user.is_empty() {
+            config.push_str(&format!(
+                "user = \"{}:{}\"\n",
+                quote(&self.user),
+                quote(&self.password)
+            ));
+        }
+        for header in &self.headers {
+            config.push_str(&format!("header = \"{}\"\n", quote(header)));
+        }
+        config
+    }
+}
+
+/// Run the command on the URL, failing with its error output.
+fn run(command: std::process::Command, url: &str) -> Result<Vec<u8>> {
+    run_with(command, url, None)
+}
+
+/// Run the command on the URL with the credentials if any, failing with its error output.
+/// The URL comes after `--`, so that it is never taken as an option.
+fn run_with(
+    mut command: std::process::Command,
+    url: &str,
+    credentials: Option<&Credentials>,
+) -> Result<Vec<u8>> {
+    if credentials.is_some() {
+        command.args(["--config", "-"]);
+    }
+    command.arg("--").arg(url);
+    let Some(credentials) = credentials else {
+        return finish(command.output()?);
+    };
+
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+    // Dropping the input closes it, for curl to go on with the request.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(credentials.config().as_bytes())?;
+    }
+    finish(child.wait_with_output()?)
+}
+
+/// Get the output of the command, or its error output if it failed.
+fn finish(output: std::process::Output) -> Result<Vec<u8>> {
+    if !output.status.success() {
+        return Err(Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Get the body of the URL, sending the headers, e.g. `Accept: application/json`.
+pub fn fetch(url: &str, headers: &[&str]) -> Result<Vec<u8>> {
+    let mut command = curl_command();
+    for header in headers {
+        command.args(["--header", header]);
+    }
+    run(command, url)
+}
+
+/// Download the URL to the file, sending the headers.
+pub fn fetch_to_file(url: &str, headers: &[&str], path: &Path) -> Result<()> {
+    let mut command = curl_command();
+    for header in headers {
+        command.args(["--header", header]);
+    }
+    command.arg("--output").arg(path);
+    run(command, url).map(|_| ())
+}
+
+/// Get the body of the URL, e.g. the listing of an SFTP folder, with the extra options of `curl`, e.g. `--range`, and
+/// the credentials if any.
+pub fn fetch_as(url: &str, options: &[&str], credentials: Option<&Credentials>) -> Result<Vec<u8>> {
+    let mut command = curl_command();
+    command.args(options);
+    run_with(command, url, credentials)
+}
+
+/// Post the file to the URL as the body, with the extra options of `curl` and the credentials if any. Returns the body
+/// of the response.
+pub fn post_file_as(
+    url: &str,
+    options: &[&str],
+    credentials: Option<&Credentials>,
+    path: &Path,
+) -> Result<Vec<u8>> {
+    let mut command = curl_command();
+    let mut data = std::ffi::OsString::from("@");
+    data.push(path);
+    command.args(options).arg("--data-binary").arg(data);
+    run_with(command, url, credentials)
+}
+
+/// Download the URL to the file, with the extra options of `curl` and the credentials if any.
+pub fn fetch_to_file_as(
+    url: &str,
+    options: &[&str],
+    credentials: Option<&Credentials>,
+    path: &Path,
+) -> Result<()> {
+    let mut command = curl_command();
+    command.args(options).arg("--output").arg(path);
+    run_with(command, url, credentials).map(|_| ())
+}
@@ -0,0 +1,545 @@
+//! Text dumps of the files, kept in pages of lines rather than in one string.
+//! Some structured reports and structure sets have dumps with millions of lines, which then don't need a contiguous
+//! allocation of their whole size, and can be shown a screen of lines at a time.
+//! The lines are also kept lowercased when the dump is generated, so that the repeated searches are plain substring
+//! matches.
+//! The same opened file can be dumped in the formats of `DumpStyle`, so that switching the format doesn't read the file
+//! again.
+//! A dump is cancelled by its token when another file is selected, which fails the next write so that the dump of a
+//! huge file stops early rather than keeping a worker busy.
+
+use crate::dataset::{dump_line_tag, open_header, primitive_to_string, tag_keyword};
+use crate::i18n::{tr, trf};
+use crate::tasks::CancelToken;
+use dicom::core::Tag;
+use dicom::core::value::Value;
+use dicom::object::{DefaultDicomObject, InMemDicomObject, open_file};
+use dicom_dump::DumpOptions;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Files larger than this are read up to the pixel data only.
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The longest value shown in the brief dump.
+const BRIEF_VALUE_LENGTH: usize = 80;
+
+/// The end of a value cut in the dump, the same as dicom-dump ends the long values of the text format with.
+pub const TRUNCATION_MARK: &str = "...";
+
+/// The number of lines in a page.
+const PAGE_LINES: usize = 4096;
+
+/// A page of lines, with the start offset of each line in the text.
+#[derive(Default)]
+struct Page {
+    text: String,
+    starts: Vec<usize>,
+}
+
+impl Page {
+    fn line(&self, index: usize) -> &str {
+        let start = self.starts[index];
+        let end = self
+            .starts
+            .get(index + 1)
+            .map_or(self.text.len(), |x| x - 1);
+        &self.text[start..end]
+    }
+
+    fn push(&mut self, line: &str) {
+        if !self.starts.is_empty() {
+            self.text.push('\n');
+        }
+        self.starts.push(self.text.len());
+        self.text.push_str(line);
+    }
+}
+
+/// The lines of a dump.
+#[derive(Default)]
+pub struct Dump {
+    pages: Vec<Page>,
+    lowercase_pages: Vec<Page>,
+    /// Some text couldn't be decoded, e.g. in an unsupported character set, and was replaced by U+FFFD.
+    has_replacements: bool,
+}
+
+impl Dump {
+    pub fn line_count(&self) -> usize {
+        self.pages
+            .last()
+            .map_or(0, |x| (self.pages.len() - 1) * PAGE_LINES + x.starts.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn has_replacements(&self) -> bool {
+        self.has_replacements
+    }
+
+    /// Get the line by its index.
+    pub fn line(&self, index: usize) -> Option<&str> {
+        let page = self.pages.get(index / PAGE_LINES)?;
+        (index % PAGE_LINES < page.starts.len()).then(|| page.line(index % PAGE_LINES))
+    }
+
+    /// Check if the line contains the lowercased text, ignoring the case.
+    pub fn line_contains(&self, index: usize, lowercase_text: &str) -> bool {
+        self.lowercase_pages
+            .get(index / PAGE_LINES)
+            .is_some_and(|x| x.line(index % PAGE_LINES).contains(lowercase_text))
+    }
+
+    /// Iterate the lines in order.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.pages
+            .iter()
+            .flat_map(|page| (0..page.starts.len()).map(|x| page.line(x)))
+    }
+
+    /// Join the lines into one string, e.g. for the scripts.
+    pub fn to_text(&self) -> String {
+        self.pages
+            .iter()
+            .map(|x| x.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn push(&mut self, line: &str) {
+        self.has_replacements |= line.contains(char::REPLACEMENT_CHARACTER);
+        if self
+            .pages
+            .last()
+            .is_none_or(|x| x.starts.len() == PAGE_LINES)
+        {
+            self.pages.push(Page::default());
+            self.lowercase_pages.push(Page::default());
+        }
+        self.pages
+            .last_mut()
+            .expect("a page was just added")
+            .push(line);
+        self.lowercase_pages
+            .last_mut()
+            .expect("a page was just added")
+            .push(&line.to_lowercase());
+    }
+}
+
+/// Split the output written into the lines of a dump, so that the whole output is never buffered.
+/// Invalid UTF-8 in a line is replaced rather than failing the dump.
+#[derive(Default)]
+pub struct DumpWriter {
+    dump: Dump,
+    partial_line: Vec<u8>,
+}
+
+impl DumpWriter {
+    /// Get the dump, with the last line even if it has no line break.
+    pub fn finish(mut self) -> Dump {
+        if !self.partial_line.is_empty() {
+            self.dump.push(&String::from_utf8_lossy(&self.partial_line));
+        }
+        self.dump
+    }
+}
+
+impl Write for DumpWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|x| *x == b'\n') {
+            self.partial_line.extend_from_slice(&rest[..end]);
+            self.dump.push(&String::from_utf8_lossy(&self.partial_line));
+            self.partial_line.clear();
+            rest = &rest[end + 1..];
+        }
+        self.partial_line.extend_from_slice(rest);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer failing the writes once the token is cancelled, which stops the writers of dicom-dump and serde_json.
+struct Cancellable<'a, W> {
+    inner: W,
+    token: &'a CancelToken,
+}
+
+impl<W: Write> Write for Cancellable<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.token.is_cancelled() {
+            return Err(std::io::Error::other("the dump was cancelled"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The formats of the dump.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DumpStyle {
+    /// The full text dump of dicom-dump.
+    #[default]
+    Text,
+    /// One line per element, with the keyword and the start of the value.
+    Brief,
+    /// The DICOM JSON model, pretty printed.
+    Json,
+}
+
+impl DumpStyle {
+    pub const ALL: [DumpStyle; 3] = [DumpStyle::Text, DumpStyle::Brief, DumpStyle::Json];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DumpStyle::Text => "Text",
+            DumpStyle::Brief => "Brief",
+            DumpStyle::Json => "JSON",
+        }
+    }
+}
+
+/// The file opened for the dump, kept to dump it again in another format.
+pub struct DumpSource {
+    pub path: PathBuf,
+    obj: DefaultDicomObject,
+    is_large_file: bool,
+}
+
+impl DumpSource {
+    /// Open the file, skipping the pixel data of large files so that the dump appears without reading the whole file.
+    pub fn open(path: &Path) -> Option<Self> {
+        let is_large_file = std::fs::metadata(path).is_ok_and(|x| x.len() > LARGE_FILE_SIZE);
+        let obj = if is_large_file {
+            open_header(path).map_err(|e| e.to_string())
+        } else {
+            open_file(path).map_err(|e| e.to_string())
+        };
+
+        match obj {
+            Ok(obj) => Some(Self {
+                path: path.to_path_buf(),
+                obj,
+                is_large_file,
+            }),
+            Err(e) => {
+                log::error!("Failed to open {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Dump the file in the format. A failure is shown in the dump rather than failing it.
+    /// The dump stops early if the token is cancelled, and is incomplete then.
+    pub fn dump(&self, style: DumpStyle, token: &CancelToken) -> Dump {
+        let mut out = Cancellable {
+            inner: DumpWriter::default(),
+            token,
+        };
+        if self.is_large_file {
+            let _ = writeln!(
+                out,
+                "# {}",
+                trf(
+                    "Pixel data is not loaded for files larger than {} MiB.",
+                    LARGE_FILE_SIZE / 1024 / 1024
+                )
+            );
+        }
+
+        let result = match style {
+            DumpStyle::Text => DumpOptions::new()
+                .width(256)
+                .color_mode(dicom_dump::ColorMode::Never)
+                .no_limit(false)
+                .no_text_limit(false)
+                .format(dicom_dump::DumpFormat::Text)
+                .dump_file_to_with_limits(&mut out, &self.obj),
+            DumpStyle::Brief => {
+                let transfer_syntax = self.obj.meta().transfer_syntax().trim_end_matches('\0');
+                writeln!(out, "# {}", trf("Transfer syntax: {}", transfer_syntax))
+                    .and_then(|_| write_brief(&mut out, &self.obj, 0))
+            }
+            DumpStyle::Json => write_json(&mut out, &self.obj),
+        };
+        if let Err(e) = result {
+            let _ = writeln!(out, "# {}", trf("Failed to dump the file: {}", e));
+        }
+
+        out.inner.finish()
+    }
+}
+
+/// The dump of a file, with the file opened for it and the times taken.
+pub struct DumpOutput {
+    pub source: Option<DumpSource>,
+    pub dump: Dump,
+    pub open_time: Duration,
+    pub dump_time: Duration,
+}
+
+/// Dump the file in the format, opening it unless the source is given.
+pub fn dump_file(
+    path: &Path,
+    source: Option<DumpSource>,
+    style: DumpStyle,
+    token: &CancelToken,
+) -> DumpOutput {
+    let start = Instant::now();
+    let source = source.or_else(|| DumpSource::open(path));
+    let open_time = start.elapsed();
+
+    let start = Instant::now();
+    let dump = source
+        .as_ref()
+        .map_or_else(Dump::default, |x| x.dump(style, token));
+
+    DumpOutput {
+        source,
+        dump,
+        open_time,
+        dump_time: start.elapsed(),
+    }
+}
+
+/// Write one line per element, indenting the items of the sequences.
+/// The top-level lines start with the tag and the VR like the text dump, so that they can be parsed the same way.
+fn write_brief(out: &mut impl Write, obj: &InMemDicomObject, depth: usize) -> std::io::Result<()> {
+    let indent = "  ".repeat(depth);
+
+    for elem in obj {
+        let tag = elem.header().tag;
+        let vr = elem.vr();
+        let keyword = tag_keyword(tag);
+        match elem.value() {
+            Value::Primitive(value) => {
+                let mut value = primitive_to_string(vr, value);
+                if let Some((index, _)) = value.char_indices().nth(BRIEF_VALUE_LENGTH) {
+                    value.truncate(index);
+                    value.push_str(TRUNCATION_MARK);
+                }
+                writeln!(out, "{indent}{tag} {vr} {keyword} = {value}")?;
+            }
+            Value::Sequence(seq) => {
+                writeln!(
+                    out,
+                    "{indent}{tag} {vr} {keyword} ({})",
+                    trf("{} items", seq.items().len())
+                )?;
+                for (index, item) in seq.items().iter().enumerate() {
+                    writeln!(out, "{indent}  {} {}", tr("Item"), index + 1)?;
+                    write_brief(out, item, depth + 2)?;
+                }
+            }
+            Value::PixelSequence(seq) => {
+                writeln!(
+                    out,
+                    "{indent}{tag} {vr} {keyword} ({})",
+                    trf("{} fragments", seq.fragments().len())
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the tag of a top-level element line whose value is cut in the text or the brief dump. A value merely ending with
+/// an ellipsis isn't cut: the text dump leaves the bracket of a cut value open, and the brief dump cuts the values at
+/// their maximum length.
+pub fn truncated_line_tag(line: &str) -> Option<Tag> {
+    let tag = dump_line_tag(line)?;
+    let value = line.trim_end().strip_suffix(TRUNCATION_MARK)?;
+    let bracket = value.find('[');
+    let is_cut = match value.find(" = ") {
+        Some(start) if bracket.is_none_or(|x| start < x) => {
+            value[start + 3..].chars().count() == BRIEF_VALUE_LENGTH
+        }
+        _ => bracket.is_some(),
+    };
+    is_cut.then_some(tag)
+}
+
+/// Write the DICOM JSON of the dataset, pretty printed as it is written rather than once it is all written.
+/// The output is cancelled by its token already.
+fn write_json(out: &mut impl Write, obj: &DefaultDicomObject) -> std::io::Result<()> {
+    let mut json = PrettyJson::new(&mut *out);
+    DumpOptions::new()
+        .format(dicom_dump::DumpFormat::Json)
+        .dump_file_to(&mut json, obj)?;
+    writeln!(out)
+}
+
+/// Pretty print the compact JSON written through it the same way as `serde_json`, a chunk at a time.
+struct PrettyJson<W> {
+    inner: W,
+    depth: usize,
+    in_string: bool,
+    is_escaped: bool,
+    /// An object or an array was opened, and nothing written in it yet.
+    is_opened: bool,
+}
+
+impl<W: Write> PrettyJson<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            depth: 0,
+            in_string: false,
+            is_escaped: false,
+            is_opened: false,
+        }
+    }
+
+    fn new_line(&mut self) -> std::io::Result<()> {
+        self.inner.write_all(b"\n")?;
+        for _ in 0..self.depth {
+            self.inner.write_all(b"  ")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for PrettyJson<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            if self.in_string {
+                if self.is_escaped {
+                    self.is_escaped = false;
+                } else if byte == b'\\' {
+                    self.is_escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                self.inner.write_all(&[byte])?;
+                continue;
+            }
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+
+            // An empty object or array is kept on one line.
+            if matches!(byte, b'}' | b']') {
+                self.depth = self.depth.saturating_sub(1);
+                if !self.is_opened {
+                    self.new_line()?;
+                }
+            } else if self.is_opened {
+                self.new_line()?;
+            }
+            self.is_opened = false;
+
+            match byte {
+                b'{' | b'[' => {
+                    self.inner.write_all(&[byte])?;
+                    self.depth += 1;
+                    self.is_opened = true;
+                }
+                b',' => {
+                    self.inner.write_all(b",")?;
+                    self.new_line()?;
+                }
+                b':' => self.inner.write_all(b": ")?,
+                b'"' => {
+                    self.in_string = true;
+                    self.inner.write_all(b"\"")?;
+                }
+                _ => self.inner.write_all(&[byte])?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BRIEF_VALUE_LENGTH, PrettyJson, TRUNCATION_MARK, truncated_line_tag, write_brief};
+    use crate::dataset::text_element;
+    use dicom::core::{Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+    use std::io::Write;
+
+    #[test]
+    fn long_values_are_cut_in_the_brief_dump() {
+        let obj = InMemDicomObject::from_element_iter([
+            text_element(tags::MODALITY, VR::CS, "CT"),
+            text_element(
+                tags::IMAGE_COMMENTS,
+                VR::LT,
+                &"x".repeat(BRIEF_VALUE_LENGTH + 1),
+            ),
+        ]);
+        let mut out = Vec::new();
+        write_brief(&mut out, &obj, 0).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "(0008,0060) CS Modality = CT");
+        assert_eq!(truncated_line_tag(lines[0]), None);
+        assert!(lines[1].ends_with(&format!(
+            "{}{TRUNCATION_MARK}",
+            "x".repeat(BRIEF_VALUE_LENGTH)
+        )));
+        assert_eq!(truncated_line_tag(lines[1]), Some(tags::IMAGE_COMMENTS));
+    }
+
+    #[test]
+    fn long_values_are_cut_in_the_text_dump() {
+        assert_eq!(
+            truncated_line_tag("(0020,4000) LT Image Comments              [xxxxxxxxxx..."),
+            Some(Tag(0x0020, 0x4000))
+        );
+        assert_eq!(
+            truncated_line_tag("    (0020,4000) LT Image Comments [xxx..."),
+            None
+        );
+    }
+
+    #[test]
+    fn values_ending_with_an_ellipsis_are_not_cut() {
+        assert_eq!(
+            truncated_line_tag("(0008,1030) LO Study Description            [Head...]"),
+            None
+        );
+        assert_eq!(
+            truncated_line_tag("(0008,1030) LO StudyDescription = Head..."),
+            None
+        );
+        assert_eq!(
+            truncated_line_tag("(0020,4000) LT ImageComments = [see below]..."),
+            None
+        );
+    }
+
+    #[test]
+    fn json_is_pretty_printed_as_it_is_written() {
+        let json = r#"{"00080060":{"vr":"CS","Value":["C\"T,{["]},"00081140":{"vr":"SQ","Value":[{}]},"00100010":{"vr":"PN"}}"#;
+        let mut out = PrettyJson::new(Vec::new());
+        for chunk in json.as_bytes().chunks(3) {
+            out.write_all(chunk).unwrap();
+        }
+
+        let expected =
+            serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(json).unwrap())
+                .unwrap();
+        assert_eq!(String::from_utf8(out.inner).unwrap(), expected);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::dataset::{primitive_to_string, visit_elements};
+use dicom::core::value::Value;
+use dicom::object::InMemDicomObject;
+use std::io::Write;
+
+/// Flatten the dataset into key/value pairs.
+/// Sequence items are addressed with indexed paths, e.g. `ReferencedSeriesSequence[0].SeriesInstanceUID`.
+pub fn flatten(obj: &InMemDicomObject) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+
+    visit_elements(obj, &mut |path, elem| match elem.value() {
+        Value::Primitive(value) => {
+            rows.push((path.to_string(), primitive_to_string(elem.vr(), value)));
+        }
+        Value::Sequence(_) => {
+            // The items are visited separately.
+        }
+        Value::PixelSequence(seq) => {
+            rows.push((
+                path.to_string(),
+                format!("<{} fragments>", seq.fragments().len()),
+            ));
+        }
+    });
+
+    rows
+}
+
+/// Write the flattened rows as CSV with a `Key,Value` header.
+pub fn write_csv(rows: &[(String, String)], writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, "Key,Value")?;
+    for (key, value) in rows {
+        writeln!(writer, "{},{}", csv_field(key), csv_field(value))?;
+    }
+    Ok(())
+}
+
+/// Write the flattened rows as a single JSON object, keeping the dataset order.
+pub fn write_json(rows: &[(String, String)], writer: &mut impl Write) -> std::io::Result<()> {
+    let map: serde_json::Map<String, serde_json::Value> = rows
+        .iter()
+        .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+        .collect();
+
+    serde_json::to_writer_pretty(writer, &map)?;
+    Ok(())
+}
+
+/// Quote the CSV field if it contains any special characters.
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
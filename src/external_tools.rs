@@ -0,0 +1,37 @@
+use crate::dataset::{expand_template, keyword_value};
+use dicom::object::InMemDicomObject;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A user-defined command to open the selected file with.
+/// The arguments can have placeholders, e.g. `{path}`, `{folder}`, `{filename}` or any tag keyword like `{SOPInstanceUID}`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ExternalTool {
+    pub name: String,
+    pub program: String,
+    pub args: String,
+}
+
+/// Fill in the placeholders of the template for the file.
+pub fn expand(template: &str, path: &Path, obj: Option<&InMemDicomObject>) -> String {
+    expand_template(template, |name| match name {
+        "path" => path.display().to_string(),
+        "folder" => path
+            .parent()
+            .map_or(String::new(), |x| x.display().to_string()),
+        "filename" => path
+            .file_name()
+            .map_or(String::new(), |x| x.to_string_lossy().to_string()),
+        keyword => obj.map_or(String::new(), |x| keyword_value(x, keyword)),
+    })
+}
+
+impl ExternalTool {
+    /// Launch the tool on the file without waiting for it.
+    pub fn launch(&self, path: &Path, obj: Option<&InMemDicomObject>) -> std::io::Result<()> {
+        std::process::Command::new(expand(&self.program, path, obj))
+            .args(self.args.split_whitespace().map(|x| expand(x, path, obj)))
+            .spawn()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Extraction of the content which can't be shown as still frames, i.e. encapsulated documents, video streams and
+//! audio waveforms.
+
+use crate::audio;
+use crate::dataset::element_string;
+use crate::organize::avoid_collision;
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{DefaultDicomObject, open_file};
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The video transfer syntaxes, with the file extensions of their streams.
+/// The streams are written as they are encapsulated, without a container: MPEG-2 program streams, and H.264 and H.265
+/// Annex B elementary streams rather than MP4 files. Players such as VLC or ffplay open them.
+const VIDEO_TRANSFER_SYNTAXES: [(&str, &str); 12] = [
+    ("1.2.840.10008.1.2.4.100", "mpg"),
+    ("1.2.840.10008.1.2.4.100.1", "mpg"),
+    ("1.2.840.10008.1.2.4.101", "mpg"),
+    ("1.2.840.10008.1.2.4.101.1", "mpg"),
+    ("1.2.840.10008.1.2.4.102", "h264"),
+    ("1.2.840.10008.1.2.4.102.1", "h264"),
+    ("1.2.840.10008.1.2.4.103", "h264"),
+    ("1.2.840.10008.1.2.4.103.1", "h264"),
+    ("1.2.840.10008.1.2.4.104", "h264"),
+    ("1.2.840.10008.1.2.4.105", "h264"),
+    ("1.2.840.10008.1.2.4.106", "h264"),
+    ("1.2.840.10008.1.2.4.107", "h265"),
+];
+
+/// Content embedded in a dataset.
+#[derive(Clone, Copy)]
+pub enum Embedded {
+    Cda,
+    Pdf,
+    /// A video stream, with the file extension.
+    Video(&'static str),
+    Audio,
+}
+
+impl Embedded {
+    /// Find the embedded content by the SOP class and the transfer syntax.
+    pub fn of(obj: &DefaultDicomObject) -> Option<Self> {
+        let sop_class_uid = element_string(obj, tags::SOP_CLASS_UID)?;
+        match sop_class_uid.as_str() {
+            uids::ENCAPSULATED_CDA_STORAGE => return Some(Self::Cda),
+            uids::ENCAPSULATED_PDF_STORAGE => return Some(Self::Pdf),
+            uids::BASIC_VOICE_AUDIO_WAVEFORM_STORAGE | uids::GENERAL_AUDIO_WAVEFORM_STORAGE => {
+                return Some(Self::Audio);
+            }
+            _ => {}
+        }
+
+        let transfer_syntax = obj.meta().transfer_syntax().trim_end_matches('\0');
+        VIDEO_TRANSFER_SYNTAXES
+            .iter()
+            .find(|(uid, _)| *uid == transfer_syntax)
+            .map(|(_, extension)| Self::Video(extension))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Cda => "CDA document",
+            Self::Pdf => "PDF document",
+            Self::Video("h264") => "H.264 video stream",
+            Self::Video("h265") => "H.265 video stream",
+            Self::Video(_) => "MPEG-2 video stream",
+            Self::Audio => "Audio",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Cda => "xml",
+            Self::Pdf => "pdf",
+            Self::Video(extension) => extension,
+            Self::Audio => "wav",
+        }
+    }
+}
+
+/// Read the embedded content of the file.
+/// The fragments of a video are joined one after another, without the item headers.
+fn read_content(path: &Path) -> Result<(Embedded, Vec<u8>)> {
+    let obj = open_file(path).map_err(Error::other)?;
+    let embedded = Embedded::of(&obj).ok_or_else(|| Error::other("no embedded content"))?;
+
+    let bytes = match embedded {
+        Embedded::Cda | Embedded::Pdf => {
+            let mut bytes = obj
+                .element(tags::ENCAPSULATED_DOCUMENT)
+                .map_err(Error::other)?
+                .to_bytes()
+                .map_err(Error::other)?
+                .to_vec();
+            // The value is padded to an even length, so cut it to the actual length if given.
+            if let Some(length) = obj
+                .get(tags::ENCAPSULATED_DOCUMENT_LENGTH)
+                .and_then(|x| x.to_int::<usize>().ok())
+            {
+                bytes.truncate(length);
+            }
+            bytes
+        }
+        Embedded::Video(_) => obj
+            .element(tags::PIXEL_DATA)
+            .map_err(Error::other)?
+            .value()
+            .fragments()
+            .ok_or_else(|| Error::other("the pixel data is not encapsulated"))?
+            .concat(),
+        Embedded::Audio => audio::to_wav(&obj)?,
+    };
+
+    Ok((embedded, bytes))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string())
+}
+
+/// Extract the embedded content of the file into the folder, named after the file.
+pub fn extract(path: &Path, dir: &Path) -> Result<PathBuf> {
+    let (embedded, bytes) = read_content(path)?;
+    let output = avoid_collision(
+        dir.join(format!("{}.{}", file_stem(path), embedded.extension())),
+        &HashSet::new(),
+    );
+    std::fs::write(&output, bytes)?;
+
+    Ok(output)
+}
+
+/// Extract the embedded content of the file into the temporary folder, to be opened by another app.
+/// Each file has one path there, keyed by its full path, which is overwritten when the content is extracted again.
+pub fn extract_temp(path: &Path) -> Result<PathBuf> {
+    let (embedded, bytes) = read_content(path)?;
+    let dir = std::env::temp_dir().join("rsdicombrowser").join("embedded");
+    std::fs::create_dir_all(&dir)?;
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    let output = dir.join(format!(
+        "{}-{:016x}.{}",
+        file_stem(path),
+        hasher.finish(),
+        embedded.extension()
+    ));
+    std::fs::write(&output, bytes)?;
+
+    Ok(output)
+}
+
+/// Open the file with the default app of the system, e.g. the video player.
+pub fn open_with_default_app(path: &Path) -> Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command.arg(path).spawn()?;
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! Export of a study as a HL7 FHIR R4 ImagingStudy resource, e.g. for the research repositories based on FHIR.
+
+use crate::dataset::{element_string, open_header};
+use crate::timeline::parse_datetime;
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::DefaultDicomObject;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+const DICOM_UID_SYSTEM: &str = "urn:dicom:uid";
+const DCM_SYSTEM: &str = "http://dicom.nema.org/resources/ontology/DCM";
+const URI_SYSTEM: &str = "urn:ietf:rfc:3986";
+const IDENTIFIER_TYPE_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v2-0203";
+
+/// Get the value of the element, or `None` if it's missing or empty.
+fn value(obj: &DefaultDicomObject, tag: Tag) -> Option<String> {
+    element_string(obj, tag).filter(|x| !x.is_empty())
+}
+
+/// Get the number of an integer string element, for the sorting and the unsigned integers of the resource.
+fn number(obj: &DefaultDicomObject, tag: Tag) -> Option<u32> {
+    value(obj, tag).and_then(|x| x.trim().parse().ok())
+}
+
+/// Format the study date and time as a FHIR dateTime. The time is only given with the timezone offset, which the
+/// dateTime requires, so otherwise only the date is given.
+fn started(obj: &DefaultDicomObject) -> Option<String> {
+    let date = value(obj, tags::STUDY_DATE)?;
+    let datetime = parse_datetime(&date, value(obj, tags::STUDY_TIME).as_deref())?;
+
+    match value(obj, tags::TIMEZONE_OFFSET_FROM_UTC) {
+        Some(offset) if offset.len() == 5 => Some(format!(
+            "{}{}:{}",
+            datetime.format("%Y-%m-%dT%H:%M:%S"),
+            &offset[..3],
+            &offset[3..]
+        )),
+        _ => Some(datetime.format("%Y-%m-%d").to_string()),
+    }
+}
+
+/// Add the field to the JSON object if the value is given.
+fn insert(object: &mut Value, key: &str, value: Option<Value>) {
+    if let Some(value) = value {
+        object[key] = value;
+    }
+}
+
+/// Build the ImagingStudy resource of the study from the headers of its files in the folder.
+/// Returns `None` if none of the files are of the study.
+pub fn imaging_study(files: &[PathBuf], study_uid: &str) -> Option<Value> {
+    let mut objects: Vec<DefaultDicomObject> = files
+        .iter()
+        .filter_map(|file| match open_header(file) {
+            Ok(obj) => Some(obj),
+            Err(e) => {
+                log::error!("Failed to read {}: {e}", file.display());
+                None
+            }
+        })
+        .filter(|obj| element_string(obj, tags::STUDY_INSTANCE_UID).as_deref() == Some(study_uid))
+        .collect();
+    objects.sort_by_key(|obj| {
+        (
+            number(obj, tags::SERIES_NUMBER),
+            number(obj, tags::INSTANCE_NUMBER),
+        )
+    });
+    let first = objects.first()?;
+
+    // Group the instances by the series, in the order of the series numbers.
+    let mut series: Vec<(String, Vec<&DefaultDicomObject>)> = Vec::new();
+    for obj in &objects {
+        let series_uid = value(obj, tags::SERIES_INSTANCE_UID).unwrap_or_default();
+        match series.iter_mut().find(|(uid, _)| *uid == series_uid) {
+            Some((_, instances)) => instances.push(obj),
+            None => series.push((series_uid, vec![obj])),
+        }
+    }
+
+    let mut modalities: Vec<String> = Vec::new();
+    let series: Vec<Value> = series
+        .iter()
+        .map(|(series_uid, instances)| {
+            let first = instances[0];
+            let mut series = json!({
+                "uid": series_uid,
+                "numberOfInstances": instances.len(),
+                "instance": instances
+                    .iter()
+                    .map(|obj| {
+                        let mut instance = json!({
+                            "uid": value(obj, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+                            "sopClass": {
+                                "system": URI_SYSTEM,
+                                "code": format!(
+                                    "urn:oid:{}",
+                                    value(obj, tags::SOP_CLASS_UID).unwrap_or_default()
+                                ),
+                            },
+                        });
+                        insert(
+                            &mut instance,
+                            "number",
+                            number(obj, tags::INSTANCE_NUMBER).map(Value::from),
+                        );
+                        instance
+                    })
+                    .collect::<Vec<_>>(),
+            });
+            let modality = value(first, tags::MODALITY).unwrap_or_default();
+            if !modalities.contains(&modality) {
+                modalities.push(modality.clone());
+            }
+            series["modality"] = json!({ "system": DCM_SYSTEM, "code": modality });
+            insert(
+                &mut series,
+                "number",
+                number(first, tags::SERIES_NUMBER).map(Value::from),
+            );
+            insert(
+                &mut series,
+                "description",
+                value(first, tags::SERIES_DESCRIPTION).map(Value::from),
+            );
+            insert(
+                &mut series,
+                "bodySite",
+                value(first, tags::BODY_PART_EXAMINED).map(|x| json!({ "display": x })),
+            );
+            series
+        })
+        .collect();
+
+    let mut identifiers = vec![json!({
+        "system": DICOM_UID_SYSTEM,
+        "value": format!("urn:oid:{study_uid}"),
+    })];
+    if let Some(accession_number) = value(first, tags::ACCESSION_NUMBER) {
+        identifiers.push(json!({
+            "type": { "coding": [{ "system": IDENTIFIER_TYPE_SYSTEM, "code": "ACSN" }] },
+            "value": accession_number,
+        }));
+    }
+
+    let mut subject = json!({});
+    insert(
+        &mut subject,
+        "identifier",
+        value(first, tags::PATIENT_ID).map(|x| json!({ "value": x })),
+    );
+    insert(
+        &mut subject,
+        "display",
+        value(first, tags::PATIENT_NAME).map(Value::from),
+    );
+
+    let mut study = json!({
+        "resourceType": "ImagingStudy",
+        "identifier": identifiers,
+        "status": "available",
+        "modality": modalities
+            .iter()
+            .map(|x| json!({ "system": DCM_SYSTEM, "code": x }))
+            .collect::<Vec<_>>(),
+        "subject": subject,
+    });
+    insert(&mut study, "started", started(first).map(Value::from));
+    insert(
+        &mut study,
+        "referrer",
+        value(first, tags::REFERRING_PHYSICIAN_NAME).map(|x| json!({ "display": x })),
+    );
+    study["numberOfSeries"] = Value::from(series.len());
+    study["numberOfInstances"] = Value::from(objects.len());
+    insert(
+        &mut study,
+        "description",
+        value(first, tags::STUDY_DESCRIPTION).map(Value::from),
+    );
+    study["series"] = Value::from(series);
+
+    Some(study)
+}
@@ -0,0 +1,166 @@
+use crate::dataset::dump_line_tag;
+use dicom::core::Tag;
+use serde::{Deserialize, Serialize};
+
+/// Quick filters over the dicom dump by tag group or module membership.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TagFilter {
+    Patient,
+    Study,
+    Series,
+    Equipment,
+    Image,
+    PixelData,
+    Private,
+}
+
+const STUDY_TAGS: &[Tag] = &[
+    Tag(0x0008, 0x0020), // StudyDate
+    Tag(0x0008, 0x0030), // StudyTime
+    Tag(0x0008, 0x0050), // AccessionNumber
+    Tag(0x0008, 0x0090), // ReferringPhysicianName
+    Tag(0x0008, 0x1030), // StudyDescription
+    Tag(0x0008, 0x1032), // ProcedureCodeSequence
+    Tag(0x0020, 0x000D), // StudyInstanceUID
+    Tag(0x0020, 0x0010), // StudyID
+];
+
+const SERIES_TAGS: &[Tag] = &[
+    Tag(0x0008, 0x0021), // SeriesDate
+    Tag(0x0008, 0x0031), // SeriesTime
+    Tag(0x0008, 0x0060), // Modality
+    Tag(0x0008, 0x103E), // SeriesDescription
+    Tag(0x0018, 0x0015), // BodyPartExamined
+    Tag(0x0018, 0x5100), // PatientPosition
+    Tag(0x0020, 0x000E), // SeriesInstanceUID
+    Tag(0x0020, 0x0011), // SeriesNumber
+    Tag(0x0020, 0x0052), // FrameOfReferenceUID
+    Tag(0x0020, 0x0060), // Laterality
+];
+
+const EQUIPMENT_TAGS: &[Tag] = &[
+    Tag(0x0008, 0x0070), // Manufacturer
+    Tag(0x0008, 0x0080), // InstitutionName
+    Tag(0x0008, 0x0081), // InstitutionAddress
+    Tag(0x0008, 0x1010), // StationName
+    Tag(0x0008, 0x1040), // InstitutionalDepartmentName
+    Tag(0x0008, 0x1090), // ManufacturerModelName
+    Tag(0x0018, 0x1000), // DeviceSerialNumber
+    Tag(0x0018, 0x1020), // SoftwareVersions
+    Tag(0x0018, 0x1050), // SpatialResolution
+    Tag(0x0018, 0x1200), // DateOfLastCalibration
+    Tag(0x0018, 0x1201), // TimeOfLastCalibration
+];
+
+const IMAGE_TAGS: &[Tag] = &[
+    Tag(0x0008, 0x0008), // ImageType
+    Tag(0x0008, 0x0023), // ContentDate
+    Tag(0x0008, 0x0033), // ContentTime
+    Tag(0x0020, 0x0013), // InstanceNumber
+    Tag(0x0020, 0x0032), // ImagePositionPatient
+    Tag(0x0020, 0x0037), // ImageOrientationPatient
+    Tag(0x0020, 0x1041), // SliceLocation
+];
+
+impl TagFilter {
+    pub const ALL: [TagFilter; 7] = [
+        TagFilter::Patient,
+        TagFilter::Study,
+        TagFilter::Series,
+        TagFilter::Equipment,
+        TagFilter::Image,
+        TagFilter::PixelData,
+        TagFilter::Private,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TagFilter::Patient => "Patient",
+            TagFilter::Study => "Study",
+            TagFilter::Series => "Series",
+            TagFilter::Equipment => "Equipment",
+            TagFilter::Image => "Image",
+            TagFilter::PixelData => "Pixel Data",
+            TagFilter::Private => "Private",
+        }
+    }
+
+    /// Check if the top-level tag belongs to the filter.
+    pub fn matches(&self, tag: Tag) -> bool {
+        match self {
+            TagFilter::Patient => tag.group() == 0x0010,
+            TagFilter::Study => STUDY_TAGS.contains(&tag) || tag.group() == 0x0032,
+            TagFilter::Series => SERIES_TAGS.contains(&tag),
+            TagFilter::Equipment => EQUIPMENT_TAGS.contains(&tag),
+            TagFilter::Image => IMAGE_TAGS.contains(&tag) || tag.group() == 0x0028,
+            TagFilter::PixelData => tag.group() == 0x7FE0,
+            TagFilter::Private => tag.group() % 2 == 1,
+        }
+    }
+}
+
+/// Get the visible lines of the dump with their line indexes.
+/// Nested lines follow the top-level element they belong to. Everything is visible if no filter is active.
+pub fn filter_lines<'a>(
+    lines: impl Iterator<Item = &'a str> + 'a,
+    filters: &'a [TagFilter],
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    let mut is_visible = filters.is_empty();
+
+    lines.enumerate().filter(move |(_, line)| {
+        if filters.is_empty() {
+            return true;
+        }
+        if let Some(tag) = dump_line_tag(line) {
+            is_visible = filters.iter().any(|x| x.matches(tag));
+        } else if !line.starts_with(' ') {
+            // Headers and blank lines are not part of any element.
+            is_visible = false;
+        }
+        is_visible
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TagFilter, filter_lines};
+
+    const DUMP: &str = "\
+# Dicom-Data-Set
+(0008,0060) CS Modality                       [CT]
+(0008,1032) SQ Procedure Code Sequence        1 item(s)
+  (FFFE,E000) na Item
+    (0008,0100) SH Code Value                 [123]
+(0010,0010) PN Patient's Name                 [Doe^John]
+(0010,0020) LO Patient ID                     [12345]
+(0029,1010) OB Private                        (16 bytes)
+(7FE0,0010) OW Pixel Data                     (512 bytes)";
+
+    fn visible(filters: &[TagFilter]) -> Vec<usize> {
+        filter_lines(DUMP.lines(), filters)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    #[test]
+    fn everything_is_visible_without_filters() {
+        assert_eq!(visible(&[]), (0..9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn top_level_elements_are_filtered_by_tag() {
+        assert_eq!(visible(&[TagFilter::Patient]), [5, 6]);
+        assert_eq!(visible(&[TagFilter::PixelData]), [8]);
+        assert_eq!(visible(&[TagFilter::Private]), [7]);
+        assert_eq!(
+            visible(&[TagFilter::Patient, TagFilter::PixelData]),
+            [5, 6, 8]
+        );
+    }
+
+    #[test]
+    fn nested_lines_follow_their_element() {
+        assert_eq!(visible(&[TagFilter::Study]), [2, 3, 4]);
+        assert_eq!(visible(&[TagFilter::Series]), [1]);
+    }
+}
@@ -0,0 +1,173 @@
+//! The summary of a folder selected in the tree, e.g. of a series at a glance: the elements with the same value in all
+//! its files, and the range or the number of the values of the others, e.g. InstanceNumber and SliceLocation.
+//! Only the top-level text elements are compared, as the sequences and the binary values have no short form.
+
+use crate::dataset::{is_binary_vr, open_header, primitive_to_string, tag_keyword};
+use crate::i18n::{tr, trf};
+use crate::privacy;
+use crate::tasks::TaskContext;
+use dicom::core::Tag;
+use dicom::core::value::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// The values of an element in the files of the folder.
+#[derive(Default)]
+struct ElementValues {
+    /// The element identifies the patient, so that its values are masked in the privacy mode.
+    is_phi: bool,
+    present: usize,
+    values: BTreeSet<String>,
+    /// The range of the values, while all of them are single numbers.
+    range: Option<(f64, f64)>,
+}
+
+impl ElementValues {
+    fn add(&mut self, value: String) {
+        let number = value.trim().parse::<f64>().ok();
+        self.range = match (self.present, self.range, number) {
+            (0, _, Some(x)) => Some((x, x)),
+            (_, Some((min, max)), Some(x)) => Some((min.min(x), max.max(x))),
+            _ => None,
+        };
+        self.present += 1;
+        self.values.insert(value);
+    }
+}
+
+/// An element whose value differs between the files, or which is missing in some.
+struct Differing {
+    tag: Tag,
+    is_phi: bool,
+    present: usize,
+    summary: String,
+}
+
+/// The summary of the files in a folder.
+#[derive(Default)]
+pub struct FolderSummary {
+    file_count: usize,
+    unreadable: usize,
+    common: Vec<(Tag, bool, String)>,
+    differing: Vec<Differing>,
+}
+
+impl FolderSummary {
+    /// Read the headers of the files and compare their elements. Stops early if the task is cancelled.
+    pub fn collect(files: &[PathBuf], context: &TaskContext) -> Self {
+        let mut elements: BTreeMap<Tag, ElementValues> = BTreeMap::new();
+        let mut summary = Self {
+            file_count: files.len(),
+            ..Default::default()
+        };
+
+        for (index, file) in files.iter().enumerate() {
+            if context.is_cancelled() {
+                break;
+            }
+            context.set_progress(index as f32 / files.len() as f32);
+
+            let Ok(obj) = open_header(file) else {
+                summary.unreadable += 1;
+                continue;
+            };
+            for elem in obj.iter() {
+                if let Value::Primitive(value) = elem.value()
+                    && !is_binary_vr(elem.vr())
+                {
+                    let tag = elem.header().tag;
+                    let element = elements.entry(tag).or_default();
+                    element.is_phi |= privacy::is_phi(tag, elem.vr());
+                    element.add(primitive_to_string(elem.vr(), value));
+                }
+            }
+        }
+
+        let read = summary.file_count - summary.unreadable;
+        for (tag, element) in elements {
+            if element.present == read && element.values.len() == 1 {
+                let value = element.values.into_iter().next().unwrap_or_default();
+                summary.common.push((tag, element.is_phi, value));
+                continue;
+            }
+
+            let summary_text = match element.range {
+                Some((min, max)) => format!("{min} – {max}"),
+                None if element.values.len() == 1 => {
+                    element.values.into_iter().next().unwrap_or_default()
+                }
+                None => trf("{} values", element.values.len()),
+            };
+            summary.differing.push(Differing {
+                tag,
+                is_phi: element.is_phi,
+                present: element.present,
+                summary: summary_text,
+            });
+        }
+
+        summary
+    }
+
+    /// Show the common elements and the differing ones, with the identifying values masked in the privacy mode.
+    pub fn ui(&self, ui: &mut egui::Ui, privacy_mode: bool) {
+        if self.file_count == 0 {
+            ui.label(tr("No DICOM files in the folder."));
+            return;
+        }
+        ui.label(trf("{} files", self.file_count));
+        if self.unreadable > 0 {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                trf("{} files could not be read", self.unreadable),
+            );
+        }
+        let read = self.file_count - self.unreadable;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::CollapsingHeader::new(trf("Differing ({})", self.differing.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("folder differing grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for element in &self.differing {
+                                ui.monospace(element.tag.to_string());
+                                ui.label(tag_keyword(element.tag));
+                                if privacy_mode && element.is_phi {
+                                    ui.monospace(privacy::MASK);
+                                } else {
+                                    ui.monospace(&element.summary);
+                                }
+                                if element.present < read {
+                                    ui.weak(trf(
+                                        "in {} files",
+                                        format!("{}/{read}", element.present),
+                                    ));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            egui::CollapsingHeader::new(trf("Common ({})", self.common.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("folder common grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (tag, is_phi, value) in &self.common {
+                                ui.monospace(tag.to_string());
+                                ui.label(tag_keyword(*tag));
+                                if privacy_mode && *is_phi {
+                                    ui.monospace(privacy::MASK);
+                                } else {
+                                    ui.monospace(value);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    }
+}
@@ -0,0 +1,55 @@
+use egui::{FontData, FontDefinitions, FontFamily};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Common locations of system fonts with CJK glyphs, tried in order.
+const CJK_FONT_PATHS: &[&str] = &[
+    // Linux
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+    // macOS
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+    // Windows
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    "C:\\Windows\\Fonts\\malgun.ttf",
+];
+
+/// Set up the fonts with the user font first (if any) and a CJK font as the fallback (if found).
+pub fn apply_fonts(ctx: &egui::Context, user_font: Option<&Path>) {
+    let mut fonts = FontDefinitions::default();
+
+    if let Some(path) = user_font {
+        match std::fs::read(path) {
+            Ok(data) => add_font(&mut fonts, "user", data, true),
+            Err(e) => log::error!("Failed to load font {}: {e}", path.display()),
+        }
+    }
+
+    if let Some(data) = CJK_FONT_PATHS.iter().find_map(|x| std::fs::read(x).ok()) {
+        add_font(&mut fonts, "cjk", data, false);
+    }
+
+    ctx.set_fonts(fonts);
+}
+
+/// Add the font to both families, either as the preferred font or as the fallback.
+fn add_font(fonts: &mut FontDefinitions, name: &str, data: Vec<u8>, is_preferred: bool) {
+    fonts
+        .font_data
+        .insert(name.to_string(), Arc::new(FontData::from_owned(data)));
+
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        let names = fonts.families.entry(family).or_default();
+        if is_preferred {
+            names.insert(0, name.to_string());
+        } else {
+            names.push(name.to_string());
+        }
+    }
+}
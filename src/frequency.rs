@@ -0,0 +1,160 @@
+//! Report of the distinct values of a tag across the files, e.g. the station names or software versions in an archive.
+
+use crate::dataset::{element_string, open_header};
+use crate::export::csv_field;
+use crate::i18n::{tr, trf};
+use crate::privacy;
+use dicom::core::{DataDictionary, Tag};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom::object::DefaultDicomObject;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// The tags commonly reported, to pick from.
+const SUGGESTED_KEYWORDS: [&str; 8] = [
+    "StationName",
+    "SoftwareVersions",
+    "ImplementationVersionName",
+    "Manufacturer",
+    "ManufacturerModelName",
+    "InstitutionName",
+    "Modality",
+    "TransferSyntaxUID",
+];
+
+/// Get the value of the tag, looking up the file meta group in the meta table.
+pub fn value_of(obj: &DefaultDicomObject, tag: Tag) -> Option<String> {
+    let meta = obj.meta();
+    let trim = |x: &str| x.trim_end_matches(['\0', ' ']).to_string();
+
+    match tag {
+        tags::MEDIA_STORAGE_SOP_CLASS_UID => Some(trim(&meta.media_storage_sop_class_uid)),
+        tags::MEDIA_STORAGE_SOP_INSTANCE_UID => Some(trim(&meta.media_storage_sop_instance_uid)),
+        tags::TRANSFER_SYNTAX_UID => Some(trim(&meta.transfer_syntax)),
+        tags::IMPLEMENTATION_CLASS_UID => Some(trim(&meta.implementation_class_uid)),
+        tags::IMPLEMENTATION_VERSION_NAME => meta.implementation_version_name.as_deref().map(trim),
+        tags::SOURCE_APPLICATION_ENTITY_TITLE => {
+            meta.source_application_entity_title.as_deref().map(trim)
+        }
+        tag => element_string(obj, tag),
+    }
+}
+
+/// Count the distinct values of the tag in the files, the most frequent first.
+/// The files without the tag are counted under `None`.
+pub fn collect(files: &[PathBuf], tag: Tag) -> Vec<(Option<String>, usize)> {
+    let mut counts: HashMap<Option<String>, usize> = HashMap::new();
+
+    for file in files {
+        match open_header(file) {
+            Ok(obj) => *counts.entry(value_of(&obj, tag)).or_default() += 1,
+            Err(e) => log::error!("Failed to read {}: {e}", file.display()),
+        }
+    }
+
+    let mut results: Vec<_> = counts.into_iter().collect();
+    results.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    results
+}
+
+/// The tag to report and the value counts.
+#[derive(Default)]
+pub struct FrequencyReport {
+    keyword: String,
+    results: Vec<(Option<String>, usize)>,
+    /// The tag counted identifies the patient, so that its values are masked in the privacy mode.
+    is_phi: bool,
+    status: String,
+}
+
+impl FrequencyReport {
+    /// Write the value counts as CSV with a `Value,Count` header.
+    pub fn write_csv(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "Value,Count")?;
+        for (value, count) in &self.results {
+            writeln!(
+                writer,
+                "{},{count}",
+                csv_field(value.as_deref().unwrap_or_default())
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Show the tag to report and the value counts, masked in the privacy mode if the tag identifies the patient.
+    /// Returns true if the report is to be exported.
+    pub fn ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf], privacy_mode: bool) -> bool {
+        let mut export = false;
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Tag"));
+            ui.text_edit_singleline(&mut self.keyword)
+                .on_hover_text(tr("A keyword or gggg,eeee"));
+            ui.menu_button("⏷", |ui| {
+                for keyword in SUGGESTED_KEYWORDS {
+                    if ui.button(keyword).clicked() {
+                        self.keyword = keyword.to_string();
+                        ui.close();
+                    }
+                }
+            });
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !files.is_empty(),
+                    egui::Button::new(trf("Count in {} files", files.len())),
+                )
+                .clicked()
+            {
+                match StandardDataDictionary.parse_tag(self.keyword.trim()) {
+                    Some(tag) => {
+                        self.results = collect(files, tag);
+                        self.is_phi = privacy::is_phi_tag(tag);
+                        self.status.clear();
+                    }
+                    None => self.status = trf("Unknown tag {}", self.keyword.trim()),
+                }
+            }
+            export = ui
+                .add_enabled(
+                    !self.results.is_empty(),
+                    egui::Button::new(tr("Export CSV")),
+                )
+                .clicked();
+        });
+
+        if !self.status.is_empty() {
+            ui.colored_label(ui.visuals().error_fg_color, &self.status);
+        }
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("frequency grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    if !self.results.is_empty() {
+                        ui.strong(tr("Value"));
+                        ui.strong(tr("Count"));
+                        ui.end_row();
+                    }
+                    let total: usize = self.results.iter().map(|x| x.1).sum();
+                    for (value, count) in &self.results {
+                        match value {
+                            Some(_) if privacy_mode && self.is_phi => ui.monospace(privacy::MASK),
+                            Some(value) => ui.monospace(value),
+                            None => ui.weak(tr("(missing)")),
+                        };
+                        ui.label(format!(
+                            "{count} ({:.1}%)",
+                            *count as f64 * 100.0 / total as f64
+                        ));
+                        ui.end_row();
+                    }
+                });
+        });
+
+        export
+    }
+}
@@ -0,0 +1,139 @@
+use crate::dataset::{open_header, primitive_to_string, visit_elements};
+use crate::i18n::tr;
+use dicom::core::Tag;
+use dicom::core::value::Value;
+use dicom::dictionary_std::tags;
+use dicom::object::mem::InMemElement;
+use dicom::object::{DefaultDicomObject, InMemDicomObject};
+use std::path::{Path, PathBuf};
+
+/// An element of the merged functional groups of a frame.
+pub struct FrameGroupRow {
+    pub path: String,
+    pub value: String,
+    pub is_per_frame: bool,
+}
+
+/// Get the items of a sequence element, or nothing if it is missing.
+fn sequence_items(obj: &InMemDicomObject, tag: Tag) -> &[InMemDicomObject] {
+    obj.get(tag).and_then(|x| x.items()).unwrap_or(&[])
+}
+
+/// Get the number of frames described by the per-frame functional groups.
+pub fn frame_count(obj: &InMemDicomObject) -> usize {
+    sequence_items(obj, tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE).len()
+}
+
+/// Merge the shared and the per-frame functional groups of the frame.
+/// The per-frame groups take precedence over the shared ones. The tags of the per-frame groups are returned as well.
+pub fn merged_frame_groups(obj: &InMemDicomObject, frame: usize) -> (InMemDicomObject, Vec<Tag>) {
+    let mut groups: Vec<&InMemElement> =
+        sequence_items(obj, tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE)
+            .first()
+            .map_or(Vec::new(), |x| x.iter().collect());
+    let mut per_frame_tags = Vec::new();
+
+    if let Some(item) = sequence_items(obj, tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE).get(frame) {
+        for elem in item {
+            let tag = elem.header().tag;
+            groups.retain(|x| x.header().tag != tag);
+            groups.push(elem);
+            per_frame_tags.push(tag);
+        }
+    }
+
+    (
+        InMemDicomObject::from_element_iter(groups.into_iter().cloned()),
+        per_frame_tags,
+    )
+}
+
+/// Browse the merged functional groups of a frame in an enhanced multi-frame object.
+#[derive(Default)]
+pub struct FunctionalGroupBrowser {
+    path: PathBuf,
+    obj: Option<DefaultDicomObject>,
+    frame: usize,
+    rows: Vec<FrameGroupRow>,
+}
+
+impl FunctionalGroupBrowser {
+    /// Load the file if it is not loaded yet.
+    fn load(&mut self, path: &Path) {
+        if self.path == path {
+            return;
+        }
+
+        self.path = path.to_path_buf();
+        self.obj = open_header(path).ok();
+        self.frame = 0;
+        self.update_rows();
+    }
+
+    /// Build the rows of the merged functional groups for the current frame.
+    fn update_rows(&mut self) {
+        self.rows.clear();
+
+        let Some(obj) = self.obj.as_ref() else {
+            return;
+        };
+        let (merged, per_frame_tags) = merged_frame_groups(obj, self.frame);
+
+        for elem in &merged {
+            let tag = elem.header().tag;
+            let single = InMemDicomObject::from_element_iter([elem.clone()]);
+
+            visit_elements(&single, &mut |path, elem| {
+                if let Value::Primitive(value) = elem.value() {
+                    self.rows.push(FrameGroupRow {
+                        path: path.to_string(),
+                        value: primitive_to_string(elem.vr(), value),
+                        is_per_frame: per_frame_tags.contains(&tag),
+                    });
+                }
+            });
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, selected_file: &Path) {
+        self.load(selected_file);
+
+        let count = self.obj.as_deref().map_or(0, frame_count);
+        if count == 0 {
+            ui.label(tr("The selected file has no per-frame functional groups."));
+            return;
+        }
+
+        let mut frame = self.frame + 1;
+        if ui
+            .add(egui::Slider::new(&mut frame, 1..=count).text(tr("Frame")))
+            .changed()
+        {
+            self.frame = frame - 1;
+            self.update_rows();
+        }
+        ui.separator();
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("functional groups grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong(tr("Element"));
+                    ui.strong(tr("Value"));
+                    ui.strong(tr("Source"));
+                    ui.end_row();
+
+                    for row in &self.rows {
+                        ui.monospace(&row.path);
+                        ui.monospace(&row.value);
+                        ui.label(tr(if row.is_per_frame {
+                            "Per-frame"
+                        } else {
+                            "Shared"
+                        }));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
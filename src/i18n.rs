@@ -0,0 +1,71 @@
+//! Translation of the UI strings.
+//! The English text is used as the key, so that English is the baseline when no translation is loaded.
+//! A translation is a `<language>.lang` file in the `locales` directory, with one `English = Translated` entry per line.
+
+use crate::settings::app_dir;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+static TRANSLATIONS: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+/// Translate the UI string, or keep it as is if there is no translation.
+pub fn tr(text: &str) -> String {
+    TRANSLATIONS
+        .read()
+        .ok()
+        .and_then(|x| x.as_ref()?.get(text).cloned())
+        .unwrap_or_else(|| text.to_string())
+}
+
+/// Translate the UI string and fill in the `{}` placeholder with the value.
+pub fn trf(text: &str, value: impl std::fmt::Display) -> String {
+    tr(text).replacen("{}", &value.to_string(), 1)
+}
+
+/// Get the directory with the translation files, shipped next to the executable.
+pub fn locales_dir() -> PathBuf {
+    app_dir("locales")
+}
+
+/// Get the languages of the translation files in the locales directory.
+pub fn available_languages() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(locales_dir()) else {
+        return Vec::new();
+    };
+
+    let mut languages: Vec<String> = entries
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "lang"))
+        .filter_map(|x| Some(x.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    languages.sort();
+    languages
+}
+
+/// Load the translation of the language, or go back to English if `None`.
+pub fn set_language(language: Option<&str>) -> std::io::Result<()> {
+    let translations = match language {
+        Some(language) => Some(parse(&std::fs::read_to_string(
+            locales_dir().join(format!("{language}.lang")),
+        )?)),
+        None => None,
+    };
+
+    if let Ok(mut x) = TRANSLATIONS.write() {
+        *x = translations;
+    }
+    Ok(())
+}
+
+/// Parse the `English = Translated` lines, skipping blank lines and `#` comments.
+fn parse(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .filter_map(|x| x.split_once(" = "))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
@@ -0,0 +1,274 @@
+//! Creation of Key Object Selection documents, which flag significant instances for downstream systems.
+
+use crate::dataset::{element_string, new_uid, open_header, sequence, text_element, write_file};
+use crate::i18n::{tr, trf};
+use crate::organize::avoid_collision;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{DefaultDicomObject, InMemDicomObject};
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+/// The document titles of CID 7010 "Key Object Selection Document Title".
+pub const TITLES: [(&str, &str); 12] = [
+    ("113000", "Of Interest"),
+    ("113001", "Rejected for Quality Reasons"),
+    ("113002", "For Referring Provider"),
+    ("113003", "For Surgery"),
+    ("113004", "For Teaching"),
+    ("113005", "For Conference"),
+    ("113006", "For Therapy"),
+    ("113007", "For Patient"),
+    ("113008", "For Peer Review"),
+    ("113009", "For Research"),
+    ("113010", "Quality Issue"),
+    ("113013", "Best In Set"),
+];
+
+/// The patient and study elements copied from the referenced instances. Missing ones are written empty.
+const COPIED_ELEMENTS: [(Tag, VR); 10] = [
+    (tags::PATIENT_NAME, VR::PN),
+    (tags::PATIENT_ID, VR::LO),
+    (tags::PATIENT_BIRTH_DATE, VR::DA),
+    (tags::PATIENT_SEX, VR::CS),
+    (tags::STUDY_INSTANCE_UID, VR::UI),
+    (tags::STUDY_DATE, VR::DA),
+    (tags::STUDY_TIME, VR::TM),
+    (tags::STUDY_ID, VR::SH),
+    (tags::ACCESSION_NUMBER, VR::SH),
+    (tags::REFERRING_PHYSICIAN_NAME, VR::PN),
+];
+
+fn code_item(value: &str, meaning: &str) -> InMemDicomObject {
+    InMemDicomObject::from_element_iter([
+        text_element(tags::CODE_VALUE, VR::SH, value),
+        text_element(tags::CODING_SCHEME_DESIGNATOR, VR::SH, "DCM"),
+        text_element(tags::CODE_MEANING, VR::LO, meaning),
+    ])
+}
+
+fn value_of(obj: &InMemDicomObject, tag: Tag) -> String {
+    element_string(obj, tag).unwrap_or_default()
+}
+
+fn referenced_sop(obj: &InMemDicomObject) -> InMemDicomObject {
+    InMemDicomObject::from_element_iter([
+        text_element(
+            tags::REFERENCED_SOP_CLASS_UID,
+            VR::UI,
+            &value_of(obj, tags::SOP_CLASS_UID),
+        ),
+        text_element(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            &value_of(obj, tags::SOP_INSTANCE_UID),
+        ),
+    ])
+}
+
+/// Build the content item referencing the instance, as an image if it has pixel data attributes.
+fn content_item(obj: &InMemDicomObject) -> InMemDicomObject {
+    let value_type = if obj.get(tags::ROWS).is_some() {
+        "IMAGE"
+    } else {
+        "COMPOSITE"
+    };
+
+    InMemDicomObject::from_element_iter([
+        text_element(tags::RELATIONSHIP_TYPE, VR::CS, "CONTAINS"),
+        text_element(tags::VALUE_TYPE, VR::CS, value_type),
+        sequence(tags::REFERENCED_SOP_SEQUENCE, vec![referenced_sop(obj)]),
+    ])
+}
+
+/// Build the evidence items, with the referenced instances grouped by study and series.
+fn evidence(objects: &[DefaultDicomObject]) -> Vec<InMemDicomObject> {
+    let mut studies: Vec<(String, Vec<(String, Vec<InMemDicomObject>)>)> = Vec::new();
+
+    for obj in objects {
+        let study_uid = value_of(obj, tags::STUDY_INSTANCE_UID);
+        let series_uid = value_of(obj, tags::SERIES_INSTANCE_UID);
+
+        let study = match studies.iter().position(|(uid, _)| *uid == study_uid) {
+            Some(index) => &mut studies[index].1,
+            None => {
+                studies.push((study_uid, Vec::new()));
+                &mut studies.last_mut().expect("the study was just added").1
+            }
+        };
+        match study.iter_mut().find(|(uid, _)| *uid == series_uid) {
+            Some((_, instances)) => instances.push(referenced_sop(obj)),
+            None => study.push((series_uid, vec![referenced_sop(obj)])),
+        }
+    }
+
+    studies
+        .into_iter()
+        .map(|(study_uid, series)| {
+            let series = series
+                .into_iter()
+                .map(|(series_uid, instances)| {
+                    InMemDicomObject::from_element_iter([
+                        text_element(tags::SERIES_INSTANCE_UID, VR::UI, &series_uid),
+                        sequence(tags::REFERENCED_SOP_SEQUENCE, instances),
+                    ])
+                })
+                .collect();
+
+            InMemDicomObject::from_element_iter([
+                text_element(tags::STUDY_INSTANCE_UID, VR::UI, &study_uid),
+                sequence(tags::REFERENCED_SERIES_SEQUENCE, series),
+            ])
+        })
+        .collect()
+}
+
+/// Create a Key Object Selection document referencing the files, with the title and the optional description.
+/// The document is written next to the first file in the study of the first file, and its path is returned.
+pub fn create(files: &[PathBuf], title: (&str, &str), description: &str) -> Result<PathBuf> {
+    let objects = files
+        .iter()
+        .map(|x| open_header(x).map_err(Error::other))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = objects.first() else {
+        return Err(Error::other("no files to reference"));
+    };
+    let patient_id = value_of(first, tags::PATIENT_ID);
+    if objects
+        .iter()
+        .any(|x| value_of(x, tags::PATIENT_ID) != patient_id)
+    {
+        return Err(Error::other("the instances belong to different patients"));
+    }
+
+    let mut content = Vec::new();
+    if !description.is_empty() {
+        content.push(InMemDicomObject::from_element_iter([
+            text_element(tags::RELATIONSHIP_TYPE, VR::CS, "CONTAINS"),
+            text_element(tags::VALUE_TYPE, VR::CS, "TEXT"),
+            sequence(
+                tags::CONCEPT_NAME_CODE_SEQUENCE,
+                vec![code_item("113012", "Key Object Description")],
+            ),
+            text_element(tags::TEXT_VALUE, VR::UT, description),
+        ]));
+    }
+    content.extend(objects.iter().map(|x| content_item(x)));
+
+    let now = chrono::Local::now();
+    let mut elements = vec![
+        text_element(
+            tags::SOP_CLASS_UID,
+            VR::UI,
+            uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE,
+        ),
+        text_element(tags::SOP_INSTANCE_UID, VR::UI, &new_uid()),
+        text_element(tags::SERIES_INSTANCE_UID, VR::UI, &new_uid()),
+        text_element(tags::MODALITY, VR::CS, "KO"),
+        text_element(tags::SERIES_NUMBER, VR::IS, "1"),
+        text_element(tags::INSTANCE_NUMBER, VR::IS, "1"),
+        text_element(tags::MANUFACTURER, VR::LO, ""),
+        text_element(
+            tags::CONTENT_DATE,
+            VR::DA,
+            &now.format("%Y%m%d").to_string(),
+        ),
+        text_element(
+            tags::CONTENT_TIME,
+            VR::TM,
+            &now.format("%H%M%S").to_string(),
+        ),
+        sequence(
+            tags::REFERENCED_PERFORMED_PROCEDURE_STEP_SEQUENCE,
+            Vec::new(),
+        ),
+        text_element(tags::VALUE_TYPE, VR::CS, "CONTAINER"),
+        sequence(
+            tags::CONCEPT_NAME_CODE_SEQUENCE,
+            vec![code_item(title.0, title.1)],
+        ),
+        text_element(tags::CONTINUITY_OF_CONTENT, VR::CS, "SEPARATE"),
+        sequence(
+            tags::CONTENT_TEMPLATE_SEQUENCE,
+            vec![InMemDicomObject::from_element_iter([
+                text_element(tags::MAPPING_RESOURCE, VR::CS, "DCMR"),
+                text_element(tags::TEMPLATE_IDENTIFIER, VR::CS, "2010"),
+            ])],
+        ),
+        sequence(
+            tags::CURRENT_REQUESTED_PROCEDURE_EVIDENCE_SEQUENCE,
+            evidence(&objects),
+        ),
+        sequence(tags::CONTENT_SEQUENCE, content),
+    ];
+    elements.extend(
+        COPIED_ELEMENTS
+            .iter()
+            .map(|(tag, vr)| text_element(*tag, *vr, &value_of(first, *tag))),
+    );
+
+    let output = avoid_collision(
+        files[0].with_file_name("KeyObjectSelection.dcm"),
+        &HashSet::new(),
+    );
+    write_file(
+        InMemDicomObject::from_element_iter(elements),
+        uids::EXPLICIT_VR_LITTLE_ENDIAN,
+        &output,
+    )?;
+
+    Ok(output)
+}
+
+/// Options of the Key Object Selection document to create.
+#[derive(Default)]
+pub struct KosTool {
+    title: usize,
+    description: String,
+    status: String,
+}
+
+impl KosTool {
+    /// Show the document options. Returns the path of the document if one was created.
+    pub fn ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf]) -> Option<PathBuf> {
+        let mut created = None;
+        let (code, meaning) = TITLES[self.title];
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("kos grid").show(ui, |ui| {
+            ui.label(tr("Title"));
+            egui::ComboBox::from_id_salt("kos title")
+                .selected_text(meaning)
+                .show_ui(ui, |ui| {
+                    for (index, (_, meaning)) in TITLES.iter().enumerate() {
+                        ui.selectable_value(&mut self.title, index, *meaning);
+                    }
+                });
+            ui.end_row();
+
+            ui.label(tr("Description"));
+            ui.text_edit_multiline(&mut self.description);
+            ui.end_row();
+        });
+
+        if ui
+            .add_enabled(!files.is_empty(), egui::Button::new(tr("Create")))
+            .clicked()
+        {
+            match create(files, (code, meaning), self.description.trim()) {
+                Ok(path) => {
+                    self.status = trf("Created {}", path.display());
+                    created = Some(path);
+                }
+                Err(e) => self.status = e.to_string(),
+            }
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        created
+    }
+}
@@ -1,4 +1,68 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod anonymize;
 mod app;
+mod archive;
+mod audio;
+mod audit;
+mod bulk_edit;
+mod checksum;
+mod cli_commands;
+mod corruption;
+mod crash;
+mod dataset;
+mod date_shift;
+mod diagnostics;
+mod dicomdir;
+mod download;
+mod dump;
+mod export;
+mod external_tools;
+mod extract;
+mod fhir;
+mod filter;
+mod folder_summary;
+mod fonts;
+mod frequency;
+mod functional_groups;
+mod i18n;
+mod kos;
+mod mesh;
+mod multiframe;
+mod onboarding;
+mod organize;
+mod orientation;
+mod orthanc;
+mod perf;
+mod pinned;
+mod privacy;
+mod quick_view;
+mod references;
+mod remote;
+mod s3;
+mod same_value;
+mod scan;
+mod scripting;
+mod search;
+mod series_check;
+mod session;
+mod settings;
+mod share_bundle;
+mod size_breakdown;
+mod stats;
+mod study_graph;
+mod study_zip;
+mod summary;
+mod surface;
+mod synthetic;
+mod tag_presence;
+mod tasks;
+mod teaching;
+mod template_check;
+mod timeline;
+mod uid_regen;
+mod undo;
+mod update;
+mod zip;
 pub use app::TemplateApp;
+pub use diagnostics::init_logging;
@@ -4,7 +4,10 @@
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    rsdicombrowser::init_logging(); // Log to stderr and the log file (if you run with `RUST_LOG=debug`).
+
+    // Disable the features modifying or removing files, e.g. when browsing a clinical archive.
+    let read_only = std::env::args().skip(1).any(|x| x == "--read-only");
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -15,7 +18,7 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "Dicom Browser",
         native_options,
-        Box::new(|cc| Ok(Box::new(rsdicombrowser::TemplateApp::new(cc)))),
+        Box::new(move |cc| Ok(Box::new(rsdicombrowser::TemplateApp::new(cc, read_only)))),
     )
 }
 
@@ -45,7 +48,7 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(eframe_template::TemplateApp::new(cc)))),
+                Box::new(|cc| Ok(Box::new(eframe_template::TemplateApp::new(cc, false)))),
             )
             .await;
 
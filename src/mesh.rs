@@ -0,0 +1,304 @@
+//! Surface meshes of the structures outlined by planar contours, e.g. the ROIs of an RT structure set, written as STL
+//! or OBJ for the 3D printing and planning tools.
+//! The contours are filled into a binary voxel grid, one plane per contour position, and the boundary of the grid is
+//! extracted with marching tetrahedra, the variant of marching cubes which needs no case tables and leaves no holes.
+
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+/// A point or a vector in the patient coordinates, in mm.
+pub type Point = [f64; 3];
+
+/// A triangle with its vertices counterclockwise when seen from the outside.
+pub type Triangle = [Point; 3];
+
+/// The corners of a unit cube, as offsets on the grid.
+const CUBE_CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [1, 1, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [1, 1, 1],
+    [0, 1, 1],
+];
+
+/// The six tetrahedra sharing the diagonal from the corner 0 to the corner 6 which fill the cube, so that the faces of
+/// the neighbouring cubes match.
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 6, 1, 2],
+    [0, 6, 2, 3],
+    [0, 6, 3, 7],
+    [0, 6, 7, 4],
+    [0, 6, 4, 5],
+    [0, 6, 5, 1],
+];
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Point, b: Point) -> Point {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: Point, b: Point) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    [
+        (a[0] + b[0]) / 2.0,
+        (a[1] + b[1]) / 2.0,
+        (a[2] + b[2]) / 2.0,
+    ]
+}
+
+/// Get the normal of the triangle, of unit length, or zero for a degenerate one.
+fn normal(triangle: &Triangle) -> Point {
+    let n = cross(sub(triangle[1], triangle[0]), sub(triangle[2], triangle[0]));
+    let length = dot(n, n).sqrt();
+    if length > 0.0 {
+        n.map(|x| x / length)
+    } else {
+        [0.0; 3]
+    }
+}
+
+/// Check if the point is inside the polygon in the x-y plane, by the even-odd rule.
+fn is_inside(polygon: &[Point], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for &point in polygon {
+        if (point[1] > y) != (previous[1] > y)
+            && x < previous[0]
+                + (y - previous[1]) * (point[0] - previous[0]) / (point[1] - previous[1])
+        {
+            inside = !inside;
+        }
+        previous = point;
+    }
+    inside
+}
+
+/// A binary voxel grid of a structure.
+struct Grid {
+    origin: Point,
+    spacing: Point,
+    size: [usize; 3],
+    voxels: Vec<bool>,
+}
+
+impl Grid {
+    fn get(&self, [i, j, k]: [usize; 3]) -> bool {
+        self.voxels[(k * self.size[1] + j) * self.size[0] + i]
+    }
+
+    fn position(&self, [i, j, k]: [usize; 3]) -> Point {
+        [
+            self.origin[0] + i as f64 * self.spacing[0],
+            self.origin[1] + j as f64 * self.spacing[1],
+            self.origin[2] + k as f64 * self.spacing[2],
+        ]
+    }
+}
+
+/// Fill the contours into a voxel grid with the in-plane spacing, and one plane per contour position.
+/// The contours must be axial, i.e. each with a constant z, and those on the same plane are combined by the even-odd
+/// rule so that the inner contours cut holes. The grid has an empty border so that the surface is closed.
+fn rasterize(contours: &[Vec<Point>], pixel_spacing: f64) -> std::result::Result<Grid, String> {
+    let contours: Vec<&Vec<Point>> = contours.iter().filter(|x| x.len() >= 3).collect();
+    if contours.is_empty() {
+        return Err("The ROI has no closed contours.".to_string());
+    }
+
+    let mut planes: Vec<f64> = Vec::new();
+    for contour in &contours {
+        let z = contour[0][2];
+        if contour.iter().any(|x| (x[2] - z).abs() > 1e-3) {
+            return Err("Only the axial contours are supported.".to_string());
+        }
+        planes.push(z);
+    }
+    planes.sort_by(f64::total_cmp);
+    planes.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+
+    let thickness = planes
+        .windows(2)
+        .map(|x| x[1] - x[0])
+        .min_by(f64::total_cmp)
+        .unwrap_or(pixel_spacing);
+
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for point in contours.iter().flat_map(|x| x.iter()) {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+
+    // The planes are laid on a regular grid starting from the first one, with an empty plane on each side.
+    let plane_count = ((planes[planes.len() - 1] - planes[0]) / thickness).round() as usize + 1;
+    let size = [
+        ((max[0] - min[0]) / pixel_spacing).ceil() as usize + 3,
+        ((max[1] - min[1]) / pixel_spacing).ceil() as usize + 3,
+        plane_count + 2,
+    ];
+    let voxel_count = size[0] * size[1] * size[2];
+    if voxel_count > 200_000_000 {
+        return Err("The ROI is too large for the pixel spacing.".to_string());
+    }
+
+    let mut grid = Grid {
+        origin: [
+            min[0] - pixel_spacing,
+            min[1] - pixel_spacing,
+            planes[0] - thickness,
+        ],
+        spacing: [pixel_spacing, pixel_spacing, thickness],
+        size,
+        voxels: vec![false; voxel_count],
+    };
+
+    for contour in contours {
+        let k = ((contour[0][2] - grid.origin[2]) / thickness).round() as usize;
+        for j in 0..size[1] {
+            for i in 0..size[0] {
+                let [x, y, _] = grid.position([i, j, k]);
+                if is_inside(contour, x, y) {
+                    let index = (k * size[1] + j) * size[0] + i;
+                    grid.voxels[index] = !grid.voxels[index];
+                }
+            }
+        }
+    }
+
+    Ok(grid)
+}
+
+/// Extract the boundary of the grid as triangles, with the vertices halfway between the inside and the outside voxels.
+fn marching_tetrahedra(grid: &Grid) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+
+    for k in 0..grid.size[2] - 1 {
+        for j in 0..grid.size[1] - 1 {
+            for i in 0..grid.size[0] - 1 {
+                let corners = CUBE_CORNERS.map(|[di, dj, dk]| [i + di, j + dj, k + dk]);
+                let inside = corners.map(|x| grid.get(x));
+                if inside.iter().all(|x| *x) || inside.iter().all(|x| !*x) {
+                    continue;
+                }
+                let positions = corners.map(|x| grid.position(x));
+
+                for tetrahedron in CUBE_TETRAHEDRA {
+                    let (inner, outer): (Vec<usize>, Vec<usize>) =
+                        tetrahedron.iter().partition(|x| inside[**x]);
+                    let edge = |a: usize, b: usize| midpoint(positions[a], positions[b]);
+
+                    let mut faces = Vec::new();
+                    match (inner.len(), outer.len()) {
+                        (1, 3) => faces.push([
+                            edge(inner[0], outer[0]),
+                            edge(inner[0], outer[1]),
+                            edge(inner[0], outer[2]),
+                        ]),
+                        (3, 1) => faces.push([
+                            edge(outer[0], inner[0]),
+                            edge(outer[0], inner[1]),
+                            edge(outer[0], inner[2]),
+                        ]),
+                        (2, 2) => {
+                            let quad = [
+                                edge(inner[0], outer[0]),
+                                edge(inner[0], outer[1]),
+                                edge(inner[1], outer[1]),
+                                edge(inner[1], outer[0]),
+                            ];
+                            faces.push([quad[0], quad[1], quad[2]]);
+                            faces.push([quad[0], quad[2], quad[3]]);
+                        }
+                        _ => continue,
+                    }
+
+                    // Orient the faces to point away from the inside.
+                    let inner_center = inner
+                        .iter()
+                        .map(|x| positions[*x])
+                        .fold([0.0; 3], |a, b| [a[0] + b[0], a[1] + b[1], a[2] + b[2]])
+                        .map(|x| x / inner.len() as f64);
+                    for mut face in faces {
+                        if dot(normal(&face), sub(face[0], inner_center)) < 0.0 {
+                            face.swap(1, 2);
+                        }
+                        triangles.push(face);
+                    }
+                }
+            }
+        }
+    }
+
+    triangles
+}
+
+/// Mesh the surface of the structure outlined by the axial contours, with the in-plane pixel spacing in mm.
+pub fn mesh_contours(
+    contours: &[Vec<Point>],
+    pixel_spacing: f64,
+) -> std::result::Result<Vec<Triangle>, String> {
+    let grid = rasterize(contours, pixel_spacing)?;
+    Ok(marching_tetrahedra(&grid))
+}
+
+/// Write the triangles as a binary STL, in mm.
+pub fn write_stl(writer: &mut impl Write, name: &str, triangles: &[Triangle]) -> Result<()> {
+    let mut header = [b' '; 80];
+    let name = name.as_bytes();
+    header[..name.len().min(80)].copy_from_slice(&name[..name.len().min(80)]);
+    writer.write_all(&header)?;
+    writer.write_all(&(triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in triangles {
+        for point in std::iter::once(normal(triangle)).chain(triangle.iter().copied()) {
+            for x in point {
+                writer.write_all(&(x as f32).to_le_bytes())?;
+            }
+        }
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Write the triangles as a Wavefront OBJ, sharing the vertices between the faces.
+pub fn write_obj(writer: &mut impl Write, name: &str, triangles: &[Triangle]) -> Result<()> {
+    writeln!(writer, "o {}", name.replace(char::is_whitespace, "_"))?;
+
+    // The vertices are matched on a micrometre grid, which is far below the voxel size.
+    let mut indices: HashMap<[i64; 3], usize> = HashMap::new();
+    let mut faces = Vec::with_capacity(triangles.len());
+    for triangle in triangles {
+        let mut face = [0; 3];
+        for (index, point) in face.iter_mut().zip(triangle) {
+            let key = point.map(|x| (x * 1000.0).round() as i64);
+            *index = match indices.get(&key) {
+                Some(x) => *x,
+                None => {
+                    writeln!(writer, "v {} {} {}", point[0], point[1], point[2])?;
+                    indices.insert(key, indices.len() + 1);
+                    indices.len()
+                }
+            };
+        }
+        faces.push(face);
+    }
+    for [a, b, c] in faces {
+        writeln!(writer, "f {a} {b} {c}")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,310 @@
+//! Conversion between enhanced multi-frame and classic single-frame instances.
+//! Only native (uncompressed) pixel data is supported, as the frames are cut and joined at the byte level.
+
+use crate::dataset::{element_string, new_uid, sequence, write_file};
+use crate::export::flatten;
+use crate::functional_groups::merged_frame_groups;
+use crate::organize::avoid_collision;
+use crate::tasks::TaskContext;
+use dicom::core::value::Value;
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::{DefaultDicomObject, InMemDicomObject, open_file};
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The classic SOP classes and their enhanced and legacy converted enhanced counterparts.
+const SOP_CLASSES: [(&str, &str, &str); 3] = [
+    (
+        uids::CT_IMAGE_STORAGE,
+        uids::ENHANCED_CT_IMAGE_STORAGE,
+        uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE,
+    ),
+    (
+        uids::MR_IMAGE_STORAGE,
+        uids::ENHANCED_MR_IMAGE_STORAGE,
+        uids::LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE,
+    ),
+    (
+        uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE,
+        uids::ENHANCED_PET_IMAGE_STORAGE,
+        uids::LEGACY_CONVERTED_ENHANCED_PET_IMAGE_STORAGE,
+    ),
+];
+
+/// The functional group sequences and the classic attributes they hold.
+const FRAME_GROUPS: [(Tag, &[Tag]); 5] = [
+    (
+        tags::PLANE_POSITION_SEQUENCE,
+        &[tags::IMAGE_POSITION_PATIENT],
+    ),
+    (
+        tags::PLANE_ORIENTATION_SEQUENCE,
+        &[tags::IMAGE_ORIENTATION_PATIENT],
+    ),
+    (
+        tags::PIXEL_MEASURES_SEQUENCE,
+        &[
+            tags::PIXEL_SPACING,
+            tags::SLICE_THICKNESS,
+            tags::SPACING_BETWEEN_SLICES,
+        ],
+    ),
+    (
+        tags::FRAME_VOILUT_SEQUENCE,
+        &[
+            tags::WINDOW_CENTER,
+            tags::WINDOW_WIDTH,
+            tags::WINDOW_CENTER_WIDTH_EXPLANATION,
+        ],
+    ),
+    (
+        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+        &[
+            tags::RESCALE_INTERCEPT,
+            tags::RESCALE_SLOPE,
+            tags::RESCALE_TYPE,
+        ],
+    ),
+];
+
+/// The multi-frame elements which don't apply to a single frame.
+const MULTI_FRAME_TAGS: [Tag; 5] = [
+    tags::NUMBER_OF_FRAMES,
+    tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::DIMENSION_ORGANIZATION_SEQUENCE,
+    tags::DIMENSION_INDEX_SEQUENCE,
+];
+
+fn int_value(obj: &InMemDicomObject, tag: Tag) -> Option<usize> {
+    obj.get(tag).and_then(|x| x.to_int::<usize>().ok())
+}
+
+/// Get the byte length of a single frame of the native pixel data.
+fn frame_length(obj: &InMemDicomObject) -> Result<usize> {
+    let rows = int_value(obj, tags::ROWS);
+    let columns = int_value(obj, tags::COLUMNS);
+    let samples = int_value(obj, tags::SAMPLES_PER_PIXEL).unwrap_or(1);
+    let bits = int_value(obj, tags::BITS_ALLOCATED);
+
+    match (rows, columns, bits) {
+        (Some(rows), Some(columns), Some(bits)) if bits % 8 == 0 => {
+            Ok(rows * columns * samples * bits / 8)
+        }
+        _ => Err(Error::other(
+            "the image pixel description is missing or unsupported",
+        )),
+    }
+}
+
+/// Get the VR and the bytes of the native pixel data.
+fn native_pixel_data(obj: &InMemDicomObject) -> Result<(VR, Vec<u8>)> {
+    let elem = obj
+        .get(tags::PIXEL_DATA)
+        .ok_or_else(|| Error::other("the file has no pixel data"))?;
+
+    match elem.value() {
+        Value::Primitive(value) => Ok((elem.vr(), value.to_bytes().into_owned())),
+        _ => Err(Error::other("only native pixel data is supported")),
+    }
+}
+
+fn sop_class(obj: &InMemDicomObject) -> String {
+    element_string(obj, tags::SOP_CLASS_UID).unwrap_or_default()
+}
+
+/// Split an enhanced multi-frame instance into classic single-frame instances in a new series.
+/// The merged functional groups of each frame are written as top-level attributes.
+/// The files are written to a `<name>_frames` folder next to the original, and their paths are returned.
+/// Stops between the frames if cancelled, removing the frames written.
+pub fn split(path: &Path, context: &TaskContext) -> Result<Vec<PathBuf>> {
+    let obj: DefaultDicomObject = open_file(path).map_err(Error::other)?;
+    let frames = int_value(&obj, tags::NUMBER_OF_FRAMES).unwrap_or(1);
+    if frames < 2 {
+        return Err(Error::other("the instance has a single frame"));
+    }
+
+    let source_class = sop_class(&obj);
+    let classic_class = SOP_CLASSES
+        .iter()
+        .find(|(_, enhanced, legacy)| source_class == *enhanced || source_class == *legacy)
+        .map(|(classic, _, _)| *classic)
+        .ok_or_else(|| Error::other(format!("unsupported SOP class {source_class}")))?;
+
+    let length = frame_length(&obj)?;
+    let (vr, pixels) = native_pixel_data(&obj)?;
+    if pixels.len() < frames * length {
+        return Err(Error::other("the pixel data is shorter than the frames"));
+    }
+
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string());
+    let output_dir = path.with_file_name(format!("{stem}_frames"));
+    std::fs::create_dir_all(&output_dir)?;
+
+    let transfer_syntax = obj.meta().transfer_syntax().to_string();
+    let series_uid = new_uid();
+    let mut outputs = Vec::new();
+
+    for frame in 0..frames {
+        if context.is_cancelled() {
+            for output in &outputs {
+                let _ = std::fs::remove_file(output);
+            }
+            return Err(Error::other("the split was cancelled"));
+        }
+        context.set_progress(frame as f32 / frames as f32);
+        let (groups, _) = merged_frame_groups(&obj, frame);
+        let mut single = (*obj).clone();
+
+        for tag in MULTI_FRAME_TAGS {
+            single.remove_element(tag);
+        }
+        for group in &groups {
+            for elem in group.items().and_then(|x| x.first()).into_iter().flatten() {
+                single.put(elem.clone());
+            }
+        }
+
+        single.put_str(tags::SOP_CLASS_UID, VR::UI, classic_class);
+        single.put_str(tags::SOP_INSTANCE_UID, VR::UI, new_uid());
+        single.put_str(tags::SERIES_INSTANCE_UID, VR::UI, series_uid.as_str());
+        single.put_str(tags::INSTANCE_NUMBER, VR::IS, (frame + 1).to_string());
+        single.put(DataElement::new(
+            tags::PIXEL_DATA,
+            vr,
+            PrimitiveValue::from(pixels[frame * length..(frame + 1) * length].to_vec()),
+        ));
+
+        let output = output_dir.join(format!("{stem}_{:04}.dcm", frame + 1));
+        write_file(single, &transfer_syntax, &output)?;
+        outputs.push(output);
+    }
+
+    Ok(outputs)
+}
+
+/// Move the classic attributes of the functional group into a group item, if any of them is present.
+fn take_group(obj: &mut InMemDicomObject, attributes: &[Tag]) -> Option<InMemDicomObject> {
+    let elements: Vec<_> = attributes
+        .iter()
+        .filter_map(|tag| obj.take_element(*tag).ok())
+        .collect();
+
+    (!elements.is_empty()).then(|| InMemDicomObject::from_element_iter(elements))
+}
+
+/// Assemble classic single-frame instances into a legacy converted enhanced multi-frame instance in a new series.
+/// The functional groups which are the same for all frames are shared, the others are kept per frame.
+/// The file is written next to the first instance, and its path is returned. Stops reading the files if cancelled.
+pub fn merge(files: &[PathBuf], context: &TaskContext) -> Result<PathBuf> {
+    let mut objects = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        if context.is_cancelled() {
+            return Err(Error::other("the merge was cancelled"));
+        }
+        context.set_progress(index as f32 / files.len() as f32);
+        let obj: DefaultDicomObject = open_file(file).map_err(Error::other)?;
+        objects.push(obj);
+    }
+    objects.sort_by_key(|x| int_value(x, tags::INSTANCE_NUMBER).unwrap_or(0));
+
+    let Some(first) = objects.first() else {
+        return Err(Error::other("no files to merge"));
+    };
+    let source_class = sop_class(first);
+    let legacy_class = SOP_CLASSES
+        .iter()
+        .find(|(classic, _, _)| source_class == *classic)
+        .map(|(_, _, legacy)| *legacy)
+        .ok_or_else(|| Error::other(format!("unsupported SOP class {source_class}")))?;
+
+    let length = frame_length(first)?;
+    let transfer_syntax = first.meta().transfer_syntax().to_string();
+    let mut pixels = Vec::with_capacity(length * objects.len());
+    let mut vr = VR::OW;
+    let mut frame_groups = Vec::new();
+
+    for obj in &mut objects {
+        if sop_class(obj) != source_class || frame_length(obj)? != length {
+            return Err(Error::other(
+                "the instances have different SOP classes or image sizes",
+            ));
+        }
+
+        let (pixel_vr, frame) = native_pixel_data(obj)?;
+        // The pixel data of an odd length is padded with one byte, which isn't part of the frame.
+        let padded = length + length % 2;
+        if frame.len() != length && frame.len() != padded {
+            return Err(Error::other(format!(
+                "the pixel data has {} bytes instead of {length}",
+                frame.len()
+            )));
+        }
+        vr = pixel_vr;
+        pixels.extend(&frame[..length]);
+        frame_groups.push(
+            FRAME_GROUPS
+                .iter()
+                .map(|(_, attributes)| take_group(obj, attributes))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let mut shared = Vec::new();
+    let mut per_frame = vec![Vec::new(); frame_groups.len()];
+    for (index, (tag, _)) in FRAME_GROUPS.iter().enumerate() {
+        let items: Vec<_> = frame_groups.iter().map(|x| x[index].as_ref()).collect();
+        if items.iter().all(Option::is_none) {
+            continue;
+        }
+
+        let first_rows = items[0].map(flatten);
+        if items.iter().all(|x| x.map(flatten) == first_rows) {
+            shared.push(sequence(*tag, items[0].into_iter().cloned().collect()));
+        } else {
+            for (frame, item) in items.into_iter().enumerate() {
+                if let Some(item) = item {
+                    per_frame[frame].push(sequence(*tag, vec![item.clone()]));
+                }
+            }
+        }
+    }
+
+    let mut merged = (*objects[0]).clone();
+    merged.put(sequence(
+        tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+        vec![InMemDicomObject::from_element_iter(shared)],
+    ));
+    merged.put(sequence(
+        tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+        per_frame
+            .into_iter()
+            .map(InMemDicomObject::from_element_iter)
+            .collect(),
+    ));
+    merged.put_str(tags::SOP_CLASS_UID, VR::UI, legacy_class);
+    merged.put_str(tags::SOP_INSTANCE_UID, VR::UI, new_uid());
+    merged.put_str(tags::SERIES_INSTANCE_UID, VR::UI, new_uid());
+    merged.put_str(tags::INSTANCE_NUMBER, VR::IS, "1");
+    merged.put_str(tags::NUMBER_OF_FRAMES, VR::IS, objects.len().to_string());
+    merged.put(DataElement::new(
+        tags::PIXEL_DATA,
+        vr,
+        PrimitiveValue::from(pixels),
+    ));
+
+    let stem = files[0]
+        .file_stem()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string());
+    let output = avoid_collision(
+        files[0].with_file_name(format!("{stem}_multiframe.dcm")),
+        &HashSet::new(),
+    );
+    write_file(merged, &transfer_syntax, &output)?;
+
+    Ok(output)
+}
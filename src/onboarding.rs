@@ -0,0 +1,154 @@
+//! The welcome screen shown before a folder is opened, with the sample files to try the app on, and the inline hints
+//! for the new users.
+//! The samples are the small public test files of pydicom, downloaded once into the data folder of the user.
+
+use crate::download;
+use crate::i18n::{tr, trf};
+use crate::settings::app_data_dir;
+use crate::tasks::{Task, TaskContext, TaskPool};
+use std::io::{Error, Result};
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+
+const SAMPLE_BASE_URL: &str =
+    "https://raw.githubusercontent.com/pydicom/pydicom/main/src/pydicom/data/test_files/";
+
+/// The sample files, a few modalities and an RT set.
+const SAMPLE_FILES: [&str; 6] = [
+    "CT_small.dcm",
+    "MR_small.dcm",
+    "rtplan.dcm",
+    "rtstruct.dcm",
+    "rtdose.dcm",
+    "waveform_ecg.dcm",
+];
+
+/// Get the folder of the sample files.
+pub fn sample_dir() -> PathBuf {
+    app_data_dir("samples")
+}
+
+/// Download the sample files not downloaded yet, stopping between the files if cancelled.
+fn download_samples(context: &TaskContext) -> Result<PathBuf> {
+    let dir = sample_dir();
+    std::fs::create_dir_all(&dir)?;
+
+    for (index, name) in SAMPLE_FILES.iter().enumerate() {
+        if context.is_cancelled() {
+            return Err(Error::other("the download was cancelled"));
+        }
+        context.set_progress(index as f32 / SAMPLE_FILES.len() as f32);
+        let path = dir.join(name);
+        if path.exists() {
+            continue;
+        }
+        // Download to a partial file first, so that an interrupted download is not taken as done.
+        let partial = dir.join(format!("{name}.part"));
+        download::fetch_to_file(&format!("{SAMPLE_BASE_URL}{name}"), &[], &partial)?;
+        std::fs::rename(&partial, &path)?;
+    }
+
+    Ok(dir)
+}
+
+/// The download of the sample files in the background.
+#[derive(Default)]
+pub struct SampleDownload {
+    pending: Option<Task<Result<PathBuf>>>,
+    error: Option<String>,
+}
+
+impl SampleDownload {
+    /// Start downloading the sample files, unless the download is running.
+    fn start(&mut self, tasks: &mut TaskPool) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        self.pending = Some(tasks.spawn(tr("Download the sample files"), download_samples));
+        self.error = None;
+    }
+
+    pub fn is_downloading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Get the folder of the sample files once they are downloaded.
+    pub fn poll(&mut self) -> Option<PathBuf> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other("the download stopped")),
+        };
+        self.pending = None;
+
+        match result {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                log::error!("Failed to download the sample files: {e}");
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// Show a hint for the new users.
+pub fn hint(ui: &mut egui::Ui, text: &str) {
+    ui.label(
+        egui::RichText::new(format!("💡 {}", tr(text)))
+            .weak()
+            .small(),
+    );
+}
+
+/// Show the welcome screen. Returns true if the folder should be picked.
+pub fn welcome_ui(
+    ui: &mut egui::Ui,
+    samples: &mut SampleDownload,
+    tasks: &mut TaskPool,
+    open_shortcut: &str,
+) -> bool {
+    let mut open_folder = false;
+
+    ui.vertical_centered(|ui| {
+        ui.add_space(40.0);
+        ui.heading(tr("Welcome to Dicom Browser"));
+        ui.label(tr(
+            "Open a folder to browse its DICOM files, their headers and their relations.",
+        ));
+        ui.add_space(20.0);
+
+        open_folder = ui
+            .button(format!("📂 {} ({open_shortcut})", tr("Open folder")))
+            .clicked();
+        let button = egui::Button::new(format!("⬇ {}", tr("Open the sample files")));
+        if ui
+            .add_enabled(!samples.is_downloading(), button)
+            .on_hover_text(trf("Downloaded once into {}", sample_dir().display()))
+            .clicked()
+        {
+            samples.start(tasks);
+        }
+        if samples.is_downloading() {
+            ui.spinner();
+        }
+        if let Some(error) = samples.error.as_ref() {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        ui.add_space(20.0);
+        hint(
+            ui,
+            "Select files in the tree on the left to show their headers.",
+        );
+        hint(ui, "Right-click a file in the tree for more actions.");
+        hint(
+            ui,
+            "Search the header in the box above it, and press Enter for the next match.",
+        );
+        hint(ui, "The tools and the other views are in the menus above.");
+    });
+
+    open_folder
+}
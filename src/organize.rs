@@ -0,0 +1,352 @@
+use crate::audit::AuditLog;
+use crate::dataset::{expand_template, keyword_value, open_header};
+use crate::i18n::{tr, trf};
+use crate::tasks::{self, Task, TaskContext, TaskPool};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_TEMPLATE: &str = "{PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{SeriesDescription}/{InstanceNumber}.dcm";
+
+/// Make the tag value safe to be used in a file name.
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .trim()
+        .chars()
+        .map(|x| {
+            if x.is_control() || matches!(x, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                x
+            }
+        })
+        .collect();
+
+    match value.as_str() {
+        "" => "UNKNOWN".to_string(),
+        // The current and the parent folders would place the file elsewhere.
+        "." | ".." => "_".to_string(),
+        _ => value,
+    }
+}
+
+/// Make the expanded template a relative path which stays inside the target folder, by replacing the empty, `.` and
+/// `..` components, e.g. from a leading `/` or a `..` in the template itself.
+fn relative_path(expanded: &str) -> PathBuf {
+    expanded
+        .split(['/', '\\'])
+        .map(|x| match x.trim() {
+            "" | "." | ".." => "_",
+            _ => x,
+        })
+        .collect()
+}
+
+/// Add a counter to the file name until it doesn't collide with the taken or existing paths.
+pub fn avoid_collision(path: PathBuf, taken: &HashSet<PathBuf>) -> PathBuf {
+    if !taken.contains(&path) && !path.exists() {
+        return path;
+    }
+
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |x| x.to_string_lossy().to_string());
+    let extension = path
+        .extension()
+        .map_or(String::new(), |x| format!(".{}", x.to_string_lossy()));
+
+    (1..)
+        .map(|x| path.with_file_name(format!("{stem}_{x}{extension}")))
+        .find(|x| !taken.contains(x) && !x.exists())
+        .expect("a free file name should be found")
+}
+
+/// Plan where each file goes according to the template, stopping early if cancelled. The files whose target would be
+/// outside the target folder are left out.
+pub fn plan(
+    files: &[PathBuf],
+    template: &str,
+    target_dir: &Path,
+    context: &TaskContext,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut taken = HashSet::new();
+
+    files
+        .iter()
+        .enumerate()
+        .take_while(|_| !context.is_cancelled())
+        .filter_map(|(index, file)| {
+            context.set_progress(index as f32 / files.len() as f32);
+            let obj = open_header(file).ok()?;
+            let expanded =
+                expand_template(template, |keyword| sanitize(&keyword_value(&obj, keyword)));
+            let target = avoid_collision(target_dir.join(relative_path(&expanded)), &taken);
+            if !target.starts_with(target_dir) {
+                log::error!("{} would be outside the target folder", target.display());
+                return None;
+            }
+            taken.insert(target.clone());
+            Some((file.clone(), target))
+        })
+        .collect()
+}
+
+/// Copy the file to the target, which is created only if it doesn't exist yet. A partial target left by a failed copy is
+/// removed.
+fn copy_new(source: &Path, target: &Path) -> Result<()> {
+    let mut input = File::open(source)?;
+    let mut output = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(target)?;
+    let result = std::io::copy(&mut input, &mut output)
+        .and_then(|_| output.set_permissions(input.metadata()?.permissions()))
+        .and_then(|()| output.sync_all());
+
+    if result.is_err() {
+        drop(output);
+        let _ = std::fs::remove_file(target);
+    }
+    result
+}
+
+/// Move the file to the target, which is created only if it doesn't exist yet. The file is linked to the target and
+/// unlinked from the source, or copied and removed across file systems.
+fn move_new(source: &Path, target: &Path) -> Result<()> {
+    std::fs::hard_link(source, target)
+        .or_else(|e| match e.kind() {
+            ErrorKind::AlreadyExists => Err(e),
+            _ => copy_new(source, target),
+        })
+        .and_then(|()| std::fs::remove_file(source))
+}
+
+/// Copy or move the files as planned until cancelled, adding the files done to `done`, and get the errors. A target
+/// which exists by now, e.g. created since the plan, is never overwritten.
+pub fn execute(
+    plan: &[(PathBuf, PathBuf)],
+    is_move: bool,
+    done: &Mutex<Vec<(PathBuf, PathBuf)>>,
+    context: &TaskContext,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (index, (source, target)) in plan.iter().enumerate() {
+        if context.is_cancelled() {
+            break;
+        }
+        context.set_progress(index as f32 / plan.len() as f32);
+        let result = target
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| {
+                if is_move {
+                    move_new(source, target)
+                } else {
+                    copy_new(source, target)
+                }
+            });
+
+        match result {
+            Ok(()) => done
+                .lock()
+                .unwrap_or_else(|x| x.into_inner())
+                .push((source.clone(), target.clone())),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                errors.push(trf("{} already exists", target.display()));
+            }
+            Err(e) => errors.push(format!("{}: {e}", source.display())),
+        }
+    }
+
+    errors
+}
+
+/// What the app needs to do after showing the organize window.
+#[derive(PartialEq)]
+pub enum OrganizeAction {
+    None,
+    PickTarget,
+}
+
+/// A run of the organize tool in the background, with the files done so far.
+struct PendingRun {
+    is_move: bool,
+    done: Arc<Mutex<Vec<(PathBuf, PathBuf)>>>,
+    task: Task<Vec<String>>,
+}
+
+/// The state of the organize window. The preview and the run are done on the worker threads.
+pub struct OrganizeTool {
+    pub template: String,
+    pub target_dir: Option<PathBuf>,
+    pub is_move: bool,
+    preview: Vec<(PathBuf, PathBuf)>,
+    pending_preview: Option<Task<Vec<(PathBuf, PathBuf)>>>,
+    pending_run: Option<PendingRun>,
+    status: String,
+}
+
+impl Default for OrganizeTool {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_TEMPLATE.to_string(),
+            target_dir: None,
+            is_move: false,
+            preview: Vec::new(),
+            pending_preview: None,
+            pending_run: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl OrganizeTool {
+    /// Take the preview and the result of the run when they are done, recording the files done in the audit log, even if
+    /// the run was cancelled. Returns true if files were moved, so that the folders are rescanned.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> bool {
+        if let Some(preview) = tasks::poll(&mut self.pending_preview) {
+            self.preview = preview;
+            self.status.clear();
+        }
+
+        let Some(run) = self.pending_run.as_ref() else {
+            return false;
+        };
+        let errors = match run.task.try_recv() {
+            Ok(errors) => errors,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => vec![tr("The run was cancelled.")],
+        };
+        let Some(run) = self.pending_run.take() else {
+            return false;
+        };
+        let done = std::mem::take(&mut *run.done.lock().unwrap_or_else(|x| x.into_inner()));
+
+        let count = done.len();
+        self.status = if errors.is_empty() {
+            trf("{} files organized.", count)
+        } else {
+            errors.join("\n")
+        };
+        if done.is_empty() {
+            return false;
+        }
+        let (sources, targets) = done.into_iter().unzip();
+        audit_log.record(if run.is_move { "Move" } else { "Copy" }, sources, targets);
+        run.is_move
+    }
+
+    /// Show the organize options and the dry-run preview.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        tasks: &mut TaskPool,
+        read_only: bool,
+    ) -> OrganizeAction {
+        let mut action = OrganizeAction::None;
+        if read_only {
+            self.is_move = false;
+        }
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("organize grid").show(ui, |ui| {
+            ui.label(tr("Template"));
+            ui.add(egui::TextEdit::singleline(&mut self.template).desired_width(400.0));
+            ui.end_row();
+
+            ui.label(tr("Target folder"));
+            ui.horizontal(|ui| {
+                let target = self
+                    .target_dir
+                    .as_ref()
+                    .map_or(tr("None"), |x| x.display().to_string());
+                ui.monospace(target);
+                if ui.button(tr("Browse")).clicked() {
+                    action = OrganizeAction::PickTarget;
+                }
+            });
+            ui.end_row();
+
+            ui.label(tr("Operation"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.is_move, false, tr("Copy"));
+                ui.add_enabled_ui(!read_only, |ui| {
+                    ui.radio_value(&mut self.is_move, true, tr("Move"))
+                        .on_disabled_hover_text(tr("Disabled in read-only mode"));
+                });
+            });
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            let Some(target_dir) = self.target_dir.clone() else {
+                return;
+            };
+            let is_busy = self.pending_preview.is_some() || self.pending_run.is_some();
+            if ui
+                .add_enabled(!is_busy, egui::Button::new(tr("Preview")))
+                .clicked()
+            {
+                let files = files.to_vec();
+                let template = self.template.clone();
+                self.pending_preview =
+                    Some(tasks.spawn(tr("Preview the organize"), move |context| {
+                        plan(&files, &template, &target_dir, context)
+                    }));
+            }
+            if ui
+                .add_enabled(
+                    !self.preview.is_empty() && !is_busy,
+                    egui::Button::new(tr("Run")),
+                )
+                .clicked()
+            {
+                // Plan the previewed files again, as files may have been created in the target folder since.
+                let sources: Vec<PathBuf> =
+                    self.preview.drain(..).map(|(source, _)| source).collect();
+                let template = self.template.clone();
+                let is_move = self.is_move;
+                let done = Arc::new(Mutex::new(Vec::new()));
+                let shared = done.clone();
+                let name = if is_move { tr("Move") } else { tr("Copy") };
+                let task = tasks.spawn(name, move |context| {
+                    let planned = plan(&sources, &template, &target_dir, context);
+                    execute(&planned, is_move, &shared, context)
+                });
+                self.pending_run = Some(PendingRun {
+                    is_move,
+                    done,
+                    task,
+                });
+                self.status.clear();
+            }
+            if is_busy {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        ui.separator();
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("organize preview grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (source, target) in &self.preview {
+                        ui.monospace(source.display().to_string());
+                        ui.monospace(target.display().to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+
+        action
+    }
+}
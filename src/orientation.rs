@@ -0,0 +1,103 @@
+//! The patient orientation of the images, from the Image Orientation (Patient) cosines in the LPS patient coordinates.
+//! The labels give the patient direction towards each edge of the image, e.g. R on the left and L on the right of an
+//! axial slice, and are shown around an outline of the image since the app doesn't render the pixels.
+
+use crate::i18n::tr;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+
+/// The components of a direction cosine smaller than this are ignored in its label.
+const LABEL_TOLERANCE: f64 = 1e-4;
+/// The tolerance of a direction cosine to be considered along an axis.
+const AXIS_TOLERANCE: f64 = 1e-3;
+
+/// Get the Image Orientation (Patient) as the row and the column direction cosines.
+pub fn image_orientation(obj: &InMemDicomObject) -> Option<([f64; 3], [f64; 3])> {
+    let values = obj
+        .get(tags::IMAGE_ORIENTATION_PATIENT)?
+        .to_multi_float64()
+        .ok()?;
+    match values[..] {
+        [a, b, c, d, e, f] => Some(([a, b, c], [d, e, f])),
+        _ => None,
+    }
+}
+
+/// Get the label of the patient direction, e.g. `L` or `LP` for an oblique one, with the main direction first.
+pub fn direction_label(direction: [f64; 3]) -> String {
+    let letters = [('L', 'R'), ('P', 'A'), ('H', 'F')];
+    let mut components: Vec<(f64, char)> = direction
+        .iter()
+        .zip(letters)
+        .filter(|(x, _)| x.abs() > LABEL_TOLERANCE)
+        .map(|(x, (positive, negative))| (x.abs(), if *x > 0.0 { positive } else { negative }))
+        .collect();
+    components.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    components.iter().map(|(_, x)| x).collect()
+}
+
+/// Check if the direction is along one of the patient axes.
+fn is_axis_aligned(direction: [f64; 3]) -> bool {
+    direction
+        .iter()
+        .any(|x| (x.abs() - 1.0).abs() < AXIS_TOLERANCE)
+}
+
+/// Check if the image is oblique, i.e. its row or column is not along a patient axis.
+pub fn is_oblique(row: [f64; 3], column: [f64; 3]) -> bool {
+    !is_axis_aligned(row) || !is_axis_aligned(column)
+}
+
+/// Show the orientation labels around an outline of the image, and warn if the image is oblique.
+pub fn ui(ui: &mut egui::Ui, obj: &InMemDicomObject) {
+    let Some((row, column)) = image_orientation(obj) else {
+        return;
+    };
+    let opposite = |x: [f64; 3]| x.map(|x| -x);
+
+    ui.horizontal(|ui| {
+        let size = egui::vec2(90.0, 90.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let color = ui.visuals().text_color();
+        let image = rect.shrink(18.0);
+        painter.rect_stroke(
+            image,
+            0.0,
+            egui::Stroke::new(1.0, color),
+            egui::StrokeKind::Middle,
+        );
+
+        let font = egui::FontId::monospace(11.0);
+        // The rows run to the right and the columns run down.
+        for (pos, align, direction) in [
+            (
+                image.left_center(),
+                egui::Align2::RIGHT_CENTER,
+                opposite(row),
+            ),
+            (image.right_center(), egui::Align2::LEFT_CENTER, row),
+            (
+                image.center_top(),
+                egui::Align2::CENTER_BOTTOM,
+                opposite(column),
+            ),
+            (image.center_bottom(), egui::Align2::CENTER_TOP, column),
+        ] {
+            let offset = align.to_sign() * -3.0;
+            painter.text(
+                pos + offset,
+                align,
+                direction_label(direction),
+                font.clone(),
+                color,
+            );
+        }
+        response.on_hover_text(tr("Patient orientation of the image"));
+
+        if is_oblique(row, column) {
+            ui.colored_label(ui.visuals().warn_fg_color, tr("Oblique image orientation"));
+        }
+    });
+}
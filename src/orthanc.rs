@@ -0,0 +1,436 @@
+//! Browsing an Orthanc server through its REST API: the patients, their studies and their series, pulling a study or
+//! a series into a cache folder to open it, and pushing the selected files to the server, which is recorded in the
+//! audit log. The instances are listed and downloaded one at a time, so that a pull can be cancelled between them.
+
+use crate::audit::AuditLog;
+use crate::cli_commands::OrthancServer;
+use crate::download::{self, Credentials};
+use crate::i18n::{tr, trf};
+use crate::privacy;
+use crate::tasks::{Task, TaskContext, TaskPool};
+use serde_json::Value;
+use std::io::{Error, Result};
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+use std::sync::{Arc, Mutex};
+
+/// Get the folder in the temp dir the studies and the series pulled from the servers are downloaded into.
+pub fn pull_dir() -> PathBuf {
+    std::env::temp_dir().join("rsdicombrowser").join("orthanc")
+}
+
+/// Get the bearer token in the `ORTHANC_TOKEN` environment variable if the server uses one, sent in the config on
+/// the standard input of `curl` rather than as an argument.
+fn credentials(server: &OrthancServer) -> Option<Credentials> {
+    server.token_auth.then(|| Credentials {
+        headers: vec![format!(
+            "Authorization: Bearer {}",
+            std::env::var("ORTHANC_TOKEN").unwrap_or_default()
+        )],
+        ..Default::default()
+    })
+}
+
+/// Check if the ID of a resource is safe to name a file with. The IDs of Orthanc are hex digits and dashes.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|x| x.is_ascii_hexdigit() || x == '-')
+}
+
+/// Get the JSON of the resource of the server, e.g. `/patients?expand`.
+fn get_json(server: &OrthancServer, resource: &str) -> Result<Value> {
+    let url = format!("{}{resource}", server.url.trim_end_matches('/'));
+    let body = download::fetch_as(
+        &url,
+        &["--header", "Accept: application/json"],
+        credentials(server).as_ref(),
+    )?;
+    serde_json::from_slice(&body).map_err(Error::other)
+}
+
+/// The level of a resource in the hierarchy of the server.
+#[derive(Clone, Copy, PartialEq)]
+enum Level {
+    Patient,
+    Study,
+    Series,
+}
+
+impl Level {
+    /// The resource listing the children of a resource of the level, expanded with their tags.
+    fn children_resource(self, id: &str) -> Option<String> {
+        match self {
+            Self::Patient => Some(format!("/patients/{id}/studies?expand")),
+            Self::Study => Some(format!("/studies/{id}/series?expand")),
+            Self::Series => None,
+        }
+    }
+
+    fn child(self) -> Self {
+        match self {
+            Self::Patient => Self::Study,
+            Self::Study | Self::Series => Self::Series,
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Patient => "patients",
+            Self::Study => "studies",
+            Self::Series => "series",
+        }
+    }
+}
+
+/// A patient, a study or a series on the server, with its children once they are listed.
+struct Node {
+    id: String,
+    level: Level,
+    label: String,
+    children: Option<Vec<Node>>,
+}
+
+impl Node {
+    /// Create the node from the expanded JSON of the resource, labelled by its main tags.
+    fn from_json(level: Level, json: &Value) -> Option<Self> {
+        let tag = |group: &str, name: &str| {
+            json.get(group)
+                .and_then(|x| x.get(name))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let label = match level {
+            Level::Patient => format!(
+                "{} ({})",
+                tag("MainDicomTags", "PatientName"),
+                tag("MainDicomTags", "PatientID")
+            ),
+            Level::Study => format!(
+                "{} {}",
+                tag("MainDicomTags", "StudyDate"),
+                tag("MainDicomTags", "StudyDescription")
+            ),
+            Level::Series => format!(
+                "{} #{} {} ({})",
+                tag("MainDicomTags", "Modality"),
+                tag("MainDicomTags", "SeriesNumber"),
+                tag("MainDicomTags", "SeriesDescription"),
+                trf(
+                    "{} instances",
+                    json.get("Instances")
+                        .and_then(Value::as_array)
+                        .map_or(0, Vec::len)
+                )
+            ),
+        };
+
+        let id = json.get("ID")?.as_str().filter(|x| is_valid_id(x))?;
+        Some(Self {
+            id: id.to_string(),
+            level,
+            label,
+            children: None,
+        })
+    }
+
+    fn find_mut(&mut self, id: &str) -> Option<&mut Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children
+            .as_mut()?
+            .iter_mut()
+            .find_map(|x| x.find_mut(id))
+    }
+}
+
+/// List the resources of the level, expanded with their tags.
+fn list(server: &OrthancServer, level: Level, resource: &str) -> Result<Vec<Node>> {
+    let json = get_json(server, resource)?;
+    let items = json
+        .as_array()
+        .ok_or_else(|| Error::other("the server didn't return a list"))?;
+    Ok(items
+        .iter()
+        .filter_map(|x| Node::from_json(level, x))
+        .collect())
+}
+
+/// Download the instances of the study or the series into its folder, skipping the instances downloaded before.
+fn pull(server: &OrthancServer, level: Level, id: &str, context: &TaskContext) -> Result<PathBuf> {
+    let instances = get_json(server, &format!("/{}/{id}/instances", level.path()))?;
+    let ids: Vec<&str> = instances
+        .as_array()
+        .ok_or_else(|| Error::other("the server didn't return a list"))?
+        .iter()
+        .filter_map(|x| x.get("ID").and_then(Value::as_str))
+        .filter(|x| is_valid_id(x))
+        .collect();
+
+    let dir = pull_dir().join(id);
+    std::fs::create_dir_all(&dir)?;
+    let credentials = credentials(server);
+    for (index, instance) in ids.iter().enumerate() {
+        if context.is_cancelled() {
+            return Err(Error::other("the download was cancelled"));
+        }
+        context.set_progress(index as f32 / ids.len() as f32);
+        let path = dir.join(format!("{instance}.dcm"));
+        if path.exists() {
+            continue;
+        }
+        // Download to a partial file first, so that an interrupted download is not taken as done.
+        let partial = dir.join(format!("{instance}.part"));
+        let url = format!(
+            "{}/instances/{instance}/file",
+            server.url.trim_end_matches('/')
+        );
+        download::fetch_to_file_as(&url, &[], credentials.as_ref(), &partial)?;
+        std::fs::rename(&partial, &path)?;
+    }
+
+    Ok(dir)
+}
+
+/// Upload the files to the server, stopping at the first failure. Returns the number of files uploaded.
+/// The files uploaded are added to the list as they are, to be recorded even if the push is cancelled or fails.
+fn push(
+    server: &OrthancServer,
+    files: &[PathBuf],
+    pushed: &Mutex<Vec<PathBuf>>,
+    context: &TaskContext,
+) -> Result<usize> {
+    let credentials = credentials(server);
+    let url = format!("{}/instances", server.url.trim_end_matches('/'));
+    for (index, file) in files.iter().enumerate() {
+        if context.is_cancelled() {
+            return Ok(index);
+        }
+        context.set_progress(index as f32 / files.len() as f32);
+        download::post_file_as(&url, &[], credentials.as_ref(), file)
+            .map_err(|e| Error::other(format!("{}: {e}", file.display())))?;
+        pushed
+            .lock()
+            .unwrap_or_else(|x| x.into_inner())
+            .push(file.clone());
+    }
+    Ok(files.len())
+}
+
+/// The result of a request to the server.
+enum Reply {
+    Patients(Vec<Node>),
+    /// The children of the node with the ID.
+    Children(String, Vec<Node>),
+    /// The folder the study or the series was pulled into.
+    Pulled(PathBuf),
+    Pushed(usize),
+}
+
+/// The browser of an Orthanc server, with the request running in the background.
+#[derive(Default)]
+pub struct OrthancBrowser {
+    server: usize,
+    patients: Vec<Node>,
+    pending: Option<Task<Result<Reply>>>,
+    /// The URL the files are pushed to, and the files pushed so far.
+    pushed: Option<(String, Arc<Mutex<Vec<PathBuf>>>)>,
+    status: String,
+}
+
+impl OrthancBrowser {
+    fn start(
+        &mut self,
+        tasks: &mut TaskPool,
+        name: String,
+        request: impl FnOnce(&TaskContext) -> Result<Reply> + Send + 'static,
+    ) {
+        if self.pending.is_none() {
+            self.pending = Some(tasks.spawn(name, request));
+            self.status.clear();
+        }
+    }
+
+    /// Take the result of the request if it is done, recording the files pushed in the audit log. Returns the folder
+    /// of a pulled study or series, to be opened.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> Option<PathBuf> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other(tr("The request was cancelled."))),
+        };
+        self.pending = None;
+
+        if let Some((url, pushed)) = self.pushed.take() {
+            let mut pushed = pushed.lock().unwrap_or_else(|x| x.into_inner());
+            let files = std::mem::take(&mut *pushed);
+            if !files.is_empty() {
+                audit_log.record("Push to Orthanc", files, vec![PathBuf::from(url)]);
+            }
+        }
+        match result {
+            Ok(Reply::Patients(patients)) => self.patients = patients,
+            Ok(Reply::Children(id, children)) => {
+                if let Some(node) = self.patients.iter_mut().find_map(|x| x.find_mut(&id)) {
+                    node.children = Some(children);
+                }
+            }
+            Ok(Reply::Pulled(dir)) => return Some(dir),
+            Ok(Reply::Pushed(count)) => self.status = trf("{} files pushed.", count),
+            Err(e) => {
+                log::error!("Orthanc request failed: {e}");
+                self.status = e.to_string();
+            }
+        }
+        None
+    }
+
+    /// Show a node and its children once they are listed, with the patients masked in the privacy mode. The request to
+    /// make is set when a study or a series is pulled, as `(level, id, true)`, or when a node is opened for the first
+    /// time, as `(level, id, false)`.
+    fn node_ui(
+        ui: &mut egui::Ui,
+        node: &Node,
+        is_busy: bool,
+        privacy_mode: bool,
+        request: &mut Option<(Level, String, bool)>,
+    ) {
+        // The patients are labelled by their name and ID.
+        let label = if privacy_mode && node.level == Level::Patient {
+            privacy::MASK
+        } else {
+            node.label.as_str()
+        };
+        let show_actions = |ui: &mut egui::Ui, request: &mut Option<(Level, String, bool)>| {
+            if node.level != Level::Patient
+                && ui
+                    .add_enabled(!is_busy, egui::Button::new(tr("Pull")))
+                    .on_hover_text(trf("Download into {}", pull_dir().display()))
+                    .clicked()
+            {
+                *request = Some((node.level, node.id.clone(), true));
+            }
+        };
+
+        match (node.level.children_resource(&node.id), &node.children) {
+            (None, _) => {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    show_actions(ui, request);
+                });
+            }
+            (Some(_), children) => {
+                let response =
+                    egui::CollapsingHeader::new(label)
+                        .id_salt(&node.id)
+                        .show(ui, |ui| {
+                            show_actions(ui, request);
+                            let children = children.as_deref().unwrap_or_default();
+                            if children.is_empty() && is_busy {
+                                ui.spinner();
+                            }
+                            for child in children {
+                                Self::node_ui(ui, child, is_busy, privacy_mode, request);
+                            }
+                        });
+                if response.fully_open() && children.is_none() && !is_busy && request.is_none() {
+                    *request = Some((node.level, node.id.clone(), false));
+                }
+            }
+        }
+    }
+
+    /// Show the servers, the patients of the chosen one with their studies and series, and the buttons to push the
+    /// selected files.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        servers: &[OrthancServer],
+        files: &[PathBuf],
+        tasks: &mut TaskPool,
+        privacy_mode: bool,
+    ) {
+        if servers.is_empty() {
+            ui.label(tr("Add an Orthanc server in the settings first."));
+            return;
+        }
+        self.server = self.server.min(servers.len() - 1);
+        let is_busy = self.pending.is_some();
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("orthanc server")
+                .selected_text(&servers[self.server].name)
+                .show_ui(ui, |ui| {
+                    for (index, server) in servers.iter().enumerate() {
+                        if ui
+                            .selectable_value(&mut self.server, index, &server.name)
+                            .changed()
+                        {
+                            self.patients.clear();
+                        }
+                    }
+                });
+            if ui
+                .add_enabled(!is_busy, egui::Button::new(tr("List patients")))
+                .clicked()
+            {
+                let server = servers[self.server].clone();
+                self.start(tasks, tr("List the Orthanc patients"), move |_| {
+                    list(&server, Level::Patient, "/patients?expand").map(Reply::Patients)
+                });
+            }
+            if ui
+                .add_enabled(
+                    !is_busy && !files.is_empty(),
+                    egui::Button::new(trf("Push {} files", files.len())),
+                )
+                .clicked()
+            {
+                let server = servers[self.server].clone();
+                let files = files.to_vec();
+                let pushed = Arc::new(Mutex::new(Vec::new()));
+                let url = format!("{}/instances", server.url.trim_end_matches('/'));
+                self.pushed = Some((url, pushed.clone()));
+                self.start(tasks, tr("Push to Orthanc"), move |context| {
+                    push(&server, &files, &pushed, context).map(Reply::Pushed)
+                });
+            }
+            if is_busy {
+                ui.spinner();
+            }
+        });
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        ui.separator();
+        let mut request = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for patient in &self.patients {
+                Self::node_ui(ui, patient, is_busy, privacy_mode, &mut request);
+            }
+        });
+
+        let server = servers[self.server].clone();
+        match request {
+            Some((level, id, true)) => {
+                self.start(tasks, tr("Pull from Orthanc"), move |context| {
+                    pull(&server, level, &id, context).map(Reply::Pulled)
+                });
+            }
+            Some((level, id, false)) => {
+                let Some(resource) = level.children_resource(&id) else {
+                    return;
+                };
+                // The children are listed once, even if the listing fails.
+                if let Some(node) = self.patients.iter_mut().find_map(|x| x.find_mut(&id)) {
+                    node.children = Some(Vec::new());
+                }
+                self.start(tasks, tr("List the Orthanc resources"), move |_| {
+                    list(&server, level.child(), &resource).map(|x| Reply::Children(id, x))
+                });
+            }
+            None => {}
+        }
+    }
+}
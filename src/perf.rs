@@ -0,0 +1,127 @@
+//! The performance overlay for diagnosing the slow frames and dumps in the field, toggled by a shortcut.
+//! Only the recent frames and dumps are kept, so that the overlay shows how the app behaves now.
+
+use crate::i18n::{tr, trf};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The number of the frames kept for the frame times.
+const FRAME_HISTORY: usize = 120;
+/// The number of the dumps kept for the dump times.
+const DUMP_HISTORY: usize = 20;
+
+/// The recent times of one measurement.
+#[derive(Default)]
+struct Times(VecDeque<Duration>);
+
+impl Times {
+    fn push(&mut self, time: Duration, limit: usize) {
+        if self.0.len() == limit {
+            self.0.pop_front();
+        }
+        self.0.push_back(time);
+    }
+
+    fn last(&self) -> Option<Duration> {
+        self.0.back().copied()
+    }
+
+    fn average(&self) -> Option<Duration> {
+        (!self.0.is_empty()).then(|| self.0.iter().sum::<Duration>() / self.0.len() as u32)
+    }
+
+    fn max(&self) -> Option<Duration> {
+        self.0.iter().max().copied()
+    }
+}
+
+fn format_time(time: Option<Duration>) -> String {
+    time.map_or("-".to_string(), |x| {
+        format!("{:.1} ms", x.as_secs_f64() * 1000.0)
+    })
+}
+
+/// The times of the frames and the dumps, and the hits of the dump cache.
+#[derive(Default)]
+pub struct PerfStats {
+    frame_start: Option<Instant>,
+    frame_times: Times,
+    open_times: Times,
+    dump_times: Times,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+impl PerfStats {
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Some(Instant::now());
+    }
+
+    pub fn end_frame(&mut self) {
+        if let Some(start) = self.frame_start.take() {
+            self.frame_times.push(start.elapsed(), FRAME_HISTORY);
+        }
+    }
+
+    /// Record the times to open and to dump a file.
+    pub fn record_dump(&mut self, open_time: Duration, dump_time: Duration) {
+        self.open_times.push(open_time, DUMP_HISTORY);
+        self.dump_times.push(dump_time, DUMP_HISTORY);
+    }
+
+    /// Record whether the dump of the selected file was cached.
+    pub fn record_dump_cache(&mut self, hit: bool) {
+        if hit {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    /// Show the overlay in the bottom right corner, above the panels and the windows.
+    pub fn ui(&self, ctx: &egui::Context, search_time: Option<Duration>) {
+        egui::Area::new(egui::Id::new("perf overlay"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::Grid::new("perf grid").show(ui, |ui| {
+                        ui.label("");
+                        ui.strong(tr("Last"));
+                        ui.strong(tr("Average"));
+                        ui.strong(tr("Max"));
+                        ui.end_row();
+
+                        for (label, times) in [
+                            ("Frame", &self.frame_times),
+                            ("Open", &self.open_times),
+                            ("Dump", &self.dump_times),
+                        ] {
+                            ui.label(tr(label));
+                            ui.monospace(format_time(times.last()));
+                            ui.monospace(format_time(times.average()));
+                            ui.monospace(format_time(times.max()));
+                            ui.end_row();
+                        }
+
+                        ui.label(tr("Search"));
+                        ui.monospace(format_time(search_time));
+                        ui.end_row();
+                    });
+
+                    let lookups = self.cache_hits + self.cache_misses;
+                    if lookups > 0 {
+                        ui.label(trf(
+                            "Dump cache hit rate: {}",
+                            format!(
+                                "{:.0}% ({}/{lookups})",
+                                self.cache_hits as f64 * 100.0 / lookups as f64,
+                                self.cache_hits
+                            ),
+                        ));
+                    }
+                });
+            });
+    }
+}
@@ -0,0 +1,93 @@
+//! The tags pinned to a strip above the dump, so that their values stay in view for whichever file is selected.
+//! The tags are kept in the settings by their keywords, or in the (gggg,eeee) form for the private ones.
+
+use crate::dataset::{keyword_value, tag_keyword};
+use crate::i18n::tr;
+use dicom::core::Tag;
+use dicom::object::InMemDicomObject;
+
+/// Check if the tag is pinned.
+pub fn is_pinned(pinned: &[String], tag: Tag) -> bool {
+    pinned.contains(&tag_keyword(tag))
+}
+
+/// Pin the tag, or unpin it if it is pinned.
+pub fn toggle(pinned: &mut Vec<String>, tag: Tag) {
+    let keyword = tag_keyword(tag);
+    if let Some(index) = pinned.iter().position(|x| *x == keyword) {
+        pinned.remove(index);
+    } else {
+        pinned.push(keyword);
+    }
+}
+
+/// Show the values of the pinned tags in the selected file, with the buttons to unpin them.
+pub fn ui(ui: &mut egui::Ui, pinned: &mut Vec<String>, obj: Option<&InMemDicomObject>) {
+    let mut unpinned = None;
+
+    egui::Grid::new("pinned tags grid")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for (index, keyword) in pinned.iter().enumerate() {
+                ui.strong(keyword);
+                let value = obj.map(|x| keyword_value(x, keyword)).unwrap_or_default();
+                ui.monospace(value);
+                if ui.small_button("✖").on_hover_text(tr("Unpin")).clicked() {
+                    unpinned = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+
+    if let Some(index) = unpinned {
+        pinned.remove(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_pinned, toggle};
+    use crate::dataset::{keyword_value, text_element};
+    use dicom::core::{Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+
+    #[test]
+    fn tags_are_pinned_by_their_keywords() {
+        let mut pinned = Vec::new();
+
+        toggle(&mut pinned, tags::SLICE_THICKNESS);
+        toggle(&mut pinned, tags::PIXEL_SPACING);
+        assert_eq!(pinned, ["SliceThickness", "PixelSpacing"]);
+        assert!(is_pinned(&pinned, tags::SLICE_THICKNESS));
+
+        toggle(&mut pinned, tags::SLICE_THICKNESS);
+        assert_eq!(pinned, ["PixelSpacing"]);
+        assert!(!is_pinned(&pinned, tags::SLICE_THICKNESS));
+    }
+
+    #[test]
+    fn private_tags_are_pinned_by_their_numbers() {
+        let mut pinned = Vec::new();
+
+        toggle(&mut pinned, Tag(0x0029, 0x1010));
+        assert_eq!(pinned, ["(0029,1010)"]);
+        assert!(is_pinned(&pinned, Tag(0x0029, 0x1010)));
+        assert!(!is_pinned(&pinned, Tag(0x0029, 0x1011)));
+    }
+
+    #[test]
+    fn pinned_values_missing_from_the_file_are_empty() {
+        let obj = InMemDicomObject::from_element_iter([
+            text_element(tags::SLICE_THICKNESS, VR::DS, "1.25"),
+            text_element(Tag(0x0029, 0x1010), VR::LO, "VENDOR"),
+        ]);
+        let mut pinned = Vec::new();
+        toggle(&mut pinned, tags::SLICE_THICKNESS);
+        toggle(&mut pinned, Tag(0x0029, 0x1010));
+        toggle(&mut pinned, tags::PIXEL_SPACING);
+
+        let values: Vec<_> = pinned.iter().map(|x| keyword_value(&obj, x)).collect();
+        assert_eq!(values, ["1.25", "VENDOR", ""]);
+    }
+}
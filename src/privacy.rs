@@ -0,0 +1,196 @@
+//! The privacy mode, which masks the identifying values on screen with ••• so that real data can be demoed or shared
+//! on screen. Only the display is masked: the files, the cached dumps, the searches and the exports are untouched.
+//! The values of the identifying elements of the selected file are also masked wherever else they appear in the dump,
+//! e.g. in the nested items and in the JSON format, whose lines have no top-level tag.
+
+use crate::dataset::{
+    dump_line_tag, dump_line_vr, primitive_to_string, sequence, text_element, visit_elements,
+};
+use dicom::core::value::Value;
+use dicom::core::{DataDictionary, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags};
+use dicom::object::InMemDicomObject;
+use std::borrow::Cow;
+
+/// The text shown in place of a masked value.
+pub const MASK: &str = "•••";
+
+/// The Other Patient IDs.
+const OTHER_PATIENT_IDS: Tag = Tag(0x0010, 0x1000);
+
+/// The elements identifying the patient, besides the person names which are all masked.
+const PHI_TAGS: [Tag; 16] = [
+    tags::PATIENT_ID,
+    tags::ISSUER_OF_PATIENT_ID,
+    OTHER_PATIENT_IDS,
+    tags::PATIENT_BIRTH_DATE,
+    tags::PATIENT_BIRTH_TIME,
+    tags::PATIENT_ADDRESS,
+    tags::PATIENT_TELEPHONE_NUMBERS,
+    tags::MEDICAL_RECORD_LOCATOR,
+    tags::MILITARY_RANK,
+    tags::PATIENT_COMMENTS,
+    tags::ADDITIONAL_PATIENT_HISTORY,
+    tags::ACCESSION_NUMBER,
+    tags::STUDY_ID,
+    tags::INSTITUTION_NAME,
+    tags::INSTITUTION_ADDRESS,
+    tags::DEVICE_SERIAL_NUMBER,
+];
+
+/// The masked values shorter than this are not searched in the other lines, as they would match too much.
+const MIN_VALUE_LENGTH: usize = 3;
+
+/// Check if the element identifies the patient, or is the name of a person.
+pub fn is_phi(tag: Tag, vr: VR) -> bool {
+    vr == VR::PN || PHI_TAGS.contains(&tag)
+}
+
+/// Check if the tag identifies the patient, or is the name of a person by its value representation in the dictionary,
+/// for the values shown without their element, e.g. in the reports across the files.
+pub fn is_phi_tag(tag: Tag) -> bool {
+    let vr = StandardDataDictionary
+        .by_tag(tag)
+        .map_or(VR::UN, |x| x.vr.relaxed());
+    is_phi(tag, vr)
+}
+
+/// Get the dataset with the values of the identifying elements masked, including those in the sequence items.
+fn mask_object(obj: &InMemDicomObject) -> InMemDicomObject {
+    let mut masked = InMemDicomObject::new_empty();
+    for elem in obj {
+        let tag = elem.header().tag;
+        match elem.value() {
+            Value::Primitive(_) if is_phi(tag, elem.vr()) => {
+                masked.put(text_element(tag, elem.vr(), MASK));
+            }
+            Value::Sequence(seq) => {
+                let items: Vec<InMemDicomObject> = seq.items().iter().map(mask_object).collect();
+                masked.put(sequence(tag, items));
+            }
+            _ => masked.put(elem.clone()),
+        }
+    }
+    masked
+}
+
+/// The masked copy of the selected dataset, and its identifying values to mask in the dump.
+pub struct PrivacyMask {
+    obj: InMemDicomObject,
+    /// The values, longest first so that a value is masked before the values inside it.
+    values: Vec<String>,
+}
+
+impl PrivacyMask {
+    pub fn new(obj: &InMemDicomObject) -> Self {
+        let mut values = Vec::new();
+        visit_elements(obj, &mut |_, elem| {
+            if let Value::Primitive(value) = elem.value()
+                && is_phi(elem.header().tag, elem.vr())
+            {
+                let value = primitive_to_string(elem.vr(), value);
+                // The multiple values are masked one by one too, as the dumps may list them apart.
+                for part in value.split('\\').chain(std::iter::once(value.as_str())) {
+                    let part = part.trim();
+                    if part.chars().count() >= MIN_VALUE_LENGTH {
+                        values.push(part.to_string());
+                    }
+                }
+            }
+        });
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        values.dedup();
+
+        Self {
+            obj: mask_object(obj),
+            values,
+        }
+    }
+
+    /// Get the dataset with the identifying values masked, for the summaries.
+    pub fn object(&self) -> &InMemDicomObject {
+        &self.obj
+    }
+
+    /// Mask the line of the dump. The value of a top-level identifying element is masked whole, since a long value is
+    /// cut in the dump and wouldn't match the complete one. The value is in brackets in the text format, and after
+    /// `=` in the brief one.
+    pub fn mask_line<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if let Some(tag) = dump_line_tag(line)
+            && is_phi(tag, dump_line_vr(line).unwrap_or(VR::UN))
+        {
+            if let Some(start) = line.find('[') {
+                let end = line.rfind(']').filter(|x| *x > start).unwrap_or(line.len());
+                return Cow::Owned(format!("{}{MASK}{}", &line[..=start], &line[end..]));
+            }
+            if let Some(start) = line.find(" = ") {
+                return Cow::Owned(format!("{} {MASK}", &line[..start + 2]));
+            }
+        }
+
+        let mut line = Cow::Borrowed(line);
+        for value in &self.values {
+            if line.contains(value.as_str()) {
+                line = Cow::Owned(line.replace(value.as_str(), MASK));
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MASK, PrivacyMask, is_phi_tag};
+    use crate::dataset::text_element;
+    use dicom::core::{Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+
+    fn mask() -> PrivacyMask {
+        PrivacyMask::new(&InMemDicomObject::from_element_iter([
+            text_element(tags::PATIENT_ID, VR::LO, "AB"),
+            text_element(tags::PATIENT_NAME, VR::PN, "Doe^Johnathan^Alexander"),
+            text_element(tags::MODALITY, VR::CS, "CT"),
+        ]))
+    }
+
+    #[test]
+    fn short_top_level_values_are_masked() {
+        assert_eq!(
+            mask().mask_line("(0010,0020) LO Patient ID                   [AB]"),
+            format!("(0010,0020) LO Patient ID                   [{MASK}]")
+        );
+    }
+
+    #[test]
+    fn truncated_top_level_values_are_masked() {
+        assert_eq!(
+            mask().mask_line("(0010,0010) PN Patient's Name               [Doe^Johnathan^Al..."),
+            format!("(0010,0010) PN Patient's Name               [{MASK}")
+        );
+        assert_eq!(
+            mask().mask_line("(0010,0010) PN PatientName = Doe^Johnathan^Al..."),
+            format!("(0010,0010) PN PatientName = {MASK}")
+        );
+    }
+
+    #[test]
+    fn values_are_masked_in_the_other_lines() {
+        assert_eq!(
+            mask().mask_line("    \"Value\": [ { \"Alphabetic\": \"Doe^Johnathan^Alexander\" } ]"),
+            format!("    \"Value\": [ {{ \"Alphabetic\": \"{MASK}\" }} ]")
+        );
+        assert_eq!(
+            mask().mask_line("(0008,0060) CS Modality                     [CT]"),
+            "(0008,0060) CS Modality                     [CT]"
+        );
+    }
+
+    #[test]
+    fn tags_are_checked_by_their_dictionary_vr() {
+        assert!(is_phi_tag(tags::REFERRING_PHYSICIAN_NAME));
+        assert!(is_phi_tag(tags::ACCESSION_NUMBER));
+        assert!(!is_phi_tag(tags::MODALITY));
+        assert!(!is_phi_tag(Tag(0x0009, 0x1001)));
+    }
+}
@@ -0,0 +1,227 @@
+//! The quick view, a panel of chosen tags with their values in the selected file, like a mini report.
+//! The tags are dragged from the dump onto the panel, and kept in named layouts, each for a modality or for any, so
+//! that the layout of the modality of the selected file is shown unless the user chose another.
+
+use crate::dataset::{element_string, keyword_value, tag_keyword};
+use crate::i18n::{tr, trf};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use serde::{Deserialize, Serialize};
+
+/// A named list of tags shown in the quick view.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickViewLayout {
+    pub name: String,
+    /// The modality the layout is for, or empty for any.
+    pub modality: String,
+    /// The keywords of the tags, or the (gggg,eeee) form for the private ones.
+    pub tags: Vec<String>,
+}
+
+impl QuickViewLayout {
+    /// Add the tag dropped from the dump, unless it is already shown.
+    fn add_tag(&mut self, tag: Tag) {
+        let keyword = tag_keyword(tag);
+        if !self.tags.contains(&keyword) {
+            self.tags.push(keyword);
+        }
+    }
+}
+
+/// Get the layouts of a new installation, a general one for any modality.
+pub fn default_layouts() -> Vec<QuickViewLayout> {
+    vec![QuickViewLayout {
+        name: "General".to_string(),
+        modality: String::new(),
+        tags: [
+            "PatientName",
+            "PatientID",
+            "StudyDate",
+            "Modality",
+            "StudyDescription",
+            "SeriesDescription",
+            "InstanceNumber",
+        ]
+        .map(str::to_string)
+        .to_vec(),
+    }]
+}
+
+/// The state of the quick view panel.
+#[derive(Default)]
+pub struct QuickView {
+    /// The name of the layout chosen by the user, or none to show the one for the modality.
+    chosen: Option<String>,
+    new_name: String,
+}
+
+impl QuickView {
+    /// Get the layout to show: the chosen one, or else the first for the modality, or else the first for any.
+    fn active_layout(&self, layouts: &[QuickViewLayout], modality: &str) -> Option<usize> {
+        if let Some(chosen) = self.chosen.as_ref() {
+            return layouts.iter().position(|x| x.name == *chosen);
+        }
+        layouts
+            .iter()
+            .position(|x| x.modality.eq_ignore_ascii_case(modality))
+            .or_else(|| layouts.iter().position(|x| x.modality.is_empty()))
+    }
+
+    /// Show the values of the tags of the layout in the selected file, with the controls to manage the layouts.
+    /// The tags dropped from the dump are added to the layout shown.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        layouts: &mut Vec<QuickViewLayout>,
+        obj: Option<&InMemDicomObject>,
+    ) {
+        let modality = obj
+            .and_then(|x| element_string(x, tags::MODALITY))
+            .unwrap_or_default();
+        let active = self.active_layout(layouts, &modality);
+
+        let mut deleted = false;
+        ui.horizontal(|ui| {
+            ui.label(tr("Layout"));
+            let selected_text = active.map_or(tr("None"), |x| layouts[x].name.clone());
+            egui::ComboBox::from_id_salt("quick view layout")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(self.chosen.is_none(), tr("By modality"))
+                        .clicked()
+                    {
+                        self.chosen = None;
+                    }
+                    for layout in layouts.iter() {
+                        let is_chosen = self.chosen.as_ref() == Some(&layout.name);
+                        if ui.selectable_label(is_chosen, &layout.name).clicked() {
+                            self.chosen = Some(layout.name.clone());
+                        }
+                    }
+                });
+            deleted = active.is_some() && ui.button(tr("Delete")).clicked();
+        });
+        if let Some(index) = active.filter(|_| deleted) {
+            layouts.remove(index);
+            self.chosen = None;
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_name)
+                    .hint_text(tr("New layout"))
+                    .desired_width(160.0),
+            );
+            let name = self.new_name.trim().to_string();
+            let can_add = !name.is_empty() && layouts.iter().all(|x| x.name != name);
+            if ui
+                .add_enabled(can_add, egui::Button::new(tr("Add")))
+                .on_hover_text(trf("For the modality {}", &modality))
+                .clicked()
+            {
+                layouts.push(QuickViewLayout {
+                    name: name.clone(),
+                    modality: modality.clone(),
+                    tags: Vec::new(),
+                });
+                self.chosen = Some(name);
+                self.new_name.clear();
+            }
+        });
+
+        let Some(index) = self.active_layout(layouts, &modality) else {
+            ui.label(tr("Add a layout to choose the tags to show."));
+            return;
+        };
+        let layout = &mut layouts[index];
+        ui.horizontal(|ui| {
+            ui.label(tr("Modality"));
+            ui.add(egui::TextEdit::singleline(&mut layout.modality).desired_width(80.0))
+                .on_hover_text(tr("Leave empty for any modality"));
+        });
+        ui.separator();
+
+        let mut removed = None;
+        let frame = egui::Frame::group(ui.style());
+        let (_, dropped) = ui.dnd_drop_zone::<Tag, ()>(frame, |ui| {
+            ui.set_min_width(ui.available_width());
+            if layout.tags.is_empty() {
+                ui.weak(tr("Drag the tags from the dump here."));
+                return;
+            }
+            egui::Grid::new("quick view grid")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (index, keyword) in layout.tags.iter().enumerate() {
+                        ui.strong(keyword);
+                        let value = obj.map(|x| keyword_value(x, keyword)).unwrap_or_default();
+                        ui.monospace(value);
+                        if ui.small_button("✖").on_hover_text(tr("Remove")).clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if let Some(index) = removed {
+            layout.tags.remove(index);
+        }
+        if let Some(tag) = dropped {
+            layout.add_tag(*tag);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuickView, QuickViewLayout, default_layouts};
+    use crate::dataset::{keyword_value, text_element};
+    use dicom::core::{Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+
+    #[test]
+    fn a_dropped_tag_is_added_once() {
+        let mut layout = QuickViewLayout::default();
+
+        layout.add_tag(tags::SLICE_THICKNESS);
+        layout.add_tag(Tag(0x0029, 0x1010));
+        layout.add_tag(tags::SLICE_THICKNESS);
+        layout.add_tag(Tag(0x0029, 0x1010));
+        assert_eq!(layout.tags, ["SliceThickness", "(0029,1010)"]);
+    }
+
+    #[test]
+    fn values_missing_from_the_file_are_shown_empty() {
+        let obj = InMemDicomObject::from_element_iter([text_element(
+            Tag(0x0029, 0x1010),
+            VR::LO,
+            "VENDOR",
+        )]);
+        let mut layout = QuickViewLayout::default();
+        layout.add_tag(Tag(0x0029, 0x1010));
+        layout.add_tag(tags::SLICE_THICKNESS);
+
+        let values: Vec<_> = layout.tags.iter().map(|x| keyword_value(&obj, x)).collect();
+        assert_eq!(values, ["VENDOR", ""]);
+    }
+
+    #[test]
+    fn the_layout_of_the_modality_is_shown() {
+        let mut layouts = default_layouts();
+        layouts.push(QuickViewLayout {
+            name: "CT".to_string(),
+            modality: "CT".to_string(),
+            tags: Vec::new(),
+        });
+        let quick_view = QuickView::default();
+
+        assert_eq!(quick_view.active_layout(&layouts, "ct"), Some(1));
+        assert_eq!(quick_view.active_layout(&layouts, "MR"), Some(0));
+    }
+}
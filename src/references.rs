@@ -0,0 +1,98 @@
+//! The instances referenced by a dataset, e.g. the source images of a presentation state, a structured report or a
+//! segmentation.
+
+use crate::dataset::visit_elements;
+use crate::i18n::{tr, trf};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// The elements with the UID of a referenced instance.
+const REFERENCE_TAGS: [Tag; 2] = [
+    tags::REFERENCED_SOP_INSTANCE_UID,
+    tags::REFERENCED_SOP_INSTANCE_UID_IN_FILE,
+];
+
+/// A referenced instance, with the path of the first element referencing it.
+pub struct Reference {
+    pub path: String,
+    pub uid: String,
+}
+
+/// Collect the instances referenced anywhere in the dataset, in order and without duplicates.
+pub fn collect(obj: &InMemDicomObject) -> Vec<Reference> {
+    let mut references: Vec<Reference> = Vec::new();
+
+    visit_elements(obj, &mut |path, elem| {
+        let tag = elem.header().tag;
+        if !REFERENCE_TAGS.contains(&tag) {
+            return;
+        }
+        let Ok(value) = elem.to_str() else {
+            return;
+        };
+        let uid = value.trim_end_matches(['\0', ' ']);
+        if !uid.is_empty() && !references.iter().any(|x| x.uid == uid) {
+            references.push(Reference {
+                path: path.to_string(),
+                uid: uid.to_string(),
+            });
+        }
+    });
+
+    references
+}
+
+/// Show the references, with links to the instances found in the folder. Returns the file clicked.
+pub fn ui(
+    ui: &mut egui::Ui,
+    references: &[Reference],
+    instance_files: &HashMap<String, PathBuf>,
+) -> Option<PathBuf> {
+    let mut clicked = None;
+
+    if references.is_empty() {
+        ui.label(tr("No referenced instances."));
+        return None;
+    }
+    let found = references
+        .iter()
+        .filter(|x| instance_files.contains_key(&x.uid))
+        .count();
+    ui.label(format!(
+        "{}, {}",
+        trf("{} referenced instances", references.len()),
+        trf("{} in the folder", found)
+    ));
+    ui.separator();
+
+    egui::ScrollArea::both().show(ui, |ui| {
+        egui::Grid::new("references grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for reference in references {
+                    match instance_files.get(&reference.uid) {
+                        Some(file) => {
+                            if ui
+                                .link(&reference.uid)
+                                .on_hover_text(file.display().to_string())
+                                .clicked()
+                            {
+                                clicked = Some(file.clone());
+                            }
+                        }
+                        None => {
+                            ui.weak(&reference.uid)
+                                .on_hover_text(tr("Not in the opened folder"));
+                        }
+                    }
+                    ui.monospace(&reference.path);
+                    ui.end_row();
+                }
+            });
+    });
+
+    clicked
+}
@@ -0,0 +1,388 @@
+//! Browsing the folders of the imaging servers over SFTP, and opening single files over SMB, by copying the files
+//! selected into a local cache folder. A folder is listed when it is expanded, and a file is copied when it is
+//! selected, so that a large archive isn't copied up front. curl lists the SFTP folders but not the SMB shares, so
+//! only a file can be opened over SMB.
+//! The keys of the SFTP servers are checked against `~/.ssh/known_hosts` by curl, which logs in with the keys in
+//! `~/.ssh`, or with the password in the `REMOTE_PASSWORD` environment variable if a user is given.
+
+use crate::download::{self, Credentials};
+use crate::i18n::tr;
+use crate::tasks::{Task, TaskPool};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+
+/// Check if the location is a URL which can be opened, e.g. `sftp://pacs/archive/`.
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with("sftp://") || location.starts_with("smb://")
+}
+
+/// Get the folder in the temp dir to copy the files of the remote location into, named after its last component and
+/// keyed by the URL and the user, so that selecting a file again only fetches it if it changed since.
+fn cache_dir(url: &str, user: &str) -> PathBuf {
+    let name: String = url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.'))
+        .collect();
+
+    let mut hasher = DefaultHasher::new();
+    (url, user).hash(&mut hasher);
+    std::env::temp_dir()
+        .join("rsdicombrowser")
+        .join("remote")
+        .join(format!("{name}-{:016x}", hasher.finish()))
+}
+
+/// A file or a subfolder in the listing of an SFTP folder.
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+}
+
+/// Parse a line of the listing, in the format of `ls -l`: the permissions, the links, the owner, the group, the size,
+/// the date in 3 fields and the name. The links and the special files are skipped, as are the names which aren't
+/// safe to write under the cache folder.
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut rest = line.trim_end_matches('\r');
+    let mut fields = Vec::with_capacity(8);
+    for _ in 0..8 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    let name = rest.trim_start();
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', ':']) {
+        return None;
+    }
+
+    let is_dir = match fields[0].chars().next()? {
+        'd' => true,
+        '-' => false,
+        _ => return None,
+    };
+    Some(Entry {
+        name: name.to_string(),
+        is_dir,
+        size: fields[4].parse().ok()?,
+    })
+}
+
+/// Get the URL of the file or the subfolder under the remote folder, at its path relative to it.
+fn entry_url(url: &str, relative: &[String], is_dir: bool) -> String {
+    let mut entry_url = url.to_string();
+    entry_url.push_str(
+        &relative
+            .iter()
+            .map(|x| download::encode(x))
+            .collect::<Vec<_>>()
+            .join("/"),
+    );
+    if is_dir && !relative.is_empty() {
+        entry_url.push('/');
+    }
+    entry_url
+}
+
+/// A file or a subfolder of the remote folder, with the entries of the subfolder once it is listed.
+struct Node {
+    entry: Entry,
+    /// The path relative to the remote folder.
+    path: Vec<String>,
+    children: Option<Vec<Node>>,
+}
+
+impl Node {
+    fn find_mut(&mut self, path: &[String]) -> Option<&mut Node> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children
+            .as_mut()?
+            .iter_mut()
+            .find_map(|x| x.find_mut(path))
+    }
+}
+
+/// List the files and the subfolders of the subfolder of the SFTP folder, the subfolders first.
+fn list(url: &str, credentials: Option<&Credentials>, relative: &[String]) -> Result<Vec<Node>> {
+    let listing = download::fetch_as(&entry_url(url, relative, true), &[], credentials)?;
+    let mut nodes: Vec<Node> = String::from_utf8_lossy(&listing)
+        .lines()
+        .filter_map(parse_entry)
+        .map(|entry| {
+            let mut path = relative.to_vec();
+            path.push(entry.name.clone());
+            Node {
+                entry,
+                path,
+                children: None,
+            }
+        })
+        .collect();
+    nodes.sort_by(|a, b| {
+        b.entry
+            .is_dir
+            .cmp(&a.entry.is_dir)
+            .then_with(|| a.entry.name.cmp(&b.entry.name))
+    });
+    Ok(nodes)
+}
+
+/// Download the file to the path, through a partial file so that an interrupted download is not taken as done.
+/// The copy made before is kept if it has the size of the file.
+fn fetch_file(
+    url: &str,
+    credentials: Option<&Credentials>,
+    path: &Path,
+    size: Option<u64>,
+) -> Result<()> {
+    if size.is_some_and(|size| std::fs::metadata(path).is_ok_and(|x| x.len() == size)) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    download::fetch_to_file_as(url, &[], credentials, &partial)?;
+    std::fs::rename(&partial, path)
+}
+
+/// Copy the remote file into its cache folder. Returns the cache folder and the copy.
+fn fetch_single(url: &str, credentials: Option<&Credentials>) -> Result<Reply> {
+    let user = credentials.map_or("", |x| x.user.as_str());
+    let dir = cache_dir(url, user);
+    let name: String = url
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|x| {
+            if x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.') {
+                x
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = match name.trim_start_matches('.') {
+        "" => "file",
+        name => name,
+    };
+    let path = dir.join(name);
+    fetch_file(url, credentials, &path, None)?;
+    Ok(Reply::Fetched(dir, path))
+}
+
+/// Copy the file of the remote folder into the cache folder, at its relative path. Returns the cache folder and the
+/// copy.
+fn fetch_entry(
+    url: &str,
+    credentials: Option<&Credentials>,
+    relative: &[String],
+    size: u64,
+) -> Result<Reply> {
+    let user = credentials.map_or("", |x| x.user.as_str());
+    let dir = cache_dir(url, user);
+    let path: PathBuf = relative.iter().fold(dir.clone(), |x, name| x.join(name));
+    fetch_file(
+        &entry_url(url, relative, false),
+        credentials,
+        &path,
+        Some(size),
+    )?;
+    Ok(Reply::Fetched(dir, path))
+}
+
+/// The result of a request to the server.
+enum Reply {
+    /// The entries of the subfolder at the path, or of the remote folder if the path is empty.
+    Listed(Vec<String>, Vec<Node>),
+    /// The cache folder, and the file copied into it.
+    Fetched(PathBuf, PathBuf),
+}
+
+/// The form to open a remote folder, with its entries listed so far and the request running in the background.
+#[derive(Default)]
+pub struct RemoteFolder {
+    url: String,
+    user: String,
+    /// The URL and the credentials of the folder being browsed.
+    opened: Option<(String, Option<Credentials>)>,
+    nodes: Vec<Node>,
+    pending: Option<Task<Result<Reply>>>,
+    status: String,
+}
+
+impl RemoteFolder {
+    fn start(
+        &mut self,
+        tasks: &mut TaskPool,
+        name: String,
+        request: impl FnOnce() -> Result<Reply> + Send + 'static,
+    ) {
+        if self.pending.is_none() {
+            self.pending = Some(tasks.spawn(name, move |_| request()));
+            self.status.clear();
+        }
+    }
+
+    /// Take the result of the request if it is done. Returns the cache folder to be opened, and the file copied into
+    /// it to be selected.
+    pub fn poll(&mut self) -> Option<(PathBuf, PathBuf)> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other(tr("The copy was cancelled."))),
+        };
+        self.pending = None;
+
+        match result {
+            Ok(Reply::Listed(path, nodes)) if path.is_empty() => self.nodes = nodes,
+            Ok(Reply::Listed(path, nodes)) => {
+                if let Some(node) = self.nodes.iter_mut().find_map(|x| x.find_mut(&path)) {
+                    node.children = Some(nodes);
+                }
+            }
+            Ok(Reply::Fetched(dir, file)) => return Some((dir, file)),
+            Err(e) => {
+                log::error!("Failed to open {}: {e}", self.url);
+                self.status = e.to_string();
+            }
+        }
+        None
+    }
+
+    /// Show a file, or a subfolder with its entries once they are listed. The subfolder to list, or the file to copy,
+    /// is set as `(path, size)` when it is opened for the first time, or when it is clicked.
+    fn node_ui(
+        ui: &mut egui::Ui,
+        node: &Node,
+        is_busy: bool,
+        request: &mut Option<(Vec<String>, Option<u64>)>,
+    ) {
+        if !node.entry.is_dir {
+            if ui
+                .add_enabled(
+                    !is_busy,
+                    egui::Button::new(node.entry.name.as_str()).frame(false),
+                )
+                .on_hover_text(tr("Copy the file into the cache folder, and open it"))
+                .clicked()
+            {
+                *request = Some((node.path.clone(), Some(node.entry.size)));
+            }
+            return;
+        }
+
+        let response = egui::CollapsingHeader::new(&node.entry.name)
+            .id_salt(&node.path)
+            .show(ui, |ui| {
+                let children = node.children.as_deref().unwrap_or_default();
+                if children.is_empty() && is_busy {
+                    ui.spinner();
+                }
+                for child in children {
+                    Self::node_ui(ui, child, is_busy, request);
+                }
+            });
+        if response.fully_open() && node.children.is_none() && !is_busy && request.is_none() {
+            *request = Some((node.path.clone(), None));
+        }
+    }
+
+    /// Show the URL and the user to open, the button to open them, and the entries of the folder opened.
+    pub fn ui(&mut self, ui: &mut egui::Ui, tasks: &mut TaskPool) {
+        egui::Grid::new("remote folder grid").show(ui, |ui| {
+            ui.label(tr("URL"));
+            ui.add(
+                egui::TextEdit::singleline(&mut self.url)
+                    .hint_text("sftp://host/folder/")
+                    .desired_width(320.0),
+            );
+            ui.end_row();
+
+            ui.label(tr("User"));
+            ui.text_edit_singleline(&mut self.user)
+                .on_hover_text(tr("The password is read from REMOTE_PASSWORD."));
+            ui.end_row();
+        });
+
+        let url = self.url.trim().to_string();
+        let is_busy = self.pending.is_some();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(is_remote(&url) && !is_busy, egui::Button::new(tr("Open")))
+                .on_hover_text(tr(
+                    "List the folder, or copy the file into a cache folder and open it",
+                ))
+                .clicked()
+            {
+                let credentials = (!self.user.trim().is_empty()).then(|| Credentials {
+                    user: self.user.trim().to_string(),
+                    password: std::env::var("REMOTE_PASSWORD").unwrap_or_default(),
+                    ..Default::default()
+                });
+                self.opened = None;
+                self.nodes.clear();
+                if !url.ends_with('/') {
+                    self.start(tasks, tr("Copy the remote file"), move || {
+                        fetch_single(&url, credentials.as_ref())
+                    });
+                } else if url.starts_with("smb://") {
+                    self.status = tr("The SMB folders can't be listed, enter the URL of a file.");
+                } else {
+                    self.opened = Some((url.clone(), credentials.clone()));
+                    self.start(tasks, tr("List the remote folder"), move || {
+                        list(&url, credentials.as_ref(), &[]).map(|x| Reply::Listed(Vec::new(), x))
+                    });
+                }
+            }
+            if is_busy {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.colored_label(ui.visuals().error_fg_color, &self.status);
+        }
+
+        let Some((url, credentials)) = self.opened.clone() else {
+            return;
+        };
+        ui.separator();
+        let mut request = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for node in &self.nodes {
+                Self::node_ui(ui, node, is_busy, &mut request);
+            }
+        });
+
+        match request {
+            Some((path, Some(size))) => {
+                self.start(tasks, tr("Copy the remote file"), move || {
+                    fetch_entry(&url, credentials.as_ref(), &path, size)
+                });
+            }
+            Some((path, None)) => {
+                // The entries are listed once, even if the listing fails.
+                if let Some(node) = self.nodes.iter_mut().find_map(|x| x.find_mut(&path)) {
+                    node.children = Some(Vec::new());
+                }
+                self.start(tasks, tr("List the remote folder"), move || {
+                    list(&url, credentials.as_ref(), &path).map(|x| Reply::Listed(path, x))
+                });
+            }
+            None => {}
+        }
+    }
+}
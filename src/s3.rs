@@ -0,0 +1,454 @@
+//! Browsing the S3-compatible buckets, e.g. of AWS, MinIO or Ceph: listing the objects under a prefix, checking if the
+//! objects shown are DICOM files by getting only their first bytes, and opening the selected objects by copying them
+//! into a cache folder.
+//! The requests are signed by curl with the AWS Signature Version 4 (curl 7.75 or later). The keys are read from the
+//! `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` environment variables, with `AWS_SESSION_TOKEN` if set, and the
+//! public buckets are read without them.
+
+use crate::download::{self, Credentials};
+use crate::i18n::{tr, trf};
+use crate::tasks::{Task, TaskContext, TaskPool};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, Result};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+
+/// The number of objects listed, at most.
+const MAX_OBJECTS: usize = 10_000;
+/// The number of objects checked by a request in the background.
+const SNIFF_BATCH: usize = 32;
+/// The length of the preamble of a DICOM file with its `DICM` prefix.
+const PREAMBLE_LEN: usize = 132;
+
+/// Unescape the text of an XML element.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Get the texts of the elements with the name in the XML. The listings of S3 don't nest the elements of the same
+/// name, so the elements are found by their tags.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    let mut texts = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        texts.push(&rest[..end]);
+        rest = &rest[end + close.len()..];
+    }
+    texts
+}
+
+/// Get the path of the object under the folder, keeping only the normal components of its key so that nothing is
+/// written outside.
+fn object_path(dir: &Path, key: &str) -> Option<PathBuf> {
+    let components: Vec<_> = key
+        .split('/')
+        .filter(|x| !x.contains(['\\', ':']))
+        .filter_map(|x| match Path::new(x).components().next() {
+            Some(Component::Normal(x)) => Some(x),
+            _ => None,
+        })
+        .collect();
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.iter().fold(dir.to_path_buf(), |x, y| x.join(y)))
+}
+
+/// The bucket and the server it is on.
+#[derive(Clone)]
+struct Bucket {
+    endpoint: String,
+    region: String,
+    name: String,
+}
+
+impl Bucket {
+    /// Get the keys in the environment, if any, with the session token as a header.
+    fn credentials() -> Option<Credentials> {
+        let user = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default();
+        (!user.is_empty()).then(|| Credentials {
+            user,
+            password: std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+            headers: std::env::var("AWS_SESSION_TOKEN")
+                .ok()
+                .filter(|x| !x.is_empty())
+                .map(|x| format!("x-amz-security-token: {x}"))
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    /// Get the URL of the object, or of the bucket if the key is empty, with the query. The URLs are in the path
+    /// style, which the servers other than AWS support too.
+    fn url(&self, key: &str, query: &str) -> String {
+        let key: Vec<String> = key.split('/').map(download::encode).collect();
+        let mut url = format!(
+            "{}/{}/{}",
+            self.endpoint.trim_end_matches('/'),
+            download::encode(&self.name),
+            key.join("/")
+        );
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(query);
+        }
+        url
+    }
+
+    /// Get the options of `curl` to sign the requests, if there are keys.
+    fn options(credentials: Option<&Credentials>, region: &str) -> Vec<String> {
+        let mut options = Vec::new();
+        if credentials.is_some() {
+            options.push("--aws-sigv4".to_string());
+            options.push(format!("aws:amz:{region}:s3"));
+        }
+        options
+    }
+
+    /// Get the object, or the bucket if the key is empty, with the query and the extra options of `curl`, into the
+    /// file if any. Returns the body otherwise.
+    fn get(&self, key: &str, query: &str, extra: &[&str], path: Option<&Path>) -> Result<Vec<u8>> {
+        let credentials = Self::credentials();
+        let options = Self::options(credentials.as_ref(), &self.region);
+        let mut options: Vec<&str> = options.iter().map(String::as_str).collect();
+        options.extend(extra);
+
+        let url = self.url(key, query);
+        match path {
+            Some(path) => {
+                download::fetch_to_file_as(&url, &options, credentials.as_ref(), path)?;
+                Ok(Vec::new())
+            }
+            None => download::fetch_as(&url, &options, credentials.as_ref()),
+        }
+    }
+
+    /// List the objects under the prefix, at most `MAX_OBJECTS`, a page of the listing at a time.
+    fn list(&self, prefix: &str, context: &TaskContext) -> Result<Vec<Object>> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            if context.is_cancelled() {
+                return Err(Error::other("the listing was cancelled"));
+            }
+            let mut query = format!("list-type=2&prefix={}", download::encode(prefix));
+            if let Some(token) = &token {
+                query.push_str(&format!("&continuation-token={}", download::encode(token)));
+            }
+
+            let body = self.get("", &query, &[], None)?;
+            let xml = String::from_utf8_lossy(&body);
+            for contents in elements(&xml, "Contents") {
+                let key = elements(contents, "Key").first().map(|x| unescape(x));
+                let size = elements(contents, "Size")
+                    .first()
+                    .and_then(|x| x.trim().parse().ok());
+                if let (Some(key), Some(size)) = (key, size)
+                    && !key.ends_with('/')
+                {
+                    objects.push(Object {
+                        key,
+                        size,
+                        is_dicom: None,
+                        selected: false,
+                    });
+                }
+            }
+
+            token = elements(&xml, "NextContinuationToken")
+                .first()
+                .map(|x| unescape(x));
+            if token.is_none() || objects.len() >= MAX_OBJECTS {
+                objects.truncate(MAX_OBJECTS);
+                return Ok(objects);
+            }
+        }
+    }
+
+    /// Check if the object is a DICOM file, from its preamble.
+    fn sniff(&self, key: &str) -> Result<bool> {
+        let range = format!("0-{}", PREAMBLE_LEN - 1);
+        let bytes = self.get(key, "", &["--range", range.as_str()], None)?;
+        Ok(bytes.len() >= PREAMBLE_LEN && &bytes[128..PREAMBLE_LEN] == b"DICM")
+    }
+
+    /// Get the folder in the temp dir to copy the objects of the bucket into, keyed by the server and the bucket.
+    fn cache_dir(&self) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        (&self.endpoint, &self.name).hash(&mut hasher);
+        let name: String = self
+            .name
+            .chars()
+            .filter(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.'))
+            .collect();
+        std::env::temp_dir()
+            .join("rsdicombrowser")
+            .join("s3")
+            .join(format!("{name}-{:016x}", hasher.finish()))
+    }
+
+    /// Copy the objects into the cache folder, at the paths of their keys, skipping the objects of the same size as
+    /// the copies made before. Returns the cache folder.
+    fn copy(&self, objects: &[(String, u64)], context: &TaskContext) -> Result<PathBuf> {
+        let dir = self.cache_dir();
+        for (index, (key, size)) in objects.iter().enumerate() {
+            if context.is_cancelled() {
+                return Err(Error::other("the download was cancelled"));
+            }
+            context.set_progress(index as f32 / objects.len() as f32);
+
+            let Some(path) = object_path(&dir, key) else {
+                log::error!("Skipped the object {key}, which can't be named as a file");
+                continue;
+            };
+            if std::fs::metadata(&path).is_ok_and(|x| x.len() == *size) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Write to a partial file first, so that an interrupted download is not taken as done.
+            let mut partial = path.as_os_str().to_owned();
+            partial.push(".part");
+            let partial = PathBuf::from(partial);
+            self.get(key, "", &[], Some(&partial))?;
+            std::fs::rename(&partial, &path)?;
+        }
+        Ok(dir)
+    }
+}
+
+/// An object listed in the bucket.
+struct Object {
+    key: String,
+    size: u64,
+    /// Whether the object is a DICOM file, once it is checked.
+    is_dicom: Option<bool>,
+    selected: bool,
+}
+
+/// The result of a request to the server.
+enum Reply {
+    Listed(Vec<Object>),
+    /// The folder the selected objects were copied into.
+    Copied(PathBuf),
+}
+
+/// The browser of a bucket, with the request running in the background, and the objects being checked.
+pub struct BucketBrowser {
+    endpoint: String,
+    region: String,
+    bucket: String,
+    prefix: String,
+    objects: Vec<Object>,
+    pending: Option<Task<Result<Reply>>>,
+    /// The objects checked in the background, by their index and their key.
+    sniffing: Option<Task<Vec<(usize, String, bool)>>>,
+    status: String,
+}
+
+impl Default for BucketBrowser {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            prefix: String::new(),
+            objects: Vec::new(),
+            pending: None,
+            sniffing: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl BucketBrowser {
+    fn target(&self) -> Bucket {
+        Bucket {
+            endpoint: self.endpoint.trim().to_string(),
+            region: self.region.trim().to_string(),
+            name: self.bucket.trim().to_string(),
+        }
+    }
+
+    /// Take the results of the requests if they are done. Returns the folder of the copied objects, to be opened.
+    pub fn poll(&mut self) -> Option<PathBuf> {
+        if let Some(sniffing) = self.sniffing.as_ref() {
+            match sniffing.try_recv() {
+                Ok(results) => {
+                    for (index, key, is_dicom) in results {
+                        if let Some(object) = self.objects.get_mut(index)
+                            && object.key == key
+                        {
+                            object.is_dicom = Some(is_dicom);
+                        }
+                    }
+                    self.sniffing = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.sniffing = None,
+            }
+        }
+
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other(tr("The request was cancelled."))),
+        };
+        self.pending = None;
+
+        match result {
+            Ok(Reply::Listed(objects)) => {
+                self.status = trf("{} objects listed.", objects.len());
+                self.objects = objects;
+            }
+            Ok(Reply::Copied(dir)) => return Some(dir),
+            Err(e) => {
+                log::error!("S3 request failed: {e}");
+                self.status = e.to_string();
+            }
+        }
+        None
+    }
+
+    /// Check the objects in the background, unless a check is running.
+    fn sniff(&mut self, tasks: &mut TaskPool, indexes: Vec<usize>) {
+        if self.sniffing.is_some() || indexes.is_empty() {
+            return;
+        }
+        let bucket = self.target();
+        let keys: Vec<(usize, String)> = indexes
+            .into_iter()
+            .map(|x| (x, self.objects[x].key.clone()))
+            .collect();
+        self.sniffing = Some(tasks.spawn(tr("Check the S3 objects"), move |context| {
+            let mut results = Vec::new();
+            for (index, key) in keys {
+                if context.is_cancelled() {
+                    break;
+                }
+                let is_dicom = bucket.sniff(&key).unwrap_or_else(|e| {
+                    log::error!("Failed to check the object {key}: {e}");
+                    false
+                });
+                results.push((index, key, is_dicom));
+            }
+            results
+        }));
+    }
+
+    /// Show the bucket to list, the objects listed with whether they are DICOM files, and the button to open the
+    /// selected objects.
+    pub fn ui(&mut self, ui: &mut egui::Ui, tasks: &mut TaskPool) {
+        egui::Grid::new("s3 bucket grid").show(ui, |ui| {
+            ui.label(tr("Endpoint"));
+            ui.text_edit_singleline(&mut self.endpoint);
+            ui.end_row();
+            ui.label(tr("Region"));
+            ui.text_edit_singleline(&mut self.region);
+            ui.end_row();
+            ui.label(tr("Bucket"));
+            ui.text_edit_singleline(&mut self.bucket);
+            ui.end_row();
+            ui.label(tr("Prefix"));
+            ui.text_edit_singleline(&mut self.prefix);
+            ui.end_row();
+        });
+
+        let is_busy = self.pending.is_some();
+        let selected: Vec<(String, u64)> = self
+            .objects
+            .iter()
+            .filter(|x| x.selected)
+            .map(|x| (x.key.clone(), x.size))
+            .collect();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !is_busy && !self.bucket.trim().is_empty(),
+                    egui::Button::new(tr("List objects")),
+                )
+                .on_hover_text(tr(
+                    "The keys are read from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY.",
+                ))
+                .clicked()
+            {
+                let (bucket, prefix) = (self.target(), self.prefix.clone());
+                self.objects.clear();
+                self.pending = Some(tasks.spawn(tr("List the S3 objects"), move |context| {
+                    bucket.list(&prefix, context).map(Reply::Listed)
+                }));
+                self.status.clear();
+            }
+            if ui
+                .add_enabled(
+                    self.objects.iter().any(|x| x.is_dicom == Some(true)),
+                    egui::Button::new(tr("Select the DICOM objects")),
+                )
+                .clicked()
+            {
+                for object in &mut self.objects {
+                    object.selected = object.is_dicom == Some(true);
+                }
+            }
+            if ui
+                .add_enabled(
+                    !is_busy && !selected.is_empty(),
+                    egui::Button::new(trf("Open {} objects", selected.len())),
+                )
+                .on_hover_text(tr("Copy the objects into a cache folder, and open it"))
+                .clicked()
+            {
+                let bucket = self.target();
+                self.pending = Some(tasks.spawn(tr("Copy the S3 objects"), move |context| {
+                    bucket.copy(&selected, context).map(Reply::Copied)
+                }));
+                self.status.clear();
+            }
+            if is_busy {
+                ui.spinner();
+            }
+        });
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        ui.separator();
+        // Only the objects shown are checked, a batch at a time.
+        let mut unchecked = Vec::new();
+        let row_height = ui.spacing().interact_size.y;
+        egui::ScrollArea::vertical().show_rows(ui, row_height, self.objects.len(), |ui, rows| {
+            for index in rows {
+                let Object {
+                    key,
+                    size,
+                    is_dicom,
+                    selected,
+                } = &mut self.objects[index];
+                ui.horizontal(|ui| {
+                    ui.checkbox(selected, key.as_str());
+                    ui.weak(trf("{} bytes", size));
+                    match is_dicom {
+                        Some(true) => ui.label(tr("DICOM")),
+                        Some(false) => ui.weak(tr("not DICOM")),
+                        None => ui.weak("…"),
+                    };
+                });
+                if is_dicom.is_none() && unchecked.len() < SNIFF_BATCH {
+                    unchecked.push(index);
+                }
+            }
+        });
+        self.sniff(tasks, unchecked);
+    }
+}
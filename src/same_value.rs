@@ -0,0 +1,155 @@
+//! Query by example, i.e. the files sharing the value of an element with the selected file, e.g. the same
+//! FrameOfReferenceUID or AccessionNumber.
+
+use crate::dataset::{open_header, tag_keyword};
+use crate::frequency::value_of;
+use crate::i18n::{tr, trf};
+use crate::privacy;
+use crate::tasks::TaskContext;
+use dicom::core::Tag;
+use std::path::PathBuf;
+
+/// The element and value queried, and the files found with the same value.
+pub struct SameValueQuery {
+    tag: Tag,
+    value: String,
+    matches: Vec<PathBuf>,
+}
+
+impl SameValueQuery {
+    /// Find the files with the same value of the element, stopping if cancelled.
+    pub fn run(files: &[PathBuf], tag: Tag, value: String, context: &TaskContext) -> Self {
+        let mut matches = Vec::new();
+        for (index, file) in files.iter().enumerate() {
+            if context.is_cancelled() {
+                break;
+            }
+            context.set_progress(index as f32 / files.len() as f32);
+            if open_header(file).is_ok_and(|obj| value_of(&obj, tag).is_some_and(|x| x == value)) {
+                matches.push(file.clone());
+            }
+        }
+
+        Self {
+            tag,
+            value,
+            matches,
+        }
+    }
+
+    /// Show the files found, with the value masked in the privacy mode if it identifies the patient. Returns the file
+    /// clicked.
+    pub fn ui(&self, ui: &mut egui::Ui, roots: &[PathBuf], privacy_mode: bool) -> Option<PathBuf> {
+        let mut clicked = None;
+
+        ui.horizontal(|ui| {
+            ui.strong(tag_keyword(self.tag));
+            if privacy_mode && privacy::is_phi_tag(self.tag) {
+                ui.monospace(privacy::MASK);
+            } else {
+                ui.monospace(&self.value);
+            }
+        });
+        ui.label(trf("{} files with the same value", self.matches.len()));
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for file in &self.matches {
+                let name = roots
+                    .iter()
+                    .find_map(|x| file.strip_prefix(x).ok())
+                    .unwrap_or(file);
+                if ui
+                    .link(name.display().to_string())
+                    .on_hover_text(tr("Select in the tree"))
+                    .clicked()
+                {
+                    clicked = Some(file.clone());
+                }
+            }
+        });
+
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SameValueQuery;
+    use crate::dataset::{text_element, write_file};
+    use crate::tasks::TaskPool;
+    use dicom::core::{Tag, VR};
+    use dicom::dictionary_std::tags;
+    use dicom::object::InMemDicomObject;
+    use std::path::{Path, PathBuf};
+
+    /// Write a file for each value of the tag, without the element for `None`.
+    fn write_files(dir: &Path, tag: Tag, vr: VR, values: &[Option<&str>]) -> Vec<PathBuf> {
+        std::fs::create_dir_all(dir).unwrap();
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let mut obj = InMemDicomObject::from_element_iter([
+                    text_element(tags::SOP_CLASS_UID, VR::UI, "1.2.840.10008.5.1.4.1.1.2"),
+                    text_element(tags::SOP_INSTANCE_UID, VR::UI, &format!("1.2.9.{index}")),
+                ]);
+                if let Some(value) = value {
+                    obj.put(text_element(tag, vr, value));
+                }
+                let file = dir.join(format!("{index}.dcm"));
+                write_file(obj, "1.2.840.10008.1.2.1", &file).unwrap();
+                file
+            })
+            .collect()
+    }
+
+    fn run(files: &[PathBuf], tag: Tag, value: &str) -> Vec<PathBuf> {
+        let files = files.to_vec();
+        let value = value.to_string();
+        TaskPool::new(1)
+            .spawn(String::new(), move |context| {
+                SameValueQuery::run(&files, tag, value, context)
+            })
+            .wait()
+            .matches
+    }
+
+    #[test]
+    fn files_without_the_element_are_not_found() {
+        let dir =
+            std::env::temp_dir().join(format!("rsdicombrowser-{}-same-value", std::process::id()));
+        let files = write_files(
+            &dir,
+            tags::FRAME_OF_REFERENCE_UID,
+            VR::UI,
+            &[Some("1.2.3"), Some("1.2.3"), Some("1.2.4"), None],
+        );
+
+        let matches = run(&files, tags::FRAME_OF_REFERENCE_UID, "1.2.3");
+        let empty_matches = run(&files, tags::FRAME_OF_REFERENCE_UID, "");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(matches, files[..2]);
+        assert!(empty_matches.is_empty());
+    }
+
+    #[test]
+    fn files_with_the_value_of_a_private_tag_are_found() {
+        let dir = std::env::temp_dir().join(format!(
+            "rsdicombrowser-{}-same-private-value",
+            std::process::id()
+        ));
+        let files = write_files(
+            &dir,
+            Tag(0x0029, 0x1010),
+            VR::LO,
+            &[Some("A"), None, Some("A")],
+        );
+
+        let matches = run(&files, Tag(0x0029, 0x1010), "A");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(matches, [files[0].clone(), files[2].clone()]);
+    }
+}
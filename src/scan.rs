@@ -0,0 +1,455 @@
+//! Scanning of a folder for files.
+//! Errors, e.g. permission denied or symbolic link cycles, are collected as problems so that the scan carries on.
+//! The root folders are rescanned and their files probed on a worker thread, as a large folder takes a while.
+
+use crate::tasks::{CancelToken, TaskContext};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The options of the folder scan.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    pub follow_symlinks: bool,
+    /// Glob patterns of the file names to include, separated by `;`. All files are included if empty.
+    pub include_patterns: String,
+    /// Glob patterns of the file and directory names to exclude, separated by `;`.
+    pub exclude_patterns: String,
+    /// The maximum depth of the directories below the scanned folder, or unlimited if 0.
+    pub max_depth: usize,
+    pub min_file_size_kib: u64,
+    /// The maximum file size, or unlimited if 0.
+    pub max_file_size_kib: u64,
+    pub skip_hidden: bool,
+}
+
+/// A file found by the scan.
+#[derive(Clone)]
+pub struct ScannedFile {
+    path: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl ScannedFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check the file on disk against the scan, by its size and modification time.
+    pub fn check(&self) -> FileState {
+        match std::fs::metadata(&self.path) {
+            Ok(x) if x.len() == self.size && x.modified().ok() == self.modified => {
+                FileState::Unchanged
+            }
+            Ok(_) => FileState::Changed,
+            Err(_) => FileState::Missing,
+        }
+    }
+}
+
+/// The state of a scanned file on disk now.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum FileState {
+    #[default]
+    Unchanged,
+    Changed,
+    Missing,
+}
+
+/// The results of probing the scanned files, kept so that the unchanged files are not probed again on rescan.
+/// A file is considered unchanged if its size and modification time are the same.
+#[derive(Clone)]
+pub struct ProbeIndex<T> {
+    entries: HashMap<PathBuf, (u64, Option<SystemTime>, T)>,
+}
+
+impl<T> Default for ProbeIndex<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> ProbeIndex<T> {
+    /// Check if the file is unchanged since it was probed.
+    pub fn is_unchanged(&self, file: &ScannedFile) -> bool {
+        self.entries
+            .get(&file.path)
+            .is_some_and(|(size, modified, _)| *size == file.size && *modified == file.modified)
+    }
+
+    /// Get the cached result of the file, unless it changed since it was probed.
+    pub fn get(&self, file: &ScannedFile) -> Option<&T> {
+        self.is_unchanged(file).then(|| &self.entries[&file.path].2)
+    }
+
+    /// Get the cached result of the unchanged file, or probe it again.
+    pub fn probe(&mut self, file: &ScannedFile, probe: impl FnOnce(&Path) -> T) -> T {
+        if self.is_unchanged(file) {
+            return self.entries[&file.path].2.clone();
+        }
+
+        let result = probe(&file.path);
+        self.entries.insert(
+            file.path.clone(),
+            (file.size, file.modified, result.clone()),
+        );
+        result
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A path which couldn't be scanned.
+#[derive(Clone)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// The files found by the scan, sorted depth first by the names in the natural order, and the problems met.
+#[derive(Clone, Default)]
+pub struct ScanResult {
+    pub files: Vec<ScannedFile>,
+    pub problems: Vec<Problem>,
+}
+
+impl ScanResult {
+    fn problem(&mut self, path: &Path, message: impl ToString) {
+        self.problems.push(Problem {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        });
+    }
+}
+
+/// Convert the `;` separated glob patterns with `*` and `?` wildcards to case-insensitive regexes.
+fn glob_regexes(patterns: &str) -> Vec<Regex> {
+    patterns
+        .split(';')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .filter_map(|pattern| {
+            let regex = regex::escape(pattern)
+                .replace(r"\*", ".*")
+                .replace(r"\?", ".");
+            Regex::new(&format!("(?i)^{regex}$")).ok()
+        })
+        .collect()
+}
+
+/// Compare the names in the natural order, e.g. `IM2` before `IM10`, ignoring the case.
+/// The runs of digits are compared by their numbers, and the rest char by char in lowercase, independent of the locale.
+/// The names equal that way, e.g. `a` and `A` or `1` and `01`, are ordered by their bytes so that the order is total.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let digits = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+                    let mut run = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        run.push(c);
+                    }
+                    run
+                };
+                let (m, n) = (digits(&mut x), digits(&mut y));
+                let (m, n) = (m.trim_start_matches('0'), n.trim_start_matches('0'));
+                m.len().cmp(&n.len()).then_with(|| m.cmp(n))
+            }
+            (Some(c), Some(d)) => {
+                x.next();
+                y.next();
+                c.to_lowercase().cmp(d.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Check if the file or directory is hidden, i.e. a dot file or with the hidden attribute on Windows.
+fn is_hidden(name: &str, metadata: &Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0 {
+            return true;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = metadata;
+
+    name.starts_with('.')
+}
+
+struct Scanner<'a> {
+    options: &'a ScanOptions,
+    token: &'a CancelToken,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    /// The real paths of the visited directories, so that a folder linked twice is scanned once.
+    visited: HashSet<PathBuf>,
+    /// The real paths of the folders on the current path, so that the symbolic links looping back are detected.
+    ancestors: Vec<PathBuf>,
+    result: ScanResult,
+}
+
+impl Scanner<'_> {
+    /// Check if the file passes the name and size filters.
+    fn accepts_file(&self, name: &str, size: u64) -> bool {
+        let options = self.options;
+
+        (self.include.is_empty() || self.include.iter().any(|x| x.is_match(name)))
+            && size >= options.min_file_size_kib * 1024
+            && (options.max_file_size_kib == 0 || size <= options.max_file_size_kib * 1024)
+    }
+
+    fn scan_dir(&mut self, dir: &Path, depth: usize) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        let real_path = match dir.canonicalize() {
+            Ok(x) => x,
+            Err(e) => {
+                self.result.problem(dir, e);
+                return;
+            }
+        };
+        if self.ancestors.contains(&real_path) {
+            self.result.problem(dir, "symbolic link cycle, skipped");
+            return;
+        }
+        if !self.visited.insert(real_path.clone()) {
+            return;
+        }
+
+        self.ancestors.push(real_path);
+        self.scan_entries(dir, depth);
+        self.ancestors.pop();
+    }
+
+    fn scan_entries(&mut self, dir: &Path, depth: usize) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(x) => x,
+            Err(e) => {
+                self.result.problem(dir, e);
+                return;
+            }
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(x) => paths.push(x.path()),
+                Err(e) => self.result.problem(dir, e),
+            }
+        }
+        // The paths are in the same folder, so only their names differ.
+        paths.sort_by(|a, b| {
+            natural_cmp(
+                &a.file_name().unwrap_or_default().to_string_lossy(),
+                &b.file_name().unwrap_or_default().to_string_lossy(),
+            )
+        });
+
+        for path in paths {
+            let metadata = match std::fs::symlink_metadata(&path) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.result.problem(&path, e);
+                    continue;
+                }
+            };
+            // Junctions are reported as symbolic links on Windows as well.
+            let metadata = if metadata.is_symlink() {
+                if !self.options.follow_symlinks {
+                    continue;
+                }
+                match std::fs::metadata(&path) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        self.result.problem(&path, e);
+                        continue;
+                    }
+                }
+            } else {
+                metadata
+            };
+
+            let name = path
+                .file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string());
+            if self.exclude.iter().any(|x| x.is_match(&name))
+                || (self.options.skip_hidden && is_hidden(&name, &metadata))
+            {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if self.options.max_depth == 0 || depth < self.options.max_depth {
+                    self.scan_dir(&path, depth + 1);
+                }
+            } else if metadata.is_file() && self.accepts_file(&name, metadata.len()) {
+                self.result.files.push(ScannedFile {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                });
+            }
+        }
+    }
+}
+
+/// Scan the folder recursively for files, stopping early if cancelled.
+pub fn scan(root: &Path, options: &ScanOptions, token: &CancelToken) -> ScanResult {
+    let mut scanner = Scanner {
+        options,
+        token,
+        include: glob_regexes(&options.include_patterns),
+        exclude: glob_regexes(&options.exclude_patterns),
+        visited: HashSet::new(),
+        ancestors: Vec::new(),
+        result: ScanResult::default(),
+    };
+
+    scanner.scan_dir(root, 0);
+    scanner.result
+}
+
+/// The root folders rescanned, with the files of all the roots probed.
+pub struct Rescan<T> {
+    pub results: Vec<(PathBuf, ScanResult)>,
+    /// The files changed since they were last probed, whose cached data is out of date.
+    pub changed: Vec<PathBuf>,
+    pub probe_index: ProbeIndex<T>,
+}
+
+/// Rescan the root folders, and probe their files with the files of the other roots, only probing the files changed
+/// since the last scan. Stops early if cancelled.
+pub fn rescan<T: Clone>(
+    roots: &[PathBuf],
+    other_files: &[ScannedFile],
+    options: &ScanOptions,
+    mut probe_index: ProbeIndex<T>,
+    probe: impl Fn(&Path) -> T,
+    context: &TaskContext,
+) -> Rescan<T> {
+    let results: Vec<(PathBuf, ScanResult)> = roots
+        .iter()
+        .map(|root| (root.clone(), scan(root, options, context.token())))
+        .collect();
+
+    let files: Vec<&ScannedFile> = results
+        .iter()
+        .flat_map(|(_, x)| &x.files)
+        .chain(other_files)
+        .collect();
+    let mut changed = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        if context.is_cancelled() {
+            break;
+        }
+        context.set_progress(index as f32 / files.len() as f32);
+        if !probe_index.is_unchanged(file) {
+            changed.push(file.path.clone());
+        }
+        probe_index.probe(file, &probe);
+    }
+
+    Rescan {
+        results,
+        changed,
+        probe_index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScanOptions, natural_cmp, scan};
+    use crate::tasks::CancelToken;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn digits_are_compared_by_number() {
+        assert_eq!(natural_cmp("IM2", "IM10"), Ordering::Less);
+        assert_eq!(natural_cmp("IM10", "IM2"), Ordering::Greater);
+        assert_eq!(natural_cmp("IM002", "IM10"), Ordering::Less);
+        assert_eq!(natural_cmp("1.2.10", "1.2.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn digits_at_the_end() {
+        assert_eq!(natural_cmp("IM", "IM1"), Ordering::Less);
+        assert_eq!(natural_cmp("IM9", "IM10"), Ordering::Less);
+        assert_eq!(natural_cmp("IM10", "IM10a"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(natural_cmp("im2", "IM10"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("B", "a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn long_digit_runs() {
+        assert_eq!(
+            natural_cmp("f18446744073709551616", "f18446744073709551615"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            natural_cmp("f99999999999999999999999", "f100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn equal_names_are_ordered_by_bytes() {
+        assert_eq!(natural_cmp("1", "01"), Ordering::Greater);
+        assert_eq!(natural_cmp("a01b", "a1b"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "A"), Ordering::Greater);
+        assert_eq!(natural_cmp("IM1", "IM1"), Ordering::Equal);
+
+        let mut names = vec!["IM10", "im2", "IM1", "IM01", "im1"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["IM01", "IM1", "im1", "im2", "IM10"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_links_to_ancestors_are_cycles() {
+        let root =
+            std::env::temp_dir().join(format!("rsdicombrowser-{}-symlinks", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::write(root.join("a").join("IM1"), b"x").unwrap();
+        std::os::unix::fs::symlink(root.join("a"), root.join("b")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a").join("up")).unwrap();
+
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let result = scan(&root, &options, &CancelToken::default());
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.problems.len(), 1);
+        assert_eq!(result.problems[0].path, root.join("a").join("up"));
+    }
+}
@@ -0,0 +1,128 @@
+//! User scripts written in Rhai, run from the Scripts menu.
+//! A script sees the selected file as `path`, its dump as `dump`, its elements as the `elements` map
+//! (keyed by the flattened paths of the CSV export) and all the opened files as `files`.
+//! `tag(file, keyword)` reads the value of a tag from any file, e.g. for bulk checks.
+//! The scripts run on the UI thread, so they are stopped after a number of operations or a few seconds, and the
+//! depth of the calls and the size of the values are limited, so that a runaway script can't hang the app.
+
+use crate::dataset::{element_string, open_header};
+use crate::export;
+use crate::settings::app_data_dir;
+use dicom::core::DataDictionary;
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::InMemDicomObject;
+use rhai::{Array, Dynamic, Engine, Map, Scope};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The maximum number of operations of a script.
+const MAX_OPERATIONS: u64 = 50_000_000;
+/// The maximum time a script runs.
+const MAX_DURATION: Duration = Duration::from_secs(5);
+/// The maximum depth of the function calls and of the expressions.
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_EXPR_DEPTH: usize = 128;
+/// The maximum size of the strings, arrays and maps a script builds.
+const MAX_VALUE_SIZE: usize = 10_000_000;
+
+/// The input of the script.
+pub struct ScriptContext<'a> {
+    pub path: Option<&'a Path>,
+    pub obj: Option<&'a InMemDicomObject>,
+    pub dump: &'a str,
+    pub files: Vec<&'a Path>,
+}
+
+/// Get the directory with the user scripts.
+pub fn scripts_dir() -> PathBuf {
+    app_data_dir("scripts")
+}
+
+/// Get the scripts in the scripts directory.
+pub fn available_scripts() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(scripts_dir()) else {
+        return Vec::new();
+    };
+
+    let mut scripts: Vec<PathBuf> = entries
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "rhai"))
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+/// Read the value of the tag (keyword or gggg,eeee) from the file.
+fn read_tag(file: &str, keyword: &str) -> String {
+    let Some(tag) = StandardDataDictionary.parse_tag(keyword) else {
+        return String::new();
+    };
+
+    open_header(Path::new(file))
+        .ok()
+        .and_then(|obj| element_string(&obj, tag))
+        .unwrap_or_default()
+}
+
+/// Run the script and get what it printed, or the error.
+pub fn run(script: &Path, context: &ScriptContext<'_>) -> String {
+    let source = match std::fs::read_to_string(script) {
+        Ok(x) => x,
+        Err(e) => return e.to_string(),
+    };
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+        .set_max_string_size(MAX_VALUE_SIZE)
+        .set_max_array_size(MAX_VALUE_SIZE)
+        .set_max_map_size(MAX_VALUE_SIZE);
+    let started = Instant::now();
+    engine.on_progress(move |_| {
+        (started.elapsed() > MAX_DURATION).then(|| Dynamic::from("the script took too long"))
+    });
+    {
+        let output = output.clone();
+        engine.on_print(move |x| {
+            output.borrow_mut().push_str(x);
+            output.borrow_mut().push('\n');
+        });
+    }
+    engine.register_fn("tag", read_tag);
+
+    let elements: Map = context
+        .obj
+        .map(export::flatten)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| (key.into(), Dynamic::from(value)))
+        .collect();
+    let files: Array = context
+        .files
+        .iter()
+        .map(|x| Dynamic::from(x.display().to_string()))
+        .collect();
+
+    let mut scope = Scope::new();
+    scope.push(
+        "path",
+        context
+            .path
+            .map_or(String::new(), |x| x.display().to_string()),
+    );
+    scope.push("dump", context.dump.to_string());
+    scope.push("elements", elements);
+    scope.push("files", files);
+
+    if let Err(e) = engine.run_with_scope(&mut scope, &source) {
+        output.borrow_mut().push_str(&e.to_string());
+    }
+
+    output.take()
+}
@@ -0,0 +1,140 @@
+//! The scopes of the search in the dump, and the detection of a pasted UID or tag, which picks the scope to search it
+//! in, so that the most common lookups need no more than a paste.
+
+use crate::dataset::{MAX_UID_LENGTH, dump_line_tag};
+use dicom::core::{DataDictionary, Tag};
+use dicom::dictionary_std::StandardDataDictionary;
+
+/// The part of the dump lines searched.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SearchScope {
+    /// The whole line.
+    #[default]
+    Anywhere,
+    /// The tag of the element, given as (gggg,eeee) or by the keyword.
+    Tag,
+    /// The line without its tag.
+    Value,
+}
+
+impl SearchScope {
+    pub const ALL: [Self; 3] = [Self::Anywhere, Self::Tag, Self::Value];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Anywhere => "Anywhere",
+            Self::Tag => "Tag",
+            Self::Value => "Value",
+        }
+    }
+}
+
+fn is_uid(text: &str) -> bool {
+    text.len() <= MAX_UID_LENGTH
+        && text.split('.').count() > 1
+        && text
+            .split('.')
+            .all(|x| !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit()))
+}
+
+fn is_tag(text: &str) -> bool {
+    let text = text
+        .strip_prefix('(')
+        .and_then(|x| x.strip_suffix(')'))
+        .unwrap_or(text);
+    text.split_once(',').is_some_and(|(group, element)| {
+        [group, element]
+            .iter()
+            .all(|x| x.len() == 4 && x.bytes().all(|x| x.is_ascii_hexdigit()))
+    })
+}
+
+/// Get the scope to search the text in if it is a UID or a tag, e.g. pasted from another viewer or a log.
+pub fn detect_scope(text: &str) -> Option<SearchScope> {
+    let text = text.trim();
+    if is_uid(text) {
+        Some(SearchScope::Value)
+    } else if is_tag(text) {
+        Some(SearchScope::Tag)
+    } else {
+        None
+    }
+}
+
+/// Parse the tag searched for, as (gggg,eeee), gggg,eeee, ggggeeee or a keyword.
+pub fn parse_tag(text: &str) -> Option<Tag> {
+    StandardDataDictionary.parse_tag(text.trim())
+}
+
+/// Get the tag at the start of the line, after the indentation of the nested elements, or the key in the JSON format,
+/// e.g. `"00100010": {`, with the length of the tag in the line.
+fn line_tag(line: &str) -> Option<(Tag, usize)> {
+    if let Some(key) = line.strip_prefix('"') {
+        let key = key
+            .get(..9)?
+            .strip_suffix('"')
+            .filter(|x| x.bytes().all(|x| x.is_ascii_hexdigit()))?;
+        let number = u32::from_str_radix(key, 16).ok()?;
+        return Some((Tag((number >> 16) as u16, number as u16), 10));
+    }
+    Some((dump_line_tag(line)?, 11))
+}
+
+/// Check if the line is of the element with the tag.
+pub fn line_has_tag(line: &str, tag: Tag) -> bool {
+    line_tag(line.trim_start()).is_some_and(|(x, _)| x == tag)
+}
+
+/// Get the line without its tag, to search the value of the element.
+pub fn line_value(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    match line_tag(trimmed) {
+        Some((_, length)) => &trimmed[length..],
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_has_tag, line_value};
+    use dicom::core::Tag;
+
+    const PATIENT_ID: Tag = Tag(0x0010, 0x0020);
+
+    #[test]
+    fn tag_of_text_dump_lines() {
+        assert!(line_has_tag(
+            "(0010,0020) LO Patient ID [12345]",
+            PATIENT_ID
+        ));
+        assert!(line_has_tag(
+            "    (0010,0020) LO Patient ID [12345]",
+            PATIENT_ID
+        ));
+        assert!(!line_has_tag(
+            "(0010,0010) PN Patient's Name [12345]",
+            PATIENT_ID
+        ));
+        assert!(!line_has_tag("# (0010,0020)", PATIENT_ID));
+    }
+
+    #[test]
+    fn tag_of_json_dump_lines() {
+        assert!(line_has_tag("  \"00100020\": {", PATIENT_ID));
+        assert!(!line_has_tag("  \"vr\": \"LO\",", PATIENT_ID));
+    }
+
+    #[test]
+    fn value_of_dump_lines() {
+        assert_eq!(
+            line_value("(0010,0020) LO Patient ID [0010,0020]"),
+            " LO Patient ID [0010,0020]"
+        );
+        assert_eq!(
+            line_value("  (0008,0100) SH Code Value [T-1]"),
+            " SH Code Value [T-1]"
+        );
+        assert_eq!(line_value("  \"00100020\": {"), ": {");
+        assert_eq!(line_value("# Dicom-Data-Set"), "# Dicom-Data-Set");
+    }
+}
@@ -0,0 +1,194 @@
+//! Completeness checks of the series, e.g. for missing or duplicated instances.
+
+use crate::dataset::{element_string, open_header};
+use crate::i18n::{tr, trf};
+use crate::orientation::is_oblique;
+use crate::tasks::TaskContext;
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The tolerance of the orientation cosines to be considered the same.
+const ORIENTATION_TOLERANCE: f64 = 1e-3;
+/// A slice is considered missing if the gap is larger than the typical spacing by this factor.
+const GAP_FACTOR: f64 = 1.5;
+/// The maximum number of missing instance numbers listed in the warning.
+const MAX_LISTED: usize = 20;
+
+/// The result of checking a series.
+pub struct SeriesReport {
+    pub series_uid: String,
+    pub description: String,
+    pub files: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+/// The elements of an instance needed by the checks.
+struct Instance {
+    sop_instance_uid: String,
+    instance_number: Option<i64>,
+    orientation: Option<Vec<f64>>,
+    position: Option<Vec<f64>>,
+}
+
+fn floats(obj: &InMemDicomObject, tag: Tag, count: usize) -> Option<Vec<f64>> {
+    obj.get(tag)
+        .and_then(|x| x.to_multi_float64().ok())
+        .filter(|x| x.len() == count)
+}
+
+/// Check the instance numbers for gaps.
+fn check_instance_numbers(instances: &[Instance], warnings: &mut Vec<String>) {
+    let mut numbers: Vec<i64> = instances.iter().filter_map(|x| x.instance_number).collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    // Count the gaps arithmetically, as a huge instance number would make a huge gap, and list only the first ones.
+    let gaps = numbers.windows(2).map(|x| (x[0] + 1)..x[1]);
+    let count: u64 = gaps.clone().map(|x| x.end.abs_diff(x.start)).sum();
+    if count > 0 {
+        let mut listed: Vec<String> = gaps
+            .flatten()
+            .take(MAX_LISTED)
+            .map(|x| x.to_string())
+            .collect();
+        if count > MAX_LISTED as u64 {
+            listed.push("…".to_string());
+        }
+        warnings.push(trf("Missing instance numbers: {}", listed.join(", ")));
+    }
+}
+
+/// Check the SOP instance UIDs for duplicates.
+fn check_duplicates(instances: &[Instance], warnings: &mut Vec<String>) {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for instance in instances {
+        *counts.entry(&instance.sop_instance_uid).or_default() += 1;
+    }
+
+    for (uid, count) in counts {
+        if count > 1 {
+            warnings.push(format!(
+                "{} ({count}x)",
+                trf("Duplicate SOP instance UID {}", uid)
+            ));
+        }
+    }
+}
+
+/// Check that the orientations are the same, and look for missing slices along the slice normal.
+fn check_geometry(instances: &[Instance], warnings: &mut Vec<String>) {
+    let orientations: Vec<&Vec<f64>> = instances
+        .iter()
+        .filter_map(|x| x.orientation.as_ref())
+        .collect();
+    let Some(first) = orientations.first() else {
+        return;
+    };
+    if orientations.iter().any(|x| {
+        x.iter()
+            .zip(first.iter())
+            .any(|(a, b)| (a - b).abs() > ORIENTATION_TOLERANCE)
+    }) {
+        warnings.push(tr("Inconsistent image orientation"));
+        return;
+    }
+    if is_oblique(
+        [first[0], first[1], first[2]],
+        [first[3], first[4], first[5]],
+    ) {
+        warnings.push(tr("Oblique image orientation"));
+    }
+
+    // Project the positions onto the normal of the slices, i.e. the cross product of the row and column directions.
+    let normal = [
+        first[1] * first[5] - first[2] * first[4],
+        first[2] * first[3] - first[0] * first[5],
+        first[0] * first[4] - first[1] * first[3],
+    ];
+    let mut distances: Vec<f64> = instances
+        .iter()
+        .filter_map(|x| x.position.as_ref())
+        .map(|x| x.iter().zip(normal).map(|(a, b)| a * b).sum::<f64>())
+        .collect();
+    if distances.len() < 3 {
+        return;
+    }
+    distances.sort_by(f64::total_cmp);
+
+    let mut spacings: Vec<f64> = distances.windows(2).map(|x| x[1] - x[0]).collect();
+    if spacings.iter().any(|x| *x < ORIENTATION_TOLERANCE) {
+        warnings.push(tr("Multiple slices at the same position"));
+    }
+    spacings.retain(|x| *x >= ORIENTATION_TOLERANCE);
+    let mut sorted = spacings.clone();
+    sorted.sort_by(f64::total_cmp);
+    let Some(typical) = sorted.get(sorted.len() / 2).copied() else {
+        return;
+    };
+
+    let missing: usize = spacings
+        .iter()
+        .filter(|x| **x > typical * GAP_FACTOR)
+        .map(|x| (x / typical).round() as usize - 1)
+        .sum();
+    if missing > 0 {
+        warnings.push(format!(
+            "{} ({:.2} mm)",
+            trf("About {} slices missing by the spacing", missing),
+            typical
+        ));
+    }
+}
+
+/// Group the files by series and check each series. Stops reading the files if cancelled.
+pub fn check(files: &[PathBuf], context: &TaskContext) -> Vec<SeriesReport> {
+    let mut series: Vec<(SeriesReport, Vec<Instance>)> = Vec::new();
+    let mut index_of: HashMap<String, usize> = HashMap::new();
+
+    for (index, file) in files.iter().enumerate() {
+        if context.is_cancelled() {
+            break;
+        }
+        context.set_progress(index as f32 / files.len() as f32);
+        let Ok(obj) = open_header(file) else {
+            continue;
+        };
+        let series_uid = element_string(&obj, tags::SERIES_INSTANCE_UID).unwrap_or_default();
+        let index = *index_of.entry(series_uid.clone()).or_insert_with(|| {
+            series.push((
+                SeriesReport {
+                    series_uid,
+                    description: element_string(&obj, tags::SERIES_DESCRIPTION).unwrap_or_default(),
+                    files: Vec::new(),
+                    warnings: Vec::new(),
+                },
+                Vec::new(),
+            ));
+            series.len() - 1
+        });
+
+        let (report, instances) = &mut series[index];
+        report.files.push(file.clone());
+        instances.push(Instance {
+            sop_instance_uid: element_string(&obj, tags::SOP_INSTANCE_UID).unwrap_or_default(),
+            instance_number: obj
+                .get(tags::INSTANCE_NUMBER)
+                .and_then(|x| x.to_int::<i64>().ok()),
+            orientation: floats(&obj, tags::IMAGE_ORIENTATION_PATIENT, 6),
+            position: floats(&obj, tags::IMAGE_POSITION_PATIENT, 3),
+        });
+    }
+
+    series
+        .into_iter()
+        .map(|(mut report, instances)| {
+            check_instance_numbers(&instances, &mut report.warnings);
+            check_duplicates(&instances, &mut report.warnings);
+            check_geometry(&instances, &mut report.warnings);
+            report
+        })
+        .collect()
+}
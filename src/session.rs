@@ -0,0 +1,32 @@
+use crate::filter::TagFilter;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The workspace saved to a session file, so that a review can be resumed later.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub base_dir: Option<PathBuf>,
+    /// The other root folders of a workspace with several roots.
+    pub other_roots: Vec<PathBuf>,
+    pub selected_file: Option<PathBuf>,
+    pub search_input: String,
+    pub tag_filters: Vec<TagFilter>,
+    pub show_value_stats: bool,
+    pub show_functional_groups: bool,
+}
+
+impl Session {
+    /// Save the session as JSON.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Load the session from JSON.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
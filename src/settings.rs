@@ -0,0 +1,131 @@
+use crate::cli_commands::{Endpoint, OrthancServer};
+use crate::dump::DumpStyle;
+use crate::external_tools::ExternalTool;
+use crate::quick_view::{self, QuickViewLayout};
+use crate::scan::ScanOptions;
+use crate::tasks;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The user preferences persisted across restarts.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub ui_scale: f32,
+    pub font_path: Option<PathBuf>,
+    pub language: Option<String>,
+    pub external_tools: Vec<ExternalTool>,
+    pub read_only: bool,
+    pub scan: ScanOptions,
+    pub show_skipped_files: bool,
+    pub color_code_tree: bool,
+    /// The colors of the files in the tree by modality.
+    pub modality_colors: BTreeMap<String, [u8; 3]>,
+    /// The AE title of this app when sending to the endpoints.
+    pub calling_ae_title: String,
+    pub endpoints: Vec<Endpoint>,
+    pub orthanc_servers: Vec<OrthancServer>,
+    /// Check the release feed for a newer version at startup, off unless the user opts in.
+    pub check_for_updates: bool,
+    /// Show the hints for the new users above the tree and the dump.
+    pub show_hints: bool,
+    pub dump_style: DumpStyle,
+    /// The keywords of the tags pinned above the dump.
+    pub pinned_tags: Vec<String>,
+    /// The number of the threads running the background tasks.
+    pub worker_threads: usize,
+    /// Mask the identifying values on screen, e.g. when sharing the screen.
+    pub privacy_mode: bool,
+    /// The layouts of the tags in the quick view.
+    pub quick_view_layouts: Vec<QuickViewLayout>,
+    /// The root of the UIDs made when regenerating them, 2.25 for the random UIDs.
+    pub uid_root: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.2,
+            font_path: None,
+            language: None,
+            external_tools: Vec::new(),
+            read_only: false,
+            scan: ScanOptions::default(),
+            show_skipped_files: false,
+            color_code_tree: true,
+            modality_colors: default_modality_colors(),
+            calling_ae_title: "RSDICOMBROWSER".to_string(),
+            endpoints: Vec::new(),
+            orthanc_servers: Vec::new(),
+            check_for_updates: false,
+            show_hints: true,
+            dump_style: DumpStyle::default(),
+            pinned_tags: Vec::new(),
+            worker_threads: tasks::default_thread_count(),
+            privacy_mode: false,
+            quick_view_layouts: quick_view::default_layouts(),
+            uid_root: "2.25".to_string(),
+        }
+    }
+}
+
+fn default_modality_colors() -> BTreeMap<String, [u8; 3]> {
+    [
+        ("CT", [30, 100, 200]),
+        ("MR", [130, 60, 180]),
+        ("US", [0, 140, 140]),
+        ("PT", [220, 110, 0]),
+        ("NM", [180, 150, 0]),
+        ("XA", [200, 40, 40]),
+        ("CR", [110, 90, 70]),
+        ("DX", [110, 90, 70]),
+        ("MG", [200, 60, 130]),
+        ("SR", [40, 140, 40]),
+        ("PR", [40, 140, 40]),
+        ("KO", [40, 140, 40]),
+        ("DOC", [40, 140, 40]),
+        ("RTSTRUCT", [160, 80, 40]),
+        ("RTPLAN", [160, 80, 40]),
+        ("RTDOSE", [160, 80, 40]),
+    ]
+    .into_iter()
+    .map(|(modality, color)| (modality.to_string(), color))
+    .collect()
+}
+
+/// Get the path of the file or the directory shipped with the app, next to the executable.
+pub fn app_dir(name: &str) -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|x| x.parent().map(|x| x.join(name)))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Get the directory of the data of the user, e.g. `~/.local/share/rsdicombrowser` or `%APPDATA%\rsdicombrowser`, as
+/// the folder of the executable may be shared by the users or not writable. Falls back to the folder of the executable.
+fn user_data_dir() -> Option<PathBuf> {
+    let home = || {
+        std::env::var_os("HOME")
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|x| x.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|x| x.join(".local").join("share")))
+    };
+    let dir = base?.join(env!("CARGO_PKG_NAME"));
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// Get the path of the app data with the name in the data directory of the user, e.g. the audit log.
+pub fn app_data_dir(name: &str) -> PathBuf {
+    user_data_dir().map_or_else(|| app_dir(name), |x| x.join(name))
+}
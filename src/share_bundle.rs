@@ -0,0 +1,293 @@
+//! The wizard creating a ZIP bundle of the selected files to be sent by email or shared, always anonymized.
+//! The images flagged with burned-in annotation are only bundled after the user confirms that they are redacted, since
+//! the anonymization doesn't touch the pixel data.
+
+use crate::anonymize::{Anonymizer, anonymize_files};
+use crate::audit::AuditLog;
+use crate::dataset::{element_string, new_uid, open_header};
+use crate::i18n::{tr, trf};
+use crate::zip::ZipWriter;
+use dicom::dictionary_std::{tags, uids};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The transfer syntax of the bundled files.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TargetSyntax {
+    Original,
+    ExplicitLittle,
+    ImplicitLittle,
+}
+
+impl TargetSyntax {
+    pub const ALL: [TargetSyntax; 3] = [
+        TargetSyntax::Original,
+        TargetSyntax::ExplicitLittle,
+        TargetSyntax::ImplicitLittle,
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            TargetSyntax::Original => tr("Keep the original"),
+            TargetSyntax::ExplicitLittle => tr("Explicit VR Little Endian"),
+            TargetSyntax::ImplicitLittle => tr("Implicit VR Little Endian"),
+        }
+    }
+
+    fn uid(&self) -> Option<&'static str> {
+        match self {
+            TargetSyntax::Original => None,
+            TargetSyntax::ExplicitLittle => Some(uids::EXPLICIT_VR_LITTLE_ENDIAN),
+            TargetSyntax::ImplicitLittle => Some(uids::IMPLICIT_VR_LITTLE_ENDIAN),
+        }
+    }
+}
+
+/// The steps of the wizard.
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Review,
+    Options,
+    Create,
+}
+
+/// Check if the file is flagged with burned-in annotation.
+fn has_burned_in_annotation(path: &Path) -> bool {
+    open_header(path).is_ok_and(|obj| {
+        element_string(&obj, tags::BURNED_IN_ANNOTATION)
+            .is_some_and(|x| x.trim().eq_ignore_ascii_case("YES"))
+    })
+}
+
+/// The state of the wizard.
+pub struct ShareBundleTool {
+    step: Step,
+    /// The files checked for the burned-in annotation, to check again when the selection changes.
+    checked_files: Vec<PathBuf>,
+    flagged: Vec<PathBuf>,
+    redaction_confirmed: bool,
+    pub patient_name: String,
+    pub patient_id: String,
+    pub transfer_syntax: TargetSyntax,
+    pub output: Option<PathBuf>,
+    status: String,
+}
+
+impl Default for ShareBundleTool {
+    fn default() -> Self {
+        Self {
+            step: Step::Review,
+            checked_files: Vec::new(),
+            flagged: Vec::new(),
+            redaction_confirmed: false,
+            patient_name: "ANONYMOUS".to_string(),
+            patient_id: "ANON0001".to_string(),
+            transfer_syntax: TargetSyntax::ExplicitLittle,
+            output: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl ShareBundleTool {
+    /// Check the files for the burned-in annotation, if the selection changed.
+    fn check_files(&mut self, files: &[PathBuf]) {
+        if self.checked_files == files {
+            return;
+        }
+
+        self.checked_files = files.to_vec();
+        self.flagged = files
+            .iter()
+            .filter(|x| has_burned_in_annotation(x))
+            .cloned()
+            .collect();
+        self.redaction_confirmed = false;
+        self.step = Step::Review;
+        self.status.clear();
+    }
+
+    /// Check if the files may be bundled.
+    fn is_cleared(&self) -> bool {
+        self.flagged.is_empty() || self.redaction_confirmed
+    }
+
+    /// Write the bundle, staging the anonymized files in the temp dir. The files not converted are returned as
+    /// warnings.
+    fn create(&self, files: &[PathBuf], output: &Path) -> Result<Vec<String>> {
+        if !self.is_cleared() {
+            return Err(Error::other(
+                "the burned-in annotations are not confirmed as redacted",
+            ));
+        }
+
+        let staging = std::env::temp_dir()
+            .join("rsdicombrowser")
+            .join("share")
+            .join(new_uid());
+        let result = self.create_staged(files, output, &staging);
+        if staging.exists()
+            && let Err(e) = std::fs::remove_dir_all(&staging)
+        {
+            log::error!("Failed to remove {}: {e}", staging.display());
+        }
+        result
+    }
+
+    fn create_staged(
+        &self,
+        files: &[PathBuf],
+        output: &Path,
+        staging: &Path,
+    ) -> Result<Vec<String>> {
+        let mut anonymizer = Anonymizer::new(&self.patient_name, &self.patient_id);
+        let (anonymized, warnings) =
+            anonymize_files(files, &mut anonymizer, self.transfer_syntax.uid(), staging)?;
+
+        let mut zip = ZipWriter::new(std::io::BufWriter::new(std::fs::File::create(output)?));
+        for file in &anonymized {
+            let name = file
+                .file_name()
+                .map_or(String::new(), |x| x.to_string_lossy().to_string());
+            zip.add_file(&name, &std::fs::read(file)?)?;
+        }
+        zip.finish()?;
+
+        Ok(warnings)
+    }
+
+    fn review_ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf]) {
+        ui.label(trf("{} files selected", files.len()));
+        if self.flagged.is_empty() {
+            ui.label(tr("No files are flagged with burned-in annotation."));
+            return;
+        }
+
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            trf(
+                "{} files are flagged with burned-in annotation, which the anonymization doesn't remove.",
+                self.flagged.len(),
+            ),
+        );
+        egui::ScrollArea::vertical()
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for file in &self.flagged {
+                    ui.monospace(file.display().to_string());
+                }
+            });
+        ui.checkbox(
+            &mut self.redaction_confirmed,
+            tr("I confirm that the burned-in annotations are redacted"),
+        );
+    }
+
+    fn options_ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("share bundle grid").show(ui, |ui| {
+            ui.label(tr("Patient name"));
+            ui.text_edit_singleline(&mut self.patient_name);
+            ui.end_row();
+
+            ui.label(tr("Patient ID"));
+            ui.text_edit_singleline(&mut self.patient_id);
+            ui.end_row();
+
+            ui.label(tr("Transfer syntax"));
+            egui::ComboBox::from_id_salt("share bundle transfer syntax")
+                .selected_text(self.transfer_syntax.label())
+                .show_ui(ui, |ui| {
+                    for syntax in TargetSyntax::ALL {
+                        ui.selectable_value(&mut self.transfer_syntax, syntax, syntax.label());
+                    }
+                });
+            ui.end_row();
+        });
+    }
+
+    /// Show the output step. Returns true if the output file should be picked.
+    fn create_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        audit_log: &mut AuditLog,
+    ) -> bool {
+        let mut pick_output = false;
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Output file"));
+            let output = self
+                .output
+                .as_ref()
+                .map_or(tr("None"), |x| x.display().to_string());
+            ui.monospace(output);
+            pick_output = ui.button(tr("Browse")).clicked();
+        });
+
+        if let Some(output) = self.output.as_ref()
+            && ui
+                .add_enabled(
+                    !files.is_empty() && self.is_cleared(),
+                    egui::Button::new(tr("Create bundle")),
+                )
+                .clicked()
+        {
+            let result = self.create(files, output);
+            if result.is_ok() {
+                audit_log.record("Create share bundle", files.to_vec(), vec![output.clone()]);
+            }
+            self.status = match result {
+                Ok(warnings) if warnings.is_empty() => tr("Bundle created."),
+                Ok(warnings) => warnings.join("\n"),
+                Err(e) => e.to_string(),
+            };
+        }
+
+        if !self.status.is_empty() {
+            egui::ScrollArea::vertical().show(ui, |ui| ui.label(&self.status));
+        }
+
+        pick_output
+    }
+
+    /// Show the current step of the wizard for the files. Returns true if the output file should be picked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf], audit_log: &mut AuditLog) -> bool {
+        self.check_files(files);
+        let mut pick_output = false;
+
+        match self.step {
+            Step::Review => self.review_ui(ui, files),
+            Step::Options => self.options_ui(ui),
+            Step::Create => pick_output = self.create_ui(ui, files, audit_log),
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.step != Step::Review, egui::Button::new(tr("Back")))
+                .clicked()
+            {
+                self.step = match self.step {
+                    Step::Create => Step::Options,
+                    _ => Step::Review,
+                };
+            }
+            let can_continue = match self.step {
+                Step::Review => !files.is_empty() && self.is_cleared(),
+                Step::Options => true,
+                Step::Create => false,
+            };
+            if ui
+                .add_enabled(can_continue, egui::Button::new(tr("Next")))
+                .clicked()
+            {
+                self.step = match self.step {
+                    Step::Review => Step::Options,
+                    _ => Step::Create,
+                };
+            }
+        });
+
+        pick_output
+    }
+}
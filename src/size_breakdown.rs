@@ -0,0 +1,161 @@
+//! Breakdown of what makes a file big, e.g. the pixel data, private groups, icons and other bulk data.
+//! The sizes are estimated from the encoded lengths of the elements in explicit VR.
+
+use crate::dataset::{is_binary_vr, tag_keyword};
+use crate::i18n::{tr, trf};
+use dicom::core::value::Value;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::mem::InMemElement;
+use dicom::object::{InMemDicomObject, open_file};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Elements smaller than this are counted as other elements rather than listed on their own.
+const LISTED_SIZE: u64 = 1024;
+
+/// A part of the file and its size in bytes.
+pub struct SizeEntry {
+    pub label: String,
+    pub size: u64,
+}
+
+/// The size of the file and its parts, the largest first.
+pub struct SizeBreakdown {
+    pub file_size: u64,
+    pub entries: Vec<SizeEntry>,
+}
+
+/// Get the length of the element header in explicit VR.
+fn header_size(vr: VR) -> u64 {
+    match vr {
+        VR::OB
+        | VR::OD
+        | VR::OF
+        | VR::OL
+        | VR::OV
+        | VR::OW
+        | VR::SQ
+        | VR::SV
+        | VR::UC
+        | VR::UN
+        | VR::UR
+        | VR::UT
+        | VR::UV => 12,
+        _ => 8,
+    }
+}
+
+fn dataset_size(obj: &InMemDicomObject) -> u64 {
+    obj.iter().map(element_size).sum()
+}
+
+/// Estimate the encoded size of the element, including the nested items and fragments.
+fn element_size(elem: &InMemElement) -> u64 {
+    let value_size = match elem.value() {
+        Value::Primitive(value) => value.calculate_byte_len().div_ceil(2) as u64 * 2,
+        // Each item has an item header, and the sequence may have a delimiter.
+        Value::Sequence(seq) => seq.items().iter().map(|x| 8 + dataset_size(x)).sum(),
+        Value::PixelSequence(seq) => {
+            let offset_table = 8 + seq.offset_table().len() as u64 * 4;
+            let fragments: u64 = seq.fragments().iter().map(|x| 8 + x.len() as u64).sum();
+            offset_table + fragments + 8
+        }
+    };
+
+    header_size(elem.vr()) + value_size
+}
+
+/// Get the part of the file which the top-level element belongs to, or `None` if it's small enough to be
+/// counted as other elements.
+fn part_of(elem: &InMemElement, size: u64) -> Option<String> {
+    let tag = elem.header().tag;
+
+    match tag {
+        tags::PIXEL_DATA | tags::FLOAT_PIXEL_DATA | tags::DOUBLE_FLOAT_PIXEL_DATA => {
+            Some(tr("Pixel data"))
+        }
+        tags::ICON_IMAGE_SEQUENCE => Some(tr("Icon image sequence")),
+        Tag(group, 0x3000) if (0x6000..=0x601E).contains(&group) => Some(tr("Overlay data")),
+        Tag(group, _) if group % 2 == 1 => Some(trf("Private group {}", format!("{group:04X}"))),
+        _ if size >= LISTED_SIZE && (is_binary_vr(elem.vr()) || elem.vr() == VR::SQ) => {
+            Some(tag_keyword(tag))
+        }
+        _ => None,
+    }
+}
+
+/// Read the whole file and break down its size.
+pub fn breakdown(path: &Path) -> Result<SizeBreakdown, String> {
+    let file_size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    let obj = open_file(path).map_err(|e| e.to_string())?;
+
+    let mut parts: BTreeMap<String, u64> = BTreeMap::new();
+    let mut others = 0;
+    for elem in obj.iter() {
+        let size = element_size(elem);
+        match part_of(elem, size) {
+            Some(label) => *parts.entry(label).or_default() += size,
+            None => others += size,
+        }
+    }
+
+    let mut entries: Vec<SizeEntry> = parts
+        .into_iter()
+        .map(|(label, size)| SizeEntry { label, size })
+        .collect();
+    entries.push(SizeEntry {
+        label: tr("Other elements"),
+        size: others,
+    });
+    // The rest is the preamble, the file meta group and any difference of the actual encoding from the estimate.
+    let estimated: u64 = entries.iter().map(|x| x.size).sum();
+    entries.push(SizeEntry {
+        label: tr("Preamble and file meta"),
+        size: file_size.saturating_sub(estimated),
+    });
+    entries.sort_by(|a, b| b.size.cmp(&a.size));
+
+    Ok(SizeBreakdown { file_size, entries })
+}
+
+/// Format the size in bytes with a binary unit.
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+impl SizeBreakdown {
+    /// Show the parts as a sorted list with the percentages of the file size.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        ui.label(trf("File size: {}", format_size(self.file_size)));
+        ui.separator();
+
+        egui::Grid::new("size breakdown grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    let fraction = entry.size as f32 / self.file_size.max(1) as f32;
+                    ui.label(&entry.label);
+                    ui.monospace(format_size(entry.size));
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .desired_width(120.0)
+                            .text(format!("{:.1}%", fraction * 100.0)),
+                    );
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -0,0 +1,94 @@
+use crate::dataset::visit_elements;
+use dicom::core::VR;
+use dicom::core::value::Value;
+use dicom::object::InMemDicomObject;
+
+/// Statistics of a numeric element with multiple values.
+pub struct ValueStats {
+    pub path: String,
+    pub vr: VR,
+    pub values: Vec<f64>,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+/// Check if the value representation holds numbers.
+fn is_numeric_vr(vr: VR) -> bool {
+    matches!(
+        vr,
+        VR::DS
+            | VR::FD
+            | VR::FL
+            | VR::IS
+            | VR::OD
+            | VR::OF
+            | VR::OL
+            | VR::SL
+            | VR::SS
+            | VR::SV
+            | VR::UL
+            | VR::US
+            | VR::UV
+    )
+}
+
+/// Collect the statistics of all numeric elements with a value multiplicity greater than 1.
+pub fn collect(obj: &InMemDicomObject) -> Vec<ValueStats> {
+    let mut results = Vec::new();
+
+    visit_elements(obj, &mut |path, elem| {
+        let Value::Primitive(value) = elem.value() else {
+            return;
+        };
+        if !is_numeric_vr(elem.vr()) || value.multiplicity() <= 1 {
+            return;
+        }
+        let Ok(values) = value.to_multi_float64() else {
+            return;
+        };
+
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+        results.push(ValueStats {
+            path: path.to_string(),
+            vr: elem.vr(),
+            values,
+            min,
+            max,
+            mean,
+        });
+    });
+
+    results
+}
+
+/// Draw a small line chart of the values.
+pub fn sparkline(ui: &mut egui::Ui, stats: &ValueStats) {
+    let (rect, _response) = ui.allocate_exact_size(egui::vec2(120.0, 16.0), egui::Sense::hover());
+    if stats.values.len() < 2 {
+        return;
+    }
+    let range = (stats.max - stats.min).max(f64::EPSILON);
+    let step = rect.width() / (stats.values.len() - 1) as f32;
+
+    let points = stats
+        .values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let y = ((value - stats.min) / range) as f32;
+            egui::pos2(
+                rect.left() + index as f32 * step,
+                rect.bottom() - y * rect.height(),
+            )
+        })
+        .collect();
+
+    ui.painter().add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.0, egui::Color32::BLUE),
+    ));
+}
@@ -0,0 +1,243 @@
+//! Graph of the instances of a study, linked to their series and to the instances they reference, e.g. the images
+//! of a presentation state, a structured report or a segmentation.
+
+use crate::dataset::{element_string, open_header};
+use crate::i18n::{tr, trf};
+use crate::references;
+use crate::tasks::TaskContext;
+use dicom::dictionary_std::tags;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const COLUMN_WIDTH: f32 = 180.0;
+const ROW_HEIGHT: f32 = 24.0;
+const NODE_RADIUS: f32 = 6.0;
+
+/// An instance of the study.
+struct Node {
+    path: PathBuf,
+    modality: String,
+    instance_number: String,
+    column: usize,
+    row: usize,
+}
+
+/// A series of the study, as a column of its instances.
+struct Series {
+    uid: String,
+    description: String,
+}
+
+/// The instances of a study by their series, and the references between them.
+#[derive(Default)]
+pub struct StudyGraph {
+    series: Vec<Series>,
+    nodes: Vec<Node>,
+    /// The referencing and the referenced nodes.
+    edges: Vec<(usize, usize)>,
+    /// The number of referenced instances not in the folder.
+    missing_references: usize,
+}
+
+impl StudyGraph {
+    /// Collect the instances of every study in the files, and the references between them, so that selecting another
+    /// study needs no reading. Stops reading the files if cancelled.
+    pub fn collect(files: &[PathBuf], context: &TaskContext) -> HashMap<String, Self> {
+        let mut graphs: HashMap<String, Self> = HashMap::new();
+        let mut node_of_uid: HashMap<(String, String), usize> = HashMap::new();
+        let mut referenced_uids: Vec<(String, usize, Vec<String>)> = Vec::new();
+
+        for (index, file) in files.iter().enumerate() {
+            if context.is_cancelled() {
+                break;
+            }
+            context.set_progress(index as f32 / files.len() as f32);
+            let obj = match open_header(file) {
+                Ok(x) => x,
+                Err(e) => {
+                    log::error!("Failed to read {}: {e}", file.display());
+                    continue;
+                }
+            };
+            let study_uid = element_string(&obj, tags::STUDY_INSTANCE_UID).unwrap_or_default();
+            let graph = graphs.entry(study_uid.clone()).or_default();
+
+            let series_uid = element_string(&obj, tags::SERIES_INSTANCE_UID).unwrap_or_default();
+            let column = match graph.series.iter().position(|x| x.uid == series_uid) {
+                Some(x) => x,
+                None => {
+                    graph.series.push(Series {
+                        uid: series_uid,
+                        description: element_string(&obj, tags::SERIES_DESCRIPTION)
+                            .unwrap_or_default(),
+                    });
+                    graph.series.len() - 1
+                }
+            };
+            let row = graph.nodes.iter().filter(|x| x.column == column).count();
+
+            let index = graph.nodes.len();
+            if let Some(uid) = element_string(&obj, tags::SOP_INSTANCE_UID) {
+                node_of_uid.insert((study_uid.clone(), uid), index);
+            }
+            referenced_uids.push((
+                study_uid,
+                index,
+                references::collect(&obj)
+                    .into_iter()
+                    .map(|x| x.uid)
+                    .collect(),
+            ));
+            graph.nodes.push(Node {
+                path: file.clone(),
+                modality: element_string(&obj, tags::MODALITY).unwrap_or_default(),
+                instance_number: element_string(&obj, tags::INSTANCE_NUMBER).unwrap_or_default(),
+                column,
+                row,
+            });
+        }
+
+        for (study_uid, from, uids) in referenced_uids {
+            let Some(graph) = graphs.get_mut(&study_uid) else {
+                continue;
+            };
+            for uid in uids {
+                match node_of_uid.get(&(study_uid.clone(), uid)) {
+                    Some(to) => graph.edges.push((from, *to)),
+                    None => graph.missing_references += 1,
+                }
+            }
+        }
+
+        graphs
+    }
+
+    /// Show the series as columns of their instances, with arrows from the referencing instances.
+    /// Returns the file of the instance clicked.
+    pub fn ui(&self, ui: &mut egui::Ui, selected_file: Option<&PathBuf>) -> Option<PathBuf> {
+        let mut clicked = None;
+
+        if self.nodes.is_empty() {
+            ui.label(tr("No instances of the study found."));
+            return None;
+        }
+        ui.label(format!(
+            "{}, {}, {}",
+            trf("{} series", self.series.len()),
+            trf("{} instances", self.nodes.len()),
+            trf("{} references", self.edges.len())
+        ));
+        if self.missing_references > 0 {
+            ui.label(trf(
+                "{} referenced instances not in the folder",
+                self.missing_references,
+            ));
+        }
+        ui.separator();
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            let rows = self
+                .nodes
+                .iter()
+                .map(|x| x.row + 1)
+                .max()
+                .unwrap_or_default();
+            let size = egui::vec2(
+                COLUMN_WIDTH * self.series.len() as f32,
+                ROW_HEIGHT * (rows + 1) as f32,
+            );
+            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            let visuals = ui.visuals().clone();
+            let font = egui::TextStyle::Small.resolve(ui.style());
+            let series_pos = |column: usize| {
+                egui::pos2(
+                    rect.left() + COLUMN_WIDTH * column as f32 + NODE_RADIUS * 2.0,
+                    rect.top() + ROW_HEIGHT / 2.0,
+                )
+            };
+            let node_pos = |node: &Node| {
+                series_pos(node.column)
+                    + egui::vec2(NODE_RADIUS * 2.0, ROW_HEIGHT * (node.row + 1) as f32)
+            };
+
+            // The series membership, as a line down each column.
+            for (column, series) in self.series.iter().enumerate() {
+                let top = series_pos(column);
+                let label = if series.description.is_empty() {
+                    &series.uid
+                } else {
+                    &series.description
+                };
+                painter.text(
+                    top + egui::vec2(NODE_RADIUS * 2.0, 0.0),
+                    egui::Align2::LEFT_CENTER,
+                    label,
+                    egui::TextStyle::Body.resolve(ui.style()),
+                    visuals.text_color(),
+                );
+                painter.rect_filled(
+                    egui::Rect::from_center_size(top, egui::Vec2::splat(NODE_RADIUS * 2.0)),
+                    2.0,
+                    visuals.widgets.inactive.bg_fill,
+                );
+                for node in self.nodes.iter().filter(|x| x.column == column) {
+                    let pos = node_pos(node);
+                    painter.line_segment(
+                        [egui::pos2(top.x, pos.y), pos],
+                        visuals.widgets.noninteractive.bg_stroke,
+                    );
+                }
+                if let Some(last) = self.nodes.iter().filter(|x| x.column == column).last() {
+                    painter.vline(
+                        top.x,
+                        top.y..=node_pos(last).y,
+                        visuals.widgets.noninteractive.bg_stroke,
+                    );
+                }
+            }
+
+            // The references, as arrows to the referenced instances.
+            let reference_stroke = egui::Stroke::new(1.5, visuals.hyperlink_color);
+            for (from, to) in self.edges.iter().filter(|(from, to)| from != to) {
+                let start = node_pos(&self.nodes[*from]);
+                let end = node_pos(&self.nodes[*to]);
+                let direction = (end - start).normalized() * NODE_RADIUS;
+                painter.arrow(
+                    start + direction,
+                    end - start - direction * 2.0,
+                    reference_stroke,
+                );
+            }
+
+            for (index, node) in self.nodes.iter().enumerate() {
+                let center = node_pos(node);
+                let response = ui
+                    .interact(
+                        egui::Rect::from_center_size(center, egui::Vec2::splat(NODE_RADIUS * 3.0)),
+                        ui.id().with(index),
+                        egui::Sense::click(),
+                    )
+                    .on_hover_text(node.path.display().to_string());
+                let color = if response.hovered() || selected_file == Some(&node.path) {
+                    visuals.selection.bg_fill
+                } else {
+                    visuals.widgets.active.bg_fill
+                };
+                painter.circle_filled(center, NODE_RADIUS, color);
+                painter.text(
+                    center + egui::vec2(NODE_RADIUS * 2.0, 0.0),
+                    egui::Align2::LEFT_CENTER,
+                    format!("{} {}", node.modality, node.instance_number),
+                    font.clone(),
+                    visuals.weak_text_color(),
+                );
+                if response.clicked() {
+                    clicked = Some(node.path.clone());
+                }
+            }
+        });
+
+        clicked
+    }
+}
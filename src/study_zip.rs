@@ -0,0 +1,200 @@
+//! Export of a study as a ZIP archive for sharing a case, optionally anonymized and with a DICOMDIR.
+//! The anonymized files and the media folder of the DICOMDIR are staged in the temp dir, which is removed afterwards.
+
+use crate::anonymize::{Anonymizer, anonymize_files};
+use crate::audit::AuditLog;
+use crate::dataset::{element_string, new_uid, open_header};
+use crate::dicomdir::{self, MediaProfile};
+use crate::i18n::{tr, trf};
+use crate::zip::ZipWriter;
+use dicom::dictionary_std::tags;
+use std::collections::HashSet;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Find the files of the study in the folder.
+pub fn study_files(files: &[PathBuf], study_uid: &str) -> Vec<PathBuf> {
+    files
+        .iter()
+        .filter(|file| {
+            open_header(file).is_ok_and(|obj| {
+                element_string(&obj, tags::STUDY_INSTANCE_UID).as_deref() == Some(study_uid)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Add the files under the folder to the archive, named by their paths relative to the folder.
+fn add_dir(zip: &mut ZipWriter<impl std::io::Write>, dir: &Path, prefix: &Path) -> Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_>>()?;
+    entries.sort_by_key(|x| x.path());
+
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            add_dir(zip, &path, prefix)?;
+        } else {
+            let name = path.strip_prefix(prefix).unwrap_or(&path);
+            let name = name
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            zip.add_file(&name, &std::fs::read(&path)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The options of the export, and the study to export.
+pub struct StudyZipTool {
+    pub study_uid: String,
+    pub files: Vec<PathBuf>,
+    pub anonymize: bool,
+    pub patient_name: String,
+    pub patient_id: String,
+    pub include_dicomdir: bool,
+    pub output: Option<PathBuf>,
+    status: String,
+}
+
+impl Default for StudyZipTool {
+    fn default() -> Self {
+        Self {
+            study_uid: String::new(),
+            files: Vec::new(),
+            anonymize: true,
+            patient_name: "ANONYMOUS".to_string(),
+            patient_id: "ANON0001".to_string(),
+            include_dicomdir: false,
+            output: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl StudyZipTool {
+    /// Pick the study to export.
+    pub fn set_study(&mut self, study_uid: String, files: Vec<PathBuf>) {
+        self.study_uid = study_uid;
+        self.files = files;
+        self.status.clear();
+    }
+
+    /// Write the archive, staging the anonymized files and the media folder in the temp dir. The warnings of the
+    /// DICOMDIR are returned.
+    fn export(&self, output: &Path) -> Result<Vec<String>> {
+        let staging = std::env::temp_dir()
+            .join("rsdicombrowser")
+            .join("share")
+            .join(new_uid());
+        let result = self.export_staged(output, &staging);
+        if staging.exists()
+            && let Err(e) = std::fs::remove_dir_all(&staging)
+        {
+            log::error!("Failed to remove {}: {e}", staging.display());
+        }
+        result
+    }
+
+    fn export_staged(&self, output: &Path, staging: &Path) -> Result<Vec<String>> {
+        let files = if self.anonymize {
+            let mut anonymizer = Anonymizer::new(&self.patient_name, &self.patient_id);
+            anonymize_files(
+                &self.files,
+                &mut anonymizer,
+                None,
+                &staging.join("anonymized"),
+            )?
+            .0
+        } else {
+            self.files.clone()
+        };
+
+        let mut zip = ZipWriter::new(std::io::BufWriter::new(std::fs::File::create(output)?));
+        let mut warnings = Vec::new();
+        if self.include_dicomdir {
+            let media = staging.join("media");
+            // The profile only affects the warnings, which are left to the reader.
+            warnings = dicomdir::create(&files, &media, "STUDY", MediaProfile::GeneralCd)?;
+            add_dir(&mut zip, &media, &media)?;
+        } else {
+            let mut names = HashSet::new();
+            for (index, file) in files.iter().enumerate() {
+                let name = file
+                    .file_name()
+                    .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                // The files of different folders may have the same names.
+                let name = if names.contains(&name) {
+                    format!("{}_{name}", index + 1)
+                } else {
+                    name
+                };
+                zip.add_file(&name, &std::fs::read(file)?)?;
+                names.insert(name);
+            }
+        }
+        zip.finish()?;
+
+        Ok(warnings)
+    }
+
+    /// Show the options of the export. Returns true if the output file should be picked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, audit_log: &mut AuditLog) -> bool {
+        let mut pick_output = false;
+
+        ui.monospace(&self.study_uid);
+        ui.label(trf("{} files", self.files.len()));
+        ui.checkbox(&mut self.anonymize, tr("Anonymize"));
+        ui.add_enabled_ui(self.anonymize, |ui| {
+            egui::Grid::new("study zip grid").show(ui, |ui| {
+                ui.label(tr("Patient name"));
+                ui.text_edit_singleline(&mut self.patient_name);
+                ui.end_row();
+
+                ui.label(tr("Patient ID"));
+                ui.text_edit_singleline(&mut self.patient_id);
+                ui.end_row();
+            });
+        });
+        ui.checkbox(&mut self.include_dicomdir, tr("Include a DICOMDIR"));
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Output file"));
+            let output = self
+                .output
+                .as_ref()
+                .map_or(tr("None"), |x| x.display().to_string());
+            ui.monospace(output);
+            pick_output = ui.button(tr("Browse")).clicked();
+        });
+
+        if let Some(output) = self.output.as_ref()
+            && ui
+                .add_enabled(!self.files.is_empty(), egui::Button::new(tr("Export")))
+                .clicked()
+        {
+            let result = self.export(output);
+            if result.is_ok() {
+                audit_log.record(
+                    "Export study as ZIP",
+                    self.files.clone(),
+                    vec![output.clone()],
+                );
+            }
+            self.status = match result {
+                Ok(warnings) if warnings.is_empty() => tr("Study exported."),
+                Ok(warnings) => warnings.join("\n"),
+                Err(e) => e.to_string(),
+            };
+        }
+
+        if !self.status.is_empty() {
+            egui::ScrollArea::vertical().show(ui, |ui| ui.label(&self.status));
+        }
+
+        pick_output
+    }
+}
@@ -0,0 +1,268 @@
+use crate::dataset::{element_string, tag_keyword};
+use crate::i18n::{tr, trf};
+use dicom::core::Tag;
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::InMemDicomObject;
+use std::collections::HashMap;
+
+/// Render a summary of a dataset tailored to its SOP class.
+pub trait SummaryRenderer {
+    /// The title of the summary panel.
+    fn title(&self) -> &str;
+
+    /// Show the summary of the dataset.
+    fn ui(&self, ui: &mut egui::Ui, obj: &InMemDicomObject);
+}
+
+/// Show the values of a fixed list of tags.
+pub struct TagListSummary {
+    title: &'static str,
+    tags: &'static [Tag],
+}
+
+impl TagListSummary {
+    pub const fn new(title: &'static str, tags: &'static [Tag]) -> Self {
+        Self { title, tags }
+    }
+}
+
+impl SummaryRenderer for TagListSummary {
+    fn title(&self) -> &str {
+        self.title
+    }
+
+    fn ui(&self, ui: &mut egui::Ui, obj: &InMemDicomObject) {
+        summary_grid(ui, obj, self.tags);
+    }
+}
+
+/// Show the values of the tags in a two-column grid, skipping the missing ones.
+fn summary_grid(ui: &mut egui::Ui, obj: &InMemDicomObject, tags: &[Tag]) {
+    egui::Grid::new("summary grid").show(ui, |ui| {
+        for tag in tags {
+            if let Some(value) = element_string(obj, *tag) {
+                ui.strong(tag_keyword(*tag));
+                ui.monospace(value);
+                ui.end_row();
+            }
+        }
+    });
+}
+
+const GENERAL_TAGS: &[Tag] = &[
+    tags::PATIENT_NAME,
+    tags::PATIENT_ID,
+    tags::STUDY_DATE,
+    tags::STUDY_DESCRIPTION,
+    tags::MODALITY,
+    tags::SERIES_DESCRIPTION,
+    tags::SOP_CLASS_UID,
+];
+
+const CT_TAGS: &[Tag] = &[
+    tags::PATIENT_NAME,
+    tags::PATIENT_ID,
+    tags::SERIES_DESCRIPTION,
+    tags::INSTANCE_NUMBER,
+    tags::ROWS,
+    tags::COLUMNS,
+    tags::SLICE_THICKNESS,
+    tags::PIXEL_SPACING,
+    tags::KVP,
+    tags::CONVOLUTION_KERNEL,
+    tags::RESCALE_INTERCEPT,
+    tags::RESCALE_SLOPE,
+    tags::WINDOW_CENTER,
+    tags::WINDOW_WIDTH,
+];
+
+const MR_TAGS: &[Tag] = &[
+    tags::PATIENT_NAME,
+    tags::PATIENT_ID,
+    tags::SERIES_DESCRIPTION,
+    tags::INSTANCE_NUMBER,
+    tags::ROWS,
+    tags::COLUMNS,
+    tags::SLICE_THICKNESS,
+    tags::PIXEL_SPACING,
+    tags::MAGNETIC_FIELD_STRENGTH,
+    tags::SCANNING_SEQUENCE,
+    tags::REPETITION_TIME,
+    tags::ECHO_TIME,
+    tags::FLIP_ANGLE,
+];
+
+const GSPS_TAGS: &[Tag] = &[
+    tags::CONTENT_LABEL,
+    tags::CONTENT_DESCRIPTION,
+    tags::PRESENTATION_CREATION_DATE,
+    tags::PRESENTATION_CREATION_TIME,
+    tags::CONTENT_CREATOR_NAME,
+];
+
+const RT_PLAN_TAGS: &[Tag] = &[
+    tags::RT_PLAN_LABEL,
+    tags::RT_PLAN_NAME,
+    tags::RT_PLAN_DATE,
+    tags::RT_PLAN_GEOMETRY,
+];
+
+const RT_DOSE_TAGS: &[Tag] = &[
+    tags::DOSE_UNITS,
+    tags::DOSE_TYPE,
+    tags::DOSE_SUMMATION_TYPE,
+    tags::DOSE_GRID_SCALING,
+    tags::NUMBER_OF_FRAMES,
+];
+
+const ENCAPSULATED_DOCUMENT_TAGS: &[Tag] = &[
+    tags::DOCUMENT_TITLE,
+    tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT,
+    tags::BURNED_IN_ANNOTATION,
+    tags::CONTENT_DATE,
+    tags::CONTENT_TIME,
+];
+
+/// Show the document title and status flags of a structured report.
+struct StructuredReportSummary;
+
+impl SummaryRenderer for StructuredReportSummary {
+    fn title(&self) -> &str {
+        "Structured Report"
+    }
+
+    fn ui(&self, ui: &mut egui::Ui, obj: &InMemDicomObject) {
+        let title = obj
+            .get(tags::CONCEPT_NAME_CODE_SEQUENCE)
+            .and_then(|x| x.items())
+            .and_then(|x| x.first())
+            .and_then(|x| element_string(x, tags::CODE_MEANING));
+        if let Some(title) = title {
+            ui.heading(title);
+        }
+
+        summary_grid(
+            ui,
+            obj,
+            &[
+                tags::COMPLETION_FLAG,
+                tags::VERIFICATION_FLAG,
+                tags::CONTENT_DATE,
+                tags::CONTENT_TIME,
+            ],
+        );
+    }
+}
+
+/// Show the ROIs of a RT structure set.
+struct StructureSetSummary;
+
+impl SummaryRenderer for StructureSetSummary {
+    fn title(&self) -> &str {
+        "RT Structure Set"
+    }
+
+    fn ui(&self, ui: &mut egui::Ui, obj: &InMemDicomObject) {
+        summary_grid(
+            ui,
+            obj,
+            &[tags::STRUCTURE_SET_LABEL, tags::STRUCTURE_SET_DATE],
+        );
+
+        let rois = obj
+            .get(tags::STRUCTURE_SET_ROI_SEQUENCE)
+            .and_then(|x| x.items())
+            .unwrap_or(&[]);
+        ui.label(trf("{} ROIs", rois.len()));
+        for roi in rois {
+            let number = element_string(roi, tags::ROI_NUMBER).unwrap_or_default();
+            let name = element_string(roi, tags::ROI_NAME).unwrap_or_default();
+            ui.monospace(format!("{number}: {name}"));
+        }
+    }
+}
+
+/// Summary renderers keyed by the SOP Class UID.
+pub struct SummaryRegistry {
+    renderers: HashMap<String, Box<dyn SummaryRenderer>>,
+    fallback: Box<dyn SummaryRenderer>,
+}
+
+impl SummaryRegistry {
+    /// Register the renderer for the SOP class, replacing any existing one.
+    pub fn register(&mut self, sop_class_uid: &str, renderer: Box<dyn SummaryRenderer>) {
+        self.renderers.insert(sop_class_uid.to_string(), renderer);
+    }
+
+    /// Get the renderer for the SOP class, or the general one if none is registered.
+    pub fn get(&self, sop_class_uid: &str) -> &dyn SummaryRenderer {
+        self.renderers
+            .get(sop_class_uid)
+            .unwrap_or(&self.fallback)
+            .as_ref()
+    }
+
+    /// Show the summary of the dataset with the renderer of its SOP class.
+    pub fn ui(&self, ui: &mut egui::Ui, obj: &InMemDicomObject) {
+        let sop_class_uid = element_string(obj, tags::SOP_CLASS_UID).unwrap_or_default();
+        let renderer = self.get(&sop_class_uid);
+
+        egui::CollapsingHeader::new(tr(renderer.title()))
+            .id_salt("summary")
+            .default_open(true)
+            .show(ui, |ui| renderer.ui(ui, obj));
+    }
+}
+
+impl Default for SummaryRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            renderers: HashMap::new(),
+            fallback: Box::new(TagListSummary::new("Summary", GENERAL_TAGS)),
+        };
+
+        for uid in [uids::CT_IMAGE_STORAGE, uids::ENHANCED_CT_IMAGE_STORAGE] {
+            registry.register(uid, Box::new(TagListSummary::new("CT Image", CT_TAGS)));
+        }
+        for uid in [uids::MR_IMAGE_STORAGE, uids::ENHANCED_MR_IMAGE_STORAGE] {
+            registry.register(uid, Box::new(TagListSummary::new("MR Image", MR_TAGS)));
+        }
+        for uid in [
+            uids::BASIC_TEXT_SR_STORAGE,
+            uids::ENHANCED_SR_STORAGE,
+            uids::COMPREHENSIVE_SR_STORAGE,
+        ] {
+            registry.register(uid, Box::new(StructuredReportSummary));
+        }
+        registry.register(
+            uids::GRAYSCALE_SOFTCOPY_PRESENTATION_STATE_STORAGE,
+            Box::new(TagListSummary::new("Presentation State", GSPS_TAGS)),
+        );
+        registry.register(
+            uids::RT_STRUCTURE_SET_STORAGE,
+            Box::new(StructureSetSummary),
+        );
+        registry.register(
+            uids::RT_PLAN_STORAGE,
+            Box::new(TagListSummary::new("RT Plan", RT_PLAN_TAGS)),
+        );
+        registry.register(
+            uids::RT_DOSE_STORAGE,
+            Box::new(TagListSummary::new("RT Dose", RT_DOSE_TAGS)),
+        );
+        for uid in [
+            uids::ENCAPSULATED_PDF_STORAGE,
+            uids::ENCAPSULATED_CDA_STORAGE,
+        ] {
+            registry.register(
+                uid,
+                Box::new(TagListSummary::new(
+                    "Encapsulated Document",
+                    ENCAPSULATED_DOCUMENT_TAGS,
+                )),
+            );
+        }
+
+        registry
+    }
+}
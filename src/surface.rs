@@ -0,0 +1,247 @@
+//! Export of the surface of an ROI of an RT structure set as STL or OBJ, for 3D printing or the planning tools.
+//! The segmentation objects are not supported since their masks are in the pixel data, which the app doesn't decode.
+
+use crate::audit::AuditLog;
+use crate::dataset::{element_string, open_header};
+use crate::i18n::{tr, trf};
+use crate::mesh::{self, Point};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The file format of the surface.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SurfaceFormat {
+    Stl,
+    Obj,
+}
+
+impl SurfaceFormat {
+    pub const ALL: [Self; 2] = [Self::Stl, Self::Obj];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Stl => "STL",
+            Self::Obj => "OBJ",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Stl => "stl",
+            Self::Obj => "obj",
+        }
+    }
+}
+
+/// An ROI of the structure set with its closed planar contours.
+struct Roi {
+    number: i64,
+    name: String,
+    contours: Vec<Vec<Point>>,
+}
+
+fn items(obj: &InMemDicomObject, tag: dicom::core::Tag) -> &[InMemDicomObject] {
+    obj.get(tag).and_then(|x| x.items()).unwrap_or(&[])
+}
+
+/// Read the ROIs of the structure set, with the contours of each.
+fn read_rois(obj: &InMemDicomObject) -> Vec<Roi> {
+    let mut rois: Vec<Roi> = items(obj, tags::STRUCTURE_SET_ROI_SEQUENCE)
+        .iter()
+        .filter_map(|item| {
+            Some(Roi {
+                number: item.get(tags::ROI_NUMBER)?.to_int().ok()?,
+                name: element_string(item, tags::ROI_NAME).unwrap_or_default(),
+                contours: Vec::new(),
+            })
+        })
+        .collect();
+
+    for item in items(obj, tags::ROI_CONTOUR_SEQUENCE) {
+        let Some(number) = item
+            .get(tags::REFERENCED_ROI_NUMBER)
+            .and_then(|x| x.to_int::<i64>().ok())
+        else {
+            continue;
+        };
+        let Some(roi) = rois.iter_mut().find(|x| x.number == number) else {
+            continue;
+        };
+
+        for contour in items(item, tags::CONTOUR_SEQUENCE) {
+            // The points and the open contours outline no volume.
+            if element_string(contour, tags::CONTOUR_GEOMETRIC_TYPE).as_deref()
+                != Some("CLOSED_PLANAR")
+            {
+                continue;
+            }
+            let Some(data) = contour
+                .get(tags::CONTOUR_DATA)
+                .and_then(|x| x.to_multi_float64().ok())
+            else {
+                continue;
+            };
+            roi.contours
+                .push(data.chunks_exact(3).map(|x| [x[0], x[1], x[2]]).collect());
+        }
+    }
+
+    rois.retain(|x| !x.contours.is_empty());
+    rois
+}
+
+/// The options of the export, and the ROIs of the selected structure set.
+pub struct SurfaceTool {
+    source: Option<PathBuf>,
+    rois: Vec<Roi>,
+    roi: usize,
+    pub pixel_spacing: f64,
+    pub format: SurfaceFormat,
+    pub output: Option<PathBuf>,
+    status: String,
+}
+
+impl Default for SurfaceTool {
+    fn default() -> Self {
+        Self {
+            source: None,
+            rois: Vec::new(),
+            roi: 0,
+            pixel_spacing: 1.0,
+            format: SurfaceFormat::Stl,
+            output: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl SurfaceTool {
+    /// Read the ROIs of the file if it is not the current one.
+    fn set_source(&mut self, path: Option<&Path>) {
+        if self.source.as_deref() == path {
+            return;
+        }
+
+        self.source = path.map(Path::to_path_buf);
+        self.rois.clear();
+        self.roi = 0;
+        self.status.clear();
+        let Some(path) = path else {
+            return;
+        };
+        match open_header(path) {
+            Ok(obj) => {
+                if element_string(&obj, tags::MODALITY).as_deref() == Some("RTSTRUCT") {
+                    self.rois = read_rois(&obj);
+                }
+            }
+            Err(e) => log::error!("Failed to open {}: {e}", path.display()),
+        }
+    }
+
+    /// Mesh the ROI and write it, returning the path written and the number of triangles.
+    fn export(&self, roi: &Roi, output: &Path) -> Result<(PathBuf, usize)> {
+        let triangles =
+            mesh::mesh_contours(&roi.contours, self.pixel_spacing).map_err(Error::other)?;
+        let output = if output.extension().is_some() {
+            output.to_path_buf()
+        } else {
+            output.with_extension(self.format.extension())
+        };
+
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&output)?);
+        match self.format {
+            SurfaceFormat::Stl => mesh::write_stl(&mut writer, &roi.name, &triangles)?,
+            SurfaceFormat::Obj => mesh::write_obj(&mut writer, &roi.name, &triangles)?,
+        }
+        std::io::Write::flush(&mut writer)?;
+
+        Ok((output, triangles.len()))
+    }
+
+    /// Show the options of the export for the selected file. Returns true if the output file should be picked.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        selected_file: Option<&Path>,
+        audit_log: &mut AuditLog,
+    ) -> bool {
+        let mut pick_output = false;
+        self.set_source(selected_file);
+
+        let Some(source) = self.source.clone() else {
+            ui.label(tr("Select an RT structure set."));
+            return false;
+        };
+        if self.rois.is_empty() {
+            ui.label(tr("The selected file has no ROIs with closed contours."));
+            return false;
+        }
+
+        egui::Grid::new("surface grid").show(ui, |ui| {
+            ui.label(tr("ROI"));
+            let roi = &self.rois[self.roi];
+            egui::ComboBox::from_id_salt("surface roi")
+                .selected_text(format!("{} {}", roi.number, roi.name))
+                .show_ui(ui, |ui| {
+                    for (index, roi) in self.rois.iter().enumerate() {
+                        ui.selectable_value(
+                            &mut self.roi,
+                            index,
+                            format!("{} {}", roi.number, roi.name),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label(tr("Pixel spacing (mm)"));
+            ui.add(
+                egui::DragValue::new(&mut self.pixel_spacing)
+                    .range(0.1..=10.0)
+                    .speed(0.1),
+            )
+            .on_hover_text(tr(
+                "The in-plane size of the voxels the contours are filled into. The slices are spaced as the contours.",
+            ));
+            ui.end_row();
+
+            ui.label(tr("Format"));
+            ui.horizontal(|ui| {
+                for format in SurfaceFormat::ALL {
+                    ui.radio_value(&mut self.format, format, format.label());
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Output file"));
+            let output = self
+                .output
+                .as_ref()
+                .map_or(tr("None"), |x| x.display().to_string());
+            ui.monospace(output);
+            pick_output = ui.button(tr("Browse")).clicked();
+        });
+
+        if let Some(output) = self.output.as_ref()
+            && ui.button(tr("Export")).clicked()
+        {
+            self.status = match self.export(&self.rois[self.roi], output) {
+                Ok((path, triangles)) => {
+                    audit_log.record("Export ROI surface", vec![source], vec![path]);
+                    trf("Exported {} triangles.", triangles)
+                }
+                Err(e) => e.to_string(),
+            };
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        pick_output
+    }
+}
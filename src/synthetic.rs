@@ -0,0 +1,448 @@
+//! Generation of a synthetic CT or MR series, e.g. to test a PACS or a gateway without real patient data.
+//! The slices are axial, stacked along the patient axis, and share the study, series and frame of reference UIDs.
+//! The series is written on a worker thread, as a large one takes a while.
+
+use crate::audit::AuditLog;
+use crate::bulk_edit::EditAction;
+use crate::dataset::{check_text_value, new_uid, text_element, write_file};
+use crate::i18n::{tr, trf};
+use crate::organize::avoid_collision;
+use crate::tasks::{Task, TaskContext, TaskPool};
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::InMemDicomObject;
+use dicom::object::mem::InMemElement;
+use std::collections::HashSet;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+
+/// The ellipses of the modified Shepp-Logan phantom: the intensity, the half axes, the centre and the rotation in
+/// degrees, in the coordinates of a square from -1 to 1.
+const SHEPP_LOGAN: [(f64, f64, f64, f64, f64, f64); 10] = [
+    (1.0, 0.69, 0.92, 0.0, 0.0, 0.0),
+    (-0.8, 0.6624, 0.874, 0.0, -0.0184, 0.0),
+    (-0.2, 0.11, 0.31, 0.22, 0.0, -18.0),
+    (-0.2, 0.16, 0.41, -0.22, 0.0, 18.0),
+    (0.1, 0.21, 0.25, 0.0, 0.35, 0.0),
+    (0.1, 0.046, 0.046, 0.0, 0.1, 0.0),
+    (0.1, 0.046, 0.046, 0.0, -0.1, 0.0),
+    (0.1, 0.046, 0.023, -0.08, -0.605, 0.0),
+    (0.1, 0.023, 0.023, 0.0, -0.606, 0.0),
+    (0.1, 0.023, 0.046, 0.06, -0.605, 0.0),
+];
+
+/// The largest stored value of the pixels, which maps to an intensity of 1.
+const MAX_STORED_VALUE: f64 = 2000.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Modality {
+    Ct,
+    Mr,
+}
+
+impl Modality {
+    fn code(self) -> &'static str {
+        match self {
+            Self::Ct => "CT",
+            Self::Mr => "MR",
+        }
+    }
+
+    fn sop_class_uid(self) -> &'static str {
+        match self {
+            Self::Ct => uids::CT_IMAGE_STORAGE,
+            Self::Mr => uids::MR_IMAGE_STORAGE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pattern {
+    Gradient,
+    Phantom,
+    Checkerboard,
+}
+
+impl Pattern {
+    const ALL: [Self; 3] = [Self::Gradient, Self::Phantom, Self::Checkerboard];
+
+    fn label(self) -> String {
+        match self {
+            Self::Gradient => tr("Gradient"),
+            Self::Phantom => tr("Shepp-Logan phantom"),
+            Self::Checkerboard => tr("Checkerboard"),
+        }
+    }
+
+    /// Get the intensity, from 0 to 1, at the position in the volume, each coordinate from -1 to 1.
+    fn intensity(self, x: f64, y: f64, z: f64) -> f64 {
+        match self {
+            Self::Gradient => (x + y + 2.0) / 4.0,
+            // The ellipses are extended to ellipsoids, so that the phantom shrinks towards the first and last slices.
+            Self::Phantom => SHEPP_LOGAN
+                .iter()
+                .filter(|(_, a, b, x0, y0, angle)| {
+                    let (sin, cos) = angle.to_radians().sin_cos();
+                    let (dx, dy) = (x - x0, y - y0);
+                    let u = (dx * cos + dy * sin) / a;
+                    let v = (dy * cos - dx * sin) / b;
+                    u * u + v * v + z * z <= 1.0
+                })
+                .map(|(intensity, ..)| intensity)
+                .sum::<f64>()
+                .clamp(0.0, 1.0),
+            Self::Checkerboard => {
+                let square = |x: f64| ((x + 1.0) * 4.0).floor() as i64;
+                ((square(x) + square(y) + square(z)) % 2) as f64
+            }
+        }
+    }
+}
+
+/// The options of the series to generate.
+#[derive(Clone)]
+struct Options {
+    modality: Modality,
+    patient_name: String,
+    patient_id: String,
+    birth_date: String,
+    sex: String,
+    study_description: String,
+    columns: u16,
+    rows: u16,
+    slices: u16,
+    /// The pixel spacing in mm, the same along the rows and the columns.
+    pixel_spacing: f64,
+    /// The slice thickness in mm, which is also the spacing between the slices.
+    slice_thickness: f64,
+    pattern: Pattern,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            modality: Modality::Ct,
+            patient_name: "SYNTHETIC^PATIENT".to_string(),
+            patient_id: "SYN0001".to_string(),
+            birth_date: "19700101".to_string(),
+            sex: "O".to_string(),
+            study_description: "Synthetic study".to_string(),
+            columns: 256,
+            rows: 256,
+            slices: 20,
+            pixel_spacing: 1.0,
+            slice_thickness: 5.0,
+            pattern: Pattern::Phantom,
+        }
+    }
+}
+
+fn us_element(tag: Tag, value: u16) -> InMemElement {
+    DataElement::new(tag, VR::US, PrimitiveValue::from(value))
+}
+
+/// Get the pixels of the slice, the row at the top first.
+fn slice_pixels(options: &Options, slice: u16) -> Vec<u16> {
+    let coordinate = |index: u16, count: u16| {
+        if count > 1 {
+            2.0 * f64::from(index) / f64::from(count - 1) - 1.0
+        } else {
+            0.0
+        }
+    };
+    let z = coordinate(slice, options.slices);
+
+    let mut pixels = Vec::with_capacity(usize::from(options.rows) * usize::from(options.columns));
+    for row in 0..options.rows {
+        // The rows go down towards the posterior, while the phantom has its y axis up.
+        let y = -coordinate(row, options.rows);
+        for column in 0..options.columns {
+            let x = coordinate(column, options.columns);
+            let intensity = options.pattern.intensity(x, y, z);
+            pixels.push((intensity * MAX_STORED_VALUE).round() as u16);
+        }
+    }
+    pixels
+}
+
+/// Generate the series into the folder, stopping between the slices if cancelled. Returns the files written.
+fn generate(options: &Options, output_dir: &Path, context: &TaskContext) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(output_dir)?;
+
+    let now = chrono::Local::now();
+    let date = now.format("%Y%m%d").to_string();
+    let time = now.format("%H%M%S").to_string();
+    let study_uid = new_uid();
+    let series_uid = new_uid();
+    let frame_of_reference_uid = new_uid();
+    let modality = options.modality;
+    let mut taken = HashSet::new();
+    let mut outputs = Vec::new();
+
+    for slice in 0..options.slices {
+        if context.is_cancelled() {
+            return Err(Error::other("the generation was cancelled"));
+        }
+        context.set_progress(f32::from(slice) / f32::from(options.slices));
+        let location = f64::from(slice) * options.slice_thickness;
+        let mut obj = InMemDicomObject::from_element_iter([
+            text_element(tags::SPECIFIC_CHARACTER_SET, VR::CS, "ISO_IR 192"),
+            text_element(tags::SOP_CLASS_UID, VR::UI, modality.sop_class_uid()),
+            text_element(tags::SOP_INSTANCE_UID, VR::UI, &new_uid()),
+            text_element(tags::STUDY_INSTANCE_UID, VR::UI, &study_uid),
+            text_element(tags::SERIES_INSTANCE_UID, VR::UI, &series_uid),
+            text_element(
+                tags::FRAME_OF_REFERENCE_UID,
+                VR::UI,
+                &frame_of_reference_uid,
+            ),
+            text_element(tags::IMAGE_TYPE, VR::CS, "ORIGINAL\\PRIMARY\\AXIAL"),
+            text_element(tags::STUDY_DATE, VR::DA, &date),
+            text_element(tags::SERIES_DATE, VR::DA, &date),
+            text_element(tags::STUDY_TIME, VR::TM, &time),
+            text_element(tags::SERIES_TIME, VR::TM, &time),
+            text_element(tags::ACCESSION_NUMBER, VR::SH, ""),
+            text_element(tags::MODALITY, VR::CS, modality.code()),
+            text_element(tags::MANUFACTURER, VR::LO, "rsdicombrowser"),
+            text_element(tags::REFERRING_PHYSICIAN_NAME, VR::PN, ""),
+            text_element(tags::STUDY_DESCRIPTION, VR::LO, &options.study_description),
+            text_element(tags::SERIES_DESCRIPTION, VR::LO, &options.pattern.label()),
+            text_element(tags::PATIENT_NAME, VR::PN, &options.patient_name),
+            text_element(tags::PATIENT_ID, VR::LO, &options.patient_id),
+            text_element(tags::PATIENT_BIRTH_DATE, VR::DA, &options.birth_date),
+            text_element(tags::PATIENT_SEX, VR::CS, &options.sex),
+            text_element(
+                tags::SLICE_THICKNESS,
+                VR::DS,
+                &options.slice_thickness.to_string(),
+            ),
+            text_element(tags::STUDY_ID, VR::SH, "1"),
+            text_element(tags::SERIES_NUMBER, VR::IS, "1"),
+            text_element(tags::ACQUISITION_NUMBER, VR::IS, "1"),
+            text_element(tags::INSTANCE_NUMBER, VR::IS, &(slice + 1).to_string()),
+            text_element(
+                tags::IMAGE_POSITION_PATIENT,
+                VR::DS,
+                &format!(
+                    "{}\\{}\\{location}",
+                    -f64::from(options.columns) * options.pixel_spacing / 2.0,
+                    -f64::from(options.rows) * options.pixel_spacing / 2.0,
+                ),
+            ),
+            text_element(tags::IMAGE_ORIENTATION_PATIENT, VR::DS, "1\\0\\0\\0\\1\\0"),
+            text_element(tags::PATIENT_POSITION, VR::CS, "HFS"),
+            text_element(tags::SLICE_LOCATION, VR::DS, &location.to_string()),
+            us_element(tags::SAMPLES_PER_PIXEL, 1),
+            text_element(tags::PHOTOMETRIC_INTERPRETATION, VR::CS, "MONOCHROME2"),
+            us_element(tags::ROWS, options.rows),
+            us_element(tags::COLUMNS, options.columns),
+            text_element(
+                tags::PIXEL_SPACING,
+                VR::DS,
+                &format!("{0}\\{0}", options.pixel_spacing),
+            ),
+            us_element(tags::BITS_ALLOCATED, 16),
+            us_element(tags::BITS_STORED, 16),
+            us_element(tags::HIGH_BIT, 15),
+            us_element(tags::PIXEL_REPRESENTATION, 0),
+            DataElement::new(
+                tags::PIXEL_DATA,
+                VR::OW,
+                PrimitiveValue::from(slice_pixels(options, slice)),
+            ),
+        ]);
+
+        match modality {
+            // The stored values map to -1000 HU for the air up to 1000 HU.
+            Modality::Ct => {
+                obj.put(text_element(tags::KVP, VR::DS, "120"));
+                obj.put(text_element(tags::WINDOW_CENTER, VR::DS, "0"));
+                obj.put(text_element(tags::WINDOW_WIDTH, VR::DS, "2000"));
+                obj.put(text_element(tags::RESCALE_INTERCEPT, VR::DS, "-1000"));
+                obj.put(text_element(tags::RESCALE_SLOPE, VR::DS, "1"));
+                obj.put(text_element(tags::RESCALE_TYPE, VR::LO, "HU"));
+            }
+            Modality::Mr => {
+                obj.put(text_element(tags::SCANNING_SEQUENCE, VR::CS, "SE"));
+                obj.put(text_element(tags::SEQUENCE_VARIANT, VR::CS, "NONE"));
+                obj.put(text_element(tags::SCAN_OPTIONS, VR::CS, ""));
+                obj.put(text_element(tags::MR_ACQUISITION_TYPE, VR::CS, "2D"));
+                obj.put(text_element(tags::REPETITION_TIME, VR::DS, "500"));
+                obj.put(text_element(tags::ECHO_TIME, VR::DS, "20"));
+                obj.put(text_element(tags::ECHO_TRAIN_LENGTH, VR::IS, "1"));
+                obj.put(text_element(tags::MAGNETIC_FIELD_STRENGTH, VR::DS, "1.5"));
+                obj.put(text_element(tags::WINDOW_CENTER, VR::DS, "1000"));
+                obj.put(text_element(tags::WINDOW_WIDTH, VR::DS, "2000"));
+            }
+        }
+
+        let output = avoid_collision(
+            output_dir.join(format!("{}_{:04}.dcm", modality.code(), slice + 1)),
+            &taken,
+        );
+        taken.insert(output.clone());
+        write_file(obj, uids::EXPLICIT_VR_LITTLE_ENDIAN, &output)?;
+        outputs.push(output);
+    }
+
+    Ok(outputs)
+}
+
+/// The options of the series and the folder to write it into, with the generation running in the background.
+#[derive(Default)]
+pub struct SyntheticStudyTool {
+    options: Options,
+    pub target_dir: Option<PathBuf>,
+    pending: Option<Task<Result<Vec<PathBuf>>>>,
+    status: String,
+}
+
+impl SyntheticStudyTool {
+    /// Take the result of the generation if it is done. Returns the files written.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> Option<Vec<PathBuf>> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                Err(Error::other(tr("The generation was cancelled.")))
+            }
+        };
+        self.pending = None;
+
+        match result {
+            Ok(targets) => {
+                audit_log.record("Generate synthetic study", Vec::new(), targets.clone());
+                self.status = trf("{} files written.", targets.len());
+                Some(targets)
+            }
+            Err(e) => {
+                self.status = e.to_string();
+                None
+            }
+        }
+    }
+
+    /// Show the demographics, the geometry and the pattern of the series to generate.
+    pub fn ui(&mut self, ui: &mut egui::Ui, tasks: &mut TaskPool) -> EditAction {
+        let mut action = EditAction::None;
+        let options = &mut self.options;
+
+        egui::Grid::new("synthetic study grid").show(ui, |ui| {
+            ui.label(tr("Modality"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut options.modality, Modality::Ct, "CT");
+                ui.radio_value(&mut options.modality, Modality::Mr, "MR");
+            });
+            ui.end_row();
+
+            ui.label(tr("Patient name"));
+            ui.text_edit_singleline(&mut options.patient_name);
+            ui.end_row();
+
+            ui.label(tr("Patient ID"));
+            ui.text_edit_singleline(&mut options.patient_id);
+            ui.end_row();
+
+            ui.label(tr("Birth date"));
+            ui.text_edit_singleline(&mut options.birth_date)
+                .on_hover_text("YYYYMMDD");
+            ui.end_row();
+
+            ui.label(tr("Sex"));
+            ui.horizontal(|ui| {
+                for sex in ["M", "F", "O"] {
+                    ui.radio_value(&mut options.sex, sex.to_string(), sex);
+                }
+            });
+            ui.end_row();
+
+            ui.label(tr("Study description"));
+            ui.text_edit_singleline(&mut options.study_description);
+            ui.end_row();
+
+            ui.label(tr("Size"));
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut options.columns).range(16..=1024));
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut options.rows).range(16..=1024));
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut options.slices).range(1..=500));
+            });
+            ui.end_row();
+
+            ui.label(tr("Pixel spacing"));
+            ui.add(
+                egui::DragValue::new(&mut options.pixel_spacing)
+                    .range(0.1..=10.0)
+                    .speed(0.1)
+                    .suffix(" mm"),
+            );
+            ui.end_row();
+
+            ui.label(tr("Slice thickness"));
+            ui.add(
+                egui::DragValue::new(&mut options.slice_thickness)
+                    .range(0.1..=50.0)
+                    .speed(0.1)
+                    .suffix(" mm"),
+            );
+            ui.end_row();
+
+            ui.label(tr("Pattern"));
+            egui::ComboBox::from_id_salt("synthetic pattern")
+                .selected_text(options.pattern.label())
+                .show_ui(ui, |ui| {
+                    for pattern in Pattern::ALL {
+                        ui.selectable_value(&mut options.pattern, pattern, pattern.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label(tr("Target folder"));
+            ui.horizontal(|ui| {
+                let target = self
+                    .target_dir
+                    .as_ref()
+                    .map_or(tr("None"), |x| x.display().to_string());
+                ui.monospace(target);
+                if ui.button(tr("Browse")).clicked() {
+                    action = EditAction::PickTarget;
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.target_dir.is_some() && self.pending.is_none(),
+                    egui::Button::new(trf("Generate {} slices", self.options.slices)),
+                )
+                .clicked()
+                && let Some(target_dir) = self.target_dir.clone()
+            {
+                match check_text_value(VR::DA, &self.options.birth_date) {
+                    Ok(()) => {
+                        let options = self.options.clone();
+                        self.pending =
+                            Some(tasks.spawn(tr("Generate synthetic study"), move |context| {
+                                generate(&options, &target_dir, context)
+                            }));
+                        self.status.clear();
+                    }
+                    Err(e) => self.status = format!("{}: {e}", tr("Birth date")),
+                }
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        action
+    }
+}
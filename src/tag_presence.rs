@@ -0,0 +1,181 @@
+//! Report of the files which contain a tag and of those missing it, e.g. the instances lacking BodyPartExamined.
+//! The files are read on a worker thread, as a large folder takes a while.
+
+use crate::dataset::{open_header, tag_keyword, visit_elements};
+use crate::frequency::value_of;
+use crate::i18n::{tr, trf};
+use crate::tasks::{Task, TaskContext, TaskPool};
+use dicom::core::{DataDictionary, Tag};
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::DefaultDicomObject;
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+
+/// Check if the file has the element, in the file meta group, at the top level, or in the sequence items if asked.
+fn contains(obj: &DefaultDicomObject, tag: Tag, in_items: bool) -> bool {
+    if tag.group() == 0x0002 {
+        return value_of(obj, tag).is_some();
+    }
+    if obj.get(tag).is_some() {
+        return true;
+    }
+
+    let mut found = false;
+    if in_items {
+        visit_elements(obj, &mut |_, elem| found |= elem.header().tag == tag);
+    }
+    found
+}
+
+/// The files with and without the tag.
+struct Presence {
+    tag: Tag,
+    containing: Vec<PathBuf>,
+    missing: Vec<PathBuf>,
+    unreadable: usize,
+}
+
+impl Presence {
+    /// Read the files for the tag, stopping if cancelled.
+    fn collect(files: &[PathBuf], tag: Tag, in_items: bool, context: &TaskContext) -> Self {
+        let mut presence = Self {
+            tag,
+            containing: Vec::new(),
+            missing: Vec::new(),
+            unreadable: 0,
+        };
+
+        for (index, file) in files.iter().enumerate() {
+            if context.is_cancelled() {
+                break;
+            }
+            context.set_progress(index as f32 / files.len() as f32);
+            match open_header(file) {
+                Ok(obj) if contains(&obj, tag, in_items) => presence.containing.push(file.clone()),
+                Ok(_) => presence.missing.push(file.clone()),
+                Err(e) => {
+                    log::error!("Failed to read {}: {e}", file.display());
+                    presence.unreadable += 1;
+                }
+            }
+        }
+
+        presence
+    }
+}
+
+/// The tag to look for and the files found.
+#[derive(Default)]
+pub struct TagPresenceReport {
+    keyword: String,
+    in_items: bool,
+    /// List the files missing the tag rather than the ones containing it.
+    show_missing: bool,
+    presence: Option<Presence>,
+    pending: Option<Task<Presence>>,
+    status: String,
+}
+
+impl TagPresenceReport {
+    /// Show the tag to look for and the files found. Returns the file clicked.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        roots: &[PathBuf],
+        tasks: &mut TaskPool,
+    ) -> Option<PathBuf> {
+        let mut clicked = None;
+        if let Some(pending) = self.pending.as_ref() {
+            match pending.try_recv() {
+                Ok(presence) => {
+                    self.presence = Some(presence);
+                    self.pending = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Tag"));
+            ui.text_edit_singleline(&mut self.keyword)
+                .on_hover_text(tr("A keyword or gggg,eeee"));
+        });
+        ui.checkbox(&mut self.in_items, tr("Include the sequence items"));
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !files.is_empty() && self.pending.is_none(),
+                    egui::Button::new(trf("Check {} files", files.len())),
+                )
+                .clicked()
+            {
+                match StandardDataDictionary.parse_tag(self.keyword.trim()) {
+                    Some(tag) => {
+                        let (files, in_items) = (files.to_vec(), self.in_items);
+                        self.pending =
+                            Some(tasks.spawn(tr("Check the tag presence"), move |context| {
+                                Presence::collect(&files, tag, in_items, context)
+                            }));
+                        self.status.clear();
+                    }
+                    None => self.status = trf("Unknown tag {}", self.keyword.trim()),
+                }
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.colored_label(ui.visuals().error_fg_color, &self.status);
+        }
+        let presence = self.presence.as_ref()?;
+
+        ui.separator();
+        ui.strong(tag_keyword(presence.tag));
+        ui.horizontal(|ui| {
+            ui.selectable_value(
+                &mut self.show_missing,
+                false,
+                trf("Containing ({})", presence.containing.len()),
+            );
+            ui.selectable_value(
+                &mut self.show_missing,
+                true,
+                trf("Missing ({})", presence.missing.len()),
+            );
+        });
+        if presence.unreadable > 0 {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                trf("{} files could not be read", presence.unreadable),
+            );
+        }
+
+        let listed = if self.show_missing {
+            &presence.missing
+        } else {
+            &presence.containing
+        };
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for file in listed {
+                let name = roots
+                    .iter()
+                    .find_map(|x| file.strip_prefix(x).ok())
+                    .unwrap_or(file);
+                if ui
+                    .link(name.display().to_string())
+                    .on_hover_text(tr("Select in the tree"))
+                    .clicked()
+                {
+                    clicked = Some(file.clone());
+                }
+            }
+        });
+
+        clicked
+    }
+}
@@ -0,0 +1,313 @@
+//! The pool of worker threads running the background work of the app, e.g. the dumps and the downloads, with the
+//! Tasks panel listing the running and the queued tasks.
+//! The tasks are cancelled cooperatively: cancelling sets the token of the task, which the task checks between its
+//! steps, and a queued task whose token is set is dropped without running. The result of a cancelled task is never
+//! delivered, so that the receiver sees it as disconnected.
+
+use crate::i18n::{tr, trf};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// Get the default number of worker threads, a few as most of the tasks read files.
+pub fn default_thread_count() -> usize {
+    std::thread::available_parallelism().map_or(2, |x| x.get().min(4))
+}
+
+/// The token set to cancel a task.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TaskStatus {
+    Queued,
+    Running,
+}
+
+/// A task in the pool, as listed in the Tasks panel.
+struct TaskEntry {
+    id: u64,
+    name: String,
+    status: TaskStatus,
+    progress: Option<f32>,
+    token: CancelToken,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of the jobs, with the number of the threads taking them.
+struct Queue {
+    jobs: VecDeque<Job>,
+    thread_count: usize,
+    live_threads: usize,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    tasks: Mutex<Vec<TaskEntry>>,
+}
+
+/// Lock the mutex, which stays usable even if a task panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|x| x.into_inner())
+}
+
+impl Shared {
+    fn update_task(&self, id: u64, update: impl FnOnce(&mut TaskEntry)) {
+        if let Some(task) = lock(&self.tasks).iter_mut().find(|x| x.id == id) {
+            update(task);
+        }
+    }
+}
+
+thread_local! {
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Check if the current thread is a worker of the pool, whose panics only end the task.
+pub fn is_worker_thread() -> bool {
+    IS_WORKER.with(Cell::get)
+}
+
+/// Take the jobs from the queue until the pool has more threads than wanted.
+fn worker(shared: Arc<Shared>) {
+    IS_WORKER.with(|x| x.set(true));
+    loop {
+        let job = {
+            let mut queue = lock(&shared.queue);
+            loop {
+                if queue.live_threads > queue.thread_count {
+                    queue.live_threads -= 1;
+                    return;
+                }
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                queue = shared.ready.wait(queue).unwrap_or_else(|x| x.into_inner());
+            }
+        };
+        job();
+    }
+}
+
+/// The handle of a running task to report its progress and to check if it is cancelled.
+pub struct TaskContext {
+    id: u64,
+    shared: Arc<Shared>,
+    token: CancelToken,
+}
+
+impl TaskContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+
+    /// Set the progress of the task, from 0 to 1.
+    pub fn set_progress(&self, progress: f32) {
+        self.shared
+            .update_task(self.id, |x| x.progress = Some(progress.clamp(0.0, 1.0)));
+    }
+}
+
+/// A task in the pool, whose result is taken when it is done.
+pub struct Task<T> {
+    receiver: Receiver<T>,
+    token: CancelToken,
+}
+
+impl<T> Task<T> {
+    /// Take the result of the task. The task is disconnected if it was cancelled or panicked.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Cancel the task, whose result is then never delivered.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+impl<T> Task<T> {
+    /// Wait for the result of the task.
+    pub fn wait(&self) -> T {
+        self.receiver
+            .recv()
+            .expect("the task should deliver its result")
+    }
+}
+
+/// Take the result of the pending task when it is done. The task is cleared once done, cancelled or panicked.
+pub fn poll<T>(pending: &mut Option<Task<T>>) -> Option<T> {
+    match pending.as_ref()?.try_recv() {
+        Ok(result) => {
+            *pending = None;
+            Some(result)
+        }
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => {
+            *pending = None;
+            None
+        }
+    }
+}
+
+/// Cancel the pending task, if any.
+pub fn cancel<T>(pending: &mut Option<Task<T>>) {
+    if let Some(task) = pending.take() {
+        task.cancel();
+    }
+}
+
+/// The pool of the worker threads.
+pub struct TaskPool {
+    shared: Arc<Shared>,
+    next_id: u64,
+}
+
+impl TaskPool {
+    pub fn new(thread_count: usize) -> Self {
+        let pool = Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue {
+                    jobs: VecDeque::new(),
+                    thread_count: 0,
+                    live_threads: 0,
+                }),
+                ready: Condvar::new(),
+                tasks: Mutex::new(Vec::new()),
+            }),
+            next_id: 0,
+        };
+        pool.set_thread_count(thread_count);
+        pool
+    }
+
+    /// Start or stop the threads to have the number of them. The stopped threads finish their current tasks first.
+    pub fn set_thread_count(&self, thread_count: usize) {
+        let mut queue = lock(&self.shared.queue);
+        queue.thread_count = thread_count.max(1);
+        while queue.live_threads < queue.thread_count {
+            let shared = self.shared.clone();
+            match std::thread::Builder::new()
+                .name("worker".to_string())
+                .spawn(move || worker(shared))
+            {
+                Ok(_) => queue.live_threads += 1,
+                Err(e) => {
+                    log::error!("Failed to start a worker thread: {e}");
+                    break;
+                }
+            }
+        }
+        self.shared.ready.notify_all();
+    }
+
+    /// Queue the task, named for the Tasks panel.
+    pub fn spawn<T: Send + 'static>(
+        &mut self,
+        name: String,
+        task: impl FnOnce(&TaskContext) -> T + Send + 'static,
+    ) -> Task<T> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let token = CancelToken::default();
+        lock(&self.shared.tasks).push(TaskEntry {
+            id,
+            name,
+            status: TaskStatus::Queued,
+            progress: None,
+            token: token.clone(),
+        });
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let context = TaskContext {
+            id,
+            shared: self.shared.clone(),
+            token: token.clone(),
+        };
+        let job = Box::new(move || {
+            if !context.is_cancelled() {
+                context
+                    .shared
+                    .update_task(id, |x| x.status = TaskStatus::Running);
+                // A panic ends the task rather than the worker thread.
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| task(&context)));
+                if let Ok(result) = result
+                    && !context.is_cancelled()
+                {
+                    let _ = sender.send(result);
+                }
+            }
+            lock(&context.shared.tasks).retain(|x| x.id != id);
+        });
+
+        lock(&self.shared.queue).jobs.push_back(job);
+        self.shared.ready.notify_one();
+
+        Task { receiver, token }
+    }
+
+    /// Check if any task is queued or running, to keep polling for the results.
+    pub fn is_busy(&self) -> bool {
+        !lock(&self.shared.tasks).is_empty()
+    }
+
+    /// Show the running and the queued tasks, with their progress and a button to cancel each.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let tasks = lock(&self.shared.tasks);
+        ui.label(trf(
+            "{} worker threads",
+            lock(&self.shared.queue).thread_count,
+        ));
+        if tasks.is_empty() {
+            ui.label(tr("No tasks are running."));
+            return;
+        }
+
+        egui::Grid::new("tasks grid").striped(true).show(ui, |ui| {
+            for task in tasks.iter() {
+                ui.label(&task.name);
+                match (task.status, task.progress) {
+                    (TaskStatus::Queued, _) => {
+                        ui.label(tr("Queued"));
+                    }
+                    (TaskStatus::Running, Some(progress)) => {
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .desired_width(120.0)
+                                .show_percentage(),
+                        );
+                    }
+                    (TaskStatus::Running, None) => {
+                        ui.spinner();
+                    }
+                }
+                if task.token.is_cancelled() {
+                    ui.label(tr("Cancelling"));
+                } else if ui.button(tr("Cancel")).clicked() {
+                    task.token.cancel();
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
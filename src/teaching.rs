@@ -0,0 +1,200 @@
+//! Local notes and labels attached to the files, e.g. "pneumothorax" or "artifact example", to collect teaching cases.
+//! They are kept in a JSON file in the data folder of the user, keyed by the paths of the files, and never written into
+//! the files themselves.
+
+use crate::i18n::{tr, trf};
+use crate::settings::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// Get the path of the teaching files database.
+fn database_path() -> PathBuf {
+    app_data_dir("teaching_files.json")
+}
+
+/// The labels and the note of a file.
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TeachingEntry {
+    pub labels: Vec<String>,
+    pub note: String,
+}
+
+/// The teaching files, with the editor of the selected file and the filter of the list.
+#[derive(Default)]
+pub struct TeachingFiles {
+    entries: BTreeMap<PathBuf, TeachingEntry>,
+    /// The file being edited, to reload the inputs when another file is selected.
+    editing: Option<PathBuf>,
+    labels_input: String,
+    note_input: String,
+    filter: String,
+    label_filter: Option<String>,
+}
+
+impl TeachingFiles {
+    /// Load the database. A missing or invalid file gives no entries.
+    pub fn load() -> Self {
+        let entries = std::fs::read_to_string(database_path())
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default();
+
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.entries)
+            .map_err(std::io::Error::from)
+            .and_then(|x| std::fs::write(database_path(), x));
+        if let Err(e) = result {
+            log::error!("Failed to save the teaching files: {e}");
+        }
+    }
+
+    /// Store the inputs as the entry of the file, removing the entry if both are empty.
+    fn commit_inputs(&mut self, path: PathBuf) {
+        let labels: Vec<String> = self
+            .labels_input
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
+
+        if labels.is_empty() && self.note_input.trim().is_empty() {
+            self.entries.remove(&path);
+        } else {
+            self.entries.insert(
+                path,
+                TeachingEntry {
+                    labels,
+                    note: self.note_input.clone(),
+                },
+            );
+        }
+        self.save();
+    }
+
+    /// Show the labels and the note of the selected file for editing.
+    fn editor_ui(&mut self, ui: &mut egui::Ui, selected_file: &PathBuf) {
+        if self.editing.as_ref() != Some(selected_file) {
+            let entry = self.entries.get(selected_file).cloned().unwrap_or_default();
+            self.labels_input = entry.labels.join(", ");
+            self.note_input = entry.note;
+            self.editing = Some(selected_file.clone());
+        }
+
+        ui.monospace(selected_file.display().to_string());
+        let mut changed = false;
+        egui::Grid::new("teaching editor grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(tr("Labels"));
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.labels_input)
+                            .hint_text(tr("Comma separated, e.g. pneumothorax, artifact example")),
+                    )
+                    .lost_focus();
+                ui.end_row();
+
+                ui.label(tr("Note"));
+                changed |= ui
+                    .add(egui::TextEdit::multiline(&mut self.note_input).desired_rows(3))
+                    .lost_focus();
+                ui.end_row();
+            });
+
+        if changed {
+            self.commit_inputs(selected_file.clone());
+        }
+    }
+
+    /// Check if the entry matches the filter text and the picked label.
+    fn matches(&self, path: &std::path::Path, entry: &TeachingEntry) -> bool {
+        let filter = self.filter.to_lowercase();
+        let matches_text = filter.is_empty()
+            || path.to_string_lossy().to_lowercase().contains(&filter)
+            || entry.note.to_lowercase().contains(&filter)
+            || entry
+                .labels
+                .iter()
+                .any(|x| x.to_lowercase().contains(&filter));
+        let matches_label = self
+            .label_filter
+            .as_ref()
+            .is_none_or(|label| entry.labels.contains(label));
+
+        matches_text && matches_label
+    }
+
+    /// Show the editor of the selected file and the filterable list of the teaching files.
+    /// Returns the file clicked in the list.
+    pub fn ui(&mut self, ui: &mut egui::Ui, selected_file: Option<&PathBuf>) -> Option<PathBuf> {
+        let mut clicked = None;
+
+        match selected_file {
+            Some(selected_file) => self.editor_ui(ui, selected_file),
+            None => {
+                ui.label(tr("Select a file to add labels and a note."));
+            }
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Filter"));
+            ui.text_edit_singleline(&mut self.filter);
+        });
+        let labels: BTreeSet<&String> = self.entries.values().flat_map(|x| &x.labels).collect();
+        let mut label_filter = self.label_filter.clone();
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .selectable_label(label_filter.is_none(), tr("All"))
+                .clicked()
+            {
+                label_filter = None;
+            }
+            for label in labels {
+                if ui
+                    .selectable_label(label_filter.as_ref() == Some(label), label)
+                    .clicked()
+                {
+                    label_filter = Some(label.clone());
+                }
+            }
+        });
+        self.label_filter = label_filter;
+
+        let matched: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(path, entry)| self.matches(path, entry))
+            .collect();
+        ui.label(trf("{} files", matched.len()));
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("teaching files grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (path, entry) in matched {
+                        let name = path
+                            .file_name()
+                            .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                        let link = ui.link(name).on_hover_text(path.display().to_string());
+                        if link.clicked() {
+                            clicked = Some(path.clone());
+                        }
+                        ui.label(entry.labels.join(", "));
+                        ui.label(entry.note.lines().next().unwrap_or_default());
+                        ui.end_row();
+                    }
+                });
+        });
+
+        clicked
+    }
+}
@@ -0,0 +1,299 @@
+//! Validation of the files against a golden template, e.g. for the acceptance testing of a modality.
+//! The template is either a DICOM file or a JSON object of tag keywords to the expected value patterns.
+
+use crate::dataset::{element_string, open_header, tag_keyword};
+use crate::export::csv_field;
+use crate::i18n::{tr, trf};
+use dicom::core::{DataDictionary, Tag};
+use dicom::dictionary_std::StandardDataDictionary;
+use dicom::object::InMemDicomObject;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// The elements whose values are expected to be the same as in a DICOM template.
+/// Other elements of the template, e.g. the UIDs and the dates, only need to be present.
+const CONSTANT_KEYWORDS: [&str; 9] = [
+    "SOPClassUID",
+    "Modality",
+    "Manufacturer",
+    "ManufacturerModelName",
+    "SoftwareVersions",
+    "StationName",
+    "InstitutionName",
+    "BodyPartExamined",
+    "PhotometricInterpretation",
+];
+
+/// A required element, with the regex its value is expected to match if not empty.
+#[derive(Clone, Default)]
+pub struct Rule {
+    pub keyword: String,
+    pub pattern: String,
+}
+
+/// The result of validating a file.
+pub struct FileResult {
+    pub path: PathBuf,
+    pub failures: Vec<String>,
+}
+
+/// Build the rules from a JSON object, e.g. `{"Modality": "^CT$", "PatientID": null}`.
+/// A `null` or empty value only requires the element to be present.
+fn rules_from_json(path: &Path) -> Result<Vec<Rule>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let map: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&text).map_err(|e| e.to_string())?;
+
+    map.into_iter()
+        .map(|(keyword, value)| match value {
+            serde_json::Value::Null => Ok(Rule {
+                keyword,
+                pattern: String::new(),
+            }),
+            serde_json::Value::String(pattern) => Ok(Rule { keyword, pattern }),
+            _ => Err(trf("The value of {} should be a string or null", keyword)),
+        })
+        .collect()
+}
+
+/// Build the rules from the top-level elements of a DICOM file.
+fn rules_from_dicom(path: &Path) -> Result<Vec<Rule>, String> {
+    let obj = open_header(path).map_err(|e| e.to_string())?;
+
+    Ok(obj
+        .iter()
+        .map(|elem| {
+            let keyword = tag_keyword(elem.header().tag);
+            let pattern = if CONSTANT_KEYWORDS.contains(&keyword.as_str()) {
+                element_string(&obj, elem.header().tag)
+                    .map_or(String::new(), |x| format!("^{}$", regex::escape(&x)))
+            } else {
+                String::new()
+            };
+            Rule { keyword, pattern }
+        })
+        .collect())
+}
+
+/// Load the rules of the template, as JSON if the file has the `.json` extension or as DICOM otherwise.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    if path
+        .extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("json"))
+    {
+        rules_from_json(path)
+    } else {
+        rules_from_dicom(path)
+    }
+}
+
+/// Parse the tag of the keyword or `gggg,eeee`.
+fn parse_tag(keyword: &str) -> Option<Tag> {
+    StandardDataDictionary.parse_tag(keyword.trim())
+}
+
+/// Check the dataset against the rules, and return the failures.
+fn check(obj: &InMemDicomObject, rules: &[(Tag, &Rule, Option<Regex>)]) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for (tag, rule, regex) in rules {
+        if obj.get(*tag).is_none() {
+            failures.push(trf("{} is missing", &rule.keyword));
+            continue;
+        }
+        if let Some(regex) = regex {
+            let value = element_string(obj, *tag).unwrap_or_default();
+            if !regex.is_match(&value) {
+                failures.push(format!("{} [{value}] ≠ {}", rule.keyword, rule.pattern));
+            }
+        }
+    }
+
+    failures
+}
+
+/// Validate the files against the rules.
+pub fn validate(files: &[PathBuf], rules: &[Rule]) -> Result<Vec<FileResult>, String> {
+    let rules = rules
+        .iter()
+        .filter(|x| !x.keyword.trim().is_empty())
+        .map(|rule| {
+            let tag =
+                parse_tag(&rule.keyword).ok_or_else(|| trf("Unknown tag {}", &rule.keyword))?;
+            let regex = if rule.pattern.is_empty() {
+                None
+            } else {
+                Some(Regex::new(&rule.pattern).map_err(|e| e.to_string())?)
+            };
+            Ok((tag, rule, regex))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(files
+        .iter()
+        .map(|path| FileResult {
+            path: path.clone(),
+            failures: match open_header(path) {
+                Ok(obj) => check(&obj, &rules),
+                Err(e) => vec![e.to_string()],
+            },
+        })
+        .collect())
+}
+
+/// The template and the results of the last validation.
+#[derive(Default)]
+pub struct TemplateCheckTool {
+    template: Option<PathBuf>,
+    rules: Vec<Rule>,
+    results: Vec<FileResult>,
+    status: String,
+}
+
+impl TemplateCheckTool {
+    /// Load the rules of the template file.
+    pub fn load(&mut self, path: &Path) {
+        match load_rules(path) {
+            Ok(rules) => {
+                self.rules = rules;
+                self.template = Some(path.to_path_buf());
+                self.status.clear();
+            }
+            Err(e) => self.status = e,
+        }
+        self.results.clear();
+    }
+
+    /// Build the report as CSV with a `File,Result,Failures` header.
+    fn report_csv(&self) -> String {
+        let mut csv = String::from("File,Result,Failures\n");
+        for result in &self.results {
+            let verdict = if result.failures.is_empty() {
+                "PASS"
+            } else {
+                "FAIL"
+            };
+            csv.push_str(&format!(
+                "{},{verdict},{}\n",
+                csv_field(&result.path.display().to_string()),
+                csv_field(&result.failures.join("; "))
+            ));
+        }
+        csv
+    }
+
+    /// Show the rules of the template and the report. Returns true if a template is to be picked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, files: &[PathBuf]) -> bool {
+        let mut pick_template = false;
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Template"));
+            let template = self
+                .template
+                .as_ref()
+                .map_or(tr("None"), |x| x.display().to_string());
+            ui.monospace(template);
+            pick_template = ui
+                .button(tr("Browse"))
+                .on_hover_text(tr(
+                    "A DICOM file or a JSON object of tag keywords to value patterns",
+                ))
+                .clicked();
+        });
+
+        egui::CollapsingHeader::new(trf("Rules ({})", self.rules.len())).show(ui, |ui| {
+            ui.label(tr(
+                "The elements are required. The values must match the patterns if given.",
+            ));
+            egui::ScrollArea::vertical()
+                .id_salt("rules")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("template rules grid").show(ui, |ui| {
+                        ui.label(tr("Tag"));
+                        ui.label(tr("Pattern"));
+                        ui.end_row();
+
+                        let mut removed = None;
+                        for (index, rule) in self.rules.iter_mut().enumerate() {
+                            ui.text_edit_singleline(&mut rule.keyword);
+                            ui.text_edit_singleline(&mut rule.pattern);
+                            if ui.button("🗑").on_hover_text(tr("Remove")).clicked() {
+                                removed = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                        if let Some(index) = removed {
+                            self.rules.remove(index);
+                        }
+                    });
+                });
+            if ui.button(tr("Add rule")).clicked() {
+                self.rules.push(Rule::default());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !files.is_empty() && !self.rules.is_empty(),
+                    egui::Button::new(trf("Validate {} files", files.len())),
+                )
+                .clicked()
+            {
+                match validate(files, &self.rules) {
+                    Ok(results) => {
+                        self.results = results;
+                        self.status.clear();
+                    }
+                    Err(e) => self.status = e,
+                }
+            }
+            if ui
+                .add_enabled(
+                    !self.results.is_empty(),
+                    egui::Button::new(tr("Copy as CSV")),
+                )
+                .clicked()
+            {
+                ui.ctx().copy_text(self.report_csv());
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.colored_label(ui.visuals().error_fg_color, &self.status);
+        }
+        if self.results.is_empty() {
+            return pick_template;
+        }
+
+        let failed = self
+            .results
+            .iter()
+            .filter(|x| !x.failures.is_empty())
+            .count();
+        ui.label(format!(
+            "{}, {}",
+            trf("{} passed", self.results.len() - failed),
+            trf("{} failed", failed)
+        ));
+        egui::ScrollArea::both().id_salt("results").show(ui, |ui| {
+            egui::Grid::new("template results grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for result in &self.results {
+                        if result.failures.is_empty() {
+                            ui.label("✔");
+                        } else {
+                            ui.colored_label(ui.visuals().error_fg_color, "✖");
+                        }
+                        ui.monospace(result.path.display().to_string());
+                        ui.label(result.failures.join("\n"));
+                        ui.end_row();
+                    }
+                });
+        });
+
+        pick_template
+    }
+}
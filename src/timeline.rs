@@ -0,0 +1,197 @@
+//! Timeline of the studies of each patient by the study date and time.
+
+use crate::dataset::{element_string, open_header};
+use crate::i18n::{tr, trf};
+use crate::privacy::MASK;
+use crate::tasks::TaskContext;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use dicom::dictionary_std::tags;
+use std::path::PathBuf;
+
+const ROW_HEIGHT: f32 = 28.0;
+const LABEL_WIDTH: f32 = 160.0;
+const MARKER_RADIUS: f32 = 5.0;
+
+/// A study on the timeline.
+struct StudyPoint {
+    study_uid: String,
+    description: String,
+    datetime: NaiveDateTime,
+    first_file: PathBuf,
+    file_count: usize,
+}
+
+/// The studies of the patients, sorted by the date and time.
+#[derive(Default)]
+pub struct Timeline {
+    patients: Vec<(String, Vec<StudyPoint>)>,
+    undated_studies: usize,
+}
+
+/// Parse the study date and the optional time, e.g. `20240131` and `093000.123`.
+pub fn parse_datetime(date: &str, time: Option<&str>) -> Option<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(date.get(..8)?, "%Y%m%d").ok()?;
+    let time = time
+        .map(|x| format!("{:0<6}", x.get(..6).unwrap_or(x)))
+        .and_then(|x| NaiveTime::parse_from_str(&x, "%H%M%S").ok())
+        .unwrap_or_default();
+
+    Some(date.and_time(time))
+}
+
+impl Timeline {
+    /// Collect the studies of the files, stopping if cancelled.
+    pub fn collect(files: &[PathBuf], context: &TaskContext) -> Self {
+        let mut timeline = Self::default();
+        let mut undated = Vec::new();
+
+        for (index, file) in files.iter().enumerate() {
+            if context.is_cancelled() {
+                break;
+            }
+            context.set_progress(index as f32 / files.len() as f32);
+            let Ok(obj) = open_header(file) else {
+                continue;
+            };
+            let study_uid = element_string(&obj, tags::STUDY_INSTANCE_UID).unwrap_or_default();
+            let patient = [tags::PATIENT_NAME, tags::PATIENT_ID]
+                .map(|x| element_string(&obj, x).unwrap_or_default())
+                .join(" / ");
+
+            let Some(datetime) = element_string(&obj, tags::STUDY_DATE).and_then(|x| {
+                parse_datetime(&x, element_string(&obj, tags::STUDY_TIME).as_deref())
+            }) else {
+                if !undated.contains(&study_uid) {
+                    undated.push(study_uid);
+                }
+                continue;
+            };
+
+            let index = match timeline.patients.iter().position(|(x, _)| *x == patient) {
+                Some(x) => x,
+                None => {
+                    timeline.patients.push((patient, Vec::new()));
+                    timeline.patients.len() - 1
+                }
+            };
+            let studies = &mut timeline.patients[index].1;
+            match studies.iter_mut().find(|x| x.study_uid == study_uid) {
+                Some(study) => study.file_count += 1,
+                None => studies.push(StudyPoint {
+                    study_uid,
+                    description: element_string(&obj, tags::STUDY_DESCRIPTION).unwrap_or_default(),
+                    datetime,
+                    first_file: file.clone(),
+                    file_count: 1,
+                }),
+            }
+        }
+
+        for (_, studies) in &mut timeline.patients {
+            studies.sort_by_key(|x| x.datetime);
+        }
+        timeline.undated_studies = undated.len();
+        timeline
+    }
+
+    /// Show the timeline, with the patients masked in the privacy mode. Returns the first file of the study clicked.
+    pub fn ui(&self, ui: &mut egui::Ui, privacy_mode: bool) -> Option<PathBuf> {
+        let mut clicked = None;
+
+        if self.undated_studies > 0 {
+            ui.label(trf("{} studies without a study date", self.undated_studies));
+        }
+        let datetimes = self
+            .patients
+            .iter()
+            .flat_map(|(_, x)| x.iter().map(|x| x.datetime));
+        let (Some(first), Some(last)) = (datetimes.clone().min(), datetimes.max()) else {
+            ui.label(tr("No dated studies found."));
+            return None;
+        };
+        // Keep a minimum span of a day, so that a single study is in the middle.
+        let actual_span = (last - first).num_seconds();
+        let span = actual_span.max(86400);
+        let first = first - TimeDelta::seconds((span - actual_span) / 2);
+        let last = first + TimeDelta::seconds(span);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let size = egui::vec2(
+                ui.available_width(),
+                ROW_HEIGHT * (self.patients.len() + 1) as f32,
+            );
+            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            let visuals = ui.visuals().clone();
+            let plot_left = rect.left() + LABEL_WIDTH;
+            let plot_width = (rect.right() - MARKER_RADIUS * 2.0 - plot_left).max(1.0);
+            let x_of = |datetime: NaiveDateTime| {
+                plot_left + (datetime - first).num_seconds() as f32 / span as f32 * plot_width
+            };
+
+            for (row, (patient, studies)) in self.patients.iter().enumerate() {
+                let y = rect.top() + ROW_HEIGHT * (row as f32 + 0.5);
+                painter.text(
+                    egui::pos2(rect.left(), y),
+                    egui::Align2::LEFT_CENTER,
+                    if privacy_mode { MASK } else { patient.as_str() },
+                    egui::TextStyle::Body.resolve(ui.style()),
+                    visuals.text_color(),
+                );
+                painter.hline(
+                    plot_left..=rect.right(),
+                    y,
+                    visuals.widgets.noninteractive.bg_stroke,
+                );
+
+                for study in studies {
+                    let center = egui::pos2(x_of(study.datetime), y);
+                    let response = ui
+                        .interact(
+                            egui::Rect::from_center_size(
+                                center,
+                                egui::Vec2::splat(MARKER_RADIUS * 3.0),
+                            ),
+                            ui.id().with(&study.study_uid),
+                            egui::Sense::click(),
+                        )
+                        .on_hover_text(format!(
+                            "{}\n{}\n{}",
+                            study.datetime.format("%Y-%m-%d %H:%M"),
+                            study.description,
+                            trf("{} files", study.file_count)
+                        ));
+                    let color = if response.hovered() {
+                        visuals.selection.bg_fill
+                    } else {
+                        visuals.hyperlink_color
+                    };
+                    painter.circle_filled(center, MARKER_RADIUS, color);
+                    if response.clicked() {
+                        clicked = Some(study.first_file.clone());
+                    }
+                }
+            }
+
+            // Label the ends of the time axis.
+            let y = rect.bottom() - ROW_HEIGHT / 2.0;
+            let font = egui::TextStyle::Small.resolve(ui.style());
+            painter.text(
+                egui::pos2(plot_left, y),
+                egui::Align2::LEFT_CENTER,
+                first.format("%Y-%m-%d"),
+                font.clone(),
+                visuals.weak_text_color(),
+            );
+            painter.text(
+                egui::pos2(rect.right(), y),
+                egui::Align2::RIGHT_CENTER,
+                last.format("%Y-%m-%d"),
+                font,
+                visuals.weak_text_color(),
+            );
+        });
+
+        clicked
+    }
+}
@@ -0,0 +1,237 @@
+//! Regeneration of the Study, Series and SOP Instance UIDs of the selected files, e.g. to send a duplicate of test data
+//! to a PACS which already has the originals. The UIDs are remapped consistently: the files of a series get the same
+//! new Series Instance UID, and the references between the files, e.g. in the presentation states, follow the new UIDs.
+//! The Frame of Reference UIDs are regenerated with the studies or the series.
+
+use crate::audit::AuditLog;
+use crate::bulk_edit::{EditAction, EditOutput, PendingEdit, Written, write_edited};
+use crate::dataset::{element_string, new_uid_with_root, open_header, sequence, text_element};
+use crate::i18n::{tr, trf};
+use crate::tasks::{TaskContext, TaskPool};
+use dicom::core::value::Value;
+use dicom::core::{Tag, VR};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The longest root leaving enough random digits in the UIDs to keep them unique.
+const MAX_ROOT_LENGTH: usize = 40;
+
+/// Check if the root is made of numbers without leading zeros, separated by dots.
+fn is_valid_root(root: &str) -> bool {
+    root.len() <= MAX_ROOT_LENGTH
+        && root.split('.').all(|x| {
+            !x.is_empty()
+                && x.bytes().all(|x| x.is_ascii_digit())
+                && (x == "0" || !x.starts_with('0'))
+        })
+}
+
+/// Replace the mapped UIDs in the dataset and in the items of its sequences, including the references.
+fn remap_uids(obj: &mut InMemDicomObject, uids: &HashMap<String, String>) {
+    let tags: Vec<Tag> = obj.iter().map(|x| x.header().tag).collect();
+
+    for tag in tags {
+        let Ok(elem) = obj.element(tag) else {
+            continue;
+        };
+        match (elem.vr(), elem.value()) {
+            (VR::UI, Value::Primitive(_)) => {
+                let Ok(value) = elem.to_str().map(|x| x.to_string()) else {
+                    continue;
+                };
+                let values: Vec<&str> = value.split('\\').collect();
+                let remapped: Vec<&str> = values
+                    .iter()
+                    .map(|x| {
+                        let uid = x.trim_end_matches(['\0', ' ']);
+                        uids.get(uid).map_or(*x, String::as_str)
+                    })
+                    .collect();
+                if remapped != values {
+                    obj.put(text_element(tag, VR::UI, &remapped.join("\\")));
+                }
+            }
+            (_, Value::Sequence(seq)) => {
+                let mut items = seq.items().to_vec();
+                for item in &mut items {
+                    remap_uids(item, uids);
+                }
+                obj.put(sequence(tag, items));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Regenerate the UIDs of the levels in the files, and write them. The new UIDs are made for all the files first, so
+/// that a reference to a file later in the list is remapped too. The files written are added to `written`.
+pub fn regenerate(
+    files: &[PathBuf],
+    levels: &[Tag],
+    root: &str,
+    output_dir: Option<&Path>,
+    written: &Mutex<Written>,
+    context: &TaskContext,
+) -> Result<()> {
+    let mut uids: HashMap<String, String> = HashMap::new();
+    for file in files {
+        if context.is_cancelled() {
+            return Err(Error::other(tr("The edit was cancelled.")));
+        }
+        let obj = open_header(file).map_err(Error::other)?;
+        for tag in levels {
+            if let Some(uid) = element_string(&obj, *tag) {
+                uids.entry(uid).or_insert_with(|| new_uid_with_root(root));
+            }
+        }
+    }
+
+    write_edited(
+        files,
+        output_dir,
+        "Regenerate UIDs",
+        written,
+        context,
+        |obj| {
+            remap_uids(obj, &uids);
+            let sop_instance_uid = obj
+                .meta()
+                .media_storage_sop_instance_uid
+                .trim_end_matches(['\0', ' '])
+                .to_string();
+            if let Some(uid) = uids.get(&sop_instance_uid) {
+                obj.update_meta(|meta| meta.media_storage_sop_instance_uid = uid.clone());
+            }
+            Ok(())
+        },
+    )
+}
+
+/// The levels whose UIDs are regenerated, and where the files are written.
+pub struct UidRegenTool {
+    study: bool,
+    series: bool,
+    instance: bool,
+    pub output: EditOutput,
+    pending: Option<PendingEdit>,
+    status: String,
+}
+
+impl Default for UidRegenTool {
+    fn default() -> Self {
+        Self {
+            study: true,
+            series: true,
+            instance: true,
+            output: EditOutput::default(),
+            pending: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl UidRegenTool {
+    /// Take the result of the regeneration when it is done. Returns the files written.
+    pub fn poll(&mut self, audit_log: &mut AuditLog) -> Option<Vec<PathBuf>> {
+        let (status, targets) = PendingEdit::poll(&mut self.pending, audit_log)?;
+        self.status = status;
+        (!targets.is_empty()).then_some(targets)
+    }
+
+    /// Show the levels to regenerate, the UID root and the output.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        files: &[PathBuf],
+        root: &mut String,
+        tasks: &mut TaskPool,
+        read_only: bool,
+    ) -> EditAction {
+        let mut action = EditAction::None;
+
+        ui.label(trf("{} files selected", files.len()));
+        egui::Grid::new("uid regen grid").show(ui, |ui| {
+            ui.label(tr("New UIDs"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.study, tr("Study"));
+                ui.checkbox(&mut self.series, tr("Series"));
+                ui.checkbox(&mut self.instance, tr("SOP instance"));
+            });
+            ui.end_row();
+
+            ui.label(tr("UID root"));
+            ui.text_edit_singleline(root)
+                .on_hover_text(tr("The root of your organization, or 2.25 for random UIDs"));
+            ui.end_row();
+
+            if self.output.grid_ui(ui, read_only) {
+                action = EditAction::PickTarget;
+            }
+        });
+
+        // The frame of reference is shared by the series of a study, so it is new with a new study or series, or the
+        // copies would share it with the originals.
+        let levels: Vec<Tag> = [
+            (self.study, tags::STUDY_INSTANCE_UID),
+            (self.series, tags::SERIES_INSTANCE_UID),
+            (self.study || self.series, tags::FRAME_OF_REFERENCE_UID),
+            (self.instance, tags::SOP_INSTANCE_UID),
+        ]
+        .into_iter()
+        .filter_map(|(is_on, tag)| is_on.then_some(tag))
+        .collect();
+        let is_root_valid = is_valid_root(root);
+        if !is_root_valid {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                tr("The UID root should be numbers separated by dots, e.g. 1.2.826.0.1.3680043"),
+            );
+        }
+
+        let output_dir = self.output.output_dir();
+        let can_run = !files.is_empty()
+            && !levels.is_empty()
+            && is_root_valid
+            && output_dir.is_some()
+            && self.pending.is_none();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(can_run, egui::Button::new(tr("Regenerate")))
+                .clicked()
+                && let Some(output_dir) = output_dir
+            {
+                let files = files.to_vec();
+                let root = root.clone();
+                self.pending = Some(PendingEdit::spawn(
+                    tasks,
+                    "Regenerate UIDs".to_string(),
+                    move |context, written| {
+                        regenerate(
+                            &files,
+                            &levels,
+                            &root,
+                            output_dir.as_deref(),
+                            written,
+                            context,
+                        )?;
+                        Ok(String::new())
+                    },
+                ));
+                self.status.clear();
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        action
+    }
+}
@@ -0,0 +1,168 @@
+//! The undo journal of the files edited in place: each original is copied into the journal before it is overwritten,
+//! so that the last edit can be undone by copying the originals back.
+//! Each edit is a numbered folder in the journal, in the data folder of the user, with the copies and a `journal.json`
+//! listing where they came from.
+//! Only the last edits are kept, as the copies take as much space as the files.
+//! The size and the modification time of each file are recorded after it is written, so that an undo never overwrites
+//! a file changed since the edit.
+
+use crate::i18n::trf;
+use crate::settings::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const JOURNAL_FILE: &str = "journal.json";
+
+/// The number of the edits kept in the journal.
+const MAX_ENTRIES: usize = 20;
+
+/// An edit of the files in place.
+#[derive(Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub operation: String,
+    pub timestamp: String,
+    /// The edited files, each with its original copied into the journal.
+    files: Vec<(PathBuf, PathBuf)>,
+    /// The size and the modification time of each file after it was written.
+    #[serde(default)]
+    written: Vec<(PathBuf, (u64, SystemTime))>,
+}
+
+impl UndoEntry {
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+}
+
+/// Get the size and the modification time of the file.
+fn stamp(file: &Path) -> Result<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(file)?;
+    Ok((metadata.len(), metadata.modified()?))
+}
+
+/// Get the folders of the edits in the journal, the last one last. The folders without a journal, e.g. of an edit
+/// which failed before any file was copied, are skipped.
+fn entry_dirs() -> Vec<(u64, PathBuf)> {
+    let mut dirs: Vec<(u64, PathBuf)> = std::fs::read_dir(app_data_dir("undo"))
+        .map(|x| {
+            x.flatten()
+                .filter_map(|entry| {
+                    let number = entry.file_name().to_str()?.parse().ok()?;
+                    entry
+                        .path()
+                        .join(JOURNAL_FILE)
+                        .exists()
+                        .then(|| (number, entry.path()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+fn read_entry(dir: &Path) -> Result<UndoEntry> {
+    let text = std::fs::read_to_string(dir.join(JOURNAL_FILE))?;
+    serde_json::from_str(&text).map_err(Error::other)
+}
+
+/// Get the last edit in the journal, if any.
+pub fn last_entry() -> Option<UndoEntry> {
+    let (_, dir) = entry_dirs().pop()?;
+    read_entry(&dir).ok()
+}
+
+/// Undo the last edit by copying the originals back, and remove it from the journal. Returns the restored files.
+/// Nothing is restored if any of the files was changed since the edit.
+pub fn undo_last() -> Result<Vec<PathBuf>> {
+    let Some((_, dir)) = entry_dirs().pop() else {
+        return Ok(Vec::new());
+    };
+    let entry = read_entry(&dir)?;
+
+    let changed: Vec<String> = entry
+        .files
+        .iter()
+        .filter(|(file, _)| {
+            entry
+                .written
+                .iter()
+                .find(|(x, _)| x == file)
+                .is_some_and(|(_, x)| stamp(file).ok().as_ref() != Some(x))
+        })
+        .map(|(file, _)| file.display().to_string())
+        .collect();
+    if !changed.is_empty() {
+        return Err(Error::other(trf(
+            "Changed since the edit, nothing was restored: {}",
+            changed.join(", "),
+        )));
+    }
+
+    for (file, backup) in &entry.files {
+        std::fs::copy(backup, file)?;
+    }
+    std::fs::remove_dir_all(&dir)?;
+
+    Ok(entry.files.into_iter().map(|(file, _)| file).collect())
+}
+
+/// The edit being recorded into the journal.
+pub struct UndoRecorder {
+    dir: PathBuf,
+    entry: UndoEntry,
+}
+
+impl UndoRecorder {
+    /// Start recording an edit, dropping the oldest edits beyond the limit. The folder of the edit is only created
+    /// when the first file is copied.
+    pub fn begin(operation: &str) -> Result<Self> {
+        let dirs = entry_dirs();
+        for (_, dir) in dirs
+            .iter()
+            .take((dirs.len() + 1).saturating_sub(MAX_ENTRIES))
+        {
+            if let Err(e) = std::fs::remove_dir_all(dir) {
+                log::error!("Failed to remove {}: {e}", dir.display());
+            }
+        }
+
+        let number = dirs.last().map_or(1, |(x, _)| x + 1);
+        let dir = app_data_dir("undo").join(number.to_string());
+
+        Ok(Self {
+            dir,
+            entry: UndoEntry {
+                operation: operation.to_string(),
+                timestamp: chrono::Local::now().to_rfc3339(),
+                files: Vec::new(),
+                written: Vec::new(),
+            },
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let text = serde_json::to_string_pretty(&self.entry).map_err(Error::other)?;
+        std::fs::write(self.dir.join(JOURNAL_FILE), text)
+    }
+
+    /// Copy the file into the journal before it is overwritten. The journal is saved after each file, so that the
+    /// files edited before a failure can still be restored.
+    pub fn backup(&mut self, file: &Path) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let backup = self
+            .dir
+            .join(format!("{:06}.dcm", self.entry.files.len() + 1));
+        std::fs::copy(file, &backup)?;
+        self.entry.files.push((file.to_path_buf(), backup));
+        self.save()
+    }
+
+    /// Record the size and the modification time of the file after it was written.
+    pub fn written(&mut self, file: &Path) -> Result<()> {
+        self.entry.written.push((file.to_path_buf(), stamp(file)?));
+        self.save()
+    }
+}
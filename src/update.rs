@@ -0,0 +1,89 @@
+//! The opt-in check for a newer release, reading the latest release of the GitHub repository on a worker thread so
+//! that a slow network doesn't block the UI.
+
+use crate::download;
+use crate::i18n::tr;
+use crate::tasks::{Task, TaskPool};
+use std::io::{Error, Result};
+use std::sync::mpsc::TryRecvError;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/leungkkf/rsdicombrowser/releases/latest";
+
+/// A release of the app.
+pub struct Release {
+    pub version: String,
+    pub url: String,
+}
+
+/// Get the latest release from the release feed.
+fn fetch_latest_release() -> Result<Release> {
+    let body = download::fetch(LATEST_RELEASE_URL, &["Accept: application/vnd.github+json"])?;
+
+    let json: serde_json::Value = serde_json::from_slice(&body)?;
+    let field = |name: &str| {
+        json.get(name)
+            .and_then(|x| x.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| Error::other(format!("the release has no {name}")))
+    };
+    Ok(Release {
+        version: field("tag_name")?,
+        url: field("html_url")?,
+    })
+}
+
+/// Parse the version, e.g. `v0.2.1`, into its numbers.
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches(['v', 'V'])
+        .split(['.', '-', '+'])
+        .map_while(|x| x.parse().ok())
+        .collect()
+}
+
+/// Check if the version is newer than the version of this app.
+fn is_newer(version: &str) -> bool {
+    parse_version(version) > parse_version(env!("CARGO_PKG_VERSION"))
+}
+
+/// The state of the update check.
+#[derive(Default)]
+pub struct UpdateCheck {
+    pending: Option<Task<Result<Release>>>,
+    /// The newer release found.
+    pub available: Option<Release>,
+}
+
+impl UpdateCheck {
+    /// Start checking for the latest release in the background, unless a check is running.
+    pub fn start(&mut self, tasks: &mut TaskPool) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        self.pending = Some(tasks.spawn(tr("Check for updates"), |_| fetch_latest_release()));
+    }
+
+    pub fn is_checking(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Take the result of the check if it is done.
+    pub fn poll(&mut self) {
+        let Some(receiver) = self.pending.as_ref() else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Ok(Ok(release)) => {
+                log::info!("The latest release is {}", release.version);
+                self.available = is_newer(&release.version).then_some(release);
+            }
+            Ok(Err(e)) => log::warn!("Failed to check for updates: {e}"),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.pending = None;
+    }
+}
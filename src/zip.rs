@@ -0,0 +1,132 @@
+//! Writing of ZIP archives for sharing the files.
+//! The files are stored without compression, since the pixel data is mostly compressed already or compresses poorly,
+//! and the archive is limited to the sizes of the original ZIP format, i.e. 4 GiB and 65535 files.
+
+use std::io::{Error, Result, Write};
+
+/// Compute the CRC-32 of the data, as used by ZIP.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// A file written, for the central directory.
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A ZIP archive being written to the writer.
+pub struct ZipWriter<W: Write> {
+    writer: W,
+    entries: Vec<Entry>,
+    offset: u64,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            entries: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Get the offset as the 32-bit field of the format.
+    fn offset32(&self) -> Result<u32> {
+        u32::try_from(self.offset).map_err(|_| Error::other("the archive is larger than 4 GiB"))
+    }
+
+    /// Add the file with the name, e.g. `DICOM/P0000001/I0000001`. The path separators are `/`.
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.entries.len() == usize::from(u16::MAX) {
+            return Err(Error::other("the archive has more than 65535 files"));
+        }
+        let size = u32::try_from(data.len())
+            .map_err(|_| Error::other(format!("{name} is larger than 4 GiB")))?;
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(data),
+            size,
+            offset: self.offset32()?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4B50u32.to_le_bytes());
+        // The version needed to extract.
+        header.extend_from_slice(&20u16.to_le_bytes());
+        // The UTF-8 names flag.
+        header.extend_from_slice(&0x0800u16.to_le_bytes());
+        // Stored, with no date and time.
+        header.extend_from_slice(&[0; 6]);
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(data)?;
+        self.offset32()?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Write the central directory, and get the writer back.
+    pub fn finish(mut self) -> Result<W> {
+        let start = self.offset32()?;
+
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+            // The version made by and needed to extract.
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&0x0800u16.to_le_bytes());
+            directory.extend_from_slice(&[0; 6]);
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // No extra field, comment, disk number and attributes.
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        self.write(&directory)?;
+
+        let count = self.entries.len() as u16;
+        let size = self.offset32()? - start;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&size.to_le_bytes());
+        end.extend_from_slice(&start.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.write(&end)?;
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}